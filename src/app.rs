@@ -1,30 +1,336 @@
+use std::collections::BTreeSet;
 
-use iced::executor;
-use iced::{Application, Command, Element, Settings, Theme};
+use iced::widget::{button, column, row, scrollable, svg, text, text_input, Column, Space};
+use iced::{Element, Length, Task};
 
+use super::config::Config;
+use super::edit::{self, Command as EvalCommand, Program};
+use super::events::{Dates, Event, Node, QueryRef, Value};
+use super::locale::DateFormat;
+use super::saga::{RenderOptions, SagaDoc, Stamp};
+use super::validate::path_string;
+
+/// Name given to the events the GUI adds, for the user to change.
+const NEW_EVENT: &str = "New event";
+
+/// Pixels each level of the tree is indented by.
+const INDENT: f32 = 16.0;
+
+/// What the window is opened on.
+pub struct Flags {
+    pub path: String,
+    pub doc: SagaDoc,
+    /// Stamp of the file when `doc` was loaded, see `SagaDoc::save_unchanged`.
+    pub stamp: Stamp,
+    /// Expands the aliases typed in the command box.
+    pub config: Config,
+}
+
+/// Edits a document in a window: its tree on the left, its rendering on the
+/// right. Every change goes through the same `edit` commands as `saga edit`.
 pub struct App {
+    path: String,
+    doc: SagaDoc,
+    stamp: Stamp,
+    config: Config,
+    /// Path of the selected value, as used by `Node::query_ref`.
+    selected: Vec<usize>,
+    /// Nodes whose children are hidden in the tree, by path.
+    folded: BTreeSet<Vec<usize>>,
+    name: String,
+    dates: String,
+    command: String,
+    /// The document as drawn by `SagaDoc::draw()`, redrawn after every change.
+    preview: svg::Handle,
+    /// Outcome of the last action, along the bottom of the window.
+    status: String,
+    /// Whether there are changes not yet saved.
+    unsaved: bool,
 }
 
-impl Application for App {
-    type Executor = executor::Default;
-    type Flags = ();
-    type Message = ();
-    type Theme = Theme;
+#[derive(Clone, Debug)]
+pub enum Message {
+    Select(Vec<usize>),
+    /// Hides or shows the children of the node at the path.
+    Fold(Vec<usize>),
+    NameChanged(String),
+    DatesChanged(String),
+    CommandChanged(String),
+    Rename,
+    Redate,
+    /// Runs what's in the command box.
+    Run,
+    Add,
+    Remove,
+    Save,
+}
 
-    fn new(flags: Self::Flags) -> (Self, Command<Self::Message>) {
-        todo!();
+/// Opens the window, returning once it's closed.
+pub fn run(flags: Flags) -> iced::Result {
+    iced::application(App::title, App::update, App::view)
+        .run_with(move ||App::new(flags))
+}
+
+impl App {
+    fn new(flags: Flags) -> (App, Task<Message>) {
+        let mut app = App {
+            path: flags.path,
+            doc: flags.doc,
+            stamp: flags.stamp,
+            config: flags.config,
+            selected: vec![],
+            folded: BTreeSet::new(),
+            name: String::new(),
+            dates: String::new(),
+            command: String::new(),
+            preview: svg::Handle::from_memory(vec![]),
+            status: String::new(),
+            unsaved: false,
+        };
+        app.select(vec![]);
+        app.redraw();
+        (app, Task::none())
     }
 
     fn title(&self) -> String {
-        String::from("A program to compose and edit timelines.")
+        format!("{}{} - Saga", self.path, if self.unsaved { " *" } else { "" })
+    }
+
+    fn update(&mut self, message: Message) {
+        match message {
+            Message::Select(path) => self.select(path),
+            Message::Fold(path) => {
+                if !self.folded.remove(&path) {
+                    self.folded.insert(path);
+                }
+            },
+            Message::NameChanged(name) => self.name = name,
+            Message::DatesChanged(dates) => self.dates = dates,
+            Message::CommandChanged(command) => self.command = command,
+            Message::Rename => self.apply(Ok(Program(vec![EvalCommand::NameEdit(Some(self.name.clone()))]))),
+            Message::Redate => {
+                let query = format!("date {}", self.dates);
+                self.apply(Program::parse_with(&query, self.doc.calendar()).map_err(|e|e.render(&query)));
+            },
+            Message::Run => {
+                let query = self.config.expand(&self.command);
+                match Program::parse_with(&query, self.doc.calendar()) {
+                    Ok(program) if program.is_help() => self.status = edit::help_text(),
+                    parsed => {
+                        self.apply(parsed.map_err(|e|e.render(&query)));
+                        self.command.clear();
+                    },
+                }
+            },
+            Message::Add => self.add(),
+            Message::Remove => self.apply(Ok(Program(vec![EvalCommand::Delete]))),
+            Message::Save => match self.doc.save_unchanged(&self.path, self.stamp) {
+                Ok(stamp) => {
+                    self.stamp = stamp;
+                    self.unsaved = false;
+                    self.status = format!("Wrote {}", self.path);
+                },
+                Err(e) => self.status = e.to_string(),
+            },
+        }
     }
 
-    fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
-        todo!();
+    fn view(&self) -> Element<'_, Message> {
+        let mut rows = vec![];
+        self.tree_rows(self.doc.get_data(), &mut vec![], &mut rows);
+        let selected = match self.selected.is_empty() {
+            true => "/".to_string(),
+            false => path_string(&self.selected),
+        };
+        let editor = column![
+            text(format!("Selected: {}", selected)),
+            text_input("Name", &self.name)
+                .on_input(Message::NameChanged)
+                .on_submit(Message::Rename),
+            text_input("Dates, like 14/10/1806 or 1805 - 1807", &self.dates)
+                .on_input(Message::DatesChanged)
+                .on_submit(Message::Redate),
+            row![
+                button(text("Add event")).on_press(Message::Add),
+                button(text("Remove")).on_press(Message::Remove),
+                button(text("Save")).on_press(Message::Save),
+            ].spacing(8),
+            text_input("Command, like shift +2w or help", &self.command)
+                .on_input(Message::CommandChanged)
+                .on_submit(Message::Run),
+        ].spacing(8);
+        let left = column![
+            scrollable(Column::with_children(rows).spacing(2)).height(Length::Fill),
+            editor,
+        ].spacing(8).width(Length::FillPortion(1));
+        let preview = svg(self.preview.clone())
+            .width(Length::FillPortion(2))
+            .height(Length::Fill);
+        column![
+            row![left, preview].spacing(8).height(Length::Fill),
+            text(&self.status),
+        ].spacing(8).padding(8).into()
     }
 
-    fn view(&self) -> Element<'_, Self::Message> {
-        todo!();
+    /// Selects the value at `path`, filling the boxes in with its name and dates.
+    fn select(&mut self, path: Vec<usize>) {
+        match self.doc.get_data().query_ref(&path) {
+            Ok(QueryRef::Node(node)) => {
+                self.name = node.name_in(None).unwrap_or_default().to_string();
+                self.dates.clear();
+            },
+            Ok(QueryRef::Event(event)) => {
+                self.name = event.name().to_string();
+                self.dates = event.dates().to_string();
+            },
+            Err(e) => {
+                self.status = e.to_string();
+                return;
+            },
+        }
+        self.selected = path;
     }
 
+    /// Evaluates `program` on the selected value, following it if it moves.
+    fn apply(&mut self, program: Result<Program, String>) {
+        let program = match program {
+            Ok(program) => program,
+            Err(e) => {
+                self.status = e;
+                return;
+            },
+        };
+        let mut path = self.selected.clone();
+        match program.eval_at(self.doc.get_data_mut(), &mut path) {
+            Ok(()) => {
+                self.status = format!("Done: {}", program.0.iter().map(|command|command.name()).collect::<Vec<&str>>().join("; "));
+                self.changed();
+            },
+            Err(e) => self.status = e.to_string(),
+        }
+        self.select(path);
+    }
+
+    /// Adds an event dated today to the selected node, or next to the
+    /// selected event, as `saga add` would, and selects it.
+    fn add(&mut self) {
+        let mut at = self.selected.clone();
+        if let Ok(QueryRef::Event(_)) = self.doc.get_data().query_ref(&at) {
+            at.pop();
+        }
+        let today = chrono::Local::now().naive_local();
+        let dates = today.format("%d/%m/%Y").to_string().parse()
+            .unwrap_or_else(|_|Dates::from_dts(today, None));
+        let query = path_string(&at);
+        match self.doc.add_events(&query, vec![Event::new(NEW_EVENT, dates)]) {
+            Ok(()) => {
+                if let Ok(QueryRef::Node(node)) = self.doc.get_data().query_ref(&at) {
+                    at.push(node.children().len());
+                }
+                self.folded.retain(|folded|!at.starts_with(folded));
+                self.changed();
+                self.select(at);
+                self.status = format!("Added {}", NEW_EVENT);
+            },
+            Err(e) => self.status = e.to_string(),
+        }
+    }
+
+    fn changed(&mut self) {
+        self.unsaved = true;
+        self.redraw();
+    }
+
+    fn redraw(&mut self) {
+        let drawn = self.doc.draw(&RenderOptions::default()).to_string();
+        self.preview = svg::Handle::from_memory(drawn.into_bytes());
+    }
+
+    /// One row per value under `node`, at `path`, indented by depth, leaving
+    /// out the children of folded nodes.
+    fn tree_rows<'a>(&'a self, node: &'a Node, path: &mut Vec<usize>, rows: &mut Vec<Element<'a, Message>>) {
+        let folded = self.folded.contains(path.as_slice());
+        let name = node.name_in(None).unwrap_or("(No name)");
+        let fold = button(text(if folded { "▸" } else { "▾" }))
+            .style(button::text)
+            .on_press(Message::Fold(path.clone()));
+        rows.push(self.tree_row(path, Some(fold.into()), name.to_string()));
+        if folded { return; }
+        let dates = self.doc.locale();
+        for (i, value) in node.children().iter().enumerate() {
+            path.push(i + 1);
+            match value {
+                Value::Node(kid) => self.tree_rows(kid, path, rows),
+                Value::Event(event) => {
+                    let label = format!("{}  {}", event.name(), dates.format_dates(event.dates()));
+                    rows.push(self.tree_row(path, None, label));
+                },
+            }
+            path.pop();
+        }
+    }
+
+    /// A row of the tree selecting the value at `path` when `label` is
+    /// clicked, after `fold` for nodes.
+    fn tree_row<'a>(&self, path: &[usize], fold: Option<Element<'a, Message>>, label: String) -> Element<'a, Message> {
+        let style = match path == self.selected.as_slice() {
+            true => button::primary,
+            false => button::text,
+        };
+        // Events line up with the names of the nodes beside them.
+        let fold = fold.unwrap_or_else(||Space::with_width(Length::Fixed(INDENT)).into());
+        row![
+            Space::with_width(Length::Fixed(INDENT * path.len() as f32)),
+            fold,
+            button(text(label)).style(style).on_press(Message::Select(path.to_vec())),
+        ].into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{App, Flags, Message, NEW_EVENT};
+    use super::super::config::Config;
+    use super::super::events::{Event, Node};
+    use super::super::saga::{SagaDoc, Stamp};
+
+    #[test]
+    fn test_editing() {
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            Event::new("Ulm", "20/10/1805".parse().unwrap()).into_value(),
+            Node::new(Some("Prussia".to_string()), vec![
+                Event::new("Jena", "14/10/1806".parse().unwrap()).into_value(),
+            ]).into_value(),
+        ]);
+        let flags = Flags { path: "wars.json".to_string(), doc, stamp: Stamp::of(b""), config: Config::default() };
+        let (mut app, _) = App::new(flags);
+        let names = |app: &App|app.doc.get_data().iter_events().map(|e|e.name().to_string()).collect::<Vec<String>>();
+        app.update(Message::Select(vec![2, 1]));
+        assert_eq!((app.name.as_str(), app.dates.as_str()), ("Jena", "14/10/1806"));
+        app.update(Message::NameChanged("Battle of Jena".to_string()));
+        app.update(Message::Rename);
+        app.update(Message::DatesChanged("13/10/1806 - 14/10/1806".to_string()));
+        app.update(Message::Redate);
+        app.update(Message::CommandChanged("shift +1d".to_string()));
+        app.update(Message::Run);
+        assert_eq!(app.doc.get_data().iter_events().nth(1).map(|e|e.dates().to_string()), Some("14/10/1806 - 15/10/1806".to_string()));
+        assert!(app.unsaved && app.command.is_empty());
+        // Added next to the selected event, and selected in its place.
+        app.update(Message::Add);
+        assert_eq!((app.selected.clone(), app.name.as_str()), (vec![2, 2], NEW_EVENT));
+        assert_eq!(names(&app), vec!["Ulm", "Battle of Jena", NEW_EVENT]);
+        app.update(Message::Remove);
+        assert_eq!(app.selected, vec![2]);
+        assert_eq!(names(&app), vec!["Ulm", "Battle of Jena"]);
+        // Bad input is reported, leaving the document alone.
+        app.update(Message::DatesChanged("not a date".to_string()));
+        app.update(Message::Redate);
+        assert!(!app.status.is_empty());
+        assert_eq!(names(&app), vec!["Ulm", "Battle of Jena"]);
+        app.update(Message::Fold(vec![2]));
+        assert!(app.folded.contains(&vec![2]));
+        app.update(Message::Fold(vec![2]));
+        assert!(app.folded.is_empty());
+    }
 }
@@ -0,0 +1,382 @@
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, Months, NaiveDate};
+use serde::{Serialize, Deserialize};
+
+use super::events::{to_stamp, Dates, Dt};
+use super::locale::DateFormat;
+
+/// Scheme used to label tick marks along a timeline.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum AxisLabels {
+    /// Plain dates, formatted by the document's `Locale` or `Calendar`.
+    #[default]
+    Calendar,
+    /// Fiscal years beginning on the first of `start_month` (1 = January),
+    /// named after the calendar year they end in, e.g. "FY2021 Q3".
+    FiscalYear { start_month: u32 },
+    /// ISO week numbers, e.g. "Week 12, 2021".
+    Week,
+    /// Whole units elapsed since `start`, counting from 1, e.g. "Year 12".
+    Epoch {
+        #[serde(with = "serde_with::rust::display_fromstr")]
+        start: Dates,
+        unit: EpochUnit,
+        /// Word placed before the count, defaults to the unit's name.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EpochUnit {
+    Years,
+    Months,
+    Weeks,
+    Days,
+}
+
+/// Ticks drawn by `Interval::Auto` are spread no closer than this many
+/// across the visible range.
+const AUTO_TICKS: i64 = 8;
+
+/// More ticks than this are never drawn, however small the interval asked for.
+const MAX_TICKS: usize = 500;
+
+/// Spacing between the tick marks on a node's line, or the document's
+/// gridlines. Ticks fall on calendar boundaries, e.g. the first of every
+/// third month for `Months(3)`. Written like "3d", "1m", "5y" or "auto".
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawInterval", into = "String")]
+pub enum Interval {
+    Days(u32),
+    Months(u32),
+    Years(u32),
+    /// Picks one of the above to suit the range shown, see `Interval::pick`.
+    Auto,
+}
+
+/// Created when a string isn't an `Interval`.
+#[derive(Debug, PartialEq)]
+pub struct IntervalParseError(pub String);
+
+/// What documents store for an interval. Older ones held a number of ticks,
+/// which is read as `Interval::Auto` unless negative.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawInterval {
+    Text(String),
+    Ticks(f64),
+}
+
+impl Interval {
+    /// Picks the smallest of the usual spacings that puts no more than
+    /// `AUTO_TICKS` ticks between `from` and `to`.
+    pub fn pick(from: &Dt, to: &Dt) -> Interval {
+        let span = (to_stamp(to) - to_stamp(from)).max(0);
+        Interval::usual()
+            .find(|interval|span / interval.approx_seconds() <= AUTO_TICKS || *interval == Interval::Years(100_000))
+            .unwrap_or(Interval::Years(100_000))
+    }
+
+    /// The longest of the usual spacings lasting no more than `seconds`, or
+    /// a day if none is that short.
+    pub fn fitting(seconds: i64) -> Interval {
+        Interval::usual()
+            .take_while(|interval|interval.approx_seconds() <= seconds)
+            .last()
+            .unwrap_or(Interval::Days(1))
+    }
+
+    /// Days, then months, then 1, 2 and 5 years, 10, 20 and 50, and so on.
+    fn usual() -> impl Iterator<Item = Interval> {
+        let days = [1, 2, 7, 14].into_iter().map(Interval::Days);
+        let months = [1, 2, 3, 6].into_iter().map(Interval::Months);
+        let years = (0..6).flat_map(|power|[1, 2, 5].map(|step|Interval::Years(step * 10u32.pow(power))));
+        days.chain(months).chain(years)
+    }
+
+    /// Reads like "1 month" or "10 years".
+    pub fn describe(&self) -> String {
+        let (count, unit) = match self {
+            Interval::Days(n)   => (*n, "day"),
+            Interval::Months(n) => (*n, "month"),
+            Interval::Years(n)  => (*n, "year"),
+            Interval::Auto      => return "automatic".to_string(),
+        };
+        match count {
+            1 => format!("1 {}", unit),
+            n => format!("{} {}s", n, unit),
+        }
+    }
+
+    /// Resolves `Auto` for the range between `from` and `to`, leaving
+    /// explicit intervals as they are.
+    pub fn resolve(&self, from: &Dt, to: &Dt) -> Interval {
+        match self {
+            Interval::Auto => Interval::pick(from, to),
+            other => *other,
+        }
+    }
+
+    /// Every calendar boundary of this interval from `from` to `to`, both
+    /// included. Days are counted from the start of the Common Era, months
+    /// and years from year 0, so the same ticks line up across ranges.
+    pub fn ticks(&self, from: &Dt, to: &Dt) -> Vec<Dt> {
+        let interval = self.resolve(from, to);
+        let mut ticks = vec![];
+        let mut next = interval.first(from);
+        while let Some(tick) = next {
+            if tick > *to || ticks.len() >= MAX_TICKS { break; }
+            ticks.push(tick);
+            next = interval.after(&tick);
+        }
+        ticks
+    }
+
+    /// The last boundary at or before `dt`.
+    pub fn floor(&self, dt: &Dt) -> Option<Dt> {
+        let back = chrono::Duration::seconds(2 * self.approx_seconds());
+        let mut floor = self.first(&dt.checked_sub_signed(back)?)?;
+        while let Some(next) = self.after(&floor).filter(|next|next <= dt) {
+            floor = next;
+        }
+        Some(floor)
+    }
+
+    /// Rough length, for choosing between intervals.
+    pub fn approx_seconds(&self) -> i64 {
+        const DAY: i64 = 86_400;
+        match self {
+            Interval::Days(n)   => *n as i64 * DAY,
+            Interval::Months(n) => *n as i64 * 30 * DAY,
+            Interval::Years(n)  => *n as i64 * 365 * DAY,
+            Interval::Auto      => DAY,
+        }
+    }
+
+    /// The first boundary at or after `dt`.
+    fn first(&self, dt: &Dt) -> Option<Dt> {
+        let midnight = |date: NaiveDate|date.and_hms_opt(0, 0, 0);
+        let boundary = match self {
+            Interval::Days(n) => {
+                let n = (*n).max(1) as i32;
+                let days = dt.date().num_days_from_ce();
+                NaiveDate::from_num_days_from_ce_opt(days - days.rem_euclid(n))
+            },
+            Interval::Months(n) => {
+                let n = (*n).max(1) as i32;
+                let months = dt.year() * 12 + dt.month0() as i32;
+                let months = months - months.rem_euclid(n);
+                NaiveDate::from_ymd_opt(months.div_euclid(12), months.rem_euclid(12) as u32 + 1, 1)
+            },
+            Interval::Years(n) => {
+                let n = (*n).max(1) as i32;
+                NaiveDate::from_ymd_opt(dt.year() - dt.year().rem_euclid(n), 1, 1)
+            },
+            Interval::Auto => None,
+        }.and_then(midnight)?;
+        match boundary < *dt {
+            true => self.after(&boundary),
+            false => Some(boundary),
+        }
+    }
+
+    /// The boundary following `tick`.
+    fn after(&self, tick: &Dt) -> Option<Dt> {
+        match self {
+            Interval::Days(n)   => tick.checked_add_days(Days::new((*n).max(1) as u64)),
+            Interval::Months(n) => tick.checked_add_months(Months::new((*n).max(1))),
+            Interval::Years(n)  => tick.checked_add_months(Months::new((*n).max(1).saturating_mul(12))),
+            Interval::Auto      => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Interval::Days(n)   => write!(f, "{}d", n),
+            Interval::Months(n) => write!(f, "{}m", n),
+            Interval::Years(n)  => write!(f, "{}y", n),
+            Interval::Auto      => write!(f, "auto"),
+        }
+    }
+}
+
+impl FromStr for Interval {
+    type Err = IntervalParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fail = ||IntervalParseError(s.to_string());
+        let s = s.trim().to_lowercase();
+        if s == "auto" { return Ok(Interval::Auto); }
+        let split = s.len().checked_sub(1).filter(|at|s.is_char_boundary(*at)).ok_or_else(fail)?;
+        let (count, unit) = s.split_at(split);
+        let count: u32 = count.parse().ok().filter(|n|*n > 0).ok_or_else(fail)?;
+        match unit {
+            "d" => Ok(Interval::Days(count)),
+            "m" => Ok(Interval::Months(count)),
+            "y" => Ok(Interval::Years(count)),
+            _ => Err(fail()),
+        }
+    }
+}
+
+impl From<Interval> for String {
+    fn from(interval: Interval) -> String {
+        interval.to_string()
+    }
+}
+
+impl TryFrom<RawInterval> for Interval {
+    type Error = IntervalParseError;
+    fn try_from(raw: RawInterval) -> Result<Self, Self::Error> {
+        match raw {
+            RawInterval::Text(text) => text.parse(),
+            RawInterval::Ticks(ticks) if ticks >= 0.0 => Ok(Interval::Auto),
+            RawInterval::Ticks(ticks) => Err(IntervalParseError(ticks.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for IntervalParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "'{}' isn't an interval, such as 3d, 1m, 5y or auto", self.0)
+    }
+}
+
+impl std::error::Error for IntervalParseError {}
+
+impl AxisLabels {
+    pub fn is_calendar(&self) -> bool {
+        *self == AxisLabels::Calendar
+    }
+
+    /// Produces the tick label for the given date.
+    pub fn label(&self, dt: &Dt, dates: &dyn DateFormat) -> String {
+        match self {
+            AxisLabels::Calendar => dates.format_date(dt),
+            AxisLabels::FiscalYear { start_month } => {
+                let start_month = (*start_month).clamp(1, 12) as i32;
+                // Months since the fiscal year began, in [0, 12).
+                let into_year = (dt.month() as i32 - start_month).rem_euclid(12);
+                let year = match (start_month, dt.month() as i32 >= start_month) {
+                    (1, _)    => dt.year(),
+                    (_, true) => dt.year() + 1,
+                    _         => dt.year(),
+                };
+                format!("FY{} Q{}", year, into_year / 3 + 1)
+            },
+            AxisLabels::Week => {
+                let week = dt.iso_week();
+                format!("Week {}, {}", week.week(), week.year())
+            },
+            AxisLabels::Epoch { start, unit, name } => {
+                let count = unit.elapsed(start.start(), dt) + 1;
+                let name = name.as_deref().unwrap_or(unit.name());
+                format!("{} {}", name, count)
+            },
+        }
+    }
+}
+
+impl EpochUnit {
+    fn name(&self) -> &'static str {
+        match self {
+            EpochUnit::Years  => "Year",
+            EpochUnit::Months => "Month",
+            EpochUnit::Weeks  => "Week",
+            EpochUnit::Days   => "Day",
+        }
+    }
+
+    /// Counts the whole units between `from` and `to`, negative if `to` is earlier.
+    fn elapsed(&self, from: &Dt, to: &Dt) -> i64 {
+        let months = || {
+            let raw = (to.year() - from.year()) as i64 * 12
+                + to.month() as i64 - from.month() as i64;
+            // Don't count a month that hasn't been completed yet.
+            match (raw > 0 && to.day() < from.day(), raw < 0 && to.day() > from.day()) {
+                (true, _) => raw - 1,
+                (_, true) => raw + 1,
+                _ => raw,
+            }
+        };
+        match self {
+            EpochUnit::Years  => months() / 12,
+            EpochUnit::Months => months(),
+            EpochUnit::Weeks  => (*to - *from).num_weeks(),
+            EpochUnit::Days   => (*to - *from).num_days(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AxisLabels, EpochUnit, Interval, IntervalParseError};
+    use super::super::events::Dates;
+    use super::super::locale::Locale;
+
+    #[test]
+    fn test_labels() {
+        let locale = Locale::fallback();
+        let date = |s: &str|*s.parse::<Dates>().unwrap().start();
+        let fiscal = AxisLabels::FiscalYear { start_month: 7 };
+        let epoch = AxisLabels::Epoch {
+            start: "15/03/2010 0:0".parse().unwrap(),
+            unit: EpochUnit::Years,
+            name: None,
+        };
+        let ok_cases = [
+            (AxisLabels::Calendar, "05/08/2021 0:0", "August 5, 2021"),
+            (fiscal.clone(), "05/08/2021 0:0", "FY2022 Q1"),
+            (fiscal.clone(), "05/06/2021 0:0", "FY2021 Q4"),
+            (AxisLabels::FiscalYear { start_month: 1 }, "05/06/2021 0:0", "FY2021 Q2"),
+            (AxisLabels::Week, "05/08/2021 0:0", "Week 31, 2021"),
+            (epoch.clone(), "15/03/2010 0:0", "Year 1"),
+            (epoch.clone(), "14/03/2021 0:0", "Year 11"),
+            (epoch.clone(), "15/03/2021 0:0", "Year 12"),
+        ];
+        for (axis, dt, expected) in ok_cases.iter() {
+            assert_eq!(axis.label(&date(dt), locale), *expected);
+        }
+    }
+
+    #[test]
+    fn test_intervals() {
+        let date = |s: &str|*s.parse::<Dates>().unwrap().start();
+        assert_eq!("3d".parse(), Ok(Interval::Days(3)));
+        assert_eq!(" 1M".parse(), Ok(Interval::Months(1)));
+        assert_eq!("auto".parse(), Ok(Interval::Auto));
+        for bad in ["5", "0y", "y", "2w", ""] {
+            assert_eq!(bad.parse::<Interval>(), Err(IntervalParseError(bad.to_string())));
+        }
+        // Older documents counted ticks, which now picks them automatically.
+        assert_eq!(serde_json::from_str::<Interval>("5.0").unwrap(), Interval::Auto);
+        assert!(serde_json::from_str::<Interval>("-1.0").is_err());
+        assert_eq!(serde_json::from_str::<Interval>("\"2m\"").unwrap(), Interval::Months(2));
+        assert_eq!(serde_json::to_string(&Interval::Years(10)).unwrap(), "\"10y\"");
+        let pick = |from: &str, to: &str|Interval::pick(&date(from), &date(to));
+        assert_eq!(pick("01/01/2021 0:0", "05/01/2021 0:0"), Interval::Days(1));
+        assert_eq!(pick("01/01/2021 0:0", "01/04/2021 0:0"), Interval::Days(14));
+        assert_eq!(pick("01/01/2021 0:0", "01/01/2024 0:0"), Interval::Months(6));
+        assert_eq!(pick("01/01/1800 0:0", "01/01/1900 0:0"), Interval::Years(20));
+        let ticks = |interval: Interval, from: &str, to: &str|interval.ticks(&date(from), &date(to))
+            .iter().map(|dt|dt.format("%d/%m/%Y").to_string()).collect::<Vec<String>>();
+        assert_eq!(ticks(Interval::Months(3), "15/02/2021 0:0", "01/10/2021 0:0"), vec!["01/04/2021", "01/07/2021", "01/10/2021"]);
+        assert_eq!(ticks(Interval::Years(5), "02/12/1805 0:0", "01/01/1821 0:0"), vec!["01/01/1810", "01/01/1815", "01/01/1820"]);
+        assert_eq!(ticks(Interval::Days(2), "03/01/2021 12:0", "08/01/2021 0:0"), vec!["04/01/2021", "06/01/2021", "08/01/2021"]);
+        assert_eq!(ticks(Interval::Days(1), "01/01/1000 0:0", "01/01/2000 0:0").len(), 500);
+        assert_eq!(ticks(Interval::Auto, "01/01/1800 0:0", "01/01/1900 0:0").first().map(String::as_str), Some("01/01/1800"));
+        assert_eq!(Interval::fitting(40 * 86_400), Interval::Months(1));
+        assert_eq!(Interval::fitting(12 * 365 * 86_400), Interval::Years(10));
+        assert_eq!(Interval::fitting(0), Interval::Days(1));
+        assert_eq!((Interval::Years(10).describe(), Interval::Days(1).describe()), ("10 years".to_string(), "1 day".to_string()));
+        let floor = |interval: Interval, dt: &str|interval.floor(&date(dt)).map(|dt|dt.format("%d/%m/%Y").to_string());
+        assert_eq!(floor(Interval::Years(10), "02/12/1805 0:0").as_deref(), Some("01/01/1800"));
+        assert_eq!(floor(Interval::Months(3), "01/04/2021 0:0").as_deref(), Some("01/04/2021"));
+        assert_eq!(floor(Interval::Auto, "01/04/2021 0:0"), None);
+    }
+}
@@ -0,0 +1,153 @@
+use super::axis::Interval;
+use super::events::{Dates, Event, Node};
+use super::saga::{SagaDoc, SagaDocError};
+
+/// Builds a `SagaDoc` without putting its tree of `Value`s together by hand.
+/// Started with `SagaDoc::builder()`, and otherwise the same as a blank
+/// document.
+pub struct DocBuilder {
+    root: NodeBuilder,
+}
+
+/// Builds a `Node`, either the root of a document or one nested in another.
+/// Dates are parsed as they are given, the first that fails being returned
+/// by `DocBuilder::build()`.
+pub struct NodeBuilder {
+    node: Node,
+    error: Option<SagaDocError>,
+}
+
+impl SagaDoc {
+    pub fn builder() -> DocBuilder {
+        DocBuilder { root: NodeBuilder::new() }
+    }
+}
+
+impl DocBuilder {
+    /// Names the document, by naming its root node.
+    pub fn title(self, title: &str) -> Self {
+        DocBuilder { root: self.root.name(title) }
+    }
+
+    /// Adds a node, built by `build`, to the root.
+    pub fn node(self, build: impl FnOnce(NodeBuilder) -> NodeBuilder) -> Self {
+        DocBuilder { root: self.root.node(build) }
+    }
+
+    /// Adds an event to the root, see `NodeBuilder::event`.
+    pub fn event(self, name: &str, dates: &str) -> Self {
+        DocBuilder { root: self.root.event(name, dates) }
+    }
+
+    /// Adds an event to the root, see `NodeBuilder::event_with`.
+    pub fn event_with(self, name: &str, dates: &str, edit: impl FnOnce(&mut Event)) -> Self {
+        DocBuilder { root: self.root.event_with(name, dates, edit) }
+    }
+
+    pub fn build(self) -> Result<SagaDoc, SagaDocError> {
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = self.root.finish()?;
+        Ok(doc)
+    }
+}
+
+impl NodeBuilder {
+    fn new() -> NodeBuilder {
+        NodeBuilder { node: Node::from_vec(vec![]), error: None }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.node.set_name(Some(name));
+        self
+    }
+
+    pub fn offset(mut self, offset: f64) -> Self {
+        self.node.set_offset(&offset);
+        self
+    }
+
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.node.set_scale(&scale);
+        self
+    }
+
+    /// Colors the values in the node from the document's scheme `name`.
+    pub fn scheme(mut self, name: &str) -> Self {
+        self.node.set_scheme(Some(name));
+        self
+    }
+
+    /// Draws a line through the node's events, with a tick mark every
+    /// `interval` if given.
+    pub fn line(mut self, interval: Option<Interval>) -> Self {
+        self.node.set_line(Some(interval));
+        self
+    }
+
+    /// Adds an event with `dates` in any form `Dates::parse_loose_at` reads,
+    /// apart from days relative to today.
+    pub fn event(self, name: &str, dates: &str) -> Self {
+        self.event_with(name, dates, |_|{})
+    }
+
+    /// Same as `event`, letting `edit` fill in the rest of the event, such as
+    /// its descriptions and status.
+    pub fn event_with(mut self, name: &str, dates: &str, edit: impl FnOnce(&mut Event)) -> Self {
+        if self.error.is_some() { return self; }
+        let dates = Dates::parse_loose_at(dates, None)
+            .map_err(SagaDocError::from)
+            .and_then(|dates|dates.checked().map_err(SagaDocError::from));
+        match dates {
+            Ok(dates) => {
+                let mut event = Event::new(name, dates);
+                edit(&mut event);
+                self.node.push(event.into_value());
+            },
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+
+    /// Adds a node, built by `build`.
+    pub fn node(mut self, build: impl FnOnce(NodeBuilder) -> NodeBuilder) -> Self {
+        if self.error.is_some() { return self; }
+        match build(NodeBuilder::new()).finish() {
+            Ok(node) => self.node.push(node.into_value()),
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+
+    fn finish(self) -> Result<Node, SagaDocError> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.node),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::events::{QueryRef, Status};
+    use super::super::saga::{SagaDoc, SagaDocError};
+
+    #[test]
+    fn test_builder() {
+        let doc = SagaDoc::builder()
+            .title("Napoleonic Wars")
+            .node(|n|n.name("Third Coalition")
+                .event("Ulm", "16/10/1805 - 19/10/1805")
+                .event_with("Austerlitz", "1805-12-02", |event|event.set_status(Some(Status::Done))))
+            .event("Jena", "14/10/1806")
+            .build()
+            .unwrap();
+        let data = doc.get_data();
+        assert_eq!(data.name_in(None), Some("Napoleonic Wars"));
+        assert!(matches!(data.query_ref(&[1]), Ok(QueryRef::Node(node)) if node.name_in(None) == Some("Third Coalition")));
+        assert!(matches!(data.query_ref(&[1, 2]), Ok(QueryRef::Event(event)) if event.status() == Some(Status::Done)));
+        assert!(matches!(data.query_ref(&[2]), Ok(QueryRef::Event(event)) if event.name() == "Jena"));
+        let bad = |dates: &str|SagaDoc::builder().node(|n|n.event("Bad", dates)).build().err();
+        assert!(matches!(bad("yesterday"), Some(SagaDocError::DtParse(_))));
+        assert!(matches!(bad("1806 - 1805"), Some(SagaDocError::InvertedDates(_))));
+    }
+}
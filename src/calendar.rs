@@ -0,0 +1,295 @@
+use chrono::Duration;
+use serde::{Serialize, Deserialize};
+
+use super::events::{Dates, Dt, Precision};
+use super::locale::DateFormat;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// A user defined calendar, for timelines set in invented worlds
+/// ("25 Rethe 3019 Third Age"). Dates are still stored as regular
+/// timestamps, this only changes how they are entered and presented.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Calendar {
+    /// Real date that corresponds to the first day of the first year.
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    epoch: Dates,
+    months: Vec<Month>,
+    /// Eras in chronological order. Years restart at 1 in each era.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    eras: Vec<Era>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Month {
+    name: String,
+    days: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Era {
+    name: String,
+    /// Length of the era, only the last era may be left open-ended.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    years: Option<u32>,
+}
+
+/// Created when a string can't be read as a date in a `Calendar`.
+#[derive(Debug, PartialEq)]
+pub enum CalendarError {
+    /// The calendar has no months, or a month has no days.
+    Invalid,
+    Empty,
+    NotANumber(String),
+    UnknownEra(String),
+    DayOutOfRange { day: u32, month: String, days: u32 },
+    /// The date is too far from the epoch to be represented.
+    OutOfRange,
+}
+
+/// A date broken into the calendar's own units.
+#[derive(Debug, PartialEq)]
+struct CalendarDate {
+    /// Year counted from the epoch, starting at 1.
+    year: i64,
+    /// Index into `Calendar::months`.
+    month: usize,
+    /// Day of the month, starting at 1.
+    day: u32,
+    /// Seconds since the start of the day.
+    seconds: i64,
+}
+
+impl Calendar {
+    /// Number of days in one year.
+    fn year_len(&self) -> i64 {
+        self.months.iter().map(|month|month.days as i64).sum()
+    }
+
+    fn is_valid(&self) -> bool {
+        !self.months.is_empty() && self.months.iter().all(|month|month.days > 0)
+    }
+
+    /// Splits a real date into years, months and days of this calendar.
+    fn split(&self, dt: &Dt) -> CalendarDate {
+        let seconds = (*dt - *self.epoch.start()).num_seconds();
+        let days = seconds.div_euclid(SECONDS_PER_DAY);
+        let mut day_of_year = days.rem_euclid(self.year_len());
+        let mut month = 0;
+        while day_of_year >= self.months[month].days as i64 {
+            day_of_year -= self.months[month].days as i64;
+            month += 1;
+        }
+        CalendarDate {
+            year: days.div_euclid(self.year_len()) + 1,
+            month,
+            day: day_of_year as u32 + 1,
+            seconds: seconds.rem_euclid(SECONDS_PER_DAY),
+        }
+    }
+
+    /// Inverse of `split()`.
+    fn join(&self, date: &CalendarDate) -> Result<Dt, CalendarError> {
+        let before_month: i64 = self.months[..date.month]
+            .iter()
+            .map(|month|month.days as i64)
+            .sum();
+        (date.year - 1).checked_mul(self.year_len())
+            .and_then(|days|days.checked_add(before_month + date.day as i64 - 1))
+            .and_then(Duration::try_days)
+            .and_then(|days|self.epoch.start().checked_add_signed(days))
+            .and_then(|dt|dt.checked_add_signed(Duration::try_seconds(date.seconds)?))
+            .ok_or(CalendarError::OutOfRange)
+    }
+
+    /// Splits an absolute year into an era and the year within it.
+    fn era_of(&self, year: i64) -> Option<(&Era, i64)> {
+        let mut year = year;
+        for (i, era) in self.eras.iter().enumerate() {
+            match era.years {
+                Some(len) if year > len as i64 && i + 1 < self.eras.len() => year -= len as i64,
+                _ => return Some((era, year)),
+            }
+        }
+        None
+    }
+
+    /// Turns a year within the named era back into an absolute year.
+    fn absolute_year(&self, year: i64, era_name: &str) -> Result<i64, CalendarError> {
+        let mut before = 0i64;
+        for era in self.eras.iter() {
+            if era.name.eq_ignore_ascii_case(era_name) {
+                return before.checked_add(year).ok_or(CalendarError::OutOfRange);
+            }
+            before += era.years.unwrap_or(0) as i64;
+        }
+        Err(CalendarError::UnknownEra(era_name.to_string()))
+    }
+
+    /// Parses a single date such as "25 Rethe 3019 Third Age 14:30". The day,
+    /// month, era and time may each be left out.
+    pub fn parse_date(&self, s: &str) -> Result<(Dt, Precision), CalendarError> {
+        if !self.is_valid() { return Err(CalendarError::Invalid); }
+        let mut words: Vec<&str> = s.split_whitespace().collect();
+        // Optional trailing time.
+        let mut seconds = 0;
+        let mut precision = Precision::Day;
+        if let Some((h, m)) = words.last().and_then(|last|last.split_once(':')) {
+            let parse = |s: &str|s.parse::<i64>().map_err(|_|CalendarError::NotANumber(s.to_string()));
+            seconds = parse(h)? * 3600 + parse(m)? * 60;
+            precision = Precision::Minute;
+            words.pop();
+        }
+        if words.is_empty() { return Err(CalendarError::Empty); }
+        // Optional leading day, then an optional month name.
+        let (day, month) = match (words[0].parse::<u32>(), self.find_month(&words[1..]), self.find_month(&words)) {
+            (Ok(day), Some((index, len)), _) => {
+                words.drain(..len + 1);
+                (day, index)
+            },
+            (_, _, Some((index, len))) => {
+                words.drain(..len);
+                precision = precision.min(Precision::Month);
+                (1, index)
+            },
+            _ => {
+                precision = precision.min(Precision::Year);
+                (1, 0)
+            },
+        };
+        // The year, then whatever is left is the era.
+        let year_word = words.first().ok_or(CalendarError::Empty)?;
+        let year = year_word.parse::<i64>()
+            .map_err(|_|CalendarError::NotANumber(year_word.to_string()))?;
+        let year = match words[1..].join(" ") {
+            era if era.is_empty() => year,
+            era => self.absolute_year(year, &era)?,
+        };
+        let days = self.months[month].days;
+        if day == 0 || day > days {
+            let name = self.months[month].name.clone();
+            return Err(CalendarError::DayOutOfRange { day, month: name, days });
+        }
+        Ok((self.join(&CalendarDate { year, month, day, seconds })?, precision))
+    }
+
+    /// Parses either a single date, a "start - end" range or an ongoing "start - now" span.
+    pub fn parse_dates(&self, s: &str) -> Result<Dates, CalendarError> {
+        match s.split_once(" - ") {
+            Some((left, right)) if Dates::is_ongoing_word(right) => Ok(Dates::ongoing(self.parse_date(left)?)),
+            Some((left, right)) => Ok(Dates::with_precision(self.parse_date(left)?, Some(self.parse_date(right)?))),
+            None => Ok(Dates::with_precision(self.parse_date(s)?, None)),
+        }
+    }
+
+    /// Finds the month whose (possibly multi-word) name starts `words`,
+    /// returning its index and how many words it spans.
+    fn find_month(&self, words: &[&str]) -> Option<(usize, usize)> {
+        self.months.iter().enumerate().find_map(|(i, month)|{
+            let len = month.name.split_whitespace().count();
+            let candidate = words.get(..len)?.join(" ");
+            candidate.eq_ignore_ascii_case(&month.name).then_some((i, len))
+        })
+    }
+}
+
+impl DateFormat for Calendar {
+    fn format_date(&self, dt: &Dt) -> String {
+        self.format_date_at(dt, Precision::Minute)
+    }
+
+    fn format_date_at(&self, dt: &Dt, precision: Precision) -> String {
+        if !self.is_valid() { return dt.format(precision.format()).to_string(); }
+        let date = self.split(dt);
+        let year = match self.era_of(date.year) {
+            Some((era, year)) => format!("{} {}", year, era.name),
+            None => date.year.to_string(),
+        };
+        let month = &self.months[date.month].name;
+        match (precision, date.seconds) {
+            (Precision::Year, _)  => year,
+            (Precision::Month, _) => format!("{} {}", month, year),
+            (Precision::Day, _) | (_, 0) => format!("{} {} {}", date.day, month, year),
+            (_, s) => format!("{} {} {} {:02}:{:02}", date.day, month, year, s / 3600, (s % 3600) / 60),
+        }
+    }
+}
+
+impl std::fmt::Display for CalendarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CalendarError::Invalid => write!(f, "the calendar has no months, or a month has no days"),
+            CalendarError::Empty => write!(f, "no date given"),
+            CalendarError::NotANumber(s) => write!(f, "'{}' isn't a number", s),
+            CalendarError::UnknownEra(s) => write!(f, "'{}' isn't one of the calendar's eras", s),
+            CalendarError::DayOutOfRange { day, month, days } => {
+                write!(f, "{} only has {} days, so there's no day {}", month, days, day)
+            },
+            CalendarError::OutOfRange => write!(f, "the date is too far from the calendar's epoch"),
+        }
+    }
+}
+
+impl std::error::Error for CalendarError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Calendar, CalendarError};
+    use super::super::locale::DateFormat;
+
+    fn shire() -> Calendar {
+        serde_json::from_str(r#"{
+            "epoch": "1/1/2000 0:0",
+            "months": [
+                { "name": "Afteryule", "days": 30 },
+                { "name": "Solmath", "days": 30 },
+                { "name": "Forelithe", "days": 30 }
+            ],
+            "eras": [
+                { "name": "Second Age", "years": 3441 },
+                { "name": "Third Age" }
+            ]
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn test_calendar_round_trip() {
+        let calendar = shire();
+        let ok_cases = [
+            ("1 Afteryule 1 Second Age", "1 Afteryule 1 Second Age"),
+            ("25 Solmath 3019 Third Age 14:30", "25 Solmath 3019 Third Age 14:30"),
+            ("Forelithe 12 Third Age", "Forelithe 12 Third Age"),
+            ("3441", "3441 Second Age"),
+            ("3442", "1 Third Age"),
+            ("0", "0 Second Age"),
+        ];
+        for (input, expected) in ok_cases.iter() {
+            let (dt, precision) = calendar.parse_date(input).unwrap();
+            assert_eq!(calendar.format_date_at(&dt, precision), *expected, "{}", input);
+        }
+        let (dt, _) = calendar.parse_date("3442").unwrap();
+        assert_eq!(calendar.format_date(&dt), "1 Afteryule 1 Third Age");
+        let dates = calendar.parse_dates("1 Solmath 5 Third Age - 2 Solmath 5 Third Age").unwrap();
+        assert_eq!((*dates.end().unwrap() - *dates.start()).num_days(), 1);
+    }
+
+    #[test]
+    fn test_calendar_errors() {
+        let calendar = shire();
+        let err_cases = [
+            ("", CalendarError::Empty),
+            ("3 Rethe 12", CalendarError::UnknownEra("Rethe 12".to_string())),
+            ("Solmath", CalendarError::Empty),
+            ("Solmath x", CalendarError::NotANumber("x".to_string())),
+            ("12 Fourth Age", CalendarError::UnknownEra("Fourth Age".to_string())),
+            ("31 Solmath 12", CalendarError::DayOutOfRange {
+                day: 31, month: "Solmath".to_string(), days: 30
+            }),
+            ("1 Afteryule 9000000000000000", CalendarError::OutOfRange),
+            ("300000000000", CalendarError::OutOfRange),
+        ];
+        for (input, expected) in err_cases.iter() {
+            assert_eq!(calendar.parse_date(input).unwrap_err(), *expected, "{}", input);
+        }
+    }
+}
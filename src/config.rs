@@ -0,0 +1,213 @@
+use std::{
+    collections::BTreeMap,
+    io::{Error as IoError, ErrorKind},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use serde::{Serialize, Deserialize};
+
+use super::MainError;
+
+/// File in the user's home directory holding their settings.
+pub const CONFIG_FILE: &str = ".saga.json";
+
+/// Deepest nesting of nodes allowed unless the config says otherwise. The
+/// JSON parser gives up at around 60 levels regardless.
+pub const DEFAULT_MAX_DEPTH: usize = 48;
+
+/// Node `saga log` adds entries under unless the config says otherwise.
+pub const DEFAULT_JOURNAL: &str = "Journal";
+
+/// Per-user settings, shared by every document.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// Words that stand for longer edit commands, e.g. `"done": "status done"`.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// Overrides `DEFAULT_MAX_DEPTH`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    /// Commit documents to git whenever a subcommand changes them, as if
+    /// `--commit` were always given. `merge` never commits, since git runs it
+    /// in the middle of a merge, and neither does `decrypt`, which would put
+    /// the plaintext in git's history.
+    #[serde(default)]
+    pub auto_commit: bool,
+    /// What `saga open` does with a document.
+    #[serde(default)]
+    pub open: Opener,
+    /// Overrides `DEFAULT_JOURNAL`, as a `/` separated list of node names.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub journal: Option<String>,
+}
+
+/// How `saga open` shows a document.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Opener {
+    /// Renders it to an HTML page with a table of contents, opened in the
+    /// default browser.
+    #[default]
+    Html,
+    /// Renders it to an SVG file, opened in the default viewer.
+    Svg,
+    /// Draws it in the terminal.
+    Term,
+    /// Starts the interactive editor on it, in the terminal.
+    Editor,
+    /// Opens it in a window, like `saga gui`.
+    Gui,
+}
+
+/// Created when a string isn't one of the `Opener` names.
+#[derive(Debug, PartialEq)]
+pub struct OpenerParseError(pub String);
+
+#[derive(Debug)]
+pub enum ConfigError {
+    NotAConfig(serde_json::Error),
+    IoError(IoError),
+}
+
+impl Config {
+    /// Reads the user's config file, falling back to the defaults if there isn't one.
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = match config_path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Config::from_json(&contents),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(ConfigError::IoError(e)),
+        }
+    }
+
+    pub fn from_json(contents: &str) -> Result<Config, ConfigError> {
+        serde_json::from_str(contents).map_err(ConfigError::NotAConfig)
+    }
+
+    /// Deepest nesting of nodes a document may have.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH)
+    }
+
+    /// Path of names to the node `saga log` adds entries under.
+    pub fn journal(&self) -> &str {
+        self.journal.as_deref().unwrap_or(DEFAULT_JOURNAL)
+    }
+
+    /// Replaces the first word of each `;` separated command with its alias,
+    /// if it has one. Aliases aren't expanded recursively.
+    pub fn expand(&self, query: &str) -> String {
+        query.split(';').map(|command|{
+            let trimmed = command.trim_start();
+            let (head, rest) = trimmed.split_once(char::is_whitespace).unwrap_or((trimmed, ""));
+            match self.aliases.get(head) {
+                Some(expansion) if rest.is_empty() => expansion.to_string(),
+                Some(expansion) => format!("{} {}", expansion, rest),
+                None => command.to_string(),
+            }
+        }).collect::<Vec<String>>().join(";")
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home|PathBuf::from(home).join(CONFIG_FILE))
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::NotAConfig(e) => write!(f, "bad config file ~/{}: {}", CONFIG_FILE, e),
+            ConfigError::IoError(e) => write!(f, "couldn't read ~/{}: {}", CONFIG_FILE, e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::NotAConfig(e) => Some(e),
+            ConfigError::IoError(e) => Some(e),
+        }
+    }
+}
+
+impl std::fmt::Display for OpenerParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "'{}' isn't one of: html, svg, term, editor, gui", self.0)
+    }
+}
+
+impl std::error::Error for OpenerParseError {}
+
+impl FromStr for Opener {
+    type Err = OpenerParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "html"   => Ok(Opener::Html),
+            "svg"    => Ok(Opener::Svg),
+            "term"   => Ok(Opener::Term),
+            "editor" => Ok(Opener::Editor),
+            "gui"    => Ok(Opener::Gui),
+            other => Err(OpenerParseError(other.to_string())),
+        }
+    }
+}
+
+impl From<ConfigError> for MainError {
+    fn from(err: ConfigError) -> Self {
+        MainError::Config(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Opener, OpenerParseError, DEFAULT_JOURNAL, DEFAULT_MAX_DEPTH};
+
+    #[test]
+    fn test_alias_expansion() {
+        let config = Config::from_json(r#"{"aliases": {"done": "status done", "fix": "+desc Fixed:"}}"#).unwrap();
+        let ok_cases = [
+            ("done", "status done"),
+            ("fix the date", "+desc Fixed: the date"),
+            ("name Foo; done", "name Foo;status done"),
+            ("name done", "name done"),
+        ];
+        for (left, right) in ok_cases.iter() {
+            assert_eq!(config.expand(left), *right);
+        }
+        assert!(Config::from_json("{}").unwrap().aliases.is_empty());
+    }
+
+    #[test]
+    fn test_max_depth() {
+        assert_eq!(Config::from_json("{}").unwrap().max_depth(), DEFAULT_MAX_DEPTH);
+        assert_eq!(Config::from_json(r#"{"max_depth": 5}"#).unwrap().max_depth(), 5);
+    }
+
+    #[test]
+    fn test_auto_commit() {
+        assert!(!Config::from_json("{}").unwrap().auto_commit);
+        assert!(Config::from_json(r#"{"auto_commit": true}"#).unwrap().auto_commit);
+    }
+
+    #[test]
+    fn test_journal() {
+        assert_eq!(Config::from_json("{}").unwrap().journal(), DEFAULT_JOURNAL);
+        assert_eq!(Config::from_json(r#"{"journal": "Lab/Notes"}"#).unwrap().journal(), "Lab/Notes");
+    }
+
+    #[test]
+    fn test_opener() {
+        assert_eq!(Config::from_json("{}").unwrap().open, Opener::Html);
+        assert_eq!(Config::from_json(r#"{"open": "term"}"#).unwrap().open, Opener::Term);
+        assert_eq!(Config::from_json(r#"{"open": "gui"}"#).unwrap().open, Opener::Gui);
+        assert!(Config::from_json(r#"{"open": "window"}"#).is_err());
+        assert_eq!(" Editor".parse::<Opener>(), Ok(Opener::Editor));
+        assert_eq!("GUI".parse::<Opener>(), Ok(Opener::Gui));
+        assert_eq!("window".parse::<Opener>(), Err(OpenerParseError("window".to_string())));
+    }
+}
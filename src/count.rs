@@ -0,0 +1,112 @@
+use std::str::FromStr;
+
+use chrono::Datelike;
+
+use super::events::{Node, QueryRef};
+use super::validate::path_string;
+
+/// What `count()` groups events by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Grouping {
+    /// The year each event starts in.
+    Year,
+    /// The node each event sits directly in.
+    Node,
+    /// The status of each event, if it has one.
+    Status,
+}
+
+/// Created when a string isn't one of the `Grouping` names.
+#[derive(Debug, PartialEq)]
+pub struct GroupingParseError(pub String);
+
+/// Counts the events under `node` by `by`, leaving out empty groups. Years
+/// and statuses are listed in order, nodes in the order they appear.
+pub fn count(node: &Node, by: Grouping) -> Vec<(String, usize)> {
+    let mut groups: Vec<(i64, String, usize)> = vec![];
+    for (path, event) in node.iter_events_with_paths() {
+        let (key, label) = match by {
+            Grouping::Year => {
+                let year = event.dates().start().year();
+                (year as i64, year.to_string())
+            },
+            Grouping::Node => (0, node_label(node, &path[..path.len() - 1])),
+            Grouping::Status => match event.status() {
+                Some(status) => (status as i64, status.to_string()),
+                None => (i64::MAX, "none".to_string()),
+            },
+        };
+        match groups.iter_mut().find(|(_, other, _)|*other == label) {
+            Some(group) => group.2 += 1,
+            None => groups.push((key, label, 1)),
+        }
+    }
+    // Stable, so nodes keep the order they were first seen in.
+    groups.sort_by_key(|(key, _, _)|*key);
+    groups.into_iter().map(|(_, label, n)|(label, n)).collect()
+}
+
+/// Names the node at `path` by its path and, if it has one, its name.
+pub fn node_label(node: &Node, path: &[usize]) -> String {
+    let at = match path.is_empty() {
+        true => "/".to_string(),
+        false => path_string(path),
+    };
+    match node.query_ref(path) {
+        Ok(QueryRef::Node(found)) => match found.name_in(None) {
+            Some(name) => format!("{} {}", at, name),
+            None => at,
+        },
+        _ => at,
+    }
+}
+
+impl std::fmt::Display for GroupingParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "'{}' isn't one of: year, node, status", self.0)
+    }
+}
+
+impl std::error::Error for GroupingParseError {}
+
+impl FromStr for Grouping {
+    type Err = GroupingParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "year"   => Ok(Grouping::Year),
+            "node"   => Ok(Grouping::Node),
+            "status" => Ok(Grouping::Status),
+            other => Err(GroupingParseError(other.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count, Grouping};
+    use super::super::events::{Event, Node, Status};
+
+    #[test]
+    fn test_count() {
+        let event = |name: &str, dates: &str, status: Option<Status>|{
+            let mut event = Event::new(name, dates.parse().unwrap());
+            event.set_status(status);
+            event.into_value()
+        };
+        let node = Node::from_vec(vec![
+            event("Jena", "14/10/1806", Some(Status::Done)),
+            Node::new(Some("Spain".to_string()), vec![
+                event("Bailén", "1808", None),
+                event("Corunna", "16/01/1809", Some(Status::Done)),
+            ]).into_value(),
+            event("Austerlitz", "02/12/1805", Some(Status::Planned)),
+        ]);
+        let labeled = |counts: &[(&str, usize)]|counts.iter()
+            .map(|(label, n)|(label.to_string(), *n))
+            .collect::<Vec<(String, usize)>>();
+        assert_eq!(count(&node, Grouping::Year), labeled(&[("1805", 1), ("1806", 1), ("1808", 1), ("1809", 1)]));
+        assert_eq!(count(&node, Grouping::Node), labeled(&[("/", 2), ("2 Spain", 2)]));
+        assert_eq!(count(&node, Grouping::Status), labeled(&[("planned", 1), ("done", 2), ("none", 1)]));
+        assert!("tag".parse::<Grouping>().is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    io::Error as IoError,
+    sync::OnceLock,
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+    XChaCha20Poly1305, XNonce,
+};
+
+use super::MainError;
+
+/// Every encrypted document begins with these bytes, which is how we tell
+/// them apart from plain JSON documents.
+pub const MAGIC: &[u8] = b"SAGA-ENCRYPTED-V1\n";
+/// Environment variable checked before prompting the user for a passphrase.
+pub const PASS_VAR: &str = "SAGA_PASSPHRASE";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+
+/// The passphrase is only asked for once per invocation.
+static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+#[derive(Debug)]
+pub enum CryptError {
+    /// The passphrase was wrong, or the file was tampered with.
+    BadPassphrase,
+    /// The file has our header, but is too short to be a real document.
+    Malformed,
+    /// The two passphrases given during confirmation didn't match.
+    Mismatch,
+    KeyDerivation(argon2::Error),
+    /// The cipher refused to encrypt the document.
+    Encrypt(chacha20poly1305::Error),
+    IoError(IoError),
+}
+
+/// Returns true if `bytes` looks like an encrypted saga document.
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Returns true if the file at `file_path` exists and is encrypted.
+pub fn is_encrypted_file(file_path: &str) -> bool {
+    use std::fs::File;
+    use std::io::Read;
+    let mut header = [0u8; MAGIC.len()];
+    File::open(file_path)
+        .and_then(|mut f|f.read_exact(&mut header))
+        .map(|_|is_encrypted(&header))
+        .unwrap_or(false)
+}
+
+/// Encrypts `plain` with a key derived from `pass`.
+/// Output layout: MAGIC | salt | nonce | ciphertext.
+pub fn encrypt(plain: &[u8], pass: &str) -> Result<Vec<u8>, CryptError> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = make_cipher(pass, &salt)?;
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let body = cipher.encrypt(&nonce, plain)
+        .map_err(CryptError::Encrypt)?;
+    let mut out = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + body.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// Reverses `encrypt()`.
+pub fn decrypt(blob: &[u8], pass: &str) -> Result<Vec<u8>, CryptError> {
+    let rest = blob.strip_prefix(MAGIC).ok_or(CryptError::Malformed)?;
+    if rest.len() < SALT_LEN + NONCE_LEN { return Err(CryptError::Malformed); }
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, body) = rest.split_at(NONCE_LEN);
+    let cipher = make_cipher(pass, salt)?;
+    cipher.decrypt(XNonce::from_slice(nonce), body)
+        .map_err(|_|CryptError::BadPassphrase)
+}
+
+/// Fetches the passphrase from the environment, or asks the user for it.
+/// When `confirm` is set the user must type it twice.
+pub fn passphrase(confirm: bool) -> Result<String, CryptError> {
+    if let Some(pass) = PASSPHRASE.get() {
+        return Ok(pass.clone());
+    }
+    let pass = match std::env::var(PASS_VAR) {
+        Ok(pass) => pass,
+        Err(_) => {
+            let pass = rpassword::prompt_password("Passphrase > ")
+                .map_err(CryptError::IoError)?;
+            if confirm {
+                let again = rpassword::prompt_password("Again > ")
+                    .map_err(CryptError::IoError)?;
+                if pass != again { return Err(CryptError::Mismatch); }
+            }
+            pass
+        },
+    };
+    Ok(PASSPHRASE.get_or_init(||pass).clone())
+}
+
+fn make_cipher(pass: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, CryptError> {
+    let mut key = [0u8; KEY_LEN];
+    Argon2::default()
+        .hash_password_into(pass.as_bytes(), salt, &mut key)
+        .map_err(CryptError::KeyDerivation)?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+impl std::fmt::Display for CryptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CryptError::BadPassphrase => write!(f, "wrong passphrase, or the file was tampered with"),
+            CryptError::Malformed => write!(f, "the file is too short to be an encrypted document"),
+            CryptError::Mismatch => write!(f, "the passphrases didn't match"),
+            CryptError::KeyDerivation(e) => write!(f, "couldn't derive a key from the passphrase: {}", e),
+            CryptError::Encrypt(e) => write!(f, "couldn't encrypt the document: {}", e),
+            CryptError::IoError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CryptError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CryptError::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<CryptError> for MainError {
+    fn from(err: CryptError) -> Self {
+        MainError::Crypt(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decrypt, encrypt, is_encrypted};
+
+    #[test]
+    fn test_round_trip() {
+        let plain = br#"{"x":1920.0,"y":1080.0}"#;
+        let blob = encrypt(plain, "hunter2").unwrap();
+        assert!(is_encrypted(&blob));
+        assert!(!is_encrypted(plain));
+        assert_eq!(decrypt(&blob, "hunter2").unwrap(), plain.to_vec());
+        assert!(decrypt(&blob, "hunter3").is_err());
+        assert!(decrypt(&blob[..20], "hunter2").is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::BTreeSet;
+
+use serde::de::{DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Value as Json};
+
+use super::MainError;
+use super::events::{Dates, Event, Node};
+use super::saga::SagaDoc;
+use super::validate::path_string;
+
+/// Loads a document that doesn't deserialize as is, filling in missing
+/// fields with their defaults, rewriting dates it can make sense of, and
+/// dropping events without one. Returns the document along with a
+/// description of every change made.
+pub fn repair(text: &str) -> Result<(SagaDoc, Vec<String>), MainError> {
+    let mut fixes: Vec<String> = duplicate_keys(text)?.into_iter()
+        .map(|key|format!("kept the last of the duplicated '{}' keys", key))
+        .collect();
+    let mut json: Json = serde_json::from_str(text)
+        .map_err(MainError::NotASagaDoc)?;
+    if let Json::Object(doc) = &mut json {
+        let defaults = serde_json::to_value(SagaDoc::blank())
+            .map_err(MainError::SerializeFail)?;
+        fill_defaults(doc, &defaults, "document", &mut fixes);
+        if let Some(data) = doc.get_mut("data") {
+            repair_nodes(data, &mut fixes)?;
+        }
+    }
+    let doc = serde_json::from_value(json)
+        .map_err(MainError::NotASagaDoc)?;
+    Ok((doc, fixes))
+}
+
+/// Repairs `root` and every node under it.
+fn repair_nodes(root: &mut Json, fixes: &mut Vec<String>) -> Result<(), MainError> {
+    let node_defaults = serde_json::to_value(Node::new(None, vec![]))
+        .map_err(MainError::SerializeFail)?;
+    let placeholder = "01/01/2000".parse::<Dates>().expect("Valid date.");
+    let event_defaults = serde_json::to_value(Event::new("(No name)", placeholder))
+        .map_err(MainError::SerializeFail)?;
+    let mut stack: Vec<(Vec<usize>, &mut Json)> = vec![(vec![], root)];
+    while let Some((path, node)) = stack.pop() {
+        let at = match path.is_empty() {
+            true => "root node".to_string(),
+            false => path_string(&path),
+        };
+        let node = match node {
+            Json::Object(node) => node,
+            other => {
+                fixes.push(format!("{}: replaced with an empty node", at));
+                *other = node_defaults.clone();
+                continue;
+            },
+        };
+        fill_defaults(node, &node_defaults, &at, fixes);
+        let children = match node.get_mut("children") {
+            Some(Json::Array(children)) => children,
+            _ => continue,
+        };
+        // Fixed where they are first, so that messages use the original paths.
+        let mut keep = vec![];
+        for (i, child) in children.iter_mut().enumerate() {
+            let at = path_string(&[&path[..], &[i + 1]].concat());
+            keep.push(match child {
+                Json::Object(child) => repair_value(child, &event_defaults, &at, fixes),
+                _ => {
+                    fixes.push(format!("{}: removed, since it's neither an event nor a node", at));
+                    false
+                },
+            });
+        }
+        let original: Vec<usize> = (0..keep.len()).filter(|i|keep[*i]).collect();
+        let mut keep = keep.into_iter();
+        children.retain(|_|keep.next().unwrap_or(true));
+        for (child, i) in children.iter_mut().zip(original) {
+            if child.get("type").and_then(Json::as_str) == Some("Node") {
+                stack.push(([&path[..], &[i + 1]].concat(), child));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Settles whether `value` is an event or a node, and repairs it if it's an
+/// event. Returns false if the value should be dropped.
+fn repair_value(value: &mut Map<String, Json>, event_defaults: &Json, at: &str, fixes: &mut Vec<String>) -> bool {
+    if value.get("type").and_then(Json::as_str).is_none() {
+        let kind = match value.contains_key("children") {
+            true => "Node",
+            false => "Event",
+        };
+        value.insert("type".to_string(), Json::from(kind));
+        fixes.push(format!("{}: marked as {}", at, kind.to_lowercase()));
+    }
+    if value.get("type").and_then(Json::as_str) != Some("Event") {
+        return true;
+    }
+    let dates = match value.get("datetime").and_then(Json::as_str) {
+        Some(dates) => dates.to_string(),
+        None => {
+            fixes.push(format!("{}: removed, since it has no dates", at));
+            return false;
+        },
+    };
+    if dates.parse::<Dates>().is_err() {
+        match guess_dates(&dates) {
+            Some(guess) => {
+                fixes.push(format!("{}: read dates '{}' as '{}'", at, dates, guess));
+                value.insert("datetime".to_string(), Json::from(guess));
+            },
+            None => {
+                fixes.push(format!("{}: removed, since its dates '{}' can't be read", at, dates));
+                return false;
+            },
+        }
+    }
+    fill_defaults(value, event_defaults, at, fixes);
+    true
+}
+
+/// Adds the fields of `defaults` missing from `object`, and replaces those of
+/// the wrong type. Numbers written as strings are converted instead.
+fn fill_defaults(object: &mut Map<String, Json>, defaults: &Json, at: &str, fixes: &mut Vec<String>) {
+    let defaults = match defaults {
+        Json::Object(defaults) => defaults,
+        _ => return,
+    };
+    for (key, default) in defaults.iter() {
+        match (object.get(key), default) {
+            (None, _) => {
+                fixes.push(format!("{}: added missing '{}'", at, key));
+                object.insert(key.clone(), default.clone());
+            },
+            // Optional fields, which can hold anything valid.
+            (Some(_), Json::Null) => {},
+            (Some(Json::String(s)), Json::Number(_)) if s.trim().parse::<f64>().is_ok() => {
+                let number = s.trim().parse::<f64>().expect("Checked above.");
+                fixes.push(format!("{}: read '{}' \"{}\" as a number", at, key, s));
+                object.insert(key.clone(), Json::from(number));
+            },
+            (Some(value), default) if !same_type(value, default) => {
+                fixes.push(format!("{}: replaced bad '{}' with the default", at, key));
+                object.insert(key.clone(), default.clone());
+            },
+            _ => {},
+        }
+    }
+}
+
+fn same_type(a: &Json, b: &Json) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+/// Rewrites dates in formats Saga doesn't read, such as ISO 8601, the way it
+/// would write them. Days relative to today aren't guessed, as they would
+/// change meaning from one day to the next.
+fn guess_dates(dates: &str) -> Option<String> {
+    Dates::parse_loose_at(dates, None).ok().map(|dates|dates.to_string())
+}
+
+/// Lists keys that appear more than once in the same object, which
+/// serde_json would otherwise quietly collapse.
+fn duplicate_keys(text: &str) -> Result<Vec<String>, MainError> {
+    let mut found = vec![];
+    let mut reader = serde_json::Deserializer::from_str(text);
+    Duplicates(&mut found).deserialize(&mut reader)
+        .map_err(MainError::NotASagaDoc)?;
+    Ok(found)
+}
+
+/// Walks any JSON value, noting duplicated keys along the way.
+struct Duplicates<'a>(&'a mut Vec<String>);
+
+impl<'de, 'a> DeserializeSeed<'de> for Duplicates<'a> {
+    type Value = ();
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for Duplicates<'a> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "any JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> { Ok(()) }
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> { Ok(()) }
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> { Ok(()) }
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> { Ok(()) }
+    fn visit_str<E>(self, _: &str) -> Result<(), E> { Ok(()) }
+    fn visit_unit<E>(self) -> Result<(), E> { Ok(()) }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while seq.next_element_seed(Duplicates(&mut *self.0))?.is_some() {}
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut seen = BTreeSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            map.next_value_seed(Duplicates(&mut *self.0))?;
+            if !seen.insert(key.clone()) {
+                self.0.push(key);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{guess_dates, repair};
+    use super::super::events::QueryRef;
+
+    #[test]
+    fn test_guess_dates() {
+        let cases = [
+            ("1805-12-02", Some("02/12/1805")),
+            ("1805-12-02T08:00 - 1805-12-02 16:30", Some("02/12/1805 08:00 - 02/12/1805 16:30")),
+            ("02.12.1805 - now", Some("02/12/1805 - now")),
+            ("02/12/1805", Some("02/12/1805")),
+            ("next tuesday", None),
+        ];
+        for (dates, expected) in cases.iter() {
+            assert_eq!(guess_dates(dates).as_deref(), *expected);
+        }
+    }
+
+    #[test]
+    fn test_repair() {
+        let text = r#"{
+            "x": 800, "x": "900",
+            "data": {
+                "children": [
+                    {"name": "Austerlitz", "datetime": "1805-12-02"},
+                    {"type": "Event", "name": "Undated"},
+                    {"name": "Prussia", "children": [
+                        {"type": "Event", "name": "Jena", "datetime": "14/10/1806"},
+                        {"type": "Event", "datetime": "1806"}
+                    ]},
+                    7
+                ]
+            }
+        }"#;
+        let (doc, fixes) = repair(text).unwrap();
+        for expected in [
+            "kept the last of the duplicated 'x' keys",
+            "document: read 'x' \"900\" as a number",
+            "document: added missing 'y'",
+            "document: added missing 'padding'",
+            "1: marked as event",
+            "1: read dates '1805-12-02' as '02/12/1805'",
+            "2: removed, since it has no dates",
+            "3: marked as node",
+            "3:2: added missing 'name'",
+            "4: removed, since it's neither an event nor a node",
+        ] {
+            assert!(fixes.iter().any(|fix|fix == expected), "missing fix: {}", expected);
+        }
+        let data = doc.get_data();
+        assert_eq!(data.children().len(), 2);
+        assert!(matches!(data.query_ref(&[2, 1]), Ok(QueryRef::Event(event)) if event.name() == "Jena"));
+        assert!(repair("[1, 2").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use super::events::{Event, Node};
+use super::saga::SagaDoc;
+use super::stats::{length, span};
+
+/// How far an event that happened slipped from its plan, in seconds.
+#[derive(Debug, PartialEq)]
+pub struct Slip {
+    pub name: String,
+    pub planned: Vec<usize>,
+    pub actual: Vec<usize>,
+    /// How much later it started than planned, negative if earlier.
+    pub start: i64,
+    /// How much longer it lasted than planned, negative if shorter.
+    pub duration: i64,
+}
+
+/// Every planned event matched with the one that happened, and those that
+/// couldn't be, by path.
+#[derive(Debug, Default, PartialEq)]
+pub struct Drift {
+    pub slips: Vec<Slip>,
+    /// Planned events that didn't happen.
+    pub missing: Vec<Vec<usize>>,
+    /// Events that happened without being planned.
+    pub unplanned: Vec<Vec<usize>>,
+}
+
+/// Matches the events of `planned` with those of `actual` by name, the nth
+/// of a name in one with the nth of it in the other, in document order.
+pub fn drift(planned: &Node, actual: &Node) -> Drift {
+    let mut theirs: BTreeMap<&str, Vec<(Vec<usize>, &Event)>> = BTreeMap::new();
+    for (path, event) in actual.iter_events_with_paths() {
+        theirs.entry(event.name()).or_default().push((path, event));
+    }
+    // Reversed, so the first of each name is popped first.
+    theirs.values_mut().for_each(|events|events.reverse());
+    let mut drift = Drift::default();
+    for (path, event) in planned.iter_events_with_paths() {
+        match theirs.get_mut(event.name()).and_then(|events|events.pop()) {
+            Some((actual, happened)) => {
+                let (planned_span, actual_span) = (span(event), span(happened));
+                drift.slips.push(Slip {
+                    name: event.name().to_string(),
+                    planned: path,
+                    actual,
+                    start: actual_span.0 - planned_span.0,
+                    duration: (actual_span.1 - actual_span.0) - (planned_span.1 - planned_span.0),
+                });
+            },
+            None => drift.missing.push(path),
+        }
+    }
+    drift.unplanned = theirs.into_values().flatten().map(|(path, _)|path).collect();
+    drift.unplanned.sort();
+    drift
+}
+
+impl Drift {
+    /// How many events started late, on time and early.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let count = |f: fn(&i64) -> bool|self.slips.iter().filter(|slip|f(&slip.start)).count();
+        (count(|start|*start > 0), count(|start|*start == 0), count(|start|*start < 0))
+    }
+
+    /// Average change in start, nothing if no events were matched.
+    pub fn average(&self) -> Option<i64> {
+        let total: i64 = self.slips.iter().map(|slip|slip.start).sum();
+        (!self.slips.is_empty()).then(||total / self.slips.len() as i64)
+    }
+
+    /// The event whose start moved furthest, either way.
+    pub fn worst(&self) -> Option<&Slip> {
+        self.slips.iter().max_by_key(|slip|slip.start.abs())
+    }
+
+    /// Copies of `planned` and `actual` ready to be drawn together with
+    /// `SagaDoc::overlay_scene` and annotated: the only description left is
+    /// how far each matched event of `actual` slipped.
+    pub fn annotate(&self, planned: &SagaDoc, actual: &SagaDoc) -> (SagaDoc, SagaDoc) {
+        let (mut planned, mut actual) = (planned.clone(), actual.clone());
+        planned.get_data_mut().visit_events_mut(&mut |_, event|event.set_descriptions(vec![]));
+        actual.get_data_mut().visit_events_mut(&mut |path, event|{
+            let slip = self.slips.iter().find(|slip|slip.actual == path);
+            event.set_descriptions(slip.map(|slip|slip.to_string()).into_iter().collect());
+        });
+        (planned, actual)
+    }
+}
+
+/// `seconds` as a change, like "+3 days" or "-2 hours".
+pub fn signed(seconds: i64) -> String {
+    match seconds {
+        0 => "±0".to_string(),
+        _ if seconds > 0 => format!("+{}", length(seconds)),
+        _ => format!("-{}", length(-seconds)),
+    }
+}
+
+impl std::fmt::Display for Slip {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "start {}, length {}", signed(self.start), signed(self.duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{drift, signed};
+    use super::super::events::{Event, Node};
+    use super::super::saga::SagaDoc;
+
+    #[test]
+    fn test_drift() {
+        let event = |name: &str, dates: &str|Event::new(name, dates.parse().unwrap()).into_value();
+        let planned = Node::from_vec(vec![
+            event("Design", "01/01/2020 - 10/01/2020"),
+            event("Review", "15/01/2020"),
+            event("Review", "15/02/2020"),
+            event("Launch", "01/03/2020"),
+        ]);
+        let actual = Node::from_vec(vec![
+            event("Design", "01/01/2020 - 14/01/2020"),
+            Node::new(Some("Later".to_string()), vec![
+                event("Review", "13/01/2020"),
+                event("Review", "20/02/2020"),
+                event("Party", "02/03/2020"),
+            ]).into_value(),
+        ]);
+        let found = drift(&planned, &actual);
+        let day = 86400;
+        let slips: Vec<(&str, Vec<usize>, i64, i64)> = found.slips.iter()
+            .map(|slip|(slip.name.as_str(), slip.actual.clone(), slip.start / day, slip.duration / day))
+            .collect();
+        assert_eq!(slips, vec![
+            ("Design", vec![1], 0, 4),
+            ("Review", vec![2, 1], -2, 0),
+            ("Review", vec![2, 2], 5, 0),
+        ]);
+        assert_eq!(found.missing, vec![vec![4]]);
+        assert_eq!(found.unplanned, vec![vec![2, 3]]);
+        assert_eq!(found.counts(), (1, 1, 1));
+        assert_eq!(found.average(), Some(day));
+        assert_eq!(found.worst().map(|slip|slip.start / day), Some(5));
+        assert_eq!(signed(-2 * day), "-2 days");
+        assert_eq!(found.slips[0].to_string(), "start ±0, length +4 days");
+        let (mut first, mut second) = (SagaDoc::blank(), SagaDoc::blank());
+        *first.get_data_mut() = planned;
+        *second.get_data_mut() = actual;
+        let (_, annotated) = found.annotate(&first, &second);
+        let descriptions: Vec<Vec<String>> = annotated.get_data().iter_events()
+            .map(|event|event.descriptions_in(None).to_vec())
+            .collect();
+        assert_eq!(descriptions[1], vec!["start -2 days, length ±0".to_string()]);
+        assert!(descriptions[3].is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+
+use super::events::{to_stamp, Node, Query, QueryRef};
+
+/// How alike two names have to be, from 0 to 1, for their events to be
+/// reported, see `similarity()`.
+pub const SIMILARITY: f64 = 0.8;
+
+/// Two events that are likely the same one, entered twice.
+#[derive(Debug, PartialEq)]
+pub struct Dupe {
+    /// Path of the event coming first in the document, which a merge keeps.
+    pub first: Vec<usize>,
+    /// Path of the other, which a merge removes.
+    pub second: Vec<usize>,
+    /// How alike their names are.
+    pub similarity: f64,
+}
+
+/// Finds every pair of events under `root` whose names are at least
+/// `SIMILARITY` alike and whose dates overlap, in document order.
+pub fn find(root: &Node) -> Vec<Dupe> {
+    let mut events: Vec<(Vec<usize>, String, i64, i64)> = root.iter_events_with_paths()
+        .map(|(path, event)|{
+            let (start, end) = event.dates().span();
+            let (start, end) = (to_stamp(&start), end.as_ref().map(to_stamp));
+            (path, normalize(event.name()), start, end.unwrap_or(start))
+        })
+        .collect();
+    events.sort_by_key(|(_, _, start, _)|*start);
+    let mut dupes = vec![];
+    for (i, (path, name, _, end)) in events.iter().enumerate() {
+        // Sorted by start, so the rest start after this one has ended.
+        for (other, other_name, _, _) in events[i + 1..].iter().take_while(|(_, _, start, _)|start <= end) {
+            let similarity = similarity(name, other_name);
+            if similarity >= SIMILARITY {
+                let (first, second) = match path < other {
+                    true => (path.clone(), other.clone()),
+                    false => (other.clone(), path.clone()),
+                };
+                dupes.push(Dupe { first, second, similarity });
+            }
+        }
+    }
+    dupes.sort_by(|a, b|(&a.first, &a.second).cmp(&(&b.first, &b.second)));
+    dupes
+}
+
+/// Merges the second event of each of `dupes` into the first, and removes
+/// it, returning how many were merged. Dupes naming an event merged away
+/// already are merged into the event it went into instead.
+pub fn merge(root: &mut Node, dupes: &[&Dupe]) -> usize {
+    // Each removed event, and the one it was merged into.
+    let mut merged: BTreeMap<Vec<usize>, Vec<usize>> = BTreeMap::new();
+    for dupe in dupes {
+        let mut into = &dupe.first;
+        while let Some(next) = merged.get(into) {
+            into = next;
+        }
+        if *into == dupe.second || merged.contains_key(&dupe.second) {
+            continue;
+        }
+        let into = into.clone();
+        let other = match root.query_ref(&dupe.second) {
+            Ok(QueryRef::Event(event)) => event.clone(),
+            _ => continue,
+        };
+        if let Ok(Query::Event(event)) = root.query_mut(&into) {
+            event.merge(other);
+            merged.insert(dupe.second.clone(), into);
+        }
+    }
+    // Last first, so that removing one doesn't move the others.
+    for path in merged.keys().rev() {
+        let _ = root.replace(path, vec![]);
+    }
+    merged.len()
+}
+
+/// Lowercases `name` and keeps only its letters and digits, one space
+/// between each word, so punctuation and spacing don't tell names apart.
+fn normalize(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char|!c.is_alphanumeric())
+        .filter(|word|!word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// How alike `a` and `b` are, from 0 for nothing in common to 1 for equal,
+/// as the share of characters left alone by the fewest edits between them.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let longest = a.len().max(b.len());
+    if longest == 0 { return 1.0; }
+    1.0 - distance(&a, &b) as f64 / longest as f64
+}
+
+/// Levenshtein distance: the fewest insertions, deletions and substitutions
+/// turning `a` into `b`.
+fn distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substituted = diagonal + (x != y) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, merge, normalize, similarity, Dupe};
+    use super::super::events::{Event, Node, QueryRef};
+
+    #[test]
+    fn test_dupes() {
+        assert_eq!(normalize("  Battle of JENA!"), "battle of jena");
+        assert_eq!(similarity("kitten", "sitting"), 1.0 - 3.0 / 7.0);
+        assert_eq!(similarity("", ""), 1.0);
+        let mut described = Event::new("Jena-Auerstedt", "14/10/1806".parse().unwrap());
+        described.add_description("Prussia is routed.");
+        let mut root = Node::from_vec(vec![
+            Event::new("Battle of Jena", "1806".parse().unwrap()).into_value(),
+            Event::new("Ulm", "20/10/1805".parse().unwrap()).into_value(),
+            Node::new(Some("Imported".to_string()), vec![
+                Event::new("battle of  jena", "14/10/1806".parse().unwrap()).into_value(),
+                // Alike, but years apart.
+                Event::new("Battle of Jena", "1813".parse().unwrap()).into_value(),
+                Event::new("Battle of Jena.", "14/10/1806".parse().unwrap()).into_value(),
+                described.into_value(),
+            ]).into_value(),
+        ]);
+        let dupes = find(&root);
+        let pairs: Vec<(Vec<usize>, Vec<usize>)> = dupes.iter()
+            .map(|dupe|(dupe.first.clone(), dupe.second.clone()))
+            .collect();
+        assert_eq!(pairs, vec![
+            (vec![1], vec![3, 1]),
+            (vec![1], vec![3, 3]),
+            (vec![3, 1], vec![3, 3]),
+        ]);
+        assert!(dupes.iter().all(|dupe|dupe.similarity == 1.0));
+        // The last merges into the first, by way of the one it went into.
+        let picked: Vec<&Dupe> = vec![&dupes[0], &dupes[2]];
+        assert_eq!(merge(&mut root, &picked), 2);
+        let names: Vec<&str> = root.iter_events().map(|event|event.name()).collect();
+        assert_eq!(names, vec!["Battle of Jena", "Ulm", "Battle of Jena", "Jena-Auerstedt"]);
+        assert!(find(&root).is_empty());
+        match root.query_ref(&[1]) {
+            Ok(QueryRef::Event(event)) => assert!(event.dates().end().is_some()),
+            _ => panic!("The first event is kept, spanning those merged into it."),
+        }
+    }
+}
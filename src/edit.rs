@@ -1,36 +1,72 @@
 
 use std::{
     num::{ParseFloatError, ParseIntError},
-    str::{
-        FromStr,
-        SplitAsciiWhitespace as SplitAscii,
-    },
+    ops::Range,
+    str::FromStr,
 };
 
+use chrono::Duration;
+use log::warn;
+use regex::Regex;
+
 use super::{
     MainError,
-    events::{Dates, DtParseError, Event, Node, Query},
+    axis::{Interval, IntervalParseError},
+    calendar::{Calendar, CalendarError},
+    saga::{ColorParseError, ColorRef},
+    events::{parse_duration, Dates, Dt, FORMAT, DtParseError, DurationParseError, Event, InvertedDates, Node, PathFail, Precision, Query, Status, StatusParseError},
 };
 
 #[derive(Debug)]
 pub enum ValueType { Node, Event }
 
-pub type EvalResult = Result<(), EvalError>;
+pub type EvalResult<T = ()> = Result<T, EvalError>;
 #[derive(Debug)]
 pub enum EvalError {
     NotApplicable(ValueType, Command),
     IndexError{index:usize, len:usize},
+    NotFound(PathFail),
+    /// The date given to `split` isn't inside the event's span.
+    NotInSpan(Dt),
+    /// The child at this index is a node where an event was needed.
+    NotAnEvent(usize),
+    /// `merge` was given the same index twice.
+    SameChild(usize),
 }
 
+/// Byte offsets of the part of a query that a `ParseError` blames.
+pub type Span = Range<usize>;
+
+/// Why a query couldn't be parsed, and where.
 #[derive(Debug, PartialEq)]
-pub enum ParseError {
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseErrorKind {
     MissingCommand,
-    MissingArgument,
+    /// Names what was expected, like `INDEX` in `-desc INDEX`.
+    MissingArgument(&'static str),
+    /// The command, and the tokens left over after it.
     ExtraArgument(String, String),
     UnknownCommand(String, Option<String>),
     NotAFloat(ParseFloatError),
     NotAInt(ParseIntError),
     NotADT(DtParseError),
+    NotAStatus(StatusParseError),
+    NotACalendarDate(CalendarError),
+    InvertedDates(InvertedDates),
+    NotARegex(regex::Error),
+    NotADuration(DurationParseError),
+    /// Time can only be stretched by factors above zero.
+    NotAFactor(f64),
+    NotAColor(ColorParseError),
+    NotAnInterval(IntervalParseError),
+    /// `help` was given alongside other commands, which it would stop
+    /// from running.
+    HelpNotAlone,
 }
 
 #[derive(Debug, PartialEq)]
@@ -53,10 +89,51 @@ pub enum Command {
     DescAdd(Option<String>),
     DescSub(usize),
     DescEdit(usize, Option<String>),
-    LineEdit(Option<Option<f64>>),
+    LineEdit(Option<Option<Interval>>),
+    /// Declares the dates a node's line covers, or leaves it to its events.
+    LineSpan(Option<Dates>),
     Offset(f64),
+    /// Added to the current offset, negative to subtract.
+    OffsetAdd(f64),
     Scale(f64),
+    /// Added to the current scale, negative to subtract.
+    ScaleAdd(f64),
     DateEdit(Dates),
+    StatusEdit(Option<Status>),
+    /// Sets the layer an event is drawn on, 0 to reset it.
+    LayerEdit(i32),
+    /// Substitutes every match in the names and descriptions.
+    Replace(Pattern, String),
+    /// Moves the dates of an event, or of every event under a node.
+    Shift(Duration),
+    /// Stretches dates away from an anchor, which defaults to the earliest start.
+    TimeScale(f64, Option<Dt>),
+    /// Copies the selected value next to itself, optionally shifting the copy.
+    Dup(Option<Duration>),
+    /// Swaps the selected value with the sibling before it.
+    MoveUp,
+    /// Swaps the selected value with the sibling after it.
+    MoveDown,
+    /// Moves the selected value to a 1-based position among its siblings.
+    MoveTo(usize),
+    /// Puts the selected value inside a new node with the given name.
+    Wrap(Option<String>),
+    /// Replaces the selected node with its children.
+    Unwrap,
+    /// Removes the selected value, selecting its parent.
+    Delete,
+    /// Cuts the selected span in two at the given date.
+    Split(Dt, Precision),
+    /// Combines two child events of the selected node into one.
+    Merge(usize, usize),
+    ColorEdit(Option<ColorRef>),
+    /// Names the color scheme used under a node.
+    SchemeEdit(Option<String>),
+    StyleEdit(Option<String>),
+    /// Hides or shows everything under a node.
+    Collapse(bool),
+    /// Names the SVG file drawn at the start of a node.
+    ImageEdit(Option<String>),
     // NodeAdd(NodePath, Box<Node>),
     // NodeSub(usize),
     // Copy(NodePath),              // from <selected@path> and push into <register>,
@@ -65,6 +142,272 @@ pub enum Command {
     // Move(NodePath, NodePath),    // from <reg[index]> to <reg[index]>.
 }
 
+/// Regex used by `replace`, compared by its source text.
+#[derive(Clone, Debug)]
+pub struct Pattern(pub Regex);
+
+/// Commands separated by `;`, evaluated in order against the same query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program(pub Vec<Command>);
+
+/// Reference for one edit command, used to generate the `help` output.
+pub struct CommandDoc {
+    pub name: &'static str,
+    /// Shorter names accepted in place of `name`.
+    pub aliases: &'static [&'static str],
+    /// Each accepted form of the command, paired with what it does.
+    pub forms: &'static [(&'static str, &'static str)],
+    pub example: &'static str,
+}
+
+pub const COMMAND_DOCS: &[CommandDoc] = &[
+    CommandDoc {
+        name: "name",
+        aliases: &["n"],
+        forms: &[
+            ("name [TEXT]", "Set the name. Nodes may have no name."),
+            ("-name", "Remove a node's name."),
+        ],
+        example: "name Battle of Austerlitz",
+    },
+    CommandDoc {
+        name: "desc",
+        aliases: &["d"],
+        forms: &[
+            ("+desc TEXT", "Add a description to an event."),
+            ("desc INDEX TEXT", "Replace the description at INDEX."),
+            ("-desc INDEX", "Delete the description at INDEX."),
+        ],
+        example: "+desc Napoleon's greatest victory.",
+    },
+    CommandDoc {
+        name: "date",
+        aliases: &["dt"],
+        forms: &[
+            ("date DATE", "Set an event's date to a point in time."),
+            ("date DATE - DATE", "Set an event's date to a span. Use 'now' as the end of ongoing spans."),
+        ],
+        example: "date 2/12/1805 8:00 - 2/12/1805 16:30",
+    },
+    CommandDoc {
+        name: "shift",
+        aliases: &[],
+        forms: &[
+            ("shift DURATION", "Move an event's dates, or those of every event under a node, by DURATION: e.g. +3d, -2w, 12h or 30min."),
+        ],
+        example: "shift +3d",
+    },
+    CommandDoc {
+        name: "dup",
+        aliases: &[],
+        forms: &[
+            ("dup [DURATION]", "Copy the selected event or node as its next sibling, shifting the copy by DURATION."),
+        ],
+        example: "dup +1w",
+    },
+    CommandDoc {
+        name: "up",
+        aliases: &[],
+        forms: &[("up", "Move the selected value before its previous sibling.")],
+        example: "up",
+    },
+    CommandDoc {
+        name: "down",
+        aliases: &[],
+        forms: &[("down", "Move the selected value after its next sibling.")],
+        example: "down",
+    },
+    CommandDoc {
+        name: "to",
+        aliases: &[],
+        forms: &[("to INDEX", "Move the selected value to INDEX among its siblings.")],
+        example: "to 1",
+    },
+    CommandDoc {
+        name: "wrap",
+        aliases: &[],
+        forms: &[("wrap [NAME]", "Put the selected value inside a new node called NAME.")],
+        example: "wrap Napoleonic Wars",
+    },
+    CommandDoc {
+        name: "unwrap",
+        aliases: &[],
+        forms: &[("unwrap", "Replace the selected node with its children.")],
+        example: "unwrap",
+    },
+    CommandDoc {
+        name: "delete",
+        aliases: &["rm"],
+        forms: &[("delete", "Remove the selected event or node, and everything in it, then select its parent.")],
+        example: "delete",
+    },
+    CommandDoc {
+        name: "split",
+        aliases: &[],
+        forms: &[("split DATE", "Cut an event's span in two at DATE, numbering the names of the parts.")],
+        example: "split 1/6/1805 0:00",
+    },
+    CommandDoc {
+        name: "merge",
+        aliases: &[],
+        forms: &[("merge INDEX INDEX", "Combine two events in a node into one spanning both, keeping all descriptions.")],
+        example: "merge 1 2",
+    },
+    CommandDoc {
+        name: "timescale",
+        aliases: &[],
+        forms: &[
+            ("timescale FACTOR [DATE]", "Stretch the dates under a node by FACTOR, away from DATE or else the earliest start."),
+        ],
+        example: "timescale 1.5 1/3/2024 9:00",
+    },
+    CommandDoc {
+        name: "status",
+        aliases: &[],
+        forms: &[
+            ("status STATUS", "Set an event's status: planned, in-progress, done or cancelled."),
+            ("-status", "Remove an event's status."),
+        ],
+        example: "status done",
+    },
+    CommandDoc {
+        name: "layer",
+        aliases: &[],
+        forms: &[
+            ("layer N", "Draw an event over those on layers below N, and under those above. Negative N sends it below the rest."),
+            ("-layer", "Put an event back on layer 0, with the rest."),
+        ],
+        example: "layer 1",
+    },
+    CommandDoc {
+        name: "replace",
+        aliases: &[],
+        forms: &[
+            ("replace REGEX [TEXT]", "Replace matches of REGEX in every name and description with TEXT. $1 inserts the first group."),
+        ],
+        example: "replace (?i)napoleon Bonaparte",
+    },
+    CommandDoc {
+        name: "color",
+        aliases: &[],
+        forms: &[
+            ("color #RRGGBB", "Set the color of a node or event."),
+            ("color SCHEME", "Use one of the document's color schemes."),
+            ("-color", "Go back to the default color."),
+        ],
+        example: "color #c3b2a4",
+    },
+    CommandDoc {
+        name: "scheme",
+        aliases: &[],
+        forms: &[
+            ("scheme NAME", "Color the values in a node from the document's scheme NAME, in turn."),
+            ("-scheme", "Go back to the scheme of the node above."),
+        ],
+        example: "scheme autumn",
+    },
+    CommandDoc {
+        name: "style",
+        aliases: &[],
+        forms: &[
+            ("style NAME", "Set the style of a node."),
+            ("-style", "Go back to the default style."),
+        ],
+        example: "style dashed",
+    },
+    CommandDoc {
+        name: "collapse",
+        aliases: &[],
+        forms: &[
+            ("collapse", "Hide everything under a node, printing it as one line and drawing it as one band."),
+            ("-collapse", "Show everything under a node again."),
+        ],
+        example: "collapse",
+    },
+    CommandDoc {
+        name: "image",
+        aliases: &[],
+        forms: &[
+            ("image PATH", "Draw the SVG file at PATH, relative to the document, just before a node's events start."),
+            ("-image", "Stop drawing a node's image."),
+        ],
+        example: "image flags/prussia.svg",
+    },
+    CommandDoc {
+        name: "line",
+        aliases: &[],
+        forms: &[
+            ("line [INTERVAL]", "Draw a line under a node, optionally with a tick every INTERVAL, like 3d, 1m, 5y or auto."),
+            ("-line", "Stop drawing a node's line."),
+        ],
+        example: "line 1y",
+    },
+    CommandDoc {
+        name: "cover",
+        aliases: &[],
+        forms: &[
+            ("cover DATE - DATE", "Declare the dates a node's line covers, to catch events falling outside it."),
+            ("-cover", "Let a node's line cover its events again."),
+        ],
+        example: "cover 1803 - 1815",
+    },
+    CommandDoc {
+        name: "offset",
+        aliases: &["o"],
+        forms: &[
+            ("offset N", "Set a node's vertical offset."),
+            ("+offset N", "Add N to a node's vertical offset."),
+            ("-offset N", "Subtract N from a node's vertical offset."),
+            ("-offset", "Reset a node's vertical offset to 0."),
+        ],
+        example: "offset 0.5",
+    },
+    CommandDoc {
+        name: "scale",
+        aliases: &[],
+        forms: &[
+            ("scale N", "Set a node's vertical scaling."),
+            ("+scale N", "Add N to a node's vertical scaling."),
+            ("-scale N", "Subtract N from a node's vertical scaling."),
+            ("-scale", "Reset a node's vertical scaling to 1."),
+        ],
+        example: "scale 2",
+    },
+    CommandDoc {
+        name: "help",
+        aliases: &[],
+        forms: &[("help", "Show this reference.")],
+        example: "help",
+    },
+    CommandDoc {
+        name: "exit",
+        aliases: &[],
+        forms: &[("exit", "Leave the editor.")],
+        example: "exit",
+    },
+];
+
+/// Produces the reference printed by the `help` command.
+pub fn help_text() -> String {
+    let width = COMMAND_DOCS.iter()
+        .flat_map(|doc|doc.forms.iter())
+        .map(|(form, _)|form.len())
+        .max()
+        .unwrap_or(0);
+    COMMAND_DOCS.iter().map(|doc|{
+        let mut lines = match doc.aliases.is_empty() {
+            true => vec![doc.name.to_string()],
+            false => vec![format!("{} ({})", doc.name, doc.aliases.join(", "))],
+        };
+        doc.forms.iter()
+            .map(|(form, about)|format!("  {:width$}  {}", form, about, width = width))
+            .for_each(|line|lines.push(line));
+        lines.push(format!("  e.g. {}", doc.example));
+        lines.join("\n")
+    }).collect::<Vec<String>>().join("\n\n")
+        + "\n\nSeparate commands with ';' to run several at once, e.g. name Foo; +desc bar"
+}
+
 impl Command {
 
     pub fn is_exit(&self) -> bool {
@@ -81,6 +424,68 @@ impl Command {
         }
     }
 
+    /// Evaluates self on the value at `path` under `root`. Unlike the other
+    /// eval functions this can change the value's parent, which `dup` and
+    /// the moving commands need. `path` follows the value if it moves.
+    pub fn eval_at(&self, root: &mut Node, path: &mut Vec<usize>) -> EvalResult {
+        let index = path.last().copied().unwrap_or(0);
+        match self {
+            Command::Dup(shift) => {
+                let mut copy = root.duplicate(path)
+                    .map_err(EvalError::NotFound)?;
+                match shift {
+                    Some(by) => Command::Shift(*by).eval_at(root, &mut copy),
+                    None => Ok(()),
+                }
+            },
+            Command::MoveUp => {
+                *path = root.move_to(path, index.saturating_sub(1))?;
+                Ok(())
+            },
+            Command::MoveDown => {
+                *path = root.move_to(path, index + 1)?;
+                Ok(())
+            },
+            Command::MoveTo(to) => {
+                *path = root.move_to(path, *to)?;
+                Ok(())
+            },
+            Command::Wrap(name) => {
+                *path = root.wrap(path, name.clone())
+                    .map_err(EvalError::NotFound)?;
+                Ok(())
+            },
+            Command::Split(at, precision) => {
+                let (first, second) = match root.query_mut(path).map_err(EvalError::NotFound)? {
+                    Query::Event(event) => event.split_at(*at, *precision)
+                        .ok_or(EvalError::NotInSpan(*at))?,
+                    Query::Node(_) => return Err(EvalError::NotApplicable(ValueType::Node, self.clone())),
+                };
+                root.replace(path, vec![first.into_value(), second.into_value()])
+                    .map_err(EvalError::NotFound)
+            },
+            Command::Delete => {
+                root.replace(path, vec![])
+                    .map_err(EvalError::NotFound)?;
+                path.pop();
+                Ok(())
+            },
+            Command::Unwrap => {
+                if let Query::Event(_) = root.query_mut(path).map_err(EvalError::NotFound)? {
+                    return Err(EvalError::NotApplicable(ValueType::Event, self.clone()));
+                }
+                *path = root.unwrap(path)
+                    .map_err(EvalError::NotFound)?;
+                Ok(())
+            },
+            _ => {
+                let mut query = root.query_mut(path)
+                    .map_err(EvalError::NotFound)?;
+                self.eval_query(&mut query)
+            },
+        }
+    }
+
     /// Wrapper that decides whether to use eval_node() or eval_query().
     pub fn eval_query(&self, query: &mut Query) -> EvalResult {
         match query {
@@ -91,11 +496,60 @@ impl Command {
 
     pub fn eval_node(&self, node: &mut Node) -> EvalResult {
         match self {
+            Command::Help => {
+                println!("{}", help_text());
+                Ok(())
+            },
             // Non-supported Node commands ================
             Command::Exit        |
-            Command::Help        |
-            Command::DateEdit(_) => {
-                Err(EvalError::NotApplicable(ValueType::Event, self.clone()))
+            Command::Dup(_)      |
+            Command::MoveUp      |
+            Command::MoveDown    |
+            Command::MoveTo(_)   |
+            Command::Wrap(_)     |
+            Command::Unwrap      |
+            Command::Delete      |
+            Command::Split(_, _) |
+            Command::DateEdit(_) |
+            Command::StatusEdit(_) |
+            Command::LayerEdit(_) => {
+                Err(EvalError::NotApplicable(ValueType::Node, self.clone()))
+            },
+            Command::Shift(by) => {
+                node.visit_events_mut(&mut |_, event|event.dates_mut().shift(*by));
+                Ok(())
+            },
+            Command::TimeScale(factor, anchor) => {
+                let anchor = anchor.or(node.iter_events().map(|e|*e.dates().start()).min());
+                if let Some(anchor) = anchor {
+                    node.visit_events_mut(&mut |_, event|event.dates_mut().scale(&anchor, *factor));
+                }
+                Ok(())
+            },
+            Command::Replace(pattern, text) => {
+                node.map_text(&mut |s|pattern.0.replace_all(s, text.as_str()).into_owned());
+                Ok(())
+            },
+            Command::Merge(a, b) => node.merge_children(*a, *b),
+            Command::ColorEdit(color) => {
+                node.set_color(color.clone());
+                Ok(())
+            },
+            Command::SchemeEdit(scheme) => {
+                node.set_scheme(scheme.as_deref());
+                Ok(())
+            },
+            Command::StyleEdit(style) => {
+                node.set_style(style.as_deref());
+                Ok(())
+            },
+            Command::Collapse(collapsed) => {
+                node.set_collapsed(*collapsed);
+                Ok(())
+            },
+            Command::ImageEdit(image) => {
+                node.set_image(image.as_deref());
+                Ok(())
             },
             // Name Commands ==============================
             Command::NameSub => {
@@ -107,8 +561,12 @@ impl Command {
                 Ok(())
             },
             // Line Commands ==============================
-            Command::LineEdit(opt_opt_f64) => {
-                node.set_line(*opt_opt_f64);
+            Command::LineEdit(opt_interval) => {
+                node.set_line(*opt_interval);
+                Ok(())
+            },
+            Command::LineSpan(span) => {
+                node.set_line_span(span.clone());
                 Ok(())
             },
             // Offset Commands ============================
@@ -116,11 +574,19 @@ impl Command {
                 node.set_offset(n);
                 Ok(())
             },
+            Command::OffsetAdd(n) => {
+                node.set_offset(&(node.offset() + n));
+                Ok(())
+            },
             // Scale Commands =============================
             Command::Scale(n) => {
                 node.set_scale(n);
                 Ok(())
             },
+            Command::ScaleAdd(n) => {
+                node.set_scale(&(node.scale() + n));
+                Ok(())
+            },
             // Pass the buck to the child event.
             Command::DescAdd(_) |
             Command::DescSub(_) |
@@ -132,12 +598,31 @@ impl Command {
 
     pub fn eval_event(&self, event: &mut Event) -> EvalResult {
         match self {
+            Command::Help => {
+                println!("{}", help_text());
+                Ok(())
+            },
             Command::Exit        |
-            Command::Help        |
+            Command::Dup(_)      |
+            Command::MoveUp      |
+            Command::MoveDown    |
+            Command::MoveTo(_)   |
+            Command::Wrap(_)     |
+            Command::Unwrap      |
+            Command::Delete      |
+            Command::Split(_, _) |
+            Command::Merge(_, _) |
             Command::Offset(_)   |
+            Command::OffsetAdd(_)|
             Command::Scale(_)    |
+            Command::ScaleAdd(_) |
             Command::NameSub     |
-            Command::LineEdit(_) => {
+            Command::SchemeEdit(_)|
+            Command::StyleEdit(_)|
+            Command::Collapse(_) |
+            Command::ImageEdit(_)|
+            Command::LineEdit(_) |
+            Command::LineSpan(_) => {
                 Err(EvalError::NotApplicable(ValueType::Event, self.clone()))
             },
             Command::NameEdit(opt_name) => {
@@ -147,7 +632,7 @@ impl Command {
                         Ok(())
                     },
                     None => {
-                        Err(EvalError::NotApplicable(ValueType::Node, self.clone()))
+                        Err(EvalError::NotApplicable(ValueType::Event, self.clone()))
                     },
                 }
             },
@@ -158,7 +643,7 @@ impl Command {
                         Ok(())
                     },
                     None => {
-                        println!("TODO: Find a way to call a text editor!");
+                        warn!("No text editor can be opened yet, give the description after the command.");
                         Ok(())
                     }
                 }
@@ -168,7 +653,7 @@ impl Command {
                 match opt_str {
                     Some(desc) => event.change_description(*index, &desc),
                     None => {
-                        println!("TODO: Find a way to call a text editor!");
+                        warn!("No text editor can be opened yet, give the description after the command.");
                         Ok(())
                     },
                 }
@@ -177,37 +662,148 @@ impl Command {
                 event.set_dates(&dates);
                 Ok(())
             },
+            Command::StatusEdit(status) => {
+                event.set_status(*status);
+                Ok(())
+            },
+            Command::LayerEdit(layer) => {
+                event.set_layer(*layer);
+                Ok(())
+            },
+            Command::Shift(by) => {
+                event.dates_mut().shift(*by);
+                Ok(())
+            },
+            Command::ColorEdit(color) => {
+                event.set_color(color.clone());
+                Ok(())
+            },
+            Command::TimeScale(factor, anchor) => {
+                let anchor = anchor.unwrap_or(*event.dates().start());
+                event.dates_mut().scale(&anchor, *factor);
+                Ok(())
+            },
+            Command::Replace(pattern, text) => {
+                event.map_text(&mut |s|pattern.0.replace_all(s, text.as_str()).into_owned());
+                Ok(())
+            },
+        }
+    }
+}
+
+/// Resolves built-in aliases, like `n` for `name`.
+fn canonical_name(head: &str) -> &str {
+    COMMAND_DOCS.iter()
+        .find(|doc|doc.aliases.contains(&head))
+        .map(|doc|doc.name)
+        .unwrap_or(head)
+}
+
+/// The whitespace separated tokens of a query, with where each one is.
+struct Tokens<'a> {
+    query: &'a str,
+    /// Where the next token is searched for from.
+    at: usize,
+    /// Span of the token last taken.
+    last: Span,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(query: &'a str) -> Tokens<'a> {
+        Tokens { query, at: 0, last: 0..0 }
+    }
+
+    fn last_span(&self) -> Span {
+        self.last.clone()
+    }
+
+    /// Blames the spot just after the last token for lacking `what`.
+    fn missing(&self, what: &'static str) -> ParseError {
+        ParseErrorKind::MissingArgument(what).at(self.last.end..self.last.end)
+    }
+
+    /// Takes every remaining token, joined with single spaces, along with
+    /// the span from the first to the last of them.
+    fn tail(&mut self) -> Option<(Span, String)> {
+        let (first, word) = self.next()?;
+        let mut words = vec![word];
+        words.extend(self.by_ref().map(|(_, word)|word));
+        Some((first.start..self.last.end, words.join(" ")))
+    }
+
+    /// Same as `tail`, without the span.
+    fn tail_text(&mut self) -> Option<String> {
+        self.tail().map(|(_, text)|text)
+    }
+
+    /// Parses the next token with `parse`, if there is one, blaming it
+    /// when that fails.
+    fn parse_next_with<T, E>(
+        &mut self,
+        parse: impl FnOnce(&str) -> Result<T, E>,
+        fail: impl FnOnce(E) -> ParseErrorKind,
+    ) -> Result<Option<T>, ParseError> {
+        match self.next() {
+            Some((span, token)) => parse(token).map(Some).map_err(|e|fail(e).at(span)),
+            None => Ok(None),
         }
     }
+
+    fn parse_next<T: FromStr>(&mut self, fail: impl FnOnce(T::Err) -> ParseErrorKind) -> Result<Option<T>, ParseError> {
+        self.parse_next_with(|token|token.parse::<T>(), fail)
+    }
+
+    /// The rest of the query as dates, read by `calendar` if there is one.
+    fn dates(&mut self, calendar: Option<&Calendar>) -> Result<Dates, ParseError> {
+        let (span, text) = self.tail()
+            .ok_or_else(||self.missing("DATE"))?;
+        let dt = match calendar {
+            Some(calendar) => calendar.parse_dates(&text)
+                .map_err(|e|ParseErrorKind::NotACalendarDate(e).at(span.clone()))?,
+            None => Dates::parse_loose(&text)
+                .map_err(|e|ParseErrorKind::NotADT(e).at(span.clone()))?,
+        };
+        dt.checked()
+            .map_err(|e|ParseErrorKind::InvertedDates(e).at(span))
+    }
+
+    /// Same as `parse_next`, but fails if there's no token left, calling it
+    /// `what`.
+    fn expect<T: FromStr>(&mut self, what: &'static str, fail: impl FnOnce(T::Err) -> ParseErrorKind) -> Result<T, ParseError> {
+        self.parse_next(fail)?.ok_or_else(||self.missing(what))
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (Span, &'a str);
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.query[self.at..];
+        let start = self.at + rest.find(|c: char|!c.is_ascii_whitespace())?;
+        let end = self.query[start..].find(|c: char|c.is_ascii_whitespace())
+            .map(|n|start + n)
+            .unwrap_or(self.query.len());
+        self.at = end;
+        self.last = start..end;
+        Some((start..end, &self.query[start..end]))
+    }
 }
 
 /// Used by serde to read struct from file.
 impl FromStr for Command {
     type Err = ParseError;
     fn from_str(query: &str) -> Result<Self, Self::Err> {
-        /// If stream is unfinished, returns `Some` containing each token
-        /// joined with a single space.
-        fn tail(stream: &mut SplitAscii<'_>) -> Option<String> {
-            let mut sentence: Vec<&str> = Vec::with_capacity(10);   // idk...
-            while let Some(word) = stream.next() {
-                sentence.push(word);
-            }
-            match sentence.is_empty() {
-                true => None,
-                false => Some(sentence[..].join(" ")),
-            }
-        }
-        /// Parses the next token in the stream, if it's there.
-        fn parse_next<T>(stream: &mut SplitAscii<'_>) -> Result<Option<T>, <T as FromStr>::Err>
-        where T: std::str::FromStr {
-            stream.next()
-                .map(|token|token.parse::<T>())
-                .transpose()
-        }
-        let mut tokens = query.split_ascii_whitespace();
-        let (modifier, head) = get_mod(
-            tokens.next().ok_or(ParseError::MissingCommand)?
-        );
+        Command::parse_with(query, None)
+    }
+}
+
+impl Command {
+    /// Parses a command, reading any dates with `calendar` when one is given.
+    pub fn parse_with(query: &str, calendar: Option<&Calendar>) -> Result<Command, ParseError> {
+        let mut tokens = Tokens::new(query);
+        let (head_span, head) = tokens.next()
+            .ok_or_else(||ParseErrorKind::MissingCommand.at(0..0))?;
+        let (modifier, head) = get_mod(head);
+        let head = canonical_name(head);
         // Decide what kind of Command we were given.
         let result = match (head, modifier) {
             // Exit =======================================
@@ -215,74 +811,369 @@ impl FromStr for Command {
             // Help =======================================
             ("help", _) => Ok(Command::Help),
             // Date =======================================
-            ("date", Mod::Edit) => {
-                let dt = tail(&mut tokens)
-                    .ok_or(ParseError::MissingArgument)?
-                    .parse::<Dates>()
-                    .map_err(|e|ParseError::NotADT(e))?;
-                Ok(Command::DateEdit(dt))
+            ("date", Mod::Edit) => Ok(Command::DateEdit(tokens.dates(calendar)?)),
+            // Status =====================================
+            ("status", Mod::Sub) => Ok(Command::StatusEdit(None)),
+            ("status", _) => {
+                let status = tokens.expect("STATUS", ParseErrorKind::NotAStatus)?;
+                Ok(Command::StatusEdit(Some(status)))
+            },
+            // Layer ======================================
+            ("layer", Mod::Sub) => Ok(Command::LayerEdit(0)),
+            ("layer", _) => {
+                let layer = tokens.expect("N", ParseErrorKind::NotAInt)?;
+                Ok(Command::LayerEdit(layer))
             },
             // Name =======================================
             ("name", Mod::Sub) => Ok(Command::NameSub),
             ("name", _) => {
-                Ok(Command::NameEdit(tail(&mut tokens)))
+                Ok(Command::NameEdit(tokens.tail_text()))
             },
             // Description ================================
             ("desc", Mod::Sub) => {
-                let n = parse_next::<usize>(&mut tokens)
-                    .map_err(|e|ParseError::NotAInt(e))?
-                    .ok_or(ParseError::MissingArgument)?;
+                let n = tokens.expect("INDEX", ParseErrorKind::NotAInt)?;
                 Ok(Command::DescSub(n))
             },
             ("desc", Mod::Add) => {
-                Ok(Command::DescAdd(tail(&mut tokens)))
+                Ok(Command::DescAdd(tokens.tail_text()))
             },
             ("desc", Mod::Edit) => {
-                let index = parse_next::<usize>(&mut tokens)
-                    .map_err(|e|ParseError::NotAInt(e))?
-                    .ok_or(ParseError::MissingArgument)?;
-                Ok(Command::DescEdit(index, tail(&mut tokens)))
+                let index = tokens.expect("INDEX", ParseErrorKind::NotAInt)?;
+                Ok(Command::DescEdit(index, tokens.tail_text()))
+            },
+            // Dup ========================================
+            ("dup", _) => {
+                let shift = tokens.parse_next_with(parse_duration, ParseErrorKind::NotADuration)?;
+                Ok(Command::Dup(shift))
+            },
+            // Moving =====================================
+            ("up", _) => Ok(Command::MoveUp),
+            ("down", _) => Ok(Command::MoveDown),
+            ("to", _) => {
+                let to = tokens.expect("INDEX", ParseErrorKind::NotAInt)?;
+                Ok(Command::MoveTo(to))
+            },
+            ("wrap", _) => Ok(Command::Wrap(tokens.tail_text())),
+            ("unwrap", _) => Ok(Command::Unwrap),
+            ("delete", _) => Ok(Command::Delete),
+            ("merge", _) => {
+                let mut index = ||tokens.expect("INDEX", ParseErrorKind::NotAInt);
+                Ok(Command::Merge(index()?, index()?))
+            },
+            ("split", _) => {
+                let (span, text) = tokens.tail()
+                    .ok_or_else(||tokens.missing("DATE"))?;
+                let (at, precision) = match calendar {
+                    Some(calendar) => calendar.parse_date(&text)
+                        .map_err(|e|ParseErrorKind::NotACalendarDate(e).at(span))?,
+                    None => {
+                        let dates = Dates::parse_loose(&text)
+                            .map_err(|e|ParseErrorKind::NotADT(e).at(span))?;
+                        (*dates.start(), dates.start_precision())
+                    },
+                };
+                Ok(Command::Split(at, precision))
+            },
+            // Color & Style ==============================
+            ("color", Mod::Sub) => Ok(Command::ColorEdit(None)),
+            ("color", _) => {
+                let color = tokens.expect("COLOR", ParseErrorKind::NotAColor)?;
+                Ok(Command::ColorEdit(Some(color)))
+            },
+            ("scheme", Mod::Sub) => Ok(Command::SchemeEdit(None)),
+            ("scheme", _) => {
+                let (_, scheme) = tokens.next()
+                    .ok_or_else(||tokens.missing("NAME"))?;
+                Ok(Command::SchemeEdit(Some(scheme.to_string())))
+            },
+            ("style", Mod::Sub) => Ok(Command::StyleEdit(None)),
+            ("style", _) => {
+                let (_, style) = tokens.next()
+                    .ok_or_else(||tokens.missing("NAME"))?;
+                Ok(Command::StyleEdit(Some(style.to_string())))
+            },
+            ("collapse", Mod::Sub) => Ok(Command::Collapse(false)),
+            ("collapse", _) => Ok(Command::Collapse(true)),
+            ("image", Mod::Sub) => Ok(Command::ImageEdit(None)),
+            ("image", _) => {
+                let (_, image) = tokens.next()
+                    .ok_or_else(||tokens.missing("PATH"))?;
+                Ok(Command::ImageEdit(Some(image.to_string())))
+            },
+            // Shift ======================================
+            ("shift", _) => {
+                let by = tokens.parse_next_with(parse_duration, ParseErrorKind::NotADuration)?
+                    .ok_or_else(||tokens.missing("DURATION"))?;
+                Ok(Command::Shift(by))
+            },
+            // Timescale ==================================
+            ("timescale", _) => {
+                let factor: f64 = tokens.expect("FACTOR", ParseErrorKind::NotAFloat)?;
+                if factor <= 0.0 || !factor.is_finite() {
+                    return Err(ParseErrorKind::NotAFactor(factor).at(tokens.last_span()));
+                }
+                let anchor = match tokens.tail() {
+                    Some((span, text)) => Some(match calendar {
+                        Some(calendar) => calendar.parse_date(&text)
+                            .map_err(|e|ParseErrorKind::NotACalendarDate(e).at(span))?.0,
+                        None => *Dates::parse_loose(&text)
+                            .map_err(|e|ParseErrorKind::NotADT(e).at(span))?.start(),
+                    }),
+                    None => None,
+                };
+                Ok(Command::TimeScale(factor, anchor))
+            },
+            // Replace ====================================
+            ("replace", _) => {
+                let pattern = tokens.parse_next_with(Regex::new, ParseErrorKind::NotARegex)?
+                    .ok_or_else(||tokens.missing("REGEX"))?;
+                Ok(Command::Replace(Pattern(pattern), tokens.tail_text().unwrap_or_default()))
             },
             // Line =======================================
+            ("cover", Mod::Sub) => Ok(Command::LineSpan(None)),
+            ("cover", _) => Ok(Command::LineSpan(Some(tokens.dates(calendar)?))),
             ("line", Mod::Sub) => Ok(Command::LineEdit(None)),
             ("line", _) => {
-                let opt_n = parse_next::<f64>(&mut tokens)
-                    .map_err(|e|ParseError::NotAFloat(e))?;
-                Ok(Command::LineEdit(Some(opt_n)))
+                let interval = tokens.parse_next(ParseErrorKind::NotAnInterval)?;
+                Ok(Command::LineEdit(Some(interval)))
             },
             // Offset =====================================
-            ("offset", Mod::Sub) => Ok(Command::Offset(0.0)),
-            ("offset", _) => {
-                let n = parse_next::<f64>(&mut tokens)
-                    .map_err(|e|ParseError::NotAFloat(e))?
-                    .ok_or(ParseError::MissingArgument)?;
-                Ok(Command::Offset(n))
+            ("offset", modifier) => {
+                let n = tokens.parse_next::<f64>(ParseErrorKind::NotAFloat)?;
+                match (modifier, n) {
+                    (Mod::Sub, None)    => Ok(Command::Offset(0.0)),
+                    (Mod::Sub, Some(n)) => Ok(Command::OffsetAdd(-n)),
+                    (Mod::Add, Some(n)) => Ok(Command::OffsetAdd(n)),
+                    (Mod::Edit, Some(n)) => Ok(Command::Offset(n)),
+                    (_, None) => Err(tokens.missing("NUMBER")),
+                }
             },
             // Scale ======================================
-            ("scale", Mod::Sub) => Ok(Command::Scale(1.0)),
-            ("scale", _) => {
-                let n = parse_next::<f64>(&mut tokens)
-                    .map_err(|e|ParseError::NotAFloat(e))?
-                    .ok_or(ParseError::MissingArgument)?;
-                Ok(Command::Scale(n))
+            ("scale", modifier) => {
+                let n = tokens.parse_next::<f64>(ParseErrorKind::NotAFloat)?;
+                match (modifier, n) {
+                    (Mod::Sub, None)    => Ok(Command::Scale(1.0)),
+                    (Mod::Sub, Some(n)) => Ok(Command::ScaleAdd(-n)),
+                    (Mod::Add, Some(n)) => Ok(Command::ScaleAdd(n)),
+                    (Mod::Edit, Some(n)) => Ok(Command::Scale(n)),
+                    (_, None) => Err(tokens.missing("NUMBER")),
+                }
             },
             (unknown, _) => {
-                let (start,end) = (unknown.to_string(), tail(&mut tokens));
-                Err(ParseError::UnknownCommand(start, end))
+                let (start,end) = (unknown.to_string(), tokens.tail_text());
+                Err(ParseErrorKind::UnknownCommand(start, end).at(head_span))
             },
         };
         // Fail if we didn't eat all the tokens.
-        let tail = tokens.collect::<Vec<&str>>();
-        match tail.is_empty() {
-            true => result,
-            false => Err(ParseError::ExtraArgument(head.to_string(), tail[..].join(" "))),
+        match tokens.tail() {
+            None => result,
+            Some((span, extra)) => Err(ParseErrorKind::ExtraArgument(head.to_string(), extra).at(span)),
         }
     }
 }
 
-impl From<ParseError> for MainError {
-    fn from(err: ParseError) -> Self {
-        MainError::CommandParse(err)
+impl Program {
+    /// Parses each `;` separated command with `Command::parse_with`, with
+    /// the spans of errors counted from the start of `query`.
+    pub fn parse_with(query: &str, calendar: Option<&Calendar>) -> Result<Program, ParseError> {
+        let mut commands = vec![];
+        let mut start = 0;
+        // Where `help` was given, if it was.
+        let mut help = None;
+        for part in query.split(';') {
+            if !part.trim().is_empty() {
+                let command = Command::parse_with(part, calendar)
+                    .map_err(|e|e.shifted(start))?;
+                if command.is_help() {
+                    let blank = part.len() - part.trim_start().len();
+                    help = Some(start + blank..start + part.trim_end().len());
+                }
+                commands.push(command);
+            }
+            start += part.len() + 1;
+        }
+        match (commands.len(), help) {
+            (0, _) => Err(ParseErrorKind::MissingCommand.at(0..0)),
+            (1, _) | (_, None) => Ok(Program(commands)),
+            (_, Some(span)) => Err(ParseErrorKind::HelpNotAlone.at(span)),
+        }
+    }
+
+    pub fn is_help(&self) -> bool {
+        self.0.iter().any(|command|command.is_help())
+    }
+
+    /// Evaluates every command on the value at `path`, stopping at the first failure.
+    pub fn eval_at(&self, root: &mut Node, path: &mut Vec<usize>) -> EvalResult {
+        self.0.iter().try_for_each(|command|command.eval_at(root, path))
+    }
+}
+
+impl FromStr for Program {
+    type Err = ParseError;
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        Program::parse_with(query, None)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Command {
+    /// The word used to write this command, for error messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Exit          => "exit",
+            Command::Help          => "help",
+            Command::NameSub       |
+            Command::NameEdit(_)   => "name",
+            Command::DescAdd(_)    |
+            Command::DescSub(_)    |
+            Command::DescEdit(_,_) => "desc",
+            Command::LineEdit(_)   => "line",
+            Command::LineSpan(_)   => "cover",
+            Command::Offset(_)     |
+            Command::OffsetAdd(_)  => "offset",
+            Command::Scale(_)      |
+            Command::ScaleAdd(_)   => "scale",
+            Command::DateEdit(_)   => "date",
+            Command::StatusEdit(_) => "status",
+            Command::LayerEdit(_)  => "layer",
+            Command::Replace(_,_)  => "replace",
+            Command::Shift(_)      => "shift",
+            Command::TimeScale(_,_)=> "timescale",
+            Command::Dup(_)        => "dup",
+            Command::MoveUp        => "up",
+            Command::MoveDown      => "down",
+            Command::MoveTo(_)     => "to",
+            Command::Wrap(_)       => "wrap",
+            Command::Unwrap        => "unwrap",
+            Command::Delete        => "delete",
+            Command::Split(_,_)    => "split",
+            Command::Merge(_,_)    => "merge",
+            Command::ColorEdit(_)  => "color",
+            Command::SchemeEdit(_) => "scheme",
+            Command::StyleEdit(_)  => "style",
+            Command::Collapse(_)   => "collapse",
+            Command::ImageEdit(_)  => "image",
+        }
+    }
+}
+
+impl std::fmt::Display for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValueType::Node  => write!(f, "nodes"),
+            ValueType::Event => write!(f, "events"),
+        }
+    }
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EvalError::NotApplicable(value_type, command) => {
+                write!(f, "'{}' can't be used on {}", command.name(), value_type)
+            },
+            EvalError::IndexError{index, len} => {
+                write!(f, "index {} is out of range, there are only {}", index, len)
+            },
+            EvalError::NotFound(fail) => write!(f, "{}", fail),
+            EvalError::NotInSpan(dt) => {
+                write!(f, "{} isn't inside the event's span", dt.format(FORMAT))
+            },
+            EvalError::NotAnEvent(index) => write!(f, "{} is a node, not an event", index),
+            EvalError::SameChild(index) => write!(f, "can't merge child {} with itself", index),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EvalError::NotFound(fail) => Some(fail),
+            _ => None,
+        }
+    }
+}
+
+impl ParseErrorKind {
+    pub fn at(self, span: Span) -> ParseError {
+        ParseError { kind: self, span }
+    }
+}
+
+impl ParseError {
+    /// Moves the span along by `by` bytes, for when the query parsed was
+    /// part of a longer one.
+    pub fn shifted(self, by: usize) -> ParseError {
+        let span = self.span.start + by..self.span.end + by;
+        ParseError { span, ..self }
+    }
+
+    /// Describes self beneath `query`, the text that was parsed, with
+    /// carets under the span to blame.
+    pub fn render(&self, query: &str) -> String {
+        let width = |s: &str|s.chars().count();
+        let start = width(query.get(..self.span.start).unwrap_or(query));
+        let carets = width(query.get(self.span.clone()).unwrap_or_default()).max(1);
+        format!("{}\n  {}\n  {}{}", self, query, " ".repeat(start), "^".repeat(carets))
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::MissingCommand => write!(f, "no command given, try 'help'"),
+            ParseErrorKind::MissingArgument(what) => write!(f, "missing {}, try 'help'", what),
+            ParseErrorKind::ExtraArgument(command, extra) => {
+                write!(f, "'{}' takes fewer arguments, '{}' is left over", command, extra)
+            },
+            ParseErrorKind::UnknownCommand(command, _) => {
+                write!(f, "unknown command '{}', try 'help'", command)
+            },
+            ParseErrorKind::NotAFloat(e) => write!(f, "expected a number: {}", e),
+            ParseErrorKind::NotAInt(e) => write!(f, "expected a whole number: {}", e),
+            ParseErrorKind::NotADT(e) => write!(f, "bad date: {}", e),
+            ParseErrorKind::NotAStatus(e) => write!(f, "{}", e),
+            ParseErrorKind::NotACalendarDate(e) => write!(f, "{}", e),
+            ParseErrorKind::InvertedDates(e) => write!(f, "{}", e),
+            ParseErrorKind::NotARegex(e) => write!(f, "{}", e),
+            ParseErrorKind::NotADuration(e) => write!(f, "{}", e),
+            ParseErrorKind::NotAFactor(factor) => {
+                write!(f, "the factor has to be above zero, not {}", factor)
+            },
+            ParseErrorKind::NotAColor(e) => write!(f, "{}", e),
+            ParseErrorKind::NotAnInterval(e) => write!(f, "{}", e),
+            ParseErrorKind::HelpNotAlone => write!(f, "'help' can't be combined with other commands"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::NotAFloat(e) => Some(e),
+            ParseErrorKind::NotAInt(e) => Some(e),
+            ParseErrorKind::NotADT(e) => Some(e),
+            ParseErrorKind::NotAStatus(e) => Some(e),
+            ParseErrorKind::NotACalendarDate(e) => Some(e),
+            ParseErrorKind::InvertedDates(e) => Some(e),
+            ParseErrorKind::NotARegex(e) => Some(e),
+            ParseErrorKind::NotADuration(e) => Some(e),
+            ParseErrorKind::NotAColor(e) => Some(e),
+            ParseErrorKind::NotAnInterval(e) => Some(e),
+            _ => None,
+        }
     }
 }
 
@@ -294,8 +1185,12 @@ impl From<EvalError> for MainError {
 
 #[cfg(test)]
 mod tests {
-    use super::{Command, get_mod, Mod, ParseError};
-    use super::super::events::Dates;
+    use chrono::Duration;
+    use regex::Regex;
+    use super::{Command, COMMAND_DOCS, get_mod, help_text, Mod, ParseErrorKind, Pattern, Program};
+    use super::super::axis::{Interval, IntervalParseError};
+    use super::super::saga::{ColorParseError, ColorRef};
+    use super::super::events::{Dates, DurationParseError, Event, InvertedDates, Node, Precision, PrintOptions, Status, StatusParseError};
 
     #[test]
     fn test_get_mod() {
@@ -309,15 +1204,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_help_examples() {
+        let help = help_text();
+        for doc in COMMAND_DOCS.iter() {
+            println!("{}", doc.example);
+            assert!(doc.example.parse::<Command>().is_ok());
+            assert!(help.contains(doc.example));
+        }
+    }
+
     #[test]
     fn test_command_parsing() {
         let ok_cases = [
             ("exit", Command::Exit),
             ("help", Command::Help),
             ("line", Command::LineEdit(Some(None))),
-            ("line 5", Command::LineEdit(Some(Some(5.0)))),
+            ("line 5y", Command::LineEdit(Some(Some(Interval::Years(5))))),
             ("+line", Command::LineEdit(Some(None))),
-            ("+line 5", Command::LineEdit(Some(Some(5.0)))),
+            ("+line auto", Command::LineEdit(Some(Some(Interval::Auto)))),
+            ("cover 1/1/1803 0:0 - 1/1/1815 0:0", Command::LineSpan(Some("1/1/1803 0:0 - 1/1/1815 0:0".parse::<Dates>().unwrap()))),
+            ("-cover", Command::LineSpan(None)),
             ("-name", Command::NameSub),
             ("name", Command::NameEdit(None)),
             ("name hello", Command::NameEdit(Some("hello".to_string()))),
@@ -329,52 +1236,357 @@ mod tests {
             ("desc 5", Command::DescEdit(5usize, None)),
             ("-scale", Command::Scale(1.0)),
             ("scale 0.5", Command::Scale(0.5)),
-            ("+scale 0.5", Command::Scale(0.5)),
+            ("+scale 0.5", Command::ScaleAdd(0.5)),
+            ("-scale 0.5", Command::ScaleAdd(-0.5)),
             ("-offset", Command::Offset(0.0)),
             ("offset 2.0", Command::Offset(2.0)),
-            ("+offset 2.0", Command::Offset(2.0)),
+            ("+offset 2.0", Command::OffsetAdd(2.0)),
+            ("-offset 2.0", Command::OffsetAdd(-2.0)),
             ("date 1/1/1990 0:0 - 1/1/1991 0:0", Command::DateEdit("1/1/1990 0:0 - 1/1/1991 0:0".parse::<Dates>().unwrap())),
             ("date 1/1/1990 0:0", Command::DateEdit("1/1/1990 0:0".parse::<Dates>().unwrap())),
+            ("date March 1990 - 1992-05-17", Command::DateEdit("03/1990 - 17/05/1992".parse::<Dates>().unwrap())),
+            ("status planned", Command::StatusEdit(Some(Status::Planned))),
+            ("status in-progress", Command::StatusEdit(Some(Status::InProgress))),
+            ("-status", Command::StatusEdit(None)),
+            ("layer 2", Command::LayerEdit(2)),
+            ("layer -1", Command::LayerEdit(-1)),
+            ("-layer", Command::LayerEdit(0)),
+            ("replace a+ b", Command::Replace(Pattern(Regex::new("a+").unwrap()), "b".to_string())),
+            ("replace a+", Command::Replace(Pattern(Regex::new("a+").unwrap()), String::new())),
+            ("shift -2w", Command::Shift(Duration::weeks(-2))),
+            ("dup", Command::Dup(None)),
+            ("dup +1w", Command::Dup(Some(Duration::weeks(1)))),
+            ("up", Command::MoveUp),
+            ("down", Command::MoveDown),
+            ("to 3", Command::MoveTo(3)),
+            ("wrap", Command::Wrap(None)),
+            ("wrap Napoleonic Wars", Command::Wrap(Some("Napoleonic Wars".to_string()))),
+            ("unwrap", Command::Unwrap),
+            ("delete", Command::Delete),
+            ("rm", Command::Delete),
+            ("merge 1 3", Command::Merge(1, 3)),
+            ("split 6/1805", Command::Split(*"1/6/1805".parse::<Dates>().unwrap().start(), Precision::Month)),
+            ("color #f80", Command::ColorEdit(Some("#ff8800".parse().unwrap()))),
+            ("color warm", Command::ColorEdit(Some(ColorRef::Scheme("warm".to_string())))),
+            ("-color", Command::ColorEdit(None)),
+            ("style dashed", Command::StyleEdit(Some("dashed".to_string()))),
+            ("-style", Command::StyleEdit(None)),
+            ("collapse", Command::Collapse(true)),
+            ("-collapse", Command::Collapse(false)),
+            ("image flags/prussia.svg", Command::ImageEdit(Some("flags/prussia.svg".to_string()))),
+            ("-image", Command::ImageEdit(None)),
+            ("scheme autumn", Command::SchemeEdit(Some("autumn".to_string()))),
+            ("-scheme", Command::SchemeEdit(None)),
+            ("timescale 2", Command::TimeScale(2.0, None)),
+            ("timescale 0.5 1/1/1990 0:0", Command::TimeScale(0.5, Some(*"1/1/1990 0:0".parse::<Dates>().unwrap().start()))),
+            ("n hello", Command::NameEdit(Some("hello".to_string()))),
+            ("-n", Command::NameSub),
+            ("+d TEXT", Command::DescAdd(Some("TEXT".to_string()))),
+            ("o 2.0", Command::Offset(2.0)),
+            ("+o 2.0", Command::OffsetAdd(2.0)),
+            ("dt 1/1/1990 0:0", Command::DateEdit("1/1/1990 0:0".parse::<Dates>().unwrap())),
         ];
         for (left, right) in ok_cases.iter() {
             println!("{}", left);
             assert_eq!(left.parse::<Command>().unwrap(), *right);
         }
         let err_cases = [
-            ( "", ParseError::MissingCommand),
-            ( "+offset", ParseError::MissingArgument),
+            ( "", ParseErrorKind::MissingCommand, 0..0),
+            ( "+offset", ParseErrorKind::MissingArgument("NUMBER"), 7..7),
+            ( "scale", ParseErrorKind::MissingArgument("NUMBER"), 5..5),
             (
                 "booty buttcheeks",
-                ParseError::UnknownCommand("booty".to_string(), Some("buttcheeks".to_string()))
+                ParseErrorKind::UnknownCommand("booty".to_string(), Some("buttcheeks".to_string())),
+                0..5,
             ),
             (
                 "exit world",
-                ParseError::ExtraArgument("exit".to_string(), "world".to_string())
+                ParseErrorKind::ExtraArgument("exit".to_string(), "world".to_string()),
+                5..10,
             ),
             (
                 "help world",
-                ParseError::ExtraArgument("help".to_string(), "world".to_string())
+                ParseErrorKind::ExtraArgument("help".to_string(), "world".to_string()),
+                5..10,
             ),
             (
                 "-name hello",
-                ParseError::ExtraArgument("name".to_string(), "hello".to_string())
+                ParseErrorKind::ExtraArgument("name".to_string(), "hello".to_string()),
+                6..11,
             ),
             (
-                "+line 5 4",
-                ParseError::ExtraArgument("line".to_string(), "4".to_string())
+                "+line 5d  4 3",
+                ParseErrorKind::ExtraArgument("line".to_string(), "4 3".to_string()),
+                10..13,
             ),
             (
-                "+line hello",
-                ParseError::NotAFloat("hello".parse::<f64>().unwrap_err())
+                "+line 5",
+                ParseErrorKind::NotAnInterval(IntervalParseError("5".to_string())),
+                6..7,
             ),
             (
                 "desc 3.14",
-                ParseError::NotAInt("3.14".parse::<usize>().unwrap_err())
+                ParseErrorKind::NotAInt("3.14".parse::<usize>().unwrap_err()),
+                5..9,
+            ),
+            ( "-desc", ParseErrorKind::MissingArgument("INDEX"), 5..5),
+            (
+                "-desc 2 3",
+                ParseErrorKind::ExtraArgument("desc".to_string(), "3".to_string()),
+                8..9,
+            ),
+            ( "status", ParseErrorKind::MissingArgument("STATUS"), 6..6),
+            ( "replace", ParseErrorKind::MissingArgument("REGEX"), 7..7),
+            ( "shift", ParseErrorKind::MissingArgument("DURATION"), 5..5),
+            ( "merge 1", ParseErrorKind::MissingArgument("INDEX"), 7..7),
+            ( "color", ParseErrorKind::MissingArgument("COLOR"), 5..5),
+            ( "color #ff88", ParseErrorKind::NotAColor(ColorParseError("#ff88".to_string())), 6..11),
+            ( "timescale", ParseErrorKind::MissingArgument("FACTOR"), 9..9),
+            ( "timescale -1", ParseErrorKind::NotAFactor(-1.0), 10..12),
+            ( "shift 2", ParseErrorKind::NotADuration(DurationParseError("2".to_string())), 6..7),
+            ( "replace a( b", ParseErrorKind::NotARegex("a(".parse::<Regex>().unwrap_err()), 8..10),
+            (
+                "date 1/1/1991 0:0 - 1/1/1990 0:0",
+                ParseErrorKind::InvertedDates(InvertedDates("1/1/1991 0:0 - 1/1/1990 0:0".parse().unwrap())),
+                5..32,
+            ),
+            (
+                "status finished",
+                ParseErrorKind::NotAStatus(StatusParseError("finished".to_string())),
+                7..15,
             ),
         ];
-        for (left, right) in err_cases.iter() {
+        for (left, kind, span) in err_cases {
             println!("{}", left);
-            assert_eq!(left.parse::<Command>().unwrap_err(), *right);
+            assert_eq!(left.parse::<Command>().unwrap_err(), kind.at(span));
         }
     }
+
+    #[test]
+    fn test_program_parsing() {
+        let program = "name Foo; +desc bar; offset 2".parse::<Program>().unwrap();
+        assert_eq!(program, Program(vec![
+            Command::NameEdit(Some("Foo".to_string())),
+            Command::DescAdd(Some("bar".to_string())),
+            Command::Offset(2.0),
+        ]));
+        assert_eq!("name Foo;".parse::<Program>().unwrap(), Program(vec![Command::NameEdit(Some("Foo".to_string()))]));
+        assert_eq!(" ; ".parse::<Program>().unwrap_err(), ParseErrorKind::MissingCommand.at(0..0));
+        let err = "name Foo; offset".parse::<Program>().unwrap_err();
+        assert_eq!(err, ParseErrorKind::MissingArgument("NUMBER").at(16..16));
+        assert_eq!(
+            "name Foo; +line x".parse::<Program>().unwrap_err(),
+            ParseErrorKind::NotAnInterval(IntervalParseError("x".to_string())).at(16..17)
+        );
+        assert_eq!("help".parse::<Program>().unwrap(), Program(vec![Command::Help]));
+        assert_eq!("name Foo;  help ; offset 2".parse::<Program>().unwrap_err(), ParseErrorKind::HelpNotAlone.at(11..15));
+    }
+
+    #[test]
+    fn test_error_rendering() {
+        let query = "name Föö; shift 2";
+        let err = query.parse::<Program>().unwrap_err();
+        assert_eq!(err.render(query), [
+            "'2' isn't a duration, such as +3d or -2w",
+            "  name Föö; shift 2",
+            "                  ^",
+        ].join("\n"));
+        let query = "exit now please";
+        assert!(query.parse::<Program>().unwrap_err().render(query).ends_with("\n       ^^^^^^^^^^"));
+    }
+
+    #[test]
+    fn test_relative_adjustments() {
+        let mut node = Node::from_vec(vec![]);
+        let program = "offset 1; +offset 2; -offset 0.5; +scale 1; -scale 0.25".parse::<Program>().unwrap();
+        program.eval_at(&mut node, &mut vec![]).unwrap();
+        assert_eq!((node.offset(), node.scale()), (2.5, 1.75));
+        "-offset; -scale".parse::<Program>().unwrap().eval_at(&mut node, &mut vec![]).unwrap();
+        assert_eq!((node.offset(), node.scale()), (0.0, 1.0));
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut event = Event::new("Battle of Ulm", "1/1/1805 0:0".parse().unwrap());
+        event.add_description("Ulm surrendered.");
+        let mut node = Node::new(Some("Ulm Campaign".to_string()), vec![event.into_value()]);
+        let program = "replace Ulm(\\b) Elchingen$1".parse::<Program>().unwrap();
+        program.eval_at(&mut node, &mut vec![]).unwrap();
+        let text = node.print(0, &PrintOptions { verbose: true, ..PrintOptions::default() });
+        assert!(text.contains("Elchingen Campaign"));
+        assert!(text.contains("Battle of Elchingen"));
+        assert!(text.contains("Elchingen surrendered."));
+        assert!(!text.contains("Ulm"));
+    }
+
+    #[test]
+    fn test_shift() {
+        let mut node = Node::from_vec(vec![
+            Event::new("A", "1/1/1990 0:0".parse().unwrap()).into_value(),
+            Node::from_vec(vec![
+                Event::new("B", "1/1/1990 0:0 - 2/1/1990 0:0".parse().unwrap()).into_value(),
+            ]).into_value(),
+        ]);
+        "shift +3d".parse::<Program>().unwrap().eval_at(&mut node, &mut vec![]).unwrap();
+        let dates = node.iter_events().map(|e|e.dates().clone()).collect::<Vec<Dates>>();
+        assert_eq!(dates, vec![
+            "4/1/1990 0:0".parse::<Dates>().unwrap(),
+            "4/1/1990 0:0 - 5/1/1990 0:0".parse::<Dates>().unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn test_timescale() {
+        let mut node = Node::from_vec(vec![
+            Event::new("A", "1/1/1990 0:0".parse().unwrap()).into_value(),
+            Event::new("B", "3/1/1990 0:0 - 4/1/1990 0:0".parse().unwrap()).into_value(),
+        ]);
+        "timescale 2".parse::<Program>().unwrap().eval_at(&mut node, &mut vec![]).unwrap();
+        let dates = node.iter_events().map(|e|e.dates().clone()).collect::<Vec<Dates>>();
+        assert_eq!(dates, vec![
+            "1/1/1990 0:0".parse::<Dates>().unwrap(),
+            "5/1/1990 0:0 - 7/1/1990 0:0".parse::<Dates>().unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn test_dup() {
+        let mut root = Node::from_vec(vec![
+            Node::from_vec(vec![
+                Event::new("A", "1/1/1990 0:0".parse().unwrap()).into_value(),
+                Event::new("B", "2/1/1990 0:0".parse().unwrap()).into_value(),
+            ]).into_value(),
+        ]);
+        "dup +1w".parse::<Program>().unwrap().eval_at(&mut root, &mut vec![1, 1]).unwrap();
+        let events = root.iter_events()
+            .map(|e|(e.name().to_string(), e.dates().to_string()))
+            .collect::<Vec<(String, String)>>();
+        assert_eq!(events, vec![
+            ("A".to_string(), "01/01/1990 00:00".to_string()),
+            ("A".to_string(), "08/01/1990 00:00".to_string()),
+            ("B".to_string(), "02/01/1990 00:00".to_string()),
+        ]);
+        "dup".parse::<Program>().unwrap().eval_at(&mut root, &mut vec![1]).unwrap();
+        assert_eq!(root.iter_events().count(), 6);
+        assert!("dup".parse::<Program>().unwrap().eval_at(&mut root, &mut vec![]).is_err());
+        assert!("dup".parse::<Program>().unwrap().eval_at(&mut root, &mut vec![7]).is_err());
+    }
+
+    #[test]
+    fn test_moving() {
+        let mut root = Node::from_vec(vec![
+            Event::new("A", "1/1/1990 0:0".parse().unwrap()).into_value(),
+            Event::new("B", "1/1/1990 0:0".parse().unwrap()).into_value(),
+            Event::new("C", "1/1/1990 0:0".parse().unwrap()).into_value(),
+        ]);
+        let names = |root: &Node|root.iter_events().map(|e|e.name()).collect::<Vec<&str>>().join("");
+        let mut path = vec![1];
+        "down; down".parse::<Program>().unwrap().eval_at(&mut root, &mut path).unwrap();
+        assert_eq!((names(&root), path.clone()), ("BCA".to_string(), vec![3]));
+        "up".parse::<Program>().unwrap().eval_at(&mut root, &mut path).unwrap();
+        assert_eq!((names(&root), path.clone()), ("BAC".to_string(), vec![2]));
+        "to 1".parse::<Program>().unwrap().eval_at(&mut root, &mut path).unwrap();
+        assert_eq!((names(&root), path.clone()), ("ABC".to_string(), vec![1]));
+        assert!("up".parse::<Program>().unwrap().eval_at(&mut root, &mut path).is_err());
+        assert!("to 4".parse::<Program>().unwrap().eval_at(&mut root, &mut path).is_err());
+        assert_eq!(names(&root), "ABC");
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let mut root = Node::from_vec(vec![
+            Event::new("A", "1/1/1990 0:0".parse().unwrap()).into_value(),
+            Event::new("B", "1/1/1990 0:0".parse().unwrap()).into_value(),
+        ]);
+        let mut path = vec![2];
+        "wrap Wars".parse::<Program>().unwrap().eval_at(&mut root, &mut path).unwrap();
+        assert_eq!(path, vec![2, 1]);
+        assert_eq!(root.iter_nodes().filter_map(|n|n.name_in(None)).collect::<Vec<&str>>(), vec!["Wars"]);
+        assert!("unwrap".parse::<Program>().unwrap().eval_at(&mut root, &mut path).is_err());
+        path.pop();
+        "unwrap".parse::<Program>().unwrap().eval_at(&mut root, &mut path).unwrap();
+        assert_eq!(path, Vec::<usize>::new());
+        assert_eq!(root.iter_nodes().count(), 1);
+        assert_eq!(root.iter_events().map(|e|e.name()).collect::<Vec<&str>>(), vec!["A", "B"]);
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut root = Node::from_vec(vec![
+            Event::new("A", "1/1/1990 0:0".parse().unwrap()).into_value(),
+            Node::new(Some("Wars".to_string()), vec![
+                Event::new("B", "1/1/1990 0:0".parse().unwrap()).into_value(),
+                Event::new("C", "1/1/1990 0:0".parse().unwrap()).into_value(),
+            ]).into_value(),
+        ]);
+        let mut path = vec![2, 1];
+        "delete".parse::<Program>().unwrap().eval_at(&mut root, &mut path).unwrap();
+        assert_eq!(path, vec![2]);
+        assert_eq!(root.iter_events().map(|e|e.name()).collect::<Vec<&str>>(), vec!["A", "C"]);
+        "rm".parse::<Program>().unwrap().eval_at(&mut root, &mut path).unwrap();
+        assert_eq!((path.clone(), root.children().len()), (vec![], 1));
+        // There is nothing to remove the root from.
+        assert!("delete".parse::<Program>().unwrap().eval_at(&mut root, &mut path).is_err());
+    }
+
+    #[test]
+    fn test_split() {
+        let mut event = Event::new("War", "1/1/1805 0:0 - 1/1/1806 0:0".parse().unwrap());
+        event.add_description("A long war.");
+        let mut root = Node::from_vec(vec![event.into_value()]);
+        "split 1/6/1805 0:0".parse::<Program>().unwrap().eval_at(&mut root, &mut vec![1]).unwrap();
+        let events = root.iter_events()
+            .map(|e|(e.name().to_string(), e.dates().to_string(), e.descriptions_in(None).to_vec()))
+            .collect::<Vec<(String, String, Vec<String>)>>();
+        assert_eq!(events, vec![
+            ("War (1)".to_string(), "01/01/1805 00:00 - 01/06/1805 00:00".to_string(), vec!["A long war.".to_string()]),
+            ("War (2)".to_string(), "01/06/1805 00:00 - 01/01/1806 00:00".to_string(), vec!["A long war.".to_string()]),
+        ]);
+        assert!("split 1/1/1900 0:0".parse::<Program>().unwrap().eval_at(&mut root, &mut vec![1]).is_err());
+        assert!("split 1/3/1805 0:0".parse::<Program>().unwrap().eval_at(&mut root, &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = Event::new("Siege", "1/1/1805 0:0 - 1/2/1805 0:0".parse().unwrap());
+        a.add_description("Began.");
+        let mut b = Event::new("Surrender", "1/3/1805 0:0".parse().unwrap());
+        b.add_description("Ended.");
+        let mut root = Node::from_vec(vec![
+            b.into_value(),
+            Node::from_vec(vec![]).into_value(),
+            a.into_value(),
+        ]);
+        "merge 3 1".parse::<Program>().unwrap().eval_at(&mut root, &mut vec![]).unwrap();
+        let events = root.iter_events()
+            .map(|e|(e.name().to_string(), e.dates().to_string(), e.descriptions_in(None).join(" ")))
+            .collect::<Vec<(String, String, String)>>();
+        assert_eq!(events, vec![(
+            "Surrender".to_string(),
+            "01/01/1805 00:00 - 01/03/1805 00:00".to_string(),
+            "Ended. Began.".to_string(),
+        )]);
+        let fail = |query: &str, root: &mut Node|{
+            query.parse::<Program>().unwrap().eval_at(root, &mut vec![]).err().unwrap().to_string()
+        };
+        assert_eq!(fail("merge 1 2", &mut root), "2 is a node, not an event");
+        assert_eq!(fail("merge 1 1", &mut root), "can't merge child 1 with itself");
+        assert_eq!(fail("merge 1 5", &mut root), "index 5 is out of range, there are only 2");
+    }
+
+    #[test]
+    fn test_not_applicable() {
+        let mut root = Node::from_vec(vec![
+            Event::new("Ulm", "20/10/1805".parse().unwrap()).into_value(),
+            Node::from_vec(vec![]).into_value(),
+        ]);
+        let fail = |query: &str, mut path: Vec<usize>, root: &mut Node|{
+            query.parse::<Program>().unwrap().eval_at(root, &mut path).err().unwrap().to_string()
+        };
+        assert_eq!(fail("date 1805", vec![2], &mut root), "'date' can't be used on nodes");
+        assert_eq!(fail("+desc Won.", vec![], &mut root), "'desc' can't be used on nodes");
+        assert_eq!(fail("offset 1", vec![1], &mut root), "'offset' can't be used on events");
+        assert_eq!(fail("split 20/10/1805", vec![2], &mut root), "'split' can't be used on nodes");
+        assert_eq!(fail("unwrap", vec![1], &mut root), "'unwrap' can't be used on events");
+    }
 }
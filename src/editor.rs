@@ -0,0 +1,298 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use log::{error, info};
+use rustyline::{CompletionType, Config as LineConfig, Context, Editor, Helper};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+
+use super::{MainError, MainResult};
+use super::config::Config;
+use super::edit::{self, COMMAND_DOCS, Program};
+use super::events::{PrintOptions, QueryRef};
+use super::input;
+use super::locale::DateFormat;
+use super::saga::{self, SagaDoc};
+use super::validate::path_string;
+
+/// File in the user's home directory that keeps the editor's history.
+const HISTORY_FILE: &str = ".saga_history";
+
+/// Commands understood by the editor on top of the edit commands.
+const EDITOR_DOCS: &[(&str, &str)] = &[
+    ("cd [PATH]", "Select the value at PATH, or the root if PATH is left out."),
+    ("cd ..", "Select the parent of the current value."),
+    ("ls", "Show the selected value."),
+    ("write", "Save the document."),
+];
+
+/// Whether the editor should keep reading lines.
+enum Flow {
+    Continue,
+    Exit,
+}
+
+/// Tab completion for the editor, refreshed from the document before every prompt.
+#[derive(Default)]
+struct Completions {
+    /// The user's own aliases, completed alongside the commands.
+    aliases: Vec<String>,
+    /// Full paths of the selected node's children.
+    children: Vec<String>,
+    /// Every date in the document, written the way `date` reads them.
+    dates: BTreeSet<String>,
+}
+
+/// State of an interactive editing session over one document.
+pub struct Session<'a> {
+    doc: &'a mut SagaDoc,
+    /// Path of the selected value, in the form used by `Node::query_ref`.
+    path: Vec<usize>,
+    /// True when there are unsaved changes.
+    dirty: bool,
+}
+
+/// Runs the editor until the user exits, calling `save` whenever they ask to write.
+pub fn run(doc: &mut SagaDoc, config: &Config, save: &mut dyn FnMut(&SagaDoc) -> MainResult) -> MainResult {
+    let line_config = LineConfig::builder()
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor: Editor<Completions, DefaultHistory> = Editor::with_config(line_config)
+        .map_err(|e|MainError::from(saga::SagaDocError::from(e)))?;
+    editor.set_helper(Some(Completions {
+        aliases: config.aliases.keys().cloned().collect(),
+        ..Completions::default()
+    }));
+    let history = history_path();
+    if let Some(history) = history.as_ref() {
+        // There is no history the first time the editor runs.
+        let _ = editor.load_history(history);
+    }
+    let mut session = Session { doc, path: vec![], dirty: false };
+    info!("Type 'help' for a list of commands.");
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.refresh(session.doc, &session.path);
+        }
+        let prompt = format!("{} > ", session.prompt());
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            // Ctrl+C abandons the current line, Ctrl+D leaves.
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(saga::SagaDocError::from(e).into()),
+        };
+        if line.trim().is_empty() { continue; }
+        let _ = editor.add_history_entry(line.as_str());
+        match session.run_line(&config.expand(&line), save) {
+            Ok(Flow::Continue) => {},
+            Ok(Flow::Exit) => break,
+            Err(e) => error!("{}", e),
+        }
+    }
+    if let Some(history) = history.as_ref() {
+        let _ = editor.save_history(history);
+    }
+    Ok(())
+}
+
+impl<'a> Session<'a> {
+    /// Shows the selected path, with "/" standing for the root.
+    fn prompt(&self) -> String {
+        match self.path.is_empty() {
+            true => "/".to_string(),
+            false => path_string(&self.path),
+        }
+    }
+
+    fn run_line(&mut self, line: &str, save: &mut dyn FnMut(&SagaDoc) -> MainResult) -> Result<Flow, MainError> {
+        let (head, rest) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        match head {
+            "cd" => self.change_path(rest.trim())?,
+            "ls" => {
+                let options = PrintOptions {
+                    verbose: true,
+                    dates: self.doc.calendar().map(|c|c as &dyn DateFormat),
+                    ..PrintOptions::default()
+                };
+                let text = match self.doc.get_data().query_ref(&self.path)? {
+                    QueryRef::Node(node) => node.print(0, &options),
+                    QueryRef::Event(event) => event.print(0, &options),
+                };
+                println!("{}", text);
+            },
+            "write" => {
+                save(self.doc)?;
+                self.dirty = false;
+            },
+            _ => {
+                let program = Program::parse_with(line, self.doc.calendar())
+                    .map_err(|e|MainError::CommandParse(line.to_string(), e))?;
+                for command in program.0.iter() {
+                    if command.is_help() {
+                        println!("{}\n\n{}", edit::help_text(), editor_help());
+                    } else if command.is_exit() {
+                        if self.dirty && input::ask_bool("Save changes? [Y/n]")? {
+                            save(self.doc)?;
+                        }
+                        return Ok(Flow::Exit);
+                    } else {
+                        command.eval_at(self.doc.get_data_mut(), &mut self.path)?;
+                        self.dirty = true;
+                    }
+                }
+            },
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Selects a new value, checking that it exists first.
+    fn change_path(&mut self, arg: &str) -> Result<(), MainError> {
+        let path = match arg {
+            ".." => self.path[..self.path.len().saturating_sub(1)].to_vec(),
+            arg => saga::parse_to_int_path(arg)?,
+        };
+        self.doc.get_data().query_ref(&path)?;
+        self.path = path;
+        Ok(())
+    }
+}
+
+impl Completions {
+    /// Collects the candidates that depend on the document and the selection.
+    fn refresh(&mut self, doc: &SagaDoc, path: &[usize]) {
+        let calendar = doc.calendar();
+        self.dates = doc.get_data().iter_events()
+            .flat_map(|event|{
+                let dates = event.dates();
+                let end = dates.end().map(|dt|(dt, dates.end_precision()));
+                std::iter::once((dates.start(), dates.start_precision())).chain(end)
+            })
+            .map(|(dt, precision)|match calendar {
+                Some(calendar) => calendar.format_date_at(dt, precision),
+                None => dt.format(precision.format()).to_string(),
+            })
+            .collect();
+        self.children = match doc.get_data().query_ref(path) {
+            Ok(QueryRef::Node(node)) => (1..=node.children().len())
+                .map(|i|path_string(&[path, &[i]].concat()))
+                .collect(),
+            _ => vec![],
+        };
+    }
+
+    /// Finds where the word being typed starts in `line`, and what it could become.
+    fn candidates(&self, line: &str) -> (usize, Vec<String>) {
+        // Only the last of several `;` separated commands matters.
+        let command = line.rfind(';').map(|i|i + 1).unwrap_or(0);
+        let start = line.rfind(char::is_whitespace).map(|i|i + 1).unwrap_or(0).max(command);
+        let word = &line[start..];
+        let options: Vec<String> = match line[command..start].split_ascii_whitespace().next() {
+            // Completing the command itself, keeping any modifier.
+            None => {
+                let sign = word.find(|c|c != '+' && c != '-').unwrap_or(word.len());
+                let (sign, word) = word.split_at(sign);
+                let editor = EDITOR_DOCS.iter().filter_map(|(form, _)|form.split(' ').next());
+                COMMAND_DOCS.iter().map(|doc|doc.name)
+                    .chain(editor)
+                    .chain(self.aliases.iter().map(|s|s.as_str()))
+                    .filter(|name|name.starts_with(word))
+                    .map(|name|format!("{}{}", sign, name))
+                    .collect::<BTreeSet<String>>()
+                    .into_iter()
+                    .collect()
+            },
+            Some("cd") => self.children.iter()
+                .map(|s|s.as_str())
+                .chain(std::iter::once(".."))
+                .filter(|path|path.starts_with(word))
+                .map(|path|path.to_string())
+                .collect(),
+            Some("date") => self.dates.iter()
+                .map(|s|s.as_str())
+                .chain(std::iter::once("now"))
+                .filter(|date|date.starts_with(word))
+                .map(|date|date.to_string())
+                .collect(),
+            Some(_) => vec![],
+        };
+        (start, options)
+    }
+}
+
+impl Completer for Completions {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, options) = self.candidates(&line[..pos]);
+        let pairs = options.into_iter()
+            .map(|s|Pair { display: s.clone(), replacement: s })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
+
+/// Reference for the editor's own commands.
+fn editor_help() -> String {
+    let lines = EDITOR_DOCS.iter()
+        .map(|(form, about)|format!("  {:17}  {}", form, about))
+        .collect::<Vec<String>>()
+        .join("\n");
+    format!("editor\n{}", lines)
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home|PathBuf::from(home).join(HISTORY_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Completions;
+    use super::super::events::{Event, Node};
+    use super::super::saga::SagaDoc;
+
+    #[test]
+    fn test_completions() {
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            Event::new("Austerlitz", "02/12/1805 08:00 - 02/12/1805 16:30".parse().unwrap()).into_value(),
+            Node::from_vec(vec![
+                Event::new("Jena", "14/10/1806".parse().unwrap()).into_value(),
+            ]).into_value(),
+        ]);
+        let mut completions = Completions { aliases: vec!["done".to_string()], ..Completions::default() };
+        completions.refresh(&doc, &[]);
+        let ok_cases = [
+            ("na", 0, vec!["name"]),
+            ("-st", 0, vec!["-status", "-style"]),
+            ("wr", 0, vec!["wrap", "write"]),
+            ("cd ", 3, vec!["1", "2", ".."]),
+            ("cd 2", 3, vec!["2"]),
+            ("date 1", 5, vec!["14/10/1806"]),
+            ("date 02/12/1805 08:00 - n", 24, vec!["now"]),
+            ("name Aus", 5, vec![]),
+            ("name Foo; sta", 10, vec!["status"]),
+            ("do", 0, vec!["done", "down"]),
+            ("name Foo;-na", 9, vec!["-name"]),
+        ];
+        for (line, start, expected) in ok_cases.iter() {
+            assert_eq!(completions.candidates(line), (*start, expected.iter().map(|s|s.to_string()).collect()));
+        }
+        completions.refresh(&doc, &[2]);
+        assert_eq!(completions.candidates("cd "), (3, vec!["2:1".to_string(), "..".to_string()]));
+    }
+}
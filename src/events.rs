@@ -1,33 +1,165 @@
 
-use std::str::FromStr;
+use std::{collections::BTreeMap, ops::RangeInclusive, str::FromStr};
 
-use chrono::{NaiveDateTime};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
 use serde::{Serialize, Deserialize};
 
 use super::MainError;
-use super::saga::{Color, SagaDocError};
+use super::axis::Interval;
+use super::locale::DateFormat;
+use super::saga::{Color, ColorRef, SagaDocError};
 use super::edit::{EvalError, EvalResult};
+use super::validate::path_string;
 
 pub const FORMAT: &'static str = "%d/%m/%Y %H:%M";
 pub type Dt = NaiveDateTime;
 pub type DtParseError = chrono::format::ParseError;
+/// A parent node's path, and the indices of its children to show, marking
+/// those that were found. Made by `Node::with_siblings()`.
+pub type SiblingGroup = (Vec<usize>, Vec<(usize, bool)>);
+
+/// Seconds since the Unix epoch at `dt`. Timestamps are always kept in
+/// seconds, as by `Dates::stamps()` and `Node::range()`.
+pub fn to_stamp(dt: &Dt) -> i64 {
+    dt.and_utc().timestamp()
+}
+
+/// The `Dt` at `stamp`, in seconds as made by `to_stamp()`, if it is within
+/// the range `Dt` covers.
+pub fn from_stamp(stamp: i64) -> Option<Dt> {
+    chrono::DateTime::from_timestamp(stamp, 0).map(|dt|dt.naive_utc())
+}
+
+/// How long it is from `start` to `end` in human terms, like "18 days" or
+/// "3 years 2 months", in at most its two largest units.
+pub fn elapsed(start: &Dt, end: &Dt) -> Option<String> {
+    use chrono::{Datelike, Months};
+    // Whole months are counted on the calendar, as they differ in length.
+    let mut months = (end.year() - start.year()) * 12 + end.month() as i32 - start.month() as i32;
+    if (end.day(), end.time()) < (start.day(), start.time()) {
+        months -= 1;
+    }
+    let months = months.max(0) as u32;
+    let since = to_stamp(&start.checked_add_months(Months::new(months))?);
+    let minutes = (to_stamp(end) - since).max(0) / 60;
+    let units = [
+        (months as i64 / 12, "year"),
+        (months as i64 % 12, "month"),
+        (minutes / (24 * 60), "day"),
+        (minutes / 60 % 24, "hour"),
+        (minutes % 60, "minute"),
+    ];
+    let first = units.iter().position(|(n, _)|*n > 0).unwrap_or(units.len() - 1);
+    let text = units[first..].iter()
+        .take(2)
+        .filter(|(n, _)|*n > 0 || first == units.len() - 1)
+        .map(|(n, unit)|format!("{} {}{}", n, unit, if *n == 1 { "" } else { "s" }))
+        .collect::<Vec<String>>()
+        .join(" ");
+    Some(text)
+}
 
 /// Main packaging struct. Essentially used to store nested/listed Events
 /// from something like a JSON or TOML file.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Node {
+    /// Required, so that data in a form Saga doesn't know, like the old
+    /// linked lists, fails to load rather than loading as an empty node.
     children: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     style_override: Option<String>,
-    color_override: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color_override: Option<ColorRef>,
+    /// Name of the color scheme whose colors are taken in turn by the
+    /// values under the node that have no color of their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scheme: Option<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
     offset: f64,
+    #[serde(default = "one", skip_serializing_if = "is_one")]
     y_scale: f64,
-    line: Option<Option<f64>>,  // (None|Draw Line|Draw Line with tick marks).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    line: Option<Option<Interval>>,  // (None|Draw Line|Draw Line with tick marks).
+    /// Dates the node's line is declared to cover, instead of those of its
+    /// events.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_with::As::<Option<serde_with::DisplayFromStr>>")]
+    line_span: Option<Dates>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     graphs: Vec<Graph>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    translations: BTreeMap<String, Translation>,
+    /// Hides everything under the node: printed as one line, and drawn as a
+    /// single band covering its events.
+    #[serde(default, skip_serializing_if = "is_false")]
+    collapsed: bool,
+    /// Path of an SVG file, such as a flag or a seal, relative to the
+    /// document, drawn just before the node's events start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+}
+
+/// A node reached by `Visits`, along with what it inherits from its ancestors.
+#[derive(Clone, Copy)]
+pub struct Visit<'a> {
+    pub node: &'a Node,
+    pub depth: usize,
+    /// Offset and scaling of the node, its own included, see `Node::transform`.
+    pub offset: f64,
+    pub scale: f64,
+}
+
+/// Pre-order iterator over a node and every node under it, keeping the
+/// nodes still to be visited on a stack rather than recursing.
+pub struct Visits<'a> {
+    stack: Vec<Visit<'a>>,
+    /// Whether to leave out the nodes under collapsed ones.
+    shown: bool,
+}
+
+/// Where a `Visitor` is in the tree. Events are given the depth and
+/// transform of the node holding them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    /// Path of the value, in the form used by `Node::query_ref`.
+    pub path: Vec<usize>,
+    pub depth: usize,
+    /// Offset and scaling of the node, as in `Visit`.
+    pub offset: f64,
+    pub scale: f64,
+}
+
+/// Callbacks for `Node::walk`, all of which do nothing by default.
+pub trait Visitor<'a> {
+    /// Called on reaching a node, before any of its children.
+    fn enter(&mut self, _node: &'a Node, _at: &Position) {}
+    /// Called once all of a node's children have been visited.
+    fn leave(&mut self, _node: &'a Node, _at: &Position) {}
+    fn event(&mut self, _event: &'a Event, _at: &Position) {}
+}
+
+/// What `Node::walk` still has to do, kept on a stack.
+enum Step<'a> {
+    Enter(&'a Node),
+    Leave(&'a Node),
+    Event(&'a Event),
+}
+
+/// Iterator over every event under a node, in document order.
+pub struct Events<'a> {
+    stack: Vec<std::slice::Iter<'a, Value>>,
+}
+
+/// Like `Events`, but also yields the path of each event, in the form used
+/// by `Node::query_ref`.
+pub struct PathEvents<'a> {
+    stack: Vec<std::iter::Enumerate<std::slice::Iter<'a, Value>>>,
+    path: Vec<usize>,
 }
 
 /// Internal enum used to store either more Nodes or leaf-like Events.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Value {
     Event(Event),
@@ -38,16 +170,106 @@ pub enum Value {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Event {
     name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     descriptions: Vec<String>,
     #[serde(with = "serde_with::rust::display_fromstr")]
     datetime: Dates,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<Status>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<ColorRef>,
+    /// Drawing order among overlapping events: those on higher layers are
+    /// drawn over those on lower ones, and those on the same in document order.
+    #[serde(default, skip_serializing_if = "is_ground")]
+    layer: i32,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    translations: BTreeMap<String, Translation>,
+}
+
+/// Per-locale variant of a name and its descriptions, keyed by language
+/// code (e.g. "fr") in `translations`. Missing fields fall back to the
+/// untranslated ones.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Translation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    descriptions: Vec<String>,
 }
 
+/// Where an `Event` is in its lifecycle. Events without a status are
+/// assumed to simply be historical.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Planned,
+    InProgress,
+    Done,
+    Cancelled,
+}
+
+/// Created when a string isn't one of the `Status` names.
+#[derive(Debug, PartialEq)]
+pub struct StatusParseError(pub String);
+
+/// Created when a string isn't a duration like "+3d" or "-2w".
+#[derive(Debug, PartialEq)]
+pub struct DurationParseError(pub String);
+
 /// Used to represent either one point in time, or a timespan.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Dates {
     start: Dt,
     end: Option<Dt>,
+    /// How precisely the start and end were given.
+    precision: (Precision, Precision),
+    /// Set for spans that haven't ended yet. These have no `end`, and
+    /// instead last until the present.
+    ongoing: bool,
+}
+
+/// Created when a span ends before it starts.
+#[derive(Debug, PartialEq)]
+pub struct InvertedDates(pub Dates);
+
+/// Words accepted in place of an end date for ongoing spans.
+const ONGOING: [&str; 2] = ["now", "..."];
+
+/// Formats read by `Dates::parse_loose` after Saga's own, in the order
+/// they are tried. Month precise ones are read as the first of the month.
+const LOOSE_FORMATS: [(Precision, &str); 11] = [
+    (Precision::Minute, "%Y-%m-%d %H:%M"),
+    (Precision::Minute, "%Y-%m-%dT%H:%M:%S"),
+    (Precision::Minute, "%Y-%m-%dT%H:%M"),
+    (Precision::Minute, "%Y/%m/%d %H:%M"),
+    (Precision::Day, "%Y-%m-%d"),
+    (Precision::Day, "%Y/%m/%d"),
+    (Precision::Day, "%d.%m.%Y"),
+    (Precision::Month, "%B %Y"),
+    (Precision::Month, "%Y-%m"),
+    (Precision::Day, "%d %B %Y"),
+    (Precision::Day, "%B %d, %Y"),
+];
+
+/// The smallest unit a date was entered with. A date only known to the year
+/// covers that whole year.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precision {
+    Year,
+    Month,
+    Day,
+    Minute,
+}
+
+/// Settings controlling the output of `Node::print` and `Event::print`.
+#[derive(Clone, Copy, Default)]
+pub struct PrintOptions<'a> {
+    /// Also show offsets, scaling, descriptions and durations.
+    pub verbose: bool,
+    /// Language used for translated names.
+    pub lang: Option<&'a str>,
+    /// Presents dates in this format instead of the storage format.
+    pub dates: Option<&'a dyn DateFormat>,
 }
 
 /// Represents the setting to draw a timeline spanning all
@@ -55,16 +277,40 @@ pub struct Dates {
 pub struct Line {
     pub start: f64,
     pub end: f64,
-    pub interval: Option<f64>,
+    pub interval: Option<Interval>,
+    /// Offset of the node, before the document's spacing is applied.
     pub y: f64,
+    pub depth: usize,
 }
 
+// Defaults left out when writing a `Node`, so that documents stay small
+// and every version of a document is written the same way.
+fn is_zero(x: &f64) -> bool { *x == 0.0 }
+fn is_one(x: &f64) -> bool { *x == 1.0 }
+fn one() -> f64 { 1.0 }
+fn is_false(x: &bool) -> bool { !*x }
+fn is_ground(layer: &i32) -> bool { *layer == 0 }
+
 /// Created when following a Node down a path fails.
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct PathFail {
     path: Vec<usize>,
+    /// Position in `path` of the index that failed, counting from 1.
     at: usize,
+    kind: PathFailKind,
+    /// Name of the deepest node reached, if it has one.
+    reached: Option<String>,
+}
+
+/// Why a `PathFail` happened.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathFailKind {
+    /// The index was 0, or past the last of the node's `len` children.
+    OutOfBounds { len: usize },
+    /// The path carried on past an event, which holds nothing.
+    IntoEvent,
+    /// The path was empty, where a value inside a node was needed.
+    Root,
 }
 
 pub enum Query<'a> {
@@ -72,7 +318,14 @@ pub enum Query<'a> {
     Event(&'a mut Event),
 }
 
-#[derive(Serialize, Deserialize)]
+/// Read-only counterpart to `Query`, returned by `Node::query_ref`.
+#[derive(Clone, Copy)]
+pub enum QueryRef<'a> {
+    Node(&'a Node),
+    Event(&'a Event),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 struct Graph {
     data: Vec<(Dt, f64)>,
     y_scale: f64,
@@ -80,7 +333,7 @@ struct Graph {
     draw_type: GraphType,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 enum GraphType {
     Scatter,
     Line,
@@ -95,10 +348,15 @@ impl Node {
             children: children,
             style_override: None,
             color_override: None,
+            scheme: None,
             offset: 0f64,
             y_scale: 1f64,
             line: None,
+            line_span: None,
             graphs: vec![],
+            translations: BTreeMap::new(),
+            collapsed: false,
+            image: None,
         }
     }
 
@@ -112,10 +370,15 @@ impl Node {
                 .collect(),
             style_override: None,
             color_override: None,
+            scheme: None,
             offset: 0f64,
             y_scale: 1f64,
             line: None,
+            line_span: None,
             graphs: vec![],
+            translations: BTreeMap::new(),
+            collapsed: false,
+            image: None,
         }
     }
 
@@ -124,74 +387,351 @@ impl Node {
     }
 
     /// Searches the node tree for the given address. If not found, returns
-    /// the remainder of the path that wasn't searched.
-    // TODO: Rework PathFail into something more useful.
-    pub fn query<'a>(&'a mut self, path: &[usize]) -> Result<Query<'a>, PathFail> {
-        if path.len() == 0 {
-            return Ok(Query::Node(self));
+    /// the path along with how far along it the search failed.
+    pub fn query_ref(&self, path: &[usize]) -> Result<QueryRef<'_>, PathFail> {
+        let mut node = self;
+        for (at, index) in path.iter().enumerate() {
+            // Decrement by 1 because the user is expecting an index-origin of 1.
+            match node.children.get(index.wrapping_sub(1)) {
+                Some(Value::Node(n)) => node = n,
+                Some(Value::Event(e)) if at + 1 == path.len() => return Ok(QueryRef::Event(e)),
+                Some(Value::Event(_)) => return Err(PathFail::new(path, at + 2, PathFailKind::IntoEvent, node)),
+                None => {
+                    let kind = PathFailKind::OutOfBounds { len: node.children.len() };
+                    return Err(PathFail::new(path, at + 1, kind, node));
+                },
+            }
         }
-        // Decrement by 1 because the user is expecting an index-origin of 1.
-        match self.children.get_mut(path[0]-1) {
-            Some(Value::Node(n)) => n.query(&path[1..]),
-            Some(Value::Event(e)) if path.len() == 1 => Ok(Query::Event(e)),
-            // TODO: Refactor Pathfail production.
-            Some(Value::Event(_)) => Err(PathFail{path:path.to_vec(),at:path.len()}),
-            None => Err(PathFail{path:path.to_vec(),at:path.len()}),
+        Ok(QueryRef::Node(node))
+    }
+
+    /// The deepest node along `path` that exists, and the path to it.
+    pub fn nearest(&self, path: &[usize]) -> (Vec<usize>, &Node) {
+        let mut node = self;
+        let mut found = vec![];
+        for index in path.iter() {
+            match node.children.get(index.wrapping_sub(1)) {
+                Some(Value::Node(n)) => node = n,
+                _ => break,
+            }
+            found.push(*index);
         }
+        (found, node)
     }
 
-    /// Produces an Iterator over all of the `Node`s contained in `Self`.
-    pub fn iter_nodes<'a>(&'a self) -> Box<dyn Iterator<Item=&'a Node> + 'a> {
-        let this = Box::new(std::iter::once(self));
-        let kids = self.children.iter().filter_map(|value|{
-            match value {
-                Value::Node(node) => Some(node.iter_nodes()),
-                Value::Event(_) => None,
+    /// Same as `query_ref`, but for editing the value found.
+    pub fn query_mut<'a>(&'a mut self, path: &[usize]) -> Result<Query<'a>, PathFail> {
+        // Checked first, so that failures can be described without holding
+        // on to a mutable borrow.
+        self.query_ref(path)?;
+        let mut node = self;
+        for index in path.iter() {
+            match node.children.get_mut(index - 1) {
+                Some(Value::Node(n)) => node = n,
+                Some(Value::Event(e)) => return Ok(Query::Event(e)),
+                None => unreachable!("query_ref() checked the path"),
+            }
+        }
+        Ok(Query::Node(node))
+    }
+
+    /// Finds the node holding the value at `path`, along with the value's
+    /// 0-based index in its `children`.
+    fn parent_mut(&mut self, path: &[usize]) -> Result<(&mut Node, usize), PathFail> {
+        let (index, parent) = path.split_last()
+            .ok_or_else(||PathFail::new(path, 0, PathFailKind::Root, self))?;
+        self.query_ref(path)?;
+        match self.query_mut(parent)? {
+            Query::Node(node) => Ok((node, index - 1)),
+            Query::Event(_) => unreachable!("query_ref() found a value inside it"),
+        }
+    }
+
+    /// Inserts a copy of the value at `path` right after it, returning the
+    /// path of the copy.
+    pub fn duplicate(&mut self, path: &[usize]) -> Result<Vec<usize>, PathFail> {
+        let (node, index) = self.parent_mut(path)?;
+        let copy = node.children[index].clone();
+        node.children.insert(index + 1, copy);
+        Ok([&path[..path.len() - 1], &[index + 2]].concat())
+    }
+
+    /// Moves the value at `path` to the 1-based position `to` among its
+    /// siblings, returning its new path.
+    pub fn move_to(&mut self, path: &[usize], to: usize) -> EvalResult<Vec<usize>> {
+        let (node, index) = self.parent_mut(path)
+            .map_err(EvalError::NotFound)?;
+        let len = node.children.len();
+        if !(1..=len).contains(&to) {
+            return Err(EvalError::IndexError{index:to, len});
+        }
+        let value = node.children.remove(index);
+        node.children.insert(to - 1, value);
+        Ok([&path[..path.len() - 1], &[to]].concat())
+    }
+
+    /// Replaces the value at `path` with a new node holding just that value,
+    /// returning the value's new path.
+    pub fn wrap(&mut self, path: &[usize], name: Option<String>) -> Result<Vec<usize>, PathFail> {
+        let (node, index) = self.parent_mut(path)?;
+        let value = node.children.remove(index);
+        node.children.insert(index, Node::new(name, vec![value]).into_value());
+        Ok([path, &[1]].concat())
+    }
+
+    /// Replaces the node at `path` with its children, returning the path of
+    /// its parent. Does nothing to events.
+    pub fn unwrap(&mut self, path: &[usize]) -> Result<Vec<usize>, PathFail> {
+        let (node, index) = self.parent_mut(path)?;
+        if let Value::Node(_) = node.children[index] {
+            if let Value::Node(mut inner) = node.children.remove(index) {
+                node.children.splice(index..index, std::mem::take(&mut inner.children));
             }
-        }).flatten();
-        Box::new(this.chain(kids))
+        }
+        Ok(path[..path.len() - 1].to_vec())
+    }
+
+    /// Merges the child events at the 1-based indices `a` and `b` into one,
+    /// kept at the earlier of the two positions.
+    pub fn merge_children(&mut self, a: usize, b: usize) -> EvalResult {
+        if a == b {
+            return Err(EvalError::SameChild(a));
+        }
+        let len = self.children.len();
+        let (first, second) = (a.min(b), a.max(b));
+        for index in [first, second] {
+            match self.children.get(index.wrapping_sub(1)) {
+                Some(Value::Event(_)) => {},
+                Some(Value::Node(_)) => return Err(EvalError::NotAnEvent(index)),
+                _ => return Err(EvalError::IndexError{index, len}),
+            }
+        }
+        let other = match self.children.remove(second - 1) {
+            Value::Event(event) => event,
+            Value::Node(_) => unreachable!("Checked above."),
+        };
+        if let Value::Event(event) = &mut self.children[first - 1] {
+            event.merge(other);
+        }
+        Ok(())
+    }
+
+    /// Replaces the value at `path` with `values`, in order.
+    pub fn replace(&mut self, path: &[usize], values: Vec<Value>) -> Result<(), PathFail> {
+        let (node, index) = self.parent_mut(path)?;
+        node.children.splice(index..=index, values);
+        Ok(())
+    }
+
+    /// Calls `f` on every Event under self along with its path, which uses
+    /// the same 1-based indices as `query()`.
+    pub fn visit_events_mut(&mut self, f: &mut impl FnMut(&[usize], &mut Event)) {
+        // One iterator per level being walked, alongside the path to the current value.
+        let mut stack = vec![self.children.iter_mut().enumerate()];
+        let mut path = vec![];
+        while !stack.is_empty() {
+            path.truncate(stack.len() - 1);
+            match stack.last_mut().and_then(|kids|kids.next()) {
+                Some((i, Value::Node(node))) => {
+                    path.push(i + 1);
+                    stack.push(node.children.iter_mut().enumerate());
+                },
+                Some((i, Value::Event(event))) => {
+                    path.push(i + 1);
+                    f(&path, event);
+                },
+                None => { stack.pop(); },
+            }
+        }
+    }
+
+    /// Keeps only the events under self that `keep` is true of, dropping
+    /// nodes with nothing left in them. Returns how many events were dropped.
+    pub fn retain_events(&mut self, keep: &mut impl FnMut(&Event) -> bool) -> usize {
+        // Every node comes before the nodes in it, so going through them
+        // backwards empties the inner nodes before their parents look.
+        let mut paths = vec![];
+        let mut stack = vec![vec![]];
+        while let Some(path) = stack.pop() {
+            if let Ok(QueryRef::Node(node)) = self.query_ref(&path) {
+                for (i, value) in node.children.iter().enumerate() {
+                    if let Value::Node(_) = value {
+                        stack.push([&path[..], &[i + 1]].concat());
+                    }
+                }
+            }
+            paths.push(path);
+        }
+        let mut dropped = 0;
+        for path in paths.iter().rev() {
+            if let Ok(Query::Node(node)) = self.query_mut(path) {
+                node.children.retain(|value|match value {
+                    Value::Event(event) => {
+                        let kept = keep(event);
+                        dropped += !kept as usize;
+                        kept
+                    },
+                    Value::Node(node) => !node.children.is_empty(),
+                });
+            }
+        }
+        dropped
+    }
+
+    pub fn children(&self) -> &[Value] { &self.children }
+
+    /// Rewrites the name of this node, and every name and description under it.
+    pub fn map_text(&mut self, f: &mut impl FnMut(&str) -> String) {
+        self.name = self.name.as_deref().map(&mut *f);
+        let mut stack = vec![self.children.iter_mut()];
+        while let Some(kids) = stack.last_mut() {
+            match kids.next() {
+                Some(Value::Node(node)) => {
+                    node.name = node.name.as_deref().map(&mut *f);
+                    stack.push(node.children.iter_mut());
+                },
+                Some(Value::Event(event)) => event.map_text(f),
+                None => { stack.pop(); },
+            }
+        }
+    }
+
+    /// Produces an Iterator over all of the `Node`s contained in `Self`.
+    pub fn iter_nodes(&self) -> impl Iterator<Item = &Node> + '_ {
+        self.iter_visits(0.0, 1.0).map(|visit|visit.node)
     }
 
     /// Produces an Iterator over all of the Events contained in Self.
-    pub fn iter_events<'a>(&'a self) -> Box<dyn Iterator<Item=&'a Event> + 'a> {
-        let kids = self.children.iter().map(|value|{
-            match value {
-                Value::Node(node) => node.iter_events(),
-                Value::Event(event) => Box::new(std::iter::once(event)),
+    pub fn iter_events(&self) -> Events<'_> {
+        Events { stack: vec![self.children.iter()] }
+    }
+
+    /// Path of the first event, in document order, that `predicate` holds for.
+    pub fn find(&self, mut predicate: impl FnMut(&Event) -> bool) -> Option<Vec<usize>> {
+        self.iter_events_with_paths()
+            .find(|(_, event)|predicate(event))
+            .map(|(path, _)|path)
+    }
+
+    /// Paths of every event that `predicate` holds for, in document order.
+    pub fn find_all(&self, mut predicate: impl FnMut(&Event) -> bool) -> Vec<Vec<usize>> {
+        self.iter_events_with_paths()
+            .filter(|(_, event)|predicate(event))
+            .map(|(path, _)|path)
+            .collect()
+    }
+
+    /// Names of self and the nodes below it leading to `path`, leaving out
+    /// unnamed ones.
+    pub fn ancestor_names(&self, path: &[usize], lang: Option<&str>) -> Vec<&str> {
+        (0..path.len())
+            .filter_map(|len|match self.query_ref(&path[..len]) {
+                Ok(QueryRef::Node(node)) => node.name_in(lang),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Groups `paths` of events, as found by `find_all`, by their parent
+    /// node, along with the sibling events within `around` places of them.
+    /// Each group holds the parent's path and the indices of the events to
+    /// show in order, marking those that were found.
+    pub fn with_siblings(&self, paths: &[Vec<usize>], around: usize) -> Vec<SiblingGroup> {
+        let mut groups: Vec<SiblingGroup> = vec![];
+        for path in paths.iter() {
+            let (index, parent) = match path.split_last() {
+                Some((index, parent)) => (*index, parent),
+                None => continue,
+            };
+            let node = match self.query_ref(parent) {
+                Ok(QueryRef::Node(node)) => node,
+                _ => continue,
+            };
+            let window = index.saturating_sub(around).max(1)..=(index + around).min(node.children.len());
+            let shown = window
+                .filter(|i|*i == index || matches!(node.children[i - 1], Value::Event(_)))
+                .map(|i|(i, i == index));
+            match groups.last_mut() {
+                Some((last, group)) if last.as_slice() == parent => {
+                    for (i, found) in shown {
+                        match group.iter_mut().find(|(j, _)|*j == i) {
+                            Some(entry) => entry.1 |= found,
+                            None => group.push((i, found)),
+                        }
+                    }
+                },
+                _ => groups.push((parent.to_vec(), shown.collect())),
             }
-        }).flatten();
-        Box::new(kids)
+        }
+        groups
+    }
+
+    /// Produces an Iterator over all of the Events contained in Self, each
+    /// with its path.
+    pub fn iter_events_with_paths(&self) -> PathEvents<'_> {
+        PathEvents { stack: vec![self.children.iter().enumerate()], path: vec![] }
     }
 
     /// Produces an Iterator of depth values intended to be zipped with self.iter().
-    pub fn depth(&self) -> Box<dyn Iterator<Item = usize> + '_> {
-        self.depth_iter(0)
+    pub fn depth(&self) -> impl Iterator<Item = usize> + '_ {
+        self.iter_visits(0.0, 1.0).map(|visit|visit.depth)
     }
 
-    fn depth_iter(&self, depth: usize) -> Box<dyn Iterator<Item = usize> + '_> {
-        let this = Box::new(std::iter::once(depth));
-        let kids = self.children.iter().filter_map(move |value|{
-            match value {
-                Value::Node(node) => Some(node.depth_iter(depth+1)),
-                Value::Event(_) => None,
-            }
-        }).flatten();
-        Box::new(this.chain(kids))
+    /// Returns an Iterator over the y-axis (Offset, Scaling) pair of self
+    /// and every node under it, as placed under a parent with `offset` and
+    /// `scale`. See `transform()` for how they add up.
+    pub fn transform_iter(&self, offset: f64, scale: f64) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.iter_visits(offset, scale).map(|visit|(visit.offset, visit.scale))
     }
 
-    /// Returns an Iterator over y-axis (Offset, Scaling) pairs.
-    pub fn transform_iter(&self, offset: f64, scale: f64) -> Box<dyn Iterator<Item = (f64, f64)> + '_> {
-        let pair = (self.offset + offset, self.y_scale * scale);
-        let this = Box::new(std::iter::once((offset, scale)));
-        let kids = self.children.iter().filter_map(move |value|{
-            match value {
-                Value::Node(node) => {
-                    Some(node.transform_iter(pair.0 * scale, pair.1))
+    /// Offset and scaling of self, placed under a parent with `parent`'s.
+    /// A node's offset moves it and everything in it down, measured in its
+    /// parent's scaling, while its `y_scale` scales the offsets of every
+    /// node nested in it, but not its own.
+    pub fn transform(&self, parent: (f64, f64)) -> (f64, f64) {
+        (parent.0 + self.offset * parent.1, parent.1 * self.y_scale)
+    }
+
+    /// Walks self and every node under it, in the same order as `iter_nodes()`,
+    /// with self placed under a parent with `offset` and `scale`.
+    pub fn iter_visits(&self, offset: f64, scale: f64) -> Visits<'_> {
+        let (offset, scale) = self.transform((offset, scale));
+        Visits { stack: vec![Visit { node: self, depth: 0, offset, scale }], shown: false }
+    }
+
+    /// Like `iter_visits()`, but without the nodes hidden under collapsed ones.
+    pub fn iter_shown(&self, offset: f64, scale: f64) -> Visits<'_> {
+        Visits { shown: true, ..self.iter_visits(offset, scale) }
+    }
+
+    /// Walks self and everything under it in document order, calling
+    /// `visitor` on the way into and out of each node, and at each event.
+    pub fn walk<'a>(&'a self, visitor: &mut impl Visitor<'a>) {
+        let (offset, scale) = self.transform((0.0, 1.0));
+        let root = Position { path: vec![], depth: 0, offset, scale };
+        let mut stack = vec![(Step::Enter(self), root)];
+        while let Some((step, at)) = stack.pop() {
+            match step {
+                Step::Enter(node) => {
+                    visitor.enter(node, &at);
+                    // Reversed, so the first child is the next to be popped.
+                    let kids = node.children.iter().enumerate().rev().map(|(i, value)|{
+                        let path = [&at.path[..], &[i + 1]].concat();
+                        match value {
+                            Value::Node(kid) => {
+                                let (offset, scale) = kid.transform((at.offset, at.scale));
+                                (Step::Enter(kid), Position { path, depth: at.depth + 1, offset, scale })
+                            },
+                            Value::Event(event) => (Step::Event(event), Position { path, ..at.clone() }),
+                        }
+                    }).collect::<Vec<_>>();
+                    stack.push((Step::Leave(node), at));
+                    stack.extend(kids);
                 },
-                Value::Event(_) => None,
+                Step::Leave(node) => visitor.leave(node, &at),
+                Step::Event(event) => visitor.event(event, &at),
             }
-        }).flatten();
-        Box::new(this.chain(kids))
+        }
     }
 
     /// Returns true if self doesn't contain any Events.
@@ -213,62 +753,164 @@ impl Node {
             .fold(max_min,|range,dt|dt.expand_range(range))
     }
 
+    /// Counts the events under self falling in each of `buckets` equal slices
+    /// of `range`. Spans count towards every slice they overlap.
+    pub fn density(&self, range: &(i64, i64), buckets: usize) -> Vec<usize> {
+        let mut counts = vec![0; buckets];
+        for event in self.iter_events() {
+            if let Some(slices) = event.datetime.buckets(range, buckets) {
+                counts[slices].iter_mut().for_each(|count|*count += 1);
+            }
+        }
+        counts
+    }
+
     /// Produces a vector that represents each drawn line in the `Node` structure.
-    pub fn lines(&self, grand_range: &(i64, i64)) -> Vec<Line> {
-        let iter = self.iter_nodes();
-        let iter_trans = self.transform_iter(0.0, 1.0);
-        let iter_depth = self.depth();
-        iter.zip(iter_trans).zip(iter_depth).map(|((node,(offset,scale)),depth)|{
-            let y = offset * scale * depth as f64;
-            match (node.line, node.location(*grand_range)) {
+    /// Lines with a declared span cover just that, unless `extend` stretches
+    /// them over any of their events falling outside it.
+    pub fn lines(&self, grand_range: &(i64, i64), extend: bool) -> Vec<Line> {
+        self.iter_shown(0.0, 1.0).filter_map(|visit|{
+            let y = visit.offset;
+            let node = visit.node;
+            let declared = node.line_span.as_ref().map(|span|{
+                let (a, b) = span.bounds();
+                let width = (grand_range.1 - grand_range.0) as f64;
+                ((a - grand_range.0) as f64 / width, (b - grand_range.0) as f64 / width)
+            });
+            let covered = match (declared, node.location(*grand_range)) {
+                (Some((a,b)), Some((c,d))) if extend => Some((a.min(c), b.max(d))),
+                (Some(declared), _) => Some(declared),
+                (None, events) => events,
+            };
+            match (node.line, covered) {
                 (Some(int), Some((a,b))) => {
-                    Some(Line { start:a, end:b, interval:int, y:y})
+                    Some(Line { start:a, end:b, interval:int, y, depth:visit.depth })
                 },
                 _ => None,
             }
-        })  .filter(|opt|opt.is_some()) // Drop all Nones...
-            .map(|opt|opt.unwrap())     // ...and retain only Ok's.
-            .collect::<Vec<_>>()        // TODO: refactor as filter_map().
+        }).collect::<Vec<_>>()
+    }
+
+    /// The span declared by the innermost node drawing a line that holds the
+    /// value at `path`, which the value is expected to fall within.
+    pub fn line_span_at(&self, path: &[usize]) -> Option<&Dates> {
+        let mut node = self;
+        let mut span = None;
+        for i in path.iter() {
+            if node.line.is_some() && node.line_span.is_some() {
+                span = node.line_span.as_ref();
+            }
+            match node.children.get(i.checked_sub(1)?) {
+                Some(Value::Node(child)) => node = child,
+                _ => break,
+            }
+        }
+        span
     }
 
     /// Produces a multiline, indented string that shows the underlying tree structure.
-    pub fn print(&self, depth: usize, verbose: bool) -> String {
+    pub fn print(&self, depth: usize, options: &PrintOptions) -> String {
+        if self.collapsed {
+            return self.summary(depth, options);
+        }
+        let mut lines = self.print_header(depth, options);
+        // Values still to print, last child first so that they pop off in order.
+        let mut stack: Vec<(&Value, usize)> = self.children.iter().rev()
+            .map(|value|(value, depth + 1))
+            .collect();
+        while let Some((value, depth)) = stack.pop() {
+            match value {
+                Value::Node(node) if node.collapsed => lines.push(node.summary(depth, options)),
+                Value::Node(node) => {
+                    lines.append(&mut node.print_header(depth, options));
+                    stack.extend(node.children.iter().rev().map(|value|(value, depth + 1)));
+                },
+                Value::Event(event) => lines.push(event.print(depth, options)),
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// One line per child, without their own children, numbered the way
+    /// paths count them.
+    pub fn list_children(&self, options: &PrintOptions) -> String {
+        self.children.iter().enumerate()
+            .map(|(i, value)|{
+                let line = match value {
+                    Value::Node(node) => node.print_header(0, options).remove(0),
+                    Value::Event(event) => event.print(0, options),
+                };
+                format!("{:>3} {}", i + 1, line)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// The lines describing this Node itself, without its children.
+    /// The lines `print()` describes self with, leaving out its children.
+    pub fn print_header(&self, depth: usize, options: &PrintOptions) -> Vec<String> {
         let pad = padding("  ", depth);
-        let start = match self.name {
-            Some(ref name) => format!("{}<Node> {}", pad, name),
+        let start = match self.name_in(options.lang) {
+            Some(name) => format!("{}<Node> {}", pad, name),
             None => format!("{}<Node> (No name)", pad),
         };
         let mut lines = vec![
             start,
         ];
-        if verbose {
+        if options.verbose {
             lines.push(format!("{}  Offset:  {}", pad, self.offset));
             lines.push(format!("{}  Scaling: {}", pad, self.y_scale));
-            if let Some(line) = self.line {
-                lines.push(format!("{}  Line: {:?}", pad, line));
+            match self.line {
+                Some(Some(interval)) => lines.push(format!("{}  Line:    ticks every {}", pad, interval)),
+                Some(None) => lines.push(format!("{}  Line:    no ticks", pad)),
+                None => {},
             }
-        }
-        let mut kids = self.children.iter().map(|value|{
-            match value {
-                Value::Node(node)   => node.print(depth+1, verbose),
-                Value::Event(event) => event.print(depth+1, verbose),
+            if let Some(span) = self.line_span.as_ref() {
+                lines.push(format!("{}  Covers:  {}", pad, span));
             }
-        }).collect::<Vec<String>>();
-        lines.append(&mut kids);
-        lines.join("\n")
+            if let Some(scheme) = self.scheme.as_ref() {
+                lines.push(format!("{}  Scheme:  {}", pad, scheme));
+            }
+        }
+        lines
     }
 
-    /// Returns the location of this Node's time range.
-    fn location(&self, range: (i64, i64)) -> Option<(f64, f64)> {
+    /// The one line a collapsed node is printed as: its first line, with how
+    /// many events it hides and the dates they cover.
+    pub fn summary(&self, depth: usize, options: &PrintOptions) -> String {
+        let header = self.print_header(depth, options).remove(0);
+        let count = self.iter_events().count();
+        // The range ends just after the last minute covered, which is shown.
+        let (start, end) = self.range();
+        let dates = match Dates::from_stamps((start, (end - 60).max(start))) {
+            Some(dates) if count > 0 => match options.dates {
+                Some(format) => format!(", {}", format.format_dates(&dates)),
+                None => format!(", {}", dates),
+            },
+            _ => String::new(),
+        };
+        format!("{} [collapsed, {} event{}{}]", header, count, if count == 1 { "" } else { "s" }, dates)
+    }
+
+    /// How many Nodes deep the tree goes, counting `self` as 1.
+    pub fn max_depth(&self) -> usize {
+        self.depth().max().unwrap_or(0) + 1
+    }
+
+    /// Returns where the events under self start and end, as fractions of
+    /// `range`. Nothing if there are no events, or `range` is empty.
+    pub fn location(&self, range: (i64, i64)) -> Option<(f64, f64)> {
         let (start,end) = range;
         let width = (end - start) as f64;
+        let (a,b) = self.range();
         match width {
             n if n == 0.0 => { None },
+            // Still the empty fold of `range()`.
+            _ if a > b => { None },
             _ => {
-                let (a,b) = (range.0 as f64, range.1 as f64);
                 Some((
-                    (a - start as f64) / width,
-                    (b - start as f64) / width,
+                    (a - start) as f64 / width,
+                    (b - start) as f64 / width,
                 ))
             },
         }
@@ -279,11 +921,65 @@ impl Node {
         self.children.push(value);
     }
 
+    /// Returns the name in the given language, falling back to the plain name.
+    pub fn name_in(&self, lang: Option<&str>) -> Option<&str> {
+        lang.and_then(|lang|self.translations.get(lang))
+            .and_then(|tr|tr.name.as_deref())
+            .or(self.name.as_deref())
+    }
+
     /// Sets the name of self.
     pub fn set_name(&mut self, name: Option<&str>) {
         self.name = name.map(|s|s.to_string());
     }
 
+    pub fn offset(&self) -> f64 { self.offset }
+
+    pub fn scale(&self) -> f64 { self.y_scale }
+
+    /// The color used for everything under the node, if one was set.
+    pub fn color(&self) -> Option<&ColorRef> { self.color_override.as_ref() }
+
+    /// Sets the color used for everything under the node.
+    pub fn set_color(&mut self, color: Option<ColorRef>) {
+        self.color_override = color;
+    }
+
+    /// Name of the color scheme used under the node, if one was set.
+    pub fn scheme(&self) -> Option<&str> { self.scheme.as_deref() }
+
+    pub fn set_scheme(&mut self, scheme: Option<&str>) {
+        self.scheme = scheme.map(|s|s.to_string());
+    }
+
+    /// Calls `f` on the name of every color scheme referred to by self, or
+    /// by anything under it.
+    pub fn visit_scheme_refs_mut(&mut self, f: &mut dyn FnMut(&mut String)) {
+        // Nodes still to be visited, kept on a stack rather than recursing.
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            if let Some(ColorRef::Scheme(name)) = node.color_override.as_mut() {
+                f(name);
+            }
+            if let Some(name) = node.scheme.as_mut() {
+                f(name);
+            }
+            for child in node.children.iter_mut() {
+                match child {
+                    Value::Node(node) => stack.push(node),
+                    Value::Event(event) => if let Some(ColorRef::Scheme(name)) = event.color.as_mut() {
+                        f(name);
+                    },
+                }
+            }
+        }
+    }
+
+    /// Sets the name of the style used for everything under the node.
+    pub fn set_style(&mut self, style: Option<&str>) {
+        self.style_override = style.map(|s|s.to_string());
+    }
+
     /// Sets the vertical offset of the element in the render.
     pub fn set_offset(&mut self, y: &f64) {
         self.offset = *y;
@@ -295,17 +991,107 @@ impl Node {
     }
 
     /// Sets the Line.
-    pub fn set_line(&mut self, line: Option<Option<f64>>) {
+    pub fn set_line(&mut self, line: Option<Option<Interval>>) {
         self.line = line.clone();
     }
 
+    pub fn line_span(&self) -> Option<&Dates> { self.line_span.as_ref() }
+
+    /// Declares the dates the node's line covers, or leaves it to cover its events.
+    pub fn set_line_span(&mut self, span: Option<Dates>) {
+        self.line_span = span;
+    }
+
+    pub fn is_collapsed(&self) -> bool { self.collapsed }
+
+    /// Hides or shows everything under the node when printing and rendering.
+    pub fn set_collapsed(&mut self, collapsed: bool) {
+        self.collapsed = collapsed;
+    }
+
+    pub fn image(&self) -> Option<&str> { self.image.as_deref() }
+
+    /// Sets the SVG file drawn at the start of the node, or removes it.
+    pub fn set_image(&mut self, image: Option<&str>) {
+        self.image = image.map(|s|s.to_string());
+    }
+
     /// Builder Method. TODO: Probably don't need, except for building explicit structs in test.
-    pub fn with_line(mut self, line: Option<f64>) -> Self {
+    pub fn with_line(mut self, line: Option<Interval>) -> Self {
         self.line = Some(line);
         self
     }
 }
 
+impl<'a> Iterator for Visits<'a> {
+    type Item = Visit<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let visit = self.stack.pop()?;
+        if self.shown && visit.node.collapsed {
+            return Some(visit);
+        }
+        // Reversed, so the first child is the next to be popped.
+        let kids = visit.node.children.iter().rev().filter_map(|value|match value {
+            Value::Node(kid) => {
+                let (offset, scale) = kid.transform((visit.offset, visit.scale));
+                Some(Visit { node: kid, depth: visit.depth + 1, offset, scale })
+            },
+            Value::Event(_) => None,
+        });
+        self.stack.extend(kids);
+        Some(visit)
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = &'a Event;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(Value::Event(event)) => return Some(event),
+                Some(Value::Node(node)) => self.stack.push(node.children.iter()),
+                None => { self.stack.pop(); },
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for PathEvents<'a> {
+    type Item = (Vec<usize>, &'a Event);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let depth = self.stack.len();
+            match self.stack.last_mut()?.next() {
+                Some((i, value)) => {
+                    self.path.truncate(depth - 1);
+                    self.path.push(i + 1);
+                    match value {
+                        Value::Event(event) => return Some((self.path.clone(), event)),
+                        Value::Node(node) => self.stack.push(node.children.iter().enumerate()),
+                    }
+                },
+                None => { self.stack.pop(); },
+            }
+        }
+    }
+}
+
+/// Drops children from an explicit stack, since the default drop would
+/// recurse once per level of nesting.
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(value) = stack.pop() {
+            if let Value::Node(mut node) = value {
+                stack.append(&mut node.children);
+            }
+        }
+    }
+}
+
 impl Event {
     /// Creates a new Event with a name and dates.
     pub fn new(name: &str, dt: Dates) -> Event {
@@ -313,20 +1099,37 @@ impl Event {
             name: name.to_string(),
             descriptions: vec![],
             datetime: dt,
+            status: None,
+            color: None,
+            layer: 0,
+            translations: BTreeMap::new(),
         }
     }
 
-    pub fn print(&self, depth: usize, verbose: bool) -> String {
+    pub fn print(&self, depth: usize, options: &PrintOptions) -> String {
         let pad = padding("  ", depth);
+        let dates = match options.dates {
+            Some(format) => format.format_dates(&self.datetime),
+            None => self.datetime.to_string(),
+        };
+        let status = self.status
+            .map(|status|format!(" ({})", status))
+            .unwrap_or_default();
+        let duration = match self.datetime.duration() {
+            Some(duration) if options.verbose => format!(", {}", duration),
+            _ => String::new(),
+        };
         let start = format!(
-            "{}<Event> {}, [{}]",
+            "{}<Event> {}, [{}{}]{}",
             pad,
-            self.name,
-            self.datetime.to_string(),
+            self.name_in(options.lang),
+            dates,
+            duration,
+            status,
         );
         let mut lines = vec![start];
-        if verbose {
-            self.descriptions
+        if options.verbose {
+            self.descriptions_in(options.lang)
                 .iter()
                 .map(|desc|format!("{}  - {}", pad, desc))
                 .for_each(|s|lines.push(s));
@@ -342,12 +1145,93 @@ impl Event {
     /// Getter for name.
     pub fn name(&self) -> &str { &self.name }
 
+    /// Returns the name in the given language, falling back to the plain name.
+    pub fn name_in(&self, lang: Option<&str>) -> &str {
+        self.translation(lang)
+            .and_then(|tr|tr.name.as_deref())
+            .unwrap_or(&self.name)
+    }
+
+    /// Returns the descriptions in the given language, falling back to the plain ones.
+    pub fn descriptions_in(&self, lang: Option<&str>) -> &[String] {
+        match self.translation(lang) {
+            Some(tr) if !tr.descriptions.is_empty() => &tr.descriptions,
+            _ => &self.descriptions,
+        }
+    }
+
+    fn translation(&self, lang: Option<&str>) -> Option<&Translation> {
+        lang.and_then(|lang|self.translations.get(lang))
+    }
+
     /// Set name.
     pub fn set_name(&mut self, new: &str) { self.name = new.to_string(); }
 
+    /// Cuts a span in two at `at`, naming the parts "name (1)" and "name (2)".
+    /// Returns `None` unless `at` falls inside the span.
+    pub fn split_at(&self, at: Dt, precision: Precision) -> Option<(Event, Event)> {
+        let (first, second) = self.datetime.split_at(at, precision)?;
+        let part = |n: usize, dates: Dates|Event {
+            name: format!("{} ({})", self.name, n),
+            datetime: dates,
+            ..self.clone()
+        };
+        Some((part(1, first), part(2, second)))
+    }
+
+    /// Absorbs `other`, spanning both and keeping both sets of descriptions,
+    /// without repeating any they share, on the higher of their layers.
+    pub fn merge(&mut self, other: Event) {
+        self.datetime = self.datetime.union(&other.datetime);
+        self.layer = self.layer.max(other.layer);
+        for desc in other.descriptions {
+            if !self.descriptions.contains(&desc) {
+                self.descriptions.push(desc);
+            }
+        }
+    }
+
+    /// Rewrites the name and each description.
+    pub fn map_text(&mut self, f: &mut impl FnMut(&str) -> String) {
+        self.name = f(&self.name);
+        self.descriptions.iter_mut().for_each(|desc|*desc = f(desc));
+    }
+
     /// Set dates.
     pub fn set_dates(&mut self, new: &Dates) { self.datetime = new.clone(); }
 
+    /// Mutable getter for dates.
+    pub fn dates_mut(&mut self) -> &mut Dates { &mut self.datetime }
+
+    /// Getter for status.
+    pub fn status(&self) -> Option<Status> { self.status }
+
+    /// Set status.
+    pub fn set_status(&mut self, new: Option<Status>) { self.status = new; }
+
+    /// Getter for the layer the event is drawn on.
+    pub fn layer(&self) -> i32 { self.layer }
+
+    /// Set layer.
+    pub fn set_layer(&mut self, new: i32) { self.layer = new; }
+
+    /// Getter for the color drawn instead of the default fill.
+    pub fn color(&self) -> Option<&ColorRef> { self.color.as_ref() }
+
+    /// Set color.
+    pub fn set_color(&mut self, new: Option<ColorRef>) { self.color = new; }
+
+    /// Returns true if `query` appears in the name or any description, ignoring case.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        std::iter::once(&self.name)
+            .chain(self.descriptions.iter())
+            .any(|text|text.to_lowercase().contains(&query))
+    }
+
+    /// Getter for dates.
+    pub fn dates(&self) -> &Dates { &self.datetime }
+
     /// Getter for dates.
     pub fn date_string(&self) -> String {
         format!("{}", self.datetime)
@@ -360,13 +1244,15 @@ impl Event {
 
     /// Returns the location of an event within the context of a given range of timestamps.
     /// If self is within the range of timestamps then the output will be in [0,1].
+    /// Spans end where their last unit does, as in `Dates::span()`.
     pub fn location(&self, range: (i64, i64)) -> (f64, Option<f64>) {
         let (start, end) = range;
         let span = (end - start) as f64;
-        let f = |date: &Dt| { (date.timestamp() - start) as f64 / span };
+        let f = |stamp: i64| { (stamp - start) as f64 / span };
+        let (from, to) = self.datetime.stamps();
         (
-            f(&self.datetime.start),
-            self.datetime.end.as_ref().map(f),
+            f(from),
+            self.datetime.end.and(to).map(f),
         )
     }
 
@@ -375,6 +1261,9 @@ impl Event {
         self.descriptions.push(new.to_string());
     }
 
+    /// Replaces every description of self.
+    pub fn set_descriptions(&mut self, new: Vec<String>) { self.descriptions = new; }
+
     /// Replaces the description at the given index.
     pub fn change_description(&mut self, index: usize, new: &str) -> EvalResult {
         match index < self.descriptions.len() {
@@ -399,23 +1288,209 @@ impl Event {
 }
 
 impl Dates {
-    /// Converts a set of timestamps into a Dates struct.
-    pub fn from(range: (i64, i64)) -> Dates {
+    /// Converts a pair of timestamps, in seconds, into a minute precise span.
+    /// Nothing if either is out of range.
+    pub fn from_stamps(range: (i64, i64)) -> Option<Dates> {
         let (start, end) = range;
+        Some(Dates::from_dts(from_stamp(start)?, Some(from_stamp(end)?)))
+    }
+
+    /// Getter for the start of the range, or the point in time.
+    pub fn start(&self) -> &Dt { &self.start }
+
+    /// Getter for the end of the range.
+    pub fn end(&self) -> Option<&Dt> { self.end.as_ref() }
+
+    /// Builds a Dates struct from a start and an optional end.
+    pub fn from_dts(start: Dt, end: Option<Dt>) -> Dates {
+        Dates::with_precision((start, Precision::Minute), end.map(|end|(end, Precision::Minute)))
+    }
+
+    /// Builds a Dates struct, recording how precisely each end was given.
+    pub fn with_precision(start: (Dt, Precision), end: Option<(Dt, Precision)>) -> Dates {
+        Dates {
+            start: start.0,
+            end: end.map(|end|end.0),
+            precision: (start.1, end.map(|end|end.1).unwrap_or(start.1)),
+            ongoing: false,
+        }
+    }
+
+    /// Builds a span that started at `start` and hasn't ended yet.
+    pub fn ongoing(start: (Dt, Precision)) -> Dates {
+        Dates {
+            ongoing: true,
+            ..Dates::with_precision(start, None)
+        }
+    }
+
+    /// Returns true if self is a span that hasn't ended yet.
+    pub fn is_ongoing(&self) -> bool { self.ongoing }
+
+    /// Returns true if self is a span that ends before it starts.
+    pub fn is_inverted(&self) -> bool {
+        self.end.map(|end|end < self.start).unwrap_or(false)
+    }
+
+    /// Passes self through, unless it ends before it starts.
+    pub fn checked(self) -> Result<Dates, InvertedDates> {
+        match self.is_inverted() {
+            true => Err(InvertedDates(self)),
+            false => Ok(self),
+        }
+    }
+
+    /// Swaps the start and end of an inverted span.
+    pub fn fix_inverted(&mut self) {
+        if let Some(end) = self.end.as_mut().filter(|end|**end < self.start) {
+            std::mem::swap(&mut self.start, end);
+            self.precision = (self.precision.1, self.precision.0);
+        }
+    }
+
+    /// Returns true if `s` is one of the words standing in for an ongoing end.
+    pub fn is_ongoing_word(s: &str) -> bool {
+        ONGOING.contains(&s.trim().to_lowercase().as_str())
+    }
+
+    /// Parses dates the way a person might type them: besides Saga's own
+    /// format, ISO 8601, month names ("March 1990") and days relative to
+    /// today ("yesterday", "last tuesday"). Spans must be separated by " - ".
+    pub fn parse_loose(s: &str) -> Result<Dates, DtParseError> {
+        Dates::parse_loose_at(s, Some(chrono::Local::now().naive_local().date()))
+    }
+
+    /// Same as `parse_loose`, but relative days are only read when `today`
+    /// is given.
+    pub fn parse_loose_at(s: &str, today: Option<NaiveDate>) -> Result<Dates, DtParseError> {
+        let parse = |s: &str|Precision::parse_loose(s, today);
+        match s.split_once(" - ") {
+            Some((left, right)) if Dates::is_ongoing_word(right) => Ok(Dates::ongoing(parse(left)?)),
+            Some((left, right)) => Ok(Dates::with_precision(parse(left)?, Some(parse(right)?))),
+            // Spans written without spaces, like "1990-1995", are left to `from_str`.
+            None => parse(s).map(|start|Dates::with_precision(start, None))
+                .or_else(|e|s.parse::<Dates>().map_err(|_|e)),
+        }
+    }
+
+    /// Moves both ends of self by `by`.
+    pub fn shift(&mut self, by: Duration) {
+        self.start += by;
+        self.end = self.end.map(|end|end + by);
+    }
+
+    /// Cuts a span in two at `at`, which has to fall strictly inside it.
+    pub fn split_at(&self, at: Dt, precision: Precision) -> Option<(Dates, Dates)> {
+        let inside = match self.end {
+            Some(end) => self.start < at && at < end,
+            None => self.ongoing && self.start < at,
+        };
+        match inside {
+            true => Some((
+                Dates { end: Some(at), precision: (self.precision.0, precision), ongoing: false, ..self.clone() },
+                Dates { start: at, precision: (precision, self.precision.1), ..self.clone() },
+            )),
+            false => None,
+        }
+    }
+
+    /// The smallest span covering both self and `other`.
+    pub fn union(&self, other: &Dates) -> Dates {
+        let first = match self.start <= other.start {
+            true => self,
+            false => other,
+        };
+        let last_of = |d: &Dates|d.end.unwrap_or(d.start);
+        let last = match last_of(self) >= last_of(other) {
+            true => self,
+            false => other,
+        };
+        // Two points at the same moment stay a point.
+        let end = match (last.end, last_of(last) == first.start) {
+            (None, true) => None,
+            _ => Some(last_of(last)),
+        };
+        let ongoing = self.ongoing || other.ongoing;
         Dates {
-            start: Dt::from_timestamp_millis(start).unwrap(),
-            end: Dt::from_timestamp_millis(end),
+            start: first.start,
+            end: end.filter(|_|!ongoing),
+            precision: (first.precision.0, last.precision.1),
+            ongoing,
         }
     }
 
-    /// Produces a set of timestamps from Self.
-    fn stamps(&self) -> (i64, Option<i64>) {
+    /// Stretches the distance of both ends from `anchor` by `factor`.
+    pub fn scale(&mut self, anchor: &Dt, factor: f64) {
+        fn scaled(dt: Dt, anchor: &Dt, factor: f64) -> Dt {
+            let seconds = (dt - *anchor).num_seconds() as f64 * factor;
+            *anchor + Duration::seconds(seconds.round() as i64)
+        }
+        self.start = scaled(self.start, anchor, factor);
+        self.end = self.end.map(|end|scaled(end, anchor, factor));
+    }
+
+    /// Getter for the precision of the start.
+    pub fn start_precision(&self) -> Precision { self.precision.0 }
+
+    /// Getter for the precision of the end.
+    pub fn end_precision(&self) -> Precision { self.precision.1 }
+
+    /// The time actually covered by self. Imprecise dates are stretched to
+    /// the end of their unit, so "1990" spans the whole year, and ongoing
+    /// spans end at the current time.
+    pub fn span(&self) -> (Dt, Option<Dt>) {
+        let end = match self.end {
+            _ if self.ongoing => Some(chrono::Local::now().naive_local().max(self.start)),
+            Some(end) => Some(self.precision.1.end_of(&end)),
+            None if self.precision.0 == Precision::Minute => None,
+            None => Some(self.precision.0.end_of(&self.start)),
+        };
+        (self.start, end)
+    }
+
+    /// How long self lasts in human terms, like "18 days" or "3 years 2
+    /// months", in at most its two largest units. Only spans have one.
+    pub fn duration(&self) -> Option<String> {
+        if self.end.is_none() && !self.ongoing {
+            return None;
+        }
+        let (start, end) = self.span();
+        elapsed(&start, &end?)
+    }
+
+    /// Which of `buckets` equal slices of `range` self overlaps, or nothing
+    /// if it falls outside `range` or there are no slices to speak of.
+    pub fn buckets(&self, range: &(i64, i64), buckets: usize) -> Option<RangeInclusive<usize>> {
+        let width = (range.1 - range.0) as f64 / buckets as f64;
+        if buckets == 0 || width <= 0.0 { return None; }
+        let bucket = |stamp: i64|(((stamp - range.0) as f64 / width) as usize).min(buckets - 1);
+        let (start, end) = self.stamps();
+        let end = end.unwrap_or(start);
+        if end < range.0 || start > range.1 { return None; }
+        Some(bucket(start.max(range.0))..=bucket(end))
+    }
+
+    /// Timestamps of where self starts and ends, in seconds, see `span()`.
+    pub fn stamps(&self) -> (i64, Option<i64>) {
+        let (start, end) = self.span();
         (
-            self.start.timestamp(),
-            self.end.as_ref().map(|some|some.timestamp()),
+            to_stamp(&start),
+            end.as_ref().map(to_stamp),
         )
     }
 
+    /// First and last timestamps of self, which are the same for points in time.
+    pub fn bounds(&self) -> (i64, i64) {
+        let (start, end) = self.stamps();
+        (start, end.unwrap_or(start))
+    }
+
+    /// True if any part of self falls outside `other`.
+    pub fn exceeds(&self, other: &Dates) -> bool {
+        let ((start, end), (from, to)) = (self.bounds(), other.bounds());
+        start < from || end > to
+    }
+
     /// Compares self to a set of timestamps and returns the timestamps that contain both.
     fn expand_range(&self, range: (i64, i64)) -> (i64, i64) {
         let (min, max) = range;
@@ -427,14 +1502,121 @@ impl Dates {
     }
 }
 
+impl Precision {
+    /// Storage format for dates given with this precision.
+    pub fn format(&self) -> &'static str {
+        match self {
+            Precision::Year   => "%Y",
+            Precision::Month  => "%m/%Y",
+            Precision::Day    => "%d/%m/%Y",
+            Precision::Minute => FORMAT,
+        }
+    }
+
+    /// Returns the start of the unit following the one `dt` falls in.
+    /// Minute precise dates are returned unchanged.
+    pub fn end_of(&self, dt: &Dt) -> Dt {
+        use chrono::{Datelike, Duration, NaiveDate};
+        let first_of = |year: i32, month: u32| {
+            NaiveDate::from_ymd_opt(year, month, 1)
+                .and_then(|date|date.and_hms_opt(0, 0, 0))
+                .unwrap_or(*dt)
+        };
+        match self {
+            Precision::Year   => first_of(dt.year() + 1, 1),
+            Precision::Month if dt.month() == 12 => first_of(dt.year() + 1, 1),
+            Precision::Month  => first_of(dt.year(), dt.month() + 1),
+            Precision::Day    => *dt + Duration::days(1),
+            Precision::Minute => *dt,
+        }
+    }
+
+    /// Parses a single date in the most precise format that fits.
+    fn parse(s: &str) -> Result<(Dt, Precision), DtParseError> {
+        use chrono::NaiveDate;
+        let s = s.trim();
+        let full = Dt::parse_from_str(s, FORMAT);
+        if let Ok(dt) = full {
+            return Ok((dt, Precision::Minute));
+        }
+        let midnight = |date: NaiveDate|date.and_hms_opt(0, 0, 0);
+        let coarse = [
+            (Precision::Day, s.to_string()),
+            (Precision::Month, format!("1/{}", s)),
+            (Precision::Year, format!("1/1/{}", s)),
+        ];
+        coarse.iter()
+            .find_map(|(precision, padded)|{
+                NaiveDate::parse_from_str(padded, Precision::Day.format()).ok()
+                    .and_then(midnight)
+                    .map(|dt|(dt, *precision))
+            })
+            .ok_or_else(||full.unwrap_err())
+    }
+
+    /// Parses a single date in Saga's own format, or else in the first of
+    /// `LOOSE_FORMATS` that fits, or as a day relative to `today`.
+    fn parse_loose(s: &str, today: Option<NaiveDate>) -> Result<(Dt, Precision), DtParseError> {
+        let s = s.trim();
+        let own = Precision::parse(s);
+        if own.is_ok() {
+            return own;
+        }
+        let midnight = |date: NaiveDate|date.and_hms_opt(0, 0, 0);
+        LOOSE_FORMATS.iter()
+            .find_map(|(precision, format)|{
+                let dt = match precision {
+                    Precision::Minute => Dt::parse_from_str(s, format).ok(),
+                    Precision::Month => NaiveDate::parse_from_str(&format!("1 {}", s), &format!("%d {}", format)).ok()
+                        .and_then(midnight),
+                    _ => NaiveDate::parse_from_str(s, format).ok()
+                        .and_then(midnight),
+                };
+                dt.map(|dt|(dt, *precision))
+            })
+            .or_else(||today
+                .and_then(|today|relative_day(s, today))
+                .and_then(midnight)
+                .map(|dt|(dt, Precision::Day)))
+            .ok_or_else(||own.unwrap_err())
+    }
+}
+
+/// Reads "today", "yesterday", "tomorrow", and "last" or "next" followed by
+/// a weekday, as a day relative to `today`.
+fn relative_day(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+    use chrono::{Datelike, Weekday};
+    let s = s.to_lowercase();
+    match s.as_str() {
+        "today"     => return Some(today),
+        "yesterday" => return today.pred_opt(),
+        "tomorrow"  => return today.succ_opt(),
+        _ => {},
+    }
+    let (direction, day) = s.split_once(' ')?;
+    let day = day.trim().parse::<Weekday>().ok()?;
+    let from = today.weekday().num_days_from_monday() as i64;
+    let to = day.num_days_from_monday() as i64;
+    // Never today itself, but the closest one before or after it.
+    let days = match direction {
+        "last" => -((from - to + 6).rem_euclid(7) + 1),
+        "next" => (to - from + 6).rem_euclid(7) + 1,
+        _ => return None,
+    };
+    Some(today + Duration::days(days))
+}
+
 /// Used by serde to write struct to file.
 impl std::fmt::Display for Dates {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let left = self.start.format(FORMAT).to_string();
-        let right = self.end
-            .as_ref()
-            .map(|some|format!(" - {}", some.format(FORMAT).to_string()))
-            .unwrap_or( "".to_string() );
+        let left = self.start.format(self.precision.0.format()).to_string();
+        let right = match self.ongoing {
+            true => format!(" - {}", ONGOING[0]),
+            false => self.end
+                .as_ref()
+                .map(|some|format!(" - {}", some.format(self.precision.1.format())))
+                .unwrap_or( "".to_string() ),
+        };
         let result = format!("{}{}", left, right);
         write!(f, "{}", result)
     }
@@ -445,17 +1627,133 @@ impl FromStr for Dates {
     type Err = DtParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let (left,right) = match s.split_once('-') {
+            Some((left,right)) if Dates::is_ongoing_word(right) => {
+                return Ok(Dates::ongoing(Precision::parse(left)?));
+            },
             Some((left,right)) => {
-                let start = Dt::parse_from_str(left.trim(), FORMAT)?;
-                let end   = Dt::parse_from_str(right.trim(), FORMAT)?;
+                let start = Precision::parse(left)?;
+                let end   = Precision::parse(right)?;
                 (start,Some(end))
             },
-            None => { (Dt::parse_from_str(s, FORMAT)?,None) },
+            None => { (Precision::parse(s)?,None) },
+        };
+        Ok(Dates::with_precision(left, right))
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Status::Planned    => "planned",
+            Status::InProgress => "in-progress",
+            Status::Done       => "done",
+            Status::Cancelled  => "cancelled",
         };
-        Ok(Dates { start: left, end: right })
+        write!(f, "{}", name)
     }
 }
 
+impl std::fmt::Display for StatusParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "'{}' isn't one of: planned, in-progress, done, cancelled", self.0)
+    }
+}
+
+impl std::error::Error for StatusParseError {}
+
+impl FromStr for Status {
+    type Err = StatusParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "planned"     => Ok(Status::Planned),
+            "in-progress" => Ok(Status::InProgress),
+            "done"        => Ok(Status::Done),
+            "cancelled"   => Ok(Status::Cancelled),
+            other => Err(StatusParseError(other.to_string())),
+        }
+    }
+}
+
+/// Reads a signed duration such as "3d", "+12h" or "-2w". The units are
+/// min, h, d and w.
+pub fn parse_duration(s: &str) -> Result<Duration, DurationParseError> {
+    let fail = ||DurationParseError(s.to_string());
+    let trimmed = s.trim();
+    let (sign, rest) = match trimmed.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let split = rest.find(|c: char|!c.is_ascii_digit()).ok_or_else(fail)?;
+    let (count, unit) = rest.split_at(split);
+    let count = sign * count.parse::<i64>().map_err(|_|fail())?;
+    match unit {
+        "min" => Ok(Duration::minutes(count)),
+        "h"   => Ok(Duration::hours(count)),
+        "d"   => Ok(Duration::days(count)),
+        "w"   => Ok(Duration::weeks(count)),
+        _ => Err(fail()),
+    }
+}
+
+impl std::fmt::Display for DurationParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "'{}' isn't a duration, such as +3d or -2w", self.0)
+    }
+}
+
+impl std::error::Error for DurationParseError {}
+
+impl std::fmt::Display for InvertedDates {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{}] ends before it starts", self.0)
+    }
+}
+
+impl std::error::Error for InvertedDates {}
+
+impl PathFail {
+    /// Failed at position `at` of `path`, with `node` the deepest one reached.
+    fn new(path: &[usize], at: usize, kind: PathFailKind, node: &Node) -> PathFail {
+        PathFail { path: path.to_vec(), at, kind, reached: node.name.clone() }
+    }
+
+    /// Names the node at the start of `path` that is `depth` long, which is
+    /// the deepest one reached.
+    fn reached(&self, depth: usize) -> String {
+        let at = match depth {
+            0 => "the root".to_string(),
+            _ => format!("node {}", path_string(&self.path[..depth])),
+        };
+        match self.reached.as_ref() {
+            Some(name) => format!("{} ({})", at, name),
+            None => at,
+        }
+    }
+}
+
+impl std::fmt::Display for PathFail {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let path = match self.path.is_empty() {
+            true => "/".to_string(),
+            false => path_string(&self.path),
+        };
+        write!(f, "path {} not found: ", path)?;
+        match self.kind {
+            PathFailKind::OutOfBounds { len } => write!(
+                f, "{} holds {} value(s), not {}",
+                self.reached(self.at - 1), len, self.path[self.at - 1],
+            ),
+            PathFailKind::IntoEvent => write!(
+                f, "{} is an event in {}, which can't hold {}",
+                path_string(&self.path[..self.at - 1]), self.reached(self.at - 2), self.path[self.at - 1],
+            ),
+            PathFailKind::Root => write!(f, "{} isn't inside a node", self.reached(0)),
+        }
+    }
+}
+
+impl std::error::Error for PathFail {}
+
 impl From<DtParseError> for SagaDocError {
     fn from(dt_err: DtParseError) -> Self {
         SagaDocError::DtParse(dt_err)
@@ -480,7 +1778,10 @@ fn padding(pad: &str, n: usize) -> String {
 
 #[cfg(test)]
 mod tests {
-    use crate::events::{Dates, Event, Node, Query};
+    use chrono::{Duration, NaiveDate};
+    use crate::axis::Interval;
+    use crate::events::{from_stamp, parse_duration, to_stamp, Dates, Event, Node, PathFailKind, Position, Precision, PrintOptions, Query, QueryRef, Status, Value, Visitor};
+    use crate::validate::path_string;
 
     #[test]
     fn test_date_parsing() {
@@ -493,6 +1794,340 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_date_precision() {
+        let ok_tests = [
+            ("1990", Precision::Year, "01/01/1991 00:00"),
+            ("03/1990", Precision::Month, "01/04/1990 00:00"),
+            ("12/1990", Precision::Month, "01/01/1991 00:00"),
+            ("5/3/1990", Precision::Day, "06/03/1990 00:00"),
+        ];
+        for (query, precision, end) in ok_tests.iter() {
+            let dates = query.parse::<Dates>().unwrap();
+            assert_eq!(dates.start_precision(), *precision);
+            assert_eq!(dates.span().1.unwrap().format(super::FORMAT).to_string(), *end);
+            // Re-serializing keeps the original precision.
+            assert_eq!(dates.to_string().parse::<Dates>().unwrap(), dates);
+        }
+        assert_eq!("1990".parse::<Dates>().unwrap().to_string(), "1990");
+        assert_eq!("1990 - 03/1992".parse::<Dates>().unwrap().to_string(), "1990 - 03/1992");
+        assert_eq!("1/1/1990 0:0".parse::<Dates>().unwrap().span().1, None);
+        assert!("13/1990".parse::<Dates>().is_err());
+        let mut inverted = "1995 - 03/1990".parse::<Dates>().unwrap();
+        assert!(inverted.is_inverted());
+        assert!(inverted.clone().checked().is_err());
+        inverted.fix_inverted();
+        assert_eq!(inverted.to_string(), "03/1990 - 1995");
+        assert!(inverted.checked().is_ok());
+        let ongoing = "1/1/2020 0:0 - ...".parse::<Dates>().unwrap();
+        assert!(ongoing.is_ongoing());
+        assert_eq!(ongoing.to_string(), "01/01/2020 00:00 - now");
+        assert_eq!(ongoing.to_string().parse::<Dates>().unwrap(), ongoing);
+        assert!(ongoing.span().1.unwrap() > *ongoing.start());
+        assert!("19x0".parse::<Dates>().is_err());
+    }
+
+    #[test]
+    fn test_loose_dates() {
+        // A Wednesday.
+        let today = NaiveDate::from_ymd_opt(2020, 5, 20);
+        let ok_tests = [
+            ("5/3/1990", "05/03/1990"),
+            ("1990", "1990"),
+            ("March 1990", "03/1990"),
+            ("sep 1990 - 1992", "09/1990 - 1992"),
+            ("2020-05", "05/2020"),
+            ("2020-05-17", "17/05/2020"),
+            ("2020-05-17T08:30", "17/05/2020 08:30"),
+            ("17 May 2020 - now", "17/05/2020 - now"),
+            ("May 17, 2020", "17/05/2020"),
+            ("1990-1995", "1990 - 1995"),
+            ("yesterday", "19/05/2020"),
+            ("last tuesday", "19/05/2020"),
+            ("Last Wednesday - today", "13/05/2020 - 20/05/2020"),
+            ("next monday", "25/05/2020"),
+        ];
+        for (query, expected) in ok_tests.iter() {
+            assert_eq!(Dates::parse_loose_at(query, today).unwrap().to_string(), *expected);
+        }
+        assert!(Dates::parse_loose_at("yesterday", None).is_err());
+        assert!(Dates::parse_loose_at("next fortnight", today).is_err());
+        assert!(Dates::parse_loose_at("Smarch 1990", today).is_err());
+    }
+
+    #[test]
+    fn test_duration() {
+        let tests = [
+            ("1990 - 1995", Some("6 years")),
+            ("03/1990 - 1992", Some("2 years 10 months")),
+            ("1/1/1990 0:0 - 19/1/1990 0:0", Some("18 days")),
+            ("1/1/1990 - 31/1/1990", Some("1 month")),
+            ("1/1/1990 8:00 - 1/1/1990 9:30", Some("1 hour 30 minutes")),
+            ("1/1/1990 0:0 - 1/1/1990 0:0", Some("0 minutes")),
+            ("1990", None),
+        ];
+        for (query, expected) in tests.iter() {
+            assert_eq!(query.parse::<Dates>().unwrap().duration().as_deref(), *expected);
+        }
+        let event = Event::new("Gulf War", "2/8/1990 - 28/2/1991".parse().unwrap());
+        let verbose = PrintOptions { verbose: true, ..PrintOptions::default() };
+        assert_eq!(event.print(0, &verbose), "<Event> Gulf War, [02/08/1990 - 28/02/1991, 6 months 27 days]");
+        assert!(!event.print(0, &PrintOptions::default()).contains("months"));
+    }
+
+    #[test]
+    fn test_status_parsing() {
+        let ok_tests = [
+            ("planned", Status::Planned),
+            ("In-Progress", Status::InProgress),
+            ("done", Status::Done),
+            ("cancelled", Status::Cancelled),
+        ];
+        for (query, status) in ok_tests.iter() {
+            assert_eq!(query.parse::<Status>().unwrap(), *status);
+            assert_eq!(status.to_string().parse::<Status>().unwrap(), *status);
+        }
+        assert!("finished".parse::<Status>().is_err());
+    }
+
+    #[test]
+    fn test_durations() {
+        let ok_tests = [
+            ("3d", Duration::days(3)),
+            ("+12h", Duration::hours(12)),
+            ("-2w", Duration::weeks(-2)),
+            ("90min", Duration::minutes(90)),
+        ];
+        for (query, duration) in ok_tests.iter() {
+            assert_eq!(parse_duration(query).unwrap(), *duration);
+        }
+        for query in ["", "d", "3", "3y", "+-3d"].iter() {
+            assert!(parse_duration(query).is_err());
+        }
+        let mut dates: Dates = "1/1/1990 0:0 - 3/1/1990 0:0".parse().unwrap();
+        dates.shift(Duration::days(-1));
+        assert_eq!(dates, "31/12/1989 0:0 - 2/1/1990 0:0".parse().unwrap());
+        let anchor = *"1/1/1990 0:0".parse::<Dates>().unwrap().start();
+        dates.scale(&anchor, 2.0);
+        assert_eq!(dates, "30/12/1989 0:0 - 3/1/1990 0:0".parse().unwrap());
+    }
+
+    #[test]
+    fn test_translations() {
+        let event: Event = serde_json::from_str(r#"{
+            "name": "Storming of the Bastille",
+            "descriptions": ["Start of the revolution."],
+            "datetime": "14/07/1789 0:0",
+            "translations": {
+                "fr": { "name": "Prise de la Bastille" },
+                "de": { "descriptions": ["Beginn der Revolution."] }
+            }
+        }"#).unwrap();
+        assert_eq!(event.name_in(None), "Storming of the Bastille");
+        assert_eq!(event.name_in(Some("fr")), "Prise de la Bastille");
+        assert_eq!(event.name_in(Some("de")), "Storming of the Bastille");
+        assert_eq!(event.name_in(Some("es")), "Storming of the Bastille");
+        assert_eq!(event.descriptions_in(Some("fr")), ["Start of the revolution."]);
+        assert_eq!(event.descriptions_in(Some("de")), ["Beginn der Revolution."]);
+    }
+
+    #[test]
+    fn test_iterators() {
+        let node = |name: &str, offset: f64, kids: Vec<Value>|{
+            let mut node = Node::new(Some(name.to_string()), kids);
+            node.set_offset(&offset);
+            node.into_value()
+        };
+        let event = |name: &str|Event::new(name, "1/1/1990".parse().unwrap()).into_value();
+        let root = Node::new(Some("root".to_string()), vec![
+            event("a"),
+            node("x", 1.0, vec![node("y", 2.0, vec![event("b")]), event("c")]),
+            node("z", 3.0, vec![]),
+            event("d"),
+        ]);
+        let names = root.iter_nodes().map(|n|n.name_in(None).unwrap()).collect::<Vec<&str>>();
+        assert_eq!(names, vec!["root", "x", "y", "z"]);
+        assert_eq!(root.depth().collect::<Vec<usize>>(), vec![0, 1, 2, 1]);
+        let offsets = root.transform_iter(0.0, 1.0).map(|(o, _)|o).collect::<Vec<f64>>();
+        assert_eq!(offsets, vec![0.0, 1.0, 3.0, 3.0]);
+        let events = root.iter_events().map(|e|e.name()).collect::<Vec<&str>>();
+        assert_eq!(events, vec!["a", "b", "c", "d"]);
+        let paths = root.iter_events_with_paths()
+            .map(|(path, e)|(path_string(&path), e.name()))
+            .collect::<Vec<(String, &str)>>();
+        let expected = [("1", "a"), ("2:1:1", "b"), ("2:2", "c"), ("4", "d")]
+            .map(|(path, name)|(path.to_string(), name));
+        assert_eq!(paths, expected);
+        struct Trace(Vec<String>);
+        impl<'a> Visitor<'a> for Trace {
+            fn enter(&mut self, node: &'a Node, at: &Position) {
+                self.0.push(format!("+{} {} {}", node.name_in(None).unwrap(), at.depth, at.offset));
+            }
+            fn leave(&mut self, node: &'a Node, _: &Position) {
+                self.0.push(format!("-{}", node.name_in(None).unwrap()));
+            }
+            fn event(&mut self, event: &'a Event, at: &Position) {
+                self.0.push(format!("{} {}", event.name(), path_string(&at.path)));
+            }
+        }
+        let mut trace = Trace(vec![]);
+        root.walk(&mut trace);
+        assert_eq!(trace.0, vec![
+            "+root 0 0", "a 1", "+x 1 1", "+y 2 3", "b 2:1:1", "-y", "c 2:2", "-x", "+z 1 3", "-z", "d 4", "-root",
+        ]);
+    }
+
+    #[test]
+    fn test_transforms() {
+        // x is moved down by 1 and halves the offsets within it, so y, moved
+        // down by 2 within x, ends up at 2. y's own scaling doesn't move it.
+        let mut y = Node::new(None, vec![]);
+        y.set_offset(&2.0);
+        y.set_scale(&3.0);
+        let mut x = Node::new(None, vec![y.into_value()]);
+        x.set_offset(&1.0);
+        x.set_scale(&0.5);
+        let root = Node::new(None, vec![x.into_value()]);
+        let pairs = root.transform_iter(0.0, 1.0).collect::<Vec<(f64, f64)>>();
+        assert_eq!(pairs, vec![(0.0, 1.0), (1.0, 0.5), (2.0, 1.5)]);
+        // Placing the root elsewhere moves and scales everything with it.
+        let moved = root.transform_iter(10.0, 2.0).collect::<Vec<(f64, f64)>>();
+        assert_eq!(moved, vec![(10.0, 2.0), (12.0, 1.0), (14.0, 3.0)]);
+    }
+
+    #[test]
+    fn test_line_location() {
+        let event = |dates: &str|Event::new("", dates.parse().unwrap()).into_value();
+        let node = Node::from_vec(vec![
+            event("01/01/2000 00:00"),
+            Node::from_vec(vec![
+                event("01/01/2000 01:00"),
+                event("01/01/2000 02:00"),
+            ]).with_line(None).into_value(),
+            Node::from_vec(vec![]).with_line(None).into_value(),
+            event("01/01/2000 04:00"),
+        ]);
+        let lines = node.lines(&node.range(), false);
+        // Nodes without events have nowhere to draw a line.
+        assert_eq!(lines.len(), 1);
+        assert_eq!((lines[0].start, lines[0].end), (0.25, 0.5));
+        let mut spanned = Node::from_vec(vec![]);
+        spanned.set_line_span(Some("1805 - 1807".parse().unwrap()));
+        let json = serde_json::to_string(&spanned).unwrap();
+        assert!(json.contains(r#""line_span":"1805 - 1807""#));
+        let read: Node = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.line_span(), spanned.line_span());
+    }
+
+    #[test]
+    fn test_collapsed() {
+        let event = |name: &str, dates: &str|Event::new(name, dates.parse().unwrap()).into_value();
+        let mut campaign = Node::new(Some("Campaign".to_string()), vec![
+            event("Ulm", "20/10/1805"),
+            Node::from_vec(vec![event("Austerlitz", "02/12/1805")]).with_line(None).into_value(),
+        ]);
+        campaign.set_collapsed(true);
+        let root = Node::from_vec(vec![
+            event("Trafalgar", "21/10/1805"),
+            campaign.into_value(),
+        ]);
+        let text = root.print(0, &PrintOptions::default());
+        assert_eq!(text.lines().collect::<Vec<&str>>(), vec![
+            "<Node> (No name)",
+            "  <Event> Trafalgar, [21/10/1805]",
+            "  <Node> Campaign [collapsed, 2 events, 20/10/1805 00:00 - 02/12/1805 23:59]",
+        ]);
+        // The line of the node inside is hidden along with it.
+        assert!(root.lines(&root.range(), false).is_empty());
+        assert_eq!(root.iter_shown(0.0, 1.0).count(), 2);
+        assert_eq!(root.iter_visits(0.0, 1.0).count(), 3);
+        let json = serde_json::to_string(&root).unwrap();
+        assert_eq!(json.matches(r#""collapsed":true"#).count(), 1);
+        assert!(!serde_json::to_string(&Node::from_vec(vec![])).unwrap().contains("collapsed"));
+    }
+
+    #[test]
+    fn test_path_fail() {
+        let mut root = Node::from_vec(vec![
+            Node::new(Some("Spain".to_string()), vec![
+                Event::new("Bailén", "1808".parse().unwrap()).into_value(),
+            ]).into_value(),
+        ]);
+        let fail = |root: &Node, path: &[usize]|root.query_ref(path).err().unwrap().to_string();
+        assert_eq!(fail(&root, &[1, 1, 2]), "path 1:1:2 not found: 1:1 is an event in node 1 (Spain), which can't hold 2");
+        assert_eq!(fail(&root, &[1, 0]), "path 1:0 not found: node 1 (Spain) holds 1 value(s), not 0");
+        assert_eq!(fail(&root, &[2]), "path 2 not found: the root holds 1 value(s), not 2");
+        assert_eq!(root.wrap(&[], None).unwrap_err().to_string(), "path / not found: the root isn't inside a node");
+        assert!(matches!(root.query_mut(&[1, 1, 1]).err().unwrap().kind, PathFailKind::IntoEvent));
+    }
+
+    #[test]
+    fn test_stamps() {
+        let dates = "01/01/1990 00:00 - 15/06/1990 12:30".parse::<Dates>().unwrap();
+        let (start, end) = dates.stamps();
+        assert_eq!(start, 631152000);
+        assert_eq!(Dates::from_stamps((start, end.unwrap())), Some(dates));
+        assert_eq!(from_stamp(to_stamp(&NaiveDate::from_ymd_opt(1805, 12, 2).unwrap().and_hms_opt(8, 0, 0).unwrap())).unwrap().to_string(), "1805-12-02 08:00:00");
+        assert_eq!(Dates::from_stamps((0, i64::MAX)), None);
+    }
+
+    #[test]
+    fn test_density() {
+        let event = |dates: &str|Event::new("", dates.parse().unwrap()).into_value();
+        let node = Node::from_vec(vec![
+            event("01/01/1800 00:00"),
+            event("01/01/1800 00:00 - 01/01/1840 00:00"),
+            event("01/01/1899 00:00"),
+            event("01/01/1900 00:00"),
+        ]);
+        let range = node.range();
+        assert_eq!(node.density(&range, 4), vec![2, 1, 0, 2]);
+        assert_eq!(node.density(&range, 0), Vec::<usize>::new());
+        assert_eq!(Node::from_vec(vec![]).density(&(0, 0), 3), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 5_000;
+        let mut node = Node::from_vec(vec![
+            Event::new("Bottom", "01/01/2000".parse().unwrap()).into_value(),
+        ]);
+        for _ in 1..depth {
+            node = Node::from_vec(vec![node.into_value()]);
+        }
+        assert_eq!(node.max_depth(), depth);
+        let mut path = vec![1; depth];
+        assert!(matches!(node.query_mut(&path), Ok(Query::Event(_))));
+        path.push(1);
+        assert!(node.query_mut(&path).is_err());
+        assert_eq!(node.print(0, &PrintOptions::default()).lines().count(), depth + 1);
+        let mut visited = vec![];
+        node.visit_events_mut(&mut |path, _|visited.push(path.len()));
+        assert_eq!(visited, vec![depth]);
+        node.map_text(&mut |text|text.to_uppercase());
+        assert_eq!(node.iter_events().next().unwrap().name(), "BOTTOM");
+    }
+
+    #[test]
+    fn test_grep_context() {
+        let event = |name: &str|Event::new(name, "1944".parse().unwrap()).into_value();
+        let node = Node::new(Some("World War II".to_string()), vec![
+            Node::new(Some("Pacific Theater".to_string()), vec![
+                event("Leyte"), event("Midway"), Node::from_vec(vec![]).into_value(), event("Iwo Jima"), event("Okinawa"), event("Coral Sea"),
+            ]).into_value(),
+            event("D-Day"),
+        ]);
+        assert_eq!(node.ancestor_names(&[1, 2], None), vec!["World War II", "Pacific Theater"]);
+        let found = node.find_all(|event|["Iwo Jima", "Okinawa", "D-Day"].contains(&event.name()));
+        assert_eq!(found, vec![vec![1, 4], vec![1, 5], vec![2]]);
+        assert_eq!(node.with_siblings(&found[..1], 0), vec![(vec![1], vec![(4, true)])]);
+        // Overlapping context is only shown once, and nodes are left out.
+        assert_eq!(node.with_siblings(&found, 1), vec![
+            (vec![1], vec![(4, true), (5, true), (6, false)]),
+            (vec![], vec![(2, true)]),
+        ]);
+    }
+
     #[test]
     fn test_node_querying() {
         let mut test_node = Node::from_vec(vec![
@@ -506,14 +2141,14 @@ mod tests {
                 Event::new("Fifth Event",  "03/12/1997 0:0 - 04/12/1997 0:0".parse().unwrap()).into_value(),
                 Event::new("Sixth Event", "04/12/1997 0:0 - 06/12/1997 0:0".parse().unwrap()).into_value(),
                 Event::new("Seventh Event",  "07/12/1997 0:0 - 09/12/1997 0:0".parse().unwrap()).into_value(),
-            ]).with_line(Some(5.0)).into_value(),
+            ]).with_line(Some(Interval::Auto)).into_value(),
         ]).with_line(None);
-        println!("{}\n", test_node.print(0, false));
+        println!("{}\n", test_node.print(0, &PrintOptions::default()));
         let range = test_node.range();
         let event_iter = test_node.iter_events().collect::<Vec<&Event>>();
         let node_iter  = test_node.iter_nodes().collect::<Vec<&Node>>();
         println!("---");
-        let lines  = test_node.lines(&range);
+        let lines  = test_node.lines(&range, true);
         println!("Checking iter...");
         assert_eq!(event_iter.len(), 7);
         println!("Checking iter_nodes...");
@@ -534,12 +2169,12 @@ mod tests {
         ];
         for (query, is_node) in ok_queries.iter() {
             println!("Testing Ok case: {:?}", query);
-            let query = test_node.query(&query[..]).unwrap();
-            let tag = match query {
+            let tag = match test_node.query_mut(&query[..]).unwrap() {
                 Query::Node(_) => true,
                 Query::Event(_) => false,
             };
             assert_eq!(*is_node, tag);
+            assert_eq!(*is_node, matches!(test_node.query_ref(&query[..]), Ok(QueryRef::Node(_))));
         }
         let err_queries: Vec<Vec<usize>> = vec![
             vec![4],            // Index out of bounds on root.
@@ -552,8 +2187,14 @@ mod tests {
         ];
         for query in err_queries.iter() {
             println!("Testing Err case: {:?}", query);
-            assert!(test_node.query(&query[..]).is_err());
+            assert!(test_node.query_mut(&query[..]).is_err());
+            assert!(test_node.query_ref(&query[..]).is_err());
         }
+        let starts_on = |day: &'static str|move |event: &Event|event.dates().start().format("%d").to_string() == day;
+        assert_eq!(test_node.find(starts_on("03")), Some(vec![3, 1, 1]));
+        assert_eq!(test_node.find_all(starts_on("04")), vec![vec![3, 1, 2], vec![3, 3]]);
+        assert_eq!(test_node.find(starts_on("05")), None);
+        assert_eq!(test_node.find_all(|event|event.name().ends_with("Event")).len(), 7);
     }
 }
 
@@ -0,0 +1,72 @@
+use std::{
+    io::Error as IoError,
+    path::Path,
+    process::{Command, Output},
+};
+
+use super::MainError;
+
+#[derive(Debug)]
+pub enum GitError {
+    /// git couldn't be run at all, most likely because it isn't installed.
+    Spawn(IoError),
+    /// git ran but failed, with what it wrote to stderr.
+    Failed(String),
+}
+
+/// Stages the file at `path` and commits it on its own with `message`, using
+/// the repository the file is in. Does nothing if the file is unchanged.
+pub fn commit(path: &str, message: &str) -> Result<(), GitError> {
+    let path = Path::new(path);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().map(Path::new).unwrap_or(path);
+    let git = |args: &[&str]|{
+        Command::new("git")
+            .arg("-C").arg(dir)
+            .args(args)
+            .arg("--").arg(name)
+            .output()
+            .map_err(GitError::Spawn)
+    };
+    checked(git(&["add"])?)?;
+    // Exits with 0 when nothing is staged for the file.
+    if git(&["diff", "--cached", "--quiet"])?.status.success() {
+        return Ok(());
+    }
+    checked(git(&["commit", "--quiet", "-m", message])?)?;
+    Ok(())
+}
+
+fn checked(output: Output) -> Result<Output, GitError> {
+    match output.status.success() {
+        true => Ok(output),
+        false => Err(GitError::Failed(String::from_utf8_lossy(&output.stderr).trim().to_string())),
+    }
+}
+
+impl std::fmt::Display for GitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GitError::Spawn(e) => write!(f, "couldn't run git: {}", e),
+            GitError::Failed(stderr) => write!(f, "git failed: {}", stderr),
+        }
+    }
+}
+
+impl std::error::Error for GitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GitError::Spawn(e) => Some(e),
+            GitError::Failed(_) => None,
+        }
+    }
+}
+
+impl From<GitError> for MainError {
+    fn from(err: GitError) -> Self {
+        MainError::Git(err)
+    }
+}
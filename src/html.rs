@@ -0,0 +1,122 @@
+use super::saga::{Entry, RenderOptions, SagaDoc};
+
+/// Most the timeline is magnified when zooming to a node.
+const MAX_ZOOM: f64 = 50.0;
+
+/// Room left either side of a node zoomed to, as a fraction of its length.
+const MARGIN: f64 = 0.05;
+
+const STYLE: &str = "\
+body { margin: 0; display: flex; height: 100vh; font-family: sans-serif; }
+#contents { flex: 0 0 16em; overflow-y: auto; padding: 1em; border-right: 1px solid #ccc; }
+#contents h1 { font-size: 1.2em; }
+#contents ul { list-style: none; padding: 0; margin: 0; }
+#contents a { display: block; padding: 0.2em 0; color: inherit; text-decoration: none; }
+#contents a:hover, #contents a.current { text-decoration: underline; }
+#timeline { flex: 1; overflow: auto; }
+#timeline svg { display: block; width: 100%; height: auto; }";
+
+/// Scales the SVG up so the clicked node fills the view, then scrolls to it.
+const SCRIPT: &str = "\
+const timeline = document.getElementById('timeline');
+const svg = timeline.querySelector('svg');
+const links = document.querySelectorAll('#contents a');
+function zoom(link) {
+  const x = +link.dataset.x, end = +link.dataset.end, y = +link.dataset.y;
+  const margin = (end - x) * MARGIN;
+  const scale = Math.min(Math.max(WIDTH / (end - x + 2 * margin), 1), MAX_ZOOM);
+  svg.style.width = (scale * 100) + '%';
+  const px = timeline.clientWidth / WIDTH * scale;
+  timeline.scrollTo({ left: (x - margin) * px, top: y * px, behavior: 'smooth' });
+  links.forEach(other => other.classList.toggle('current', other === link));
+}
+links.forEach(link => link.addEventListener('click', event => {
+  event.preventDefault();
+  zoom(link);
+}));";
+
+/// Writes `doc` as a standalone HTML page holding its SVG, beside a table of
+/// contents of its nodes that zooms the SVG to each when clicked.
+pub fn export(doc: &SagaDoc, options: &RenderOptions, title: &str) -> String {
+    let svg = doc.draw(options).to_string();
+    let (width, _) = doc.size();
+    let all = Entry { name: "All".to_string(), depth: 0, x: 0.0, end: width, y: 0.0 };
+    let items: Vec<String> = std::iter::once(&all)
+        .chain(doc.contents(options).iter())
+        .map(item)
+        .collect();
+    let script = SCRIPT
+        .replace("WIDTH", &width.to_string())
+        .replace("MAX_ZOOM", &MAX_ZOOM.to_string())
+        .replace("MARGIN", &MARGIN.to_string());
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n\
+         <nav id=\"contents\">\n<h1>{title}</h1>\n<ul>\n{}\n</ul>\n</nav>\n\
+         <main id=\"timeline\">\n{}\n</main>\n<script>\n{}\n</script>\n</body>\n</html>\n",
+        items.join("\n"),
+        svg,
+        script,
+        title = escape(title),
+    )
+}
+
+/// One link of the table of contents, indented by depth.
+fn item(entry: &Entry) -> String {
+    format!(
+        "<li style=\"padding-left: {}em\"><a href=\"#\" data-x=\"{:.1}\" data-end=\"{:.1}\" data-y=\"{:.1}\">{}</a></li>",
+        entry.depth,
+        entry.x,
+        entry.end,
+        entry.y,
+        escape(&entry.name),
+    )
+}
+
+/// Escapes `text` for use in HTML, attributes included.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, export};
+    use super::super::events::{Event, Node};
+    use super::super::saga::{RenderOptions, SagaDoc};
+
+    #[test]
+    fn test_html() {
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::new(Some("Wars".to_string()), vec![
+            Node::new(Some("Third Coalition".to_string()), vec![
+                Event::new("Ulm", "20/10/1805".parse().unwrap()).into_value(),
+                Event::new("Austerlitz", "02/12/1805".parse().unwrap()).into_value(),
+            ]).into_value(),
+            Node::new(Some("Fourth <Coalition>".to_string()), vec![
+                Event::new("Jena", "14/10/1806".parse().unwrap()).into_value(),
+            ]).into_value(),
+            // Nothing to zoom to.
+            Node::new(Some("Empty".to_string()), vec![]).into_value(),
+        ]);
+        let options = RenderOptions::default();
+        let contents = doc.contents(&options);
+        let names: Vec<&str> = contents.iter().map(|entry|entry.name.as_str()).collect();
+        assert_eq!(names, vec!["Wars", "Third Coalition", "Fourth <Coalition>"]);
+        assert_eq!(contents.iter().map(|entry|entry.depth).collect::<Vec<usize>>(), vec![0, 1, 1]);
+        let (width, _) = doc.size();
+        assert_eq!((contents[0].x, contents[0].end), (0.0, width));
+        assert_eq!(contents[1].x, 0.0);
+        assert!(contents[1].end < contents[2].x);
+        // Below the top margin of the default spacing.
+        assert!((contents[0].y - 108.0).abs() < 1e-9);
+        let html = export(&doc, &options, "Wars & Peace");
+        assert!(html.contains("<title>Wars &amp; Peace</title>"));
+        assert!(html.contains("<svg"));
+        assert!(html.contains(">All</a>"));
+        assert!(html.contains(">Fourth &lt;Coalition&gt;</a>"));
+        assert_eq!(html.matches("<li ").count(), 4);
+        assert_eq!(escape("\"a\" < b"), "&quot;a&quot; &lt; b");
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use super::events::{Node, QueryRef, Value};
+
+/// Lookup table from the paths of a document to its values, built in one
+/// walk so that repeated lookups don't have to follow the tree each time.
+/// Paths use the same 1-based form as `Node::query_ref`.
+pub struct PathIndex<'a> {
+    root: &'a Node,
+    values: HashMap<Vec<usize>, &'a Value>,
+}
+
+impl<'a> PathIndex<'a> {
+    pub fn build(root: &'a Node) -> PathIndex<'a> {
+        let mut index = PathIndex { root, values: HashMap::new() };
+        // Nodes still to be indexed, with their paths.
+        let mut stack: Vec<(Vec<usize>, &'a Node)> = vec![(vec![], root)];
+        while let Some((path, node)) = stack.pop() {
+            for (i, value) in node.children().iter().enumerate() {
+                let path = [&path[..], &[i + 1]].concat();
+                if let Value::Node(child) = value {
+                    stack.push((path.clone(), child));
+                }
+                index.values.insert(path, value);
+            }
+        }
+        index
+    }
+
+    /// Finds the value at `path`, with the empty path standing for the root.
+    pub fn get(&self, path: &[usize]) -> Option<QueryRef<'a>> {
+        if path.is_empty() {
+            return Some(QueryRef::Node(self.root));
+        }
+        self.values.get(path).map(|value|match value {
+            Value::Node(node) => QueryRef::Node(node),
+            Value::Event(event) => QueryRef::Event(event),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PathIndex;
+    use super::super::events::{Event, Node, QueryRef};
+
+    #[test]
+    fn test_path_index() {
+        let root = Node::from_vec(vec![
+            Event::new("Austerlitz", "02/12/1805".parse().unwrap()).into_value(),
+            Node::new(Some("Prussia".to_string()), vec![
+                Event::new("Jena", "14/10/1806".parse().unwrap()).into_value(),
+                Event::new("Austerlitz", "02/12/1805".parse().unwrap()).into_value(),
+            ]).into_value(),
+        ]);
+        let index = PathIndex::build(&root);
+        assert!(matches!(index.get(&[]), Some(QueryRef::Node(_))));
+        assert!(matches!(index.get(&[2]), Some(QueryRef::Node(node)) if node.name_in(None) == Some("Prussia")));
+        assert!(matches!(index.get(&[2, 1]), Some(QueryRef::Event(event)) if event.name() == "Jena"));
+        assert!(index.get(&[2, 3]).is_none());
+        assert!(index.get(&[1, 1]).is_none());
+        assert!(matches!(index.get(&[2, 2]), Some(QueryRef::Event(event)) if event.name() == "Austerlitz"));
+    }
+}
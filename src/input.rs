@@ -0,0 +1,132 @@
+use std::io::{BufRead, ErrorKind, IsTerminal, Read, Write};
+
+use log::debug;
+use rustyline::{DefaultEditor, error::ReadlineError};
+
+use super::calendar::Calendar;
+use super::events::{Dates, Event};
+use super::saga::SagaDocError;
+
+/// Longest line read, in bytes. Longer ones are refused, and when piped in
+/// aren't read any further, so they can't fill up memory.
+pub const MAX_LINE: usize = 64 * 1024;
+
+/// Reads a single line with line editing. Ctrl+C and Ctrl+D abort the prompt.
+/// Input piped in is read as is, ending with `SagaDocError::EndOfInput`.
+pub fn read_line(prompt: &str) -> Result<String, SagaDocError> {
+    let prompt = format!("{} > ", prompt);
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        print!("{}", prompt);
+        std::io::stdout().flush().map_err(SagaDocError::IoError)?;
+        return read_piped(&mut stdin.lock());
+    }
+    let mut editor = DefaultEditor::new()?;
+    let input = editor.readline(&prompt)?;
+    checked_length(input.trim_end())
+}
+
+/// Reads a line from `reader`, reading no more than `MAX_LINE` bytes of it
+/// and its line ending.
+fn read_piped(reader: &mut impl BufRead) -> Result<String, SagaDocError> {
+    let mut bytes = vec![];
+    reader.take(MAX_LINE as u64 + 2).read_until(b'\n', &mut bytes)
+        .map_err(SagaDocError::IoError)?;
+    if bytes.is_empty() {
+        return Err(SagaDocError::EndOfInput);
+    }
+    while let Some(b'\n' | b'\r') = bytes.last() {
+        bytes.pop();
+    }
+    // Before decoding, which a character cut in two at the limit would fail.
+    if bytes.len() > MAX_LINE {
+        return Err(SagaDocError::LineTooLong(MAX_LINE));
+    }
+    let line = String::from_utf8(bytes)
+        .map_err(|e|SagaDocError::NotUtf8(Some(e.utf8_error().valid_up_to())))?;
+    checked_length(line.trim_end())
+}
+
+fn checked_length(line: &str) -> Result<String, SagaDocError> {
+    match line.len() > MAX_LINE {
+        true => Err(SagaDocError::LineTooLong(MAX_LINE)),
+        false => Ok(line.to_string()),
+    }
+}
+
+/// Takes user's input after printing a prompt.
+pub fn get_user(prompt: &str) -> Result<String, SagaDocError> {
+    read_line(prompt)
+}
+
+/// Asks yes/no question to user:
+///   - Answers beginning with 'y' or 'Y' allows the user
+///     to give a follow up answer, returning Ok(Some(_)).
+///   - Answers beginning with anything else returns Ok(None).
+///   - Returns Err(_) if an Io error occured.
+pub fn ask_user(prompt: &str) -> Result<Option<String>, SagaDocError> {
+    match ask_bool(prompt)? {
+        true => get_user("").map(Some),
+        false => Ok(None),
+    }
+}
+
+/// Ask the user a for a yes/no, intrepting it as a `bool`.
+pub fn ask_bool(prompt: &str) -> Result<bool, SagaDocError> {
+    let input = read_line(prompt)?;
+    Ok(input.starts_with(['y', 'Y']))
+}
+
+/// Internal function called by `SagaDoc::add_*` to make event instances.
+pub fn make_event(calendar: Option<&Calendar>) -> Result<Event, SagaDocError> {
+    let name = get_user("Name")?;
+    let date_input: String = get_user("Date")?;
+    debug!("Reading dates from [{}]", date_input);
+    let date = match calendar {
+        Some(calendar) => calendar.parse_dates(&date_input)?,
+        None => Dates::parse_loose(&date_input)?,
+    }.checked()?;
+    let mut event = Event::new(&name, date);
+    // Get desc's for as long as the user is willing to give them.
+    while let Ok(Some(input)) = ask_user("Description [Y/n]") {
+        event.with_desc(&input);
+    }
+    Ok(event)
+}
+
+impl From<ReadlineError> for SagaDocError {
+    fn from(err: ReadlineError) -> Self {
+        match err {
+            ReadlineError::Interrupted | ReadlineError::Eof => SagaDocError::Interrupted,
+            // Terminals hand over bytes that aren't UTF-8 as such.
+            ReadlineError::Io(e) if e.kind() == ErrorKind::InvalidData => SagaDocError::NotUtf8(None),
+            ReadlineError::Io(e) => SagaDocError::IoError(e),
+            e => SagaDocError::Readline(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{read_piped, MAX_LINE};
+    use super::super::saga::SagaDocError;
+
+    #[test]
+    fn test_read_piped() {
+        let mut input = Cursor::new(b"Jena\r\n14/10/1806\nlast".to_vec());
+        assert_eq!(read_piped(&mut input).unwrap(), "Jena");
+        assert_eq!(read_piped(&mut input).unwrap(), "14/10/1806");
+        assert_eq!(read_piped(&mut input).unwrap(), "last");
+        assert!(matches!(read_piped(&mut input), Err(SagaDocError::EndOfInput)));
+        let mut bad = Cursor::new(b"Jen\xe0\n".to_vec());
+        assert!(matches!(read_piped(&mut bad), Err(SagaDocError::NotUtf8(Some(3)))));
+        let mut long = Cursor::new(vec![b'a'; MAX_LINE * 2]);
+        assert!(matches!(read_piped(&mut long), Err(SagaDocError::LineTooLong(MAX_LINE))));
+        // Only as much as the limit was read.
+        assert_eq!(long.position(), MAX_LINE as u64 + 2);
+        let mut exact = Cursor::new([vec![b'a'; MAX_LINE], b"\n".to_vec()].concat());
+        assert_eq!(read_piped(&mut exact).unwrap().len(), MAX_LINE);
+    }
+}
@@ -0,0 +1,94 @@
+use chrono::{Datelike, Timelike};
+
+use super::events::{Dates, Dt, Event, Node, Query, Value};
+use super::locale::Locale;
+
+/// Adds an entry of `text` at `now` under the node at `journal`, a `/`
+/// separated list of node names starting at `root`, then under its year and
+/// month, making any of those nodes missing. The first line of `text` names
+/// the entry, the rest becomes its description. Returns the path of the entry.
+pub fn log(root: &mut Node, journal: &str, text: &str, now: Dt, locale: &Locale) -> Vec<usize> {
+    let names = journal.split('/')
+        .map(|name|name.trim().to_string())
+        .filter(|name|!name.is_empty())
+        .chain([now.year().to_string(), locale.month_name(now.month()).to_string()]);
+    let mut path = vec![];
+    for name in names {
+        let node = node_at(root, &path);
+        let found = node.children().iter().position(|value|match value {
+            Value::Node(kid) => kid.name_in(None) == Some(name.as_str()),
+            Value::Event(_) => false,
+        });
+        path.push(match found {
+            Some(index) => index + 1,
+            None => {
+                node.push(Node::new(Some(name), vec![]).into_value());
+                node.children().len()
+            },
+        });
+    }
+    let node = node_at(root, &path);
+    node.push(entry(text, now).into_value());
+    path.push(node.children().len());
+    path
+}
+
+/// The event logging `text` at `now`, to the minute.
+fn entry(text: &str, now: Dt) -> Event {
+    let now = now.with_second(0).and_then(|dt|dt.with_nanosecond(0)).unwrap_or(now);
+    let text = text.trim();
+    let (name, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let mut event = Event::new(name.trim(), Dates::from_dts(now, None));
+    if !rest.trim().is_empty() {
+        event.add_description(rest.trim());
+    }
+    event
+}
+
+fn node_at<'a>(root: &'a mut Node, path: &[usize]) -> &'a mut Node {
+    match root.query_mut(path) {
+        Ok(Query::Node(node)) => node,
+        _ => unreachable!("Only nodes are stepped into."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::log;
+    use super::super::events::{Event, Node, QueryRef};
+    use super::super::locale::Locale;
+
+    #[test]
+    fn test_journal() {
+        let mut root = Node::from_vec(vec![
+            Event::new("Founded", "1999".parse().unwrap()).into_value(),
+        ]);
+        let at = |text: &str|chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap();
+        let english = Locale::fallback();
+        let first = log(&mut root, "Lab/Journal", "Calibrated the scope", at("2026-10-16 09:30:12"), english);
+        assert_eq!(first, vec![2, 1, 1, 1, 1]);
+        // Same month, so into the same nodes.
+        let second = log(&mut root, "Lab/Journal", "Found a comet\nNear Vega.", at("2026-10-17 23:05:00"), english);
+        assert_eq!(second, vec![2, 1, 1, 1, 2]);
+        let third = log(&mut root, "Lab/Journal", "New year", at("2027-01-01 00:00:00"), english);
+        assert_eq!(third, vec![2, 1, 2, 1, 1]);
+        let french = log(&mut root, "Lab/Journal", "Pluie", at("2027-01-02 08:00:00"), Locale::find("fr").unwrap());
+        assert_eq!(french, vec![2, 1, 2, 2, 1]);
+        match root.query_ref(&second) {
+            Ok(QueryRef::Event(event)) => {
+                assert_eq!(event.name(), "Found a comet");
+                assert_eq!(event.descriptions_in(None), ["Near Vega.".to_string()]);
+                assert_eq!(event.dates().to_string(), "17/10/2026 23:05");
+            },
+            _ => panic!("Logged an event."),
+        }
+        match root.query_ref(&[2, 1, 2, 2]) {
+            Ok(QueryRef::Node(node)) => assert_eq!(node.name_in(None), Some("janvier")),
+            _ => panic!("Made a month node."),
+        }
+        match root.query_ref(&[2, 1, 1, 1]) {
+            Ok(QueryRef::Node(node)) => assert_eq!((node.name_in(None), node.children().len()), (Some("October"), 2)),
+            _ => panic!("Made a month node."),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use chrono::{Datelike, Timelike};
+
+use super::events::{Dates, Dt, Precision};
+
+/// Describes how dates are presented to readers. This only affects
+/// rendering; documents are always stored using `events::FORMAT`.
+#[derive(Debug, PartialEq)]
+pub struct Locale {
+    pub code: &'static str,
+    months: [&'static str; 12],
+    /// Layout of a date, where `{d}`, `{m}` and `{y}` are replaced by the
+    /// day, month name and year respectively.
+    pattern: &'static str,
+}
+
+pub const LOCALES: &[Locale] = &[
+    Locale {
+        code: "en",
+        months: ["January", "February", "March", "April", "May", "June", "July",
+                 "August", "September", "October", "November", "December"],
+        pattern: "{m} {d}, {y}",
+    },
+    Locale {
+        code: "en-gb",
+        months: ["January", "February", "March", "April", "May", "June", "July",
+                 "August", "September", "October", "November", "December"],
+        pattern: "{d} {m} {y}",
+    },
+    Locale {
+        code: "fr",
+        months: ["janvier", "février", "mars", "avril", "mai", "juin", "juillet",
+                 "août", "septembre", "octobre", "novembre", "décembre"],
+        pattern: "{d} {m} {y}",
+    },
+    Locale {
+        code: "de",
+        months: ["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli",
+                 "August", "September", "Oktober", "November", "Dezember"],
+        pattern: "{d}. {m} {y}",
+    },
+    Locale {
+        code: "es",
+        months: ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio",
+                 "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+        pattern: "{d} de {m} de {y}",
+    },
+];
+
+impl Locale {
+    /// Looks up a locale by its code, ignoring case.
+    pub fn find(code: &str) -> Option<&'static Locale> {
+        LOCALES.iter().find(|locale|locale.code.eq_ignore_ascii_case(code))
+    }
+
+    /// Used when neither the document nor the user picked a locale.
+    pub fn fallback() -> &'static Locale {
+        &LOCALES[0]
+    }
+
+    /// Returns the codes of every supported locale.
+    pub fn codes() -> Vec<&'static str> {
+        LOCALES.iter().map(|locale|locale.code).collect()
+    }
+
+    /// Name of the given month, where January is 1.
+    pub fn month_name(&self, month: u32) -> &'static str {
+        self.months[(month as usize - 1) % 12]
+    }
+}
+
+/// Dates laid out by a pattern of the reader's choosing, like "{b} {y}" for
+/// "Jan 1990" or "Q{q} {y}" for "Q3 2021", naming months as `locale` does.
+/// Only changes how dates are presented, like `Locale`.
+pub struct DatePattern<'a> {
+    /// Where `{d}` and `{dd}` are replaced by the day, `{m}` and `{b}` by the
+    /// month's name and its first three letters, `{mm}` by its number, `{q}`
+    /// by the quarter, `{y}` by the year, and `{H}` and `{M}` by the hour
+    /// and minute.
+    pub pattern: &'a str,
+    pub locale: &'static Locale,
+}
+
+/// Anything that can present dates to readers.
+pub trait DateFormat {
+    /// Formats a single date.
+    fn format_date(&self, dt: &Dt) -> String;
+
+    /// Formats a single date, leaving out units finer than `precision`.
+    fn format_date_at(&self, dt: &Dt, _precision: Precision) -> String {
+        self.format_date(dt)
+    }
+
+    /// Formats either a point in time, or a timespan.
+    fn format_dates(&self, dates: &Dates) -> String {
+        let start = self.format_date_at(dates.start(), dates.start_precision());
+        if dates.is_ongoing() {
+            return format!("{} – …", start);
+        }
+        match dates.end() {
+            Some(end) => format!("{} – {}", start, self.format_date_at(end, dates.end_precision())),
+            None => start,
+        }
+    }
+}
+
+/// Lets borrowed formats stand in wherever an owned one is boxed.
+impl<T: DateFormat + ?Sized> DateFormat for &T {
+    fn format_date(&self, dt: &Dt) -> String {
+        (**self).format_date(dt)
+    }
+
+    fn format_date_at(&self, dt: &Dt, precision: Precision) -> String {
+        (**self).format_date_at(dt, precision)
+    }
+}
+
+impl DateFormat for Locale {
+    /// Only shows the time if it isn't midnight.
+    fn format_date(&self, dt: &Dt) -> String {
+        self.format_date_at(dt, Precision::Minute)
+    }
+
+    fn format_date_at(&self, dt: &Dt, precision: Precision) -> String {
+        let year = dt.year().to_string();
+        let month = self.month_name(dt.month());
+        let date = match precision {
+            Precision::Year  => return year,
+            Precision::Month => return format!("{} {}", month, year),
+            _ => self.pattern
+                .replace("{d}", &dt.day().to_string())
+                .replace("{m}", month)
+                .replace("{y}", &year),
+        };
+        match (precision, dt.hour(), dt.minute()) {
+            (Precision::Day, _, _) | (_, 0, 0) => date,
+            (_, h, m) => format!("{} {:02}:{:02}", date, h, m),
+        }
+    }
+}
+
+impl DateFormat for DatePattern<'_> {
+    /// Shows the units the pattern asks for, however precise the date.
+    fn format_date(&self, dt: &Dt) -> String {
+        let month = self.locale.month_name(dt.month());
+        self.pattern
+            .replace("{dd}", &format!("{:02}", dt.day()))
+            .replace("{d}", &dt.day().to_string())
+            .replace("{mm}", &format!("{:02}", dt.month()))
+            .replace("{m}", month)
+            .replace("{b}", &month.chars().take(3).collect::<String>())
+            .replace("{q}", &((dt.month() - 1) / 3 + 1).to_string())
+            .replace("{y}", &dt.year().to_string())
+            .replace("{H}", &format!("{:02}", dt.hour()))
+            .replace("{M}", &format!("{:02}", dt.minute()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DateFormat, DatePattern, Locale};
+    use super::super::events::Dates;
+
+    #[test]
+    fn test_format_dates() {
+        let dates: Dates = "14/07/1789 0:0 - 18/06/1815 12:30".parse().unwrap();
+        let ok_cases = [
+            ("en",    "July 14, 1789 – June 18, 1815 12:30"),
+            ("EN-GB", "14 July 1789 – 18 June 1815 12:30"),
+            ("fr",    "14 juillet 1789 – 18 juin 1815 12:30"),
+            ("de",    "14. Juli 1789 – 18. Juni 1815 12:30"),
+            ("es",    "14 de julio de 1789 – 18 de junio de 1815 12:30"),
+        ];
+        for (code, expected) in ok_cases.iter() {
+            let locale = Locale::find(code).unwrap();
+            assert_eq!(locale.format_dates(&dates), *expected);
+        }
+        assert!(Locale::find("xx").is_none());
+        let imprecise: Dates = "1789 - 06/1815".parse().unwrap();
+        assert_eq!(Locale::find("fr").unwrap().format_dates(&imprecise), "1789 – juin 1815");
+    }
+
+    #[test]
+    fn test_patterns() {
+        let dates: Dates = "01/01/1990 - 14/07/2021 09:05".parse().unwrap();
+        let ok_cases = [
+            ("{b} {y}",                 "en", "Jan 1990 – Jul 2021"),
+            ("Q{q} {y}",                "en", "Q1 1990 – Q3 2021"),
+            ("{y}-{mm}-{dd} {H}:{M}",   "en", "1990-01-01 00:00 – 2021-07-14 09:05"),
+            ("{d} {m}",                 "fr", "1 janvier – 14 juillet"),
+            ("{b}. {y}",                "de", "Jan. 1990 – Jul. 2021"),
+        ];
+        for &(pattern, code, expected) in ok_cases.iter() {
+            let locale = Locale::find(code).unwrap();
+            assert_eq!(DatePattern { pattern, locale }.format_dates(&dates), expected);
+        }
+    }
+}
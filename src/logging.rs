@@ -0,0 +1,70 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Writes log records to stderr, keeping stdout for what was asked for.
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) { return; }
+        match record.level() {
+            Level::Error => eprintln!("error: {}", record.args()),
+            Level::Warn  => eprintln!("warning: {}", record.args()),
+            _            => eprintln!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// How much to log: only errors if `quiet`, progress and timings as well as
+/// the usual notes if `verbose`.
+pub fn level(quiet: bool, verbose: bool) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (_, true) => LevelFilter::Debug,
+        _ => LevelFilter::Info,
+    }
+}
+
+/// Sends the `log` macros to stderr, at `level()`.
+pub fn init(quiet: bool, verbose: bool) {
+    // Only fails if a logger is set already, which is then kept.
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level(quiet, verbose));
+}
+
+/// Writes `n` with its thousands separated, like "1,204".
+pub fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use log::LevelFilter;
+    use super::{level, thousands};
+
+    #[test]
+    fn test_logging() {
+        assert_eq!(level(false, false), LevelFilter::Info);
+        assert_eq!(level(false, true), LevelFilter::Debug);
+        assert_eq!(level(true, true), LevelFilter::Error);
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1204), "1,204");
+        assert_eq!(thousands(1234567), "1,234,567");
+    }
+}
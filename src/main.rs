@@ -1,12 +1,8 @@
 
-#![feature(iterator_try_collect)]
-
 /*
  * Project TODO's
- *   - Impl Error for my error types, or use the thiserror crate.
  *   - Refactor open_saga_docs to return an iterator of Results, thus allowing the program to
- *     continue if a file wasn't found. Also probably ditching nightly feature
- *     `iterator_try_collect`.
+ *     continue if a file wasn't found.
  *   - arg_node & arg_add are medium sized functions differing in only 1 line of code. Refactor
  *     them (possibly into higher order functions).
  *   - Add --verbose (-v) flag to print subcommand.
@@ -33,60 +29,418 @@
  *   - Add text drawing functionality.
  */
 
-use std::{num::ParseIntError, path::PathBuf};
+use std::{collections::BTreeSet, io::{IsTerminal, Read}, num::ParseIntError, path::{Path, PathBuf}, process::{Command, ExitCode}};
 
 use clap::{arg, command, ArgMatches, Command as ClapCommand};
+use log::{info, warn};
 use serde_json::Error as JsonError;
 
+mod axis;
+mod builder;
+mod calendar;
+mod config;
+mod count;
+use axis::Interval;
+use count::Grouping;
+use config::{Config, ConfigError, Opener};
+use calendar::CalendarError;
+mod crypt;
+mod doctor;
+mod drift;
+mod dupes;
+mod git;
+mod html;
+use git::GitError;
+use crypt::CryptError;
 mod events;
-use events::{DtParseError, PathFail};
+mod index;
+use index::PathIndex;
+mod journal;
+mod locale;
+mod logging;
+mod manifest;
+use manifest::ManifestError;
+mod merge;
+mod project;
+use project::{in_dir, Project, ProjectError};
+use locale::Locale;
+use events::{DtParseError, Event, InvertedDates, Node, PathFail, PrintOptions, QueryRef, Status};
+mod remind;
+mod render;
+use render::{Renderer, Svg, Terminal};
 mod saga;
-use saga::SagaDoc;
+mod selftest;
+mod snapshot;
+mod stats;
+mod sync;
+use sync::SyncError;
+use saga::{Color, ColorParseError, Colors, Outside, Pages, RenderOptions, SagaDoc, SchemeError, MAX_PAGES};
+mod validate;
+use validate::path_string;
+mod view;
+mod editor;
+mod input;
 mod edit;
-use edit::{Command as EvalCommand, EvalError, ParseError};
-// mod app;
-// use app::App;
+use edit::{Command as EvalCommand, EvalError, ParseError, ParseErrorKind, Pattern, Program};
+mod app;
 
 pub type MainResult = Result<(), MainError>;
 
+/// Exit code of failures not told apart by the codes below. Clap exits with
+/// 2 on bad arguments, so that's left out.
+const EXIT_FAILURE: u8 = 1;
+/// Exit code of `--check` finding files that would change, and of `validate`
+/// leaving problems unfixed.
+const EXIT_CHECK: u8 = 3;
+/// Exit code of documents, dates, paths or commands that couldn't be parsed.
+const EXIT_PARSE: u8 = 4;
+/// Exit code of paths, events and snapshots that don't exist.
+const EXIT_NOT_FOUND: u8 = 5;
+/// Exit code of files and input that couldn't be read or written.
+const EXIT_IO: u8 = 6;
+
 #[derive(Debug)]
 pub enum MainError {
-    // TODO: Add file path to this.
     NotASagaDoc(serde_json::Error),
     SerializeFail(JsonError),
     FileIO(std::io::Error),
     IntoOSString(std::ffi::OsString),
     BadPathParse(ParseIntError),
     BadDateTimeParse(DtParseError),
+    BadCalendarParse(CalendarError),
+    InvertedDates(InvertedDates),
+    /// Number of problems `validate` found and didn't fix.
+    Invalid(usize),
+    /// Number of checks `selftest` failed.
+    SelfTest(usize),
+    Interrupted,
+    Readline(rustyline::error::ReadlineError),
     NodeNotFound(PathFail),
-    CommandParse(ParseError),
+    /// The command, and why it couldn't be parsed.
+    CommandParse(String, ParseError),
     Eval(EvalError),
+    Crypt(CryptError),
+    Config(ConfigError),
+    Project(ProjectError),
+    Git(GitError),
+    Sync(SyncError),
+    BadColor(ColorParseError),
+    Scheme(SchemeError),
+    /// The platform's default viewer couldn't be launched.
+    Viewer(std::io::Error),
+    /// The pager, named first, couldn't be run.
+    Pager(String, std::io::Error),
+    /// The GUI's window couldn't be opened.
+    Gui(iced::Error),
     AddToEvent,
+    /// `log` was given no text.
+    NothingToLog,
+    /// No event matches the query.
+    NoMatch(String),
+    /// Events to add weren't a JSON array of events.
+    BadEvents(JsonError),
+    /// Input piped in ran out before a line was given.
+    EndOfInput,
+    /// A line was longer than the most that's read, given in bytes.
+    LineTooLong(usize),
+    /// Input or a file wasn't valid UTF-8, from the byte given if known.
+    NotUtf8(Option<usize>),
+    /// How deeply a document's nodes are nested, and the limit that was exceeded.
+    TooDeep(usize, usize),
+    /// The file was written to by something else after it was loaded.
+    Changed,
+    /// Number of conflicts `merge` settled in favour of our side.
+    Conflicts(usize),
+    /// Number of files `--check` found would be changed.
+    WouldChange(usize),
+    /// No snapshot has this number or label.
+    NoSnapshot(String),
+    Manifest(ManifestError),
+    /// Another error, caused by the named file.
+    InFile(String, Box<MainError>),
+    /// Another error, with advice on fixing it to print below it.
+    Hinted(Box<MainError>, String),
 }
 
-fn main() -> MainResult {
+fn main() -> ExitCode {
     let arg_parser = build_arg_parser();
     let matches = arg_parser.get_matches();
+    logging::init(matches.get_flag("quiet"), matches.get_flag("verbose"));
+    // Read once here, rather than each time a document is read or written.
+    match Config::load() {
+        Ok(config) => saga::set_max_depth(config.max_depth()),
+        Err(e) => warn!("{}, nesting is limited to the default", e),
+    }
+    match run(&matches) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}: {}", matches.subcommand_name().unwrap_or("saga"), e);
+            if let Some(hint) = e.hint() {
+                eprintln!("{}", hint);
+            }
+            ExitCode::from(e.exit_code())
+        },
+    }
+}
+
+fn run(matches: &ArgMatches) -> MainResult {
     match matches.subcommand() {
+        Some(("init",    sub_matches)) => arg_init(sub_matches),
         Some(("new",     sub_matches)) => arg_new(sub_matches),
         Some(("add",     sub_matches)) => arg_add(sub_matches),
         Some(("node",    sub_matches)) => arg_node(sub_matches),
         Some(("edit",    sub_matches)) => arg_edit(sub_matches),
-        Some(("grep",    _          )) => todo!("Feature Coming Soon!"),
+        Some(("log",     sub_matches)) => arg_log(sub_matches),
+        Some(("grep",    sub_matches)) => arg_grep(sub_matches),
         Some(("print",   sub_matches)) => arg_print(sub_matches),
+        Some(("view",    sub_matches)) => arg_view(sub_matches),
+        Some(("dupes",   sub_matches)) => arg_dupes(sub_matches),
+        Some(("count",   sub_matches)) => arg_count(sub_matches),
+        Some(("stats",   sub_matches)) => arg_stats(sub_matches),
+        Some(("gaps",    sub_matches)) => arg_gaps(sub_matches),
+        Some(("drift",   sub_matches)) => arg_drift(sub_matches),
+        Some(("when",    sub_matches)) => arg_when(sub_matches),
+        Some(("remind",  sub_matches)) => arg_remind(sub_matches),
         Some(("cat",     sub_matches)) => arg_catenate(sub_matches),
+        Some(("intersect", sub_matches)) => arg_compare(sub_matches, SagaDoc::intersect, "intersect"),
+        Some(("subtract",  sub_matches)) => arg_compare(sub_matches, SagaDoc::subtract, "subtract"),
         Some(("render",  sub_matches)) => arg_render(sub_matches),
-        Some(("editor",  _          )) => todo!("Feature Coming Soon!"),
+        Some(("layout",  sub_matches)) => arg_layout(sub_matches),
+        Some(("validate",sub_matches)) => arg_validate(sub_matches),
+        Some(("doctor",  sub_matches)) => arg_doctor(sub_matches),
+        Some(("fmt",     sub_matches)) => arg_fmt(sub_matches),
+        Some(("merge",   sub_matches)) => arg_merge(sub_matches),
+        Some(("sync",    sub_matches)) => arg_sync(sub_matches),
+        Some(("snapshot",sub_matches)) => arg_snapshot(sub_matches),
+        Some(("restore", sub_matches)) => arg_restore(sub_matches),
+        Some(("sed",     sub_matches)) => arg_sed(sub_matches),
+        Some(("scheme",  sub_matches)) => arg_scheme(sub_matches),
+        Some(("encrypt", sub_matches)) => arg_encrypt(sub_matches),
+        Some(("decrypt", sub_matches)) => arg_decrypt(sub_matches),
+        Some(("editor",  sub_matches)) => arg_editor(sub_matches),
+        Some(("gui",     sub_matches)) => arg_gui(sub_matches),
+        Some(("open",    sub_matches)) => arg_open(sub_matches),
+        Some(("selftest",sub_matches)) => arg_selftest(sub_matches),
         Some(("web_app", _          )) => todo!("Feature Coming Soon!"),
-        None => { todo!("We'll launch the window at some point!"); },
+        None => build_arg_parser().print_help().map_err(MainError::FileIO),
         _ => { unreachable!("Clap should guarantee that this doesn't happen."); },
     }
 }
 
+impl MainError {
+    /// Blames `fp` for self, unless a file is already blamed.
+    fn in_file(self, fp: &str) -> MainError {
+        match self {
+            MainError::InFile(_, _) => self,
+            e => MainError::InFile(fp.to_string(), Box::new(e)),
+        }
+    }
+
+    /// Adds advice to path errors about `query`, naming the deepest node
+    /// along it under `root` and what that node holds.
+    fn with_path_hint(self, root: &Node, query: &str) -> MainError {
+        match self {
+            MainError::BadPathParse(_)                |
+            MainError::NodeNotFound(_)                |
+            MainError::Eval(EvalError::NotFound(_))   => {
+                let hint = path_hint(root, query);
+                MainError::Hinted(Box::new(self), hint)
+            },
+            MainError::InFile(fp, e) => (*e).with_path_hint(root, query).in_file(&fp),
+            e => e,
+        }
+    }
+
+    /// The code to exit with, telling the kinds of failure apart for scripts.
+    fn exit_code(&self) -> u8 {
+        match self {
+            MainError::InFile(_, e)     |
+            MainError::Hinted(e, _)     => e.exit_code(),
+            MainError::Invalid(_)       |
+            MainError::WouldChange(_)   => EXIT_CHECK,
+            MainError::NotASagaDoc(_)   |
+            MainError::IntoOSString(_)  |
+            MainError::BadPathParse(_)  |
+            MainError::BadDateTimeParse(_) |
+            MainError::BadCalendarParse(_) |
+            MainError::InvertedDates(_) |
+            MainError::CommandParse(_, _) |
+            MainError::BadColor(_)      |
+            MainError::BadEvents(_)     |
+            MainError::Manifest(_)      |
+            MainError::LineTooLong(_)   |
+            MainError::NotUtf8(_)       |
+            MainError::TooDeep(_, _)    => EXIT_PARSE,
+            MainError::NodeNotFound(_)  |
+            MainError::Eval(EvalError::NotFound(_)) |
+            MainError::NoMatch(_)       |
+            MainError::NoSnapshot(_)    => EXIT_NOT_FOUND,
+            MainError::FileIO(_)        |
+            MainError::Readline(_)      |
+            MainError::EndOfInput       |
+            MainError::Changed          |
+            MainError::Viewer(_)        |
+            MainError::Pager(_, _)      => EXIT_IO,
+            MainError::SerializeFail(_) |
+            MainError::Gui(_)           |
+            MainError::SelfTest(_)      |
+            MainError::Interrupted      |
+            MainError::Eval(_)          |
+            MainError::Crypt(_)         |
+            MainError::Config(_)        |
+            MainError::Project(_)       |
+            MainError::Git(_)           |
+            MainError::Sync(_)          |
+            MainError::Scheme(_)        |
+            MainError::AddToEvent       |
+            MainError::NothingToLog     |
+            MainError::Conflicts(_)     => EXIT_FAILURE,
+        }
+    }
+
+    /// The advice given with self, if any.
+    fn hint(&self) -> Option<&str> {
+        match self {
+            MainError::Hinted(_, hint) => Some(hint),
+            MainError::InFile(_, e) => e.hint(),
+            _ => None,
+        }
+    }
+}
+
+/// Names the deepest node along the path `query` that exists, as far as it
+/// can be read, and lists what that node holds.
+fn path_hint(root: &Node, query: &str) -> String {
+    let path: Vec<usize> = query.split(':')
+        .map_while(|s|s.trim().parse().ok())
+        .collect();
+    let (path, node) = root.nearest(&path);
+    let at = match path.is_empty() {
+        true => "/".to_string(),
+        false => path_string(&path),
+    };
+    let name = node.name_in(None).unwrap_or("(No name)");
+    match node.list_children(&PrintOptions::default()).as_str() {
+        "" => format!("the nearest path that exists is {} {}, which is empty", at, name),
+        children => format!("the nearest path that exists is {} {}, which holds:\n{}", at, name, children),
+    }
+}
+
+impl std::fmt::Display for MainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MainError::NotASagaDoc(e) => write!(f, "not a saga document: {}", e),
+            MainError::SerializeFail(e) => write!(f, "couldn't write the document: {}", e),
+            MainError::FileIO(e) => write!(f, "{}", e),
+            MainError::IntoOSString(s) => write!(f, "{:?} isn't valid unicode", s),
+            MainError::BadPathParse(e) => write!(f, "bad path, expected numbers like 1:2: {}", e),
+            MainError::BadDateTimeParse(e) => write!(f, "bad date: {}", e),
+            MainError::BadCalendarParse(e) => write!(f, "{}", e),
+            MainError::InvertedDates(e) => write!(f, "{}", e),
+            MainError::Invalid(n) => write!(f, "{} problem(s) left unfixed, try --fix", n),
+            MainError::SelfTest(n) => write!(f, "{} check(s) failed", n),
+            MainError::Interrupted => write!(f, "interrupted"),
+            MainError::Readline(e) => write!(f, "{}", e),
+            MainError::NodeNotFound(e) => write!(f, "{}", e),
+            MainError::CommandParse(query, e) => write!(f, "{}", e.render(query)),
+            MainError::Eval(e) => write!(f, "{}", e),
+            MainError::Crypt(e) => write!(f, "{}", e),
+            MainError::Config(e) => write!(f, "{}", e),
+            MainError::Project(e) => write!(f, "{}", e),
+            MainError::Git(e) => write!(f, "{}", e),
+            MainError::Sync(e) => write!(f, "{}", e),
+            MainError::BadColor(e) => write!(f, "{}", e),
+            MainError::Scheme(e) => write!(f, "{}", e),
+            MainError::Viewer(e) => write!(f, "couldn't launch a viewer: {}", e),
+            MainError::Pager(pager, e) => write!(f, "couldn't run the pager '{}' (see $PAGER): {}", pager, e),
+            MainError::Gui(e) => write!(f, "couldn't open the window: {}", e),
+            MainError::AddToEvent => write!(f, "can't add to an event, only to a node"),
+            MainError::NothingToLog => write!(f, "nothing to log, the text is empty"),
+            MainError::NoMatch(query) => write!(f, "no event matches '{}'", query),
+            MainError::BadEvents(e) => write!(f, "expected a JSON array of events: {}", e),
+            MainError::EndOfInput => write!(f, "input ended before everything asked for was given"),
+            MainError::LineTooLong(max) => write!(f, "line too long, at most {} bytes are read", logging::thousands(*max)),
+            MainError::NotUtf8(Some(at)) => write!(f, "not valid UTF-8 from byte {} on", at),
+            MainError::NotUtf8(None) => write!(f, "not valid UTF-8"),
+            MainError::TooDeep(depth, limit) => write!(
+                f, "nodes are nested {} deep, past the limit of {} (see max_depth in ~/{})",
+                depth, limit, config::CONFIG_FILE,
+            ),
+            MainError::NoSnapshot(name) => write!(f, "no snapshot numbered or labeled '{}'", name),
+            MainError::Manifest(e) => write!(f, "bad manifest, {}", e),
+            MainError::Conflicts(n) => write!(f, "{} conflict(s), settled by keeping ours", n),
+            MainError::WouldChange(n) => write!(f, "{} file(s) would be changed", n),
+            MainError::Changed => write!(f, "changed by someone else since it was read, not overwriting it"),
+            MainError::InFile(fp, e) => write!(f, "{} in {}", e, fp),
+            MainError::Hinted(e, _) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for MainError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MainError::NotASagaDoc(e) => Some(e),
+            MainError::SerializeFail(e) => Some(e),
+            MainError::FileIO(e) => Some(e),
+            MainError::BadPathParse(e) => Some(e),
+            MainError::BadDateTimeParse(e) => Some(e),
+            MainError::BadCalendarParse(e) => Some(e),
+            MainError::InvertedDates(e) => Some(e),
+            MainError::Readline(e) => Some(e),
+            MainError::NodeNotFound(e) => Some(e),
+            MainError::CommandParse(_, e) => Some(e),
+            MainError::Eval(e) => Some(e),
+            MainError::Crypt(e) => Some(e),
+            MainError::Config(e) => Some(e),
+            MainError::Project(e) => Some(e),
+            MainError::Git(e) => Some(e),
+            MainError::Sync(e) => Some(e),
+            MainError::BadColor(e) => Some(e),
+            MainError::Scheme(e) => Some(e),
+            MainError::Viewer(e) => Some(e),
+            MainError::Pager(_, e) => Some(e),
+            MainError::Gui(e) => Some(e),
+            MainError::BadEvents(e) => Some(e),
+            MainError::Manifest(e) => Some(e),
+            MainError::InFile(_, e) => Some(e.as_ref()),
+            MainError::Hinted(e, _) => Some(e.as_ref()),
+            MainError::IntoOSString(_)  |
+            MainError::Invalid(_)       |
+            MainError::SelfTest(_)      |
+            MainError::Interrupted      |
+            MainError::TooDeep(_, _)    |
+            MainError::Changed          |
+            MainError::Conflicts(_)     |
+            MainError::WouldChange(_)   |
+            MainError::NoSnapshot(_)    |
+            MainError::NothingToLog     |
+            MainError::NoMatch(_)       |
+            MainError::EndOfInput       |
+            MainError::LineTooLong(_)   |
+            MainError::NotUtf8(_)       |
+            MainError::AddToEvent => None,
+        }
+    }
+}
+
 fn build_arg_parser() -> ClapCommand {
     command!()
         // .subcommand_required(true)
         // .arg_required_else_help(true)
+        .arg(arg!(--commit "Commit each changed FILE to git afterwards, see auto_commit in the config.").global(true))
+        .arg(arg!(-q --quiet "Only report errors, for scripts.").global(true))
+        .arg(arg!(-v --verbose "Report progress and timings, and print more detail.").global(true)
+            .conflicts_with("quiet"))
+        .subcommand(
+            ClapCommand::new("init")
+                .about("Start a project in DIR, or the current directory, with defaults that subcommands run anywhere inside it pick up.")
+                .arg(arg!([DIR] "Directory to hold the project's .saga/ directory."))
+                .arg(arg!(--locale <LOCALE> "Format dates using LOCALE when rendering.")
+                    .value_parser(clap::builder::PossibleValuesParser::new(Locale::codes())))
+                .arg(arg!(--scheme <NAME> "Render with the color scheme NAME, for documents that have one."))
+                .arg(arg!(--output <DIR> "Write rendered files to DIR, relative to the project.")),
+        )
         .subcommand(
             ClapCommand::new("new")
                 .about("<FILE> Create a new Saga document.")
@@ -102,35 +456,320 @@ fn build_arg_parser() -> ClapCommand {
             ClapCommand::new("add")
                 .about("Adds an event to the given file at the listed location.")
                 .arg(arg!(<FILE>))
-                .arg(arg!(<INT_LIST>)),
+                .arg(arg!(<INT_LIST>))
+                .arg(arg!(--json <SOURCE> "Add every event of the JSON array in SOURCE, or stdin if SOURCE is -, instead of asking for one.")),
         )
         .subcommand(
             ClapCommand::new("edit")
                 .about("Adds and event to the given file at the listed location.")
                 .arg(arg!(<FILE>))
                 .arg(arg!(<INT_LIST>))
-                .arg(arg!(<COMMAND> ...)),
+                .arg(arg!(<COMMAND> ...))
+                .arg(arg!(--check "Only report whether the edit would change each file, failing if it would."))
+                .arg(arg!(--also <FILE> "Make the same edit at the same location in FILE too, if it can be made in every file. May be given more than once.")
+                    .action(clap::ArgAction::Append)),
+        )
+        .subcommand(
+            ClapCommand::new("log")
+                .about("Add TEXT to FILE as an event at the current time, in the journal node set in the config, under nodes for the year and month. Its first line is the name, the rest the description.")
+                .arg(arg!(<FILE>))
+                .arg(arg!(<TEXT>)),
+        )
+        .subcommand(
+            ClapCommand::new("editor")
+                .about("Interactively edit FILE.")
+                .arg(arg!(<FILE>)),
+        )
+        .subcommand(
+            ClapCommand::new("gui")
+                .about("Edit FILE in a window, with its tree on the left and its rendering on the right, using the same commands as edit.")
+                .arg(arg!(<FILE>)),
+        )
+        .subcommand(
+            ClapCommand::new("open")
+                .about("Show FILE the way the config's open setting says: as an HTML page in the browser (the default), an SVG file, in the terminal, in the editor, or in a window.")
+                .arg(arg!(<FILE>))
+                .arg(arg!(--with <MODE> "Show FILE this way instead: html, svg, term, editor or gui.")
+                    .value_parser(clap::value_parser!(Opener))),
+        )
+        .subcommand(
+            ClapCommand::new("selftest")
+                .about("Round trip a generated document through every file format and renderer, reporting how long each took and any mismatches.")
+                .arg(arg!(--events <N> "How many events the document holds.")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("1000"))
+                .arg(arg!(--depth <N> "How deeply its nodes are nested.")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("3"))
+                .arg(arg!(--seed <N> "Generates the same document for the same seed.")
+                    .value_parser(clap::value_parser!(u64))
+                    .default_value("1")),
         )
         .subcommand(
             ClapCommand::new("grep")
-                .about("Adds and event to the given file at the listed location.")
+                .about("List the events, and their paths, in each FILE whose name or descriptions contain QUERY.")
                 .arg(arg!(<QUERY>))
                 .arg(arg!(<FILE> ...))
+                .arg(arg!(--status <STATUS> "Only list events with this status.")
+                    .value_parser(clap::value_parser!(Status)))
+                .arg(arg!(-C --context <NUM> "Also list up to NUM sibling events on either side of each match.")
+                    .value_parser(clap::value_parser!(usize)))
         )
         .subcommand(
             ClapCommand::new("cat")
-                .about("Catenate all the listed files together into DEST.")
+                .about("Catenate all the listed files together into DEST, each under a node of its own. A FILE of @MANIFEST lists the files in MANIFEST instead, one per line, optionally followed by offset=N to move it down and label=TEXT to name it.")
                 .arg(arg!(<FILE> ...))
                 .arg(arg!(<DEST> )),
         )
+        .subcommand(
+            ClapCommand::new("intersect")
+                .about("Write the events of A that are also in B, by name and dates, to DEST, such as the planned events that happened.")
+                .arg(arg!(<A>))
+                .arg(arg!(<B>))
+                .arg(arg!(<DEST>)),
+        )
+        .subcommand(
+            ClapCommand::new("subtract")
+                .about("Write the events of A that aren't in B, by name and dates, to DEST, such as the planned events that didn't happen.")
+                .arg(arg!(<A>))
+                .arg(arg!(<B>))
+                .arg(arg!(<DEST>)),
+        )
+        .subcommand(
+            ClapCommand::new("drift")
+                .about("Print how far each event of ACTUAL slipped from PLANNED, as the change in when it started and in how long it lasted, then a summary. Events are matched by name, the nth of a name in one with the nth in the other.")
+                .arg(arg!(<PLANNED>))
+                .arg(arg!(<ACTUAL>))
+                .arg(arg!(--render "Also draw both on one time axis to PLANNED.drift.svg, each event of ACTUAL boxed with how far it slipped.")),
+        )
         .subcommand(
             ClapCommand::new("render")
                 .about("Generate an SVG file for each given FILE.")
-                .arg(arg!(<FILE> ...)),
+                .arg(arg!([FILE] ...).required_unless_present("overlay"))
+                .arg(arg!(--overlay <A> "Draw documents A and B together on one time axis, in different colors, to A.overlay.svg.")
+                    .num_args(2)
+                    .value_names(["A", "B"])
+                    .conflicts_with("FILE"))
+                .arg(arg!(--term "Draw the timeline in the terminal instead of writing an SVG file."))
+                .arg(arg!(--open "Open each SVG file written in the default viewer.")
+                    .conflicts_with("term"))
+                .arg(arg!(--pages <N> "Split the timeline over N SVG files, each covering as long a stretch of time, numbered as in FILE.1.svg.")
+                    .value_parser(clap::value_parser!(usize))
+                    .conflicts_with_all(["term", "overlay"]))
+                .arg(arg!(--per <INTERVAL> "Split the timeline into a page every INTERVAL, like 10y, instead of into N.")
+                    .value_parser(clap::value_parser!(Interval))
+                    .conflicts_with_all(["pages", "term", "overlay"]))
+                .arg(arg!(--html "Write FILE.html instead, holding the SVG beside a table of contents of its nodes that zooms to each when clicked.")
+                    .conflicts_with_all(["term", "overlay", "pages", "per"]))
+                .arg(arg!(--"out-dir" <DIR> "Write the SVG files to DIR, made if need be, instead of next to each FILE or in the project's output directory.")
+                    .conflicts_with("term"))
+                .arg(arg!(--animate <SECONDS> "Reveal events in order, sweeping across the timeline over SECONDS.")
+                    .value_parser(clap::value_parser!(f64)))
+                .arg(arg!(--density <BUCKETS> "Add a histogram of how many events fall in each of BUCKETS slices of the timeline.")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(arg!(--heat <BUCKETS> "Tint the background of each row by how many of its events fall in each of BUCKETS slices of the timeline.")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(arg!(--durations "Add how long each span lasts to its label."))
+                .arg(arg!(--ruler "Add a scale bar in the bottom right corner, showing the width of a round stretch of time like 10 years."))
+                .arg(arg!(--captions "Write each event's name on its box, or beside it on a leader line where it doesn't fit."))
+                .arg(arg!(--annotate [QUERY] "Box the first description of each event matching QUERY, as grep matches, next to it. Every event with a description if QUERY isn't given.")
+                    .default_missing_value(""))
+                .arg(arg!(--grid [INTERVAL] "Draw gridlines every INTERVAL, like 1m or 10y, picked to suit the timeline if not given.")
+                    .value_parser(clap::value_parser!(Interval))
+                    .default_missing_value("auto"))
+                .arg(arg!(--outside <MODE> "How events outside the span declared for their node's line are drawn: extend the line (the default), clip them or badge them.")
+                    .value_parser(clap::value_parser!(Outside)))
+                .arg(arg!(--lang <LANG> "Use the names and descriptions translated to LANG."))
+                .arg(arg!(--locale <LOCALE> "Format dates using LOCALE instead of the document's.")
+                    .value_parser(clap::builder::PossibleValuesParser::new(Locale::codes())))
+                .arg(arg!(--"date-format" <PATTERN> "Lay dates out like PATTERN instead of as the document or locale does, e.g. \"{b} {y}\" for Jan 1990 or \"Q{q} {y}\" for Q3 2021. Also {d}, {dd}, {m}, {mm}, {H} and {M}.")),
+        )
+        .subcommand(
+            ClapCommand::new("layout")
+                .about("Print where render would draw each shape of FILE, as JSON in the document's pixel space, for drawing it elsewhere.")
+                .arg(arg!(<FILE>))
+                .arg(arg!(-o --output <PATH> "Write the JSON to PATH instead of printing it."))
+                .arg(arg!(--density <BUCKETS> "Add a histogram of how many events fall in each of BUCKETS slices of the timeline.")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(arg!(--heat <BUCKETS> "Tint the background of each row by how many of its events fall in each of BUCKETS slices of the timeline.")
+                    .value_parser(clap::value_parser!(usize)))
+                .arg(arg!(--durations "Add how long each span lasts to its label."))
+                .arg(arg!(--ruler "Add a scale bar in the bottom right corner, showing the width of a round stretch of time like 10 years."))
+                .arg(arg!(--captions "Write each event's name on its box, or beside it on a leader line where it doesn't fit."))
+                .arg(arg!(--annotate [QUERY] "Box the first description of each event matching QUERY, as grep matches, next to it. Every event with a description if QUERY isn't given.")
+                    .default_missing_value(""))
+                .arg(arg!(--grid [INTERVAL] "Draw gridlines every INTERVAL, like 1m or 10y, picked to suit the timeline if not given.")
+                    .value_parser(clap::value_parser!(Interval))
+                    .default_missing_value("auto"))
+                .arg(arg!(--outside <MODE> "How events outside the span declared for their node's line are drawn: extend the line (the default), clip them or badge them.")
+                    .value_parser(clap::value_parser!(Outside)))
+                .arg(arg!(--lang <LANG> "Use the names and descriptions translated to LANG."))
+                .arg(arg!(--locale <LOCALE> "Format dates using LOCALE instead of the document's.")
+                    .value_parser(clap::builder::PossibleValuesParser::new(Locale::codes())))
+                .arg(arg!(--"date-format" <PATTERN> "Lay dates out like PATTERN instead of as the document or locale does, e.g. \"{b} {y}\" for Jan 1990 or \"Q{q} {y}\" for Q3 2021. Also {d}, {dd}, {m}, {mm}, {H} and {M}.")),
         )
         .subcommand(
             ClapCommand::new("print")
-                .about("Get a rough overview of each given FILE.")
+                .about("Get a rough overview of each given FILE. With --verbose, also show offsets, scaling, descriptions and how long each span lasts.")
+                .arg(arg!(<FILE> ...))
+                .arg(arg!(--chrono "List every event by its start date, along with its path, instead of the tree."))
+                .arg(arg!(--lang <LANG> "Use the names and descriptions translated to LANG.")),
+        )
+        .subcommand(
+            ClapCommand::new("view")
+                .about("Read FILE page by page: its tree with every detail, each event numbered by when it starts.")
+                .arg(arg!(<FILE>))
+                .arg(arg!(--"no-pager" "Print the view instead of paging through it."))
+                .arg(arg!(--lang <LANG> "Use the names and descriptions translated to LANG.")),
+        )
+        .subcommand(
+            ClapCommand::new("dupes")
+                .about("List events of FILE that are likely duplicates: alike names and overlapping dates, as often happens when combining documents.")
+                .arg(arg!(<FILE>))
+                .arg(arg!(--merge "Ask about each pair in turn, merging those agreed to into the one coming first.")),
+        )
+        .subcommand(
+            ClapCommand::new("count")
+                .about("Count the events in each given FILE, grouped by year, node or status.")
+                .arg(arg!(<FILE> ...))
+                .arg(arg!(--by <GROUPING> "Group events by year, node or status.")
+                    .value_parser(clap::value_parser!(Grouping))
+                    .default_value("year")),
+        )
+        .subcommand(
+            ClapCommand::new("stats")
+                .about("Report how much of its range the events in each given FILE cover, how long they last on average in each node, and the longest gaps between them.")
+                .arg(arg!(<FILE> ...))
+                .arg(arg!(--gaps <N> "How many of the longest gaps to list.")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("3")),
+        )
+        .subcommand(
+            ClapCommand::new("gaps")
+                .about("List the stretches of time lasting at least --min that no event under the node at INT_LIST of FILE covers, the whole document if INT_LIST isn't given.")
+                .arg(arg!(<FILE>))
+                .arg(arg!([INT_LIST]))
+                .arg(arg!(--min <DURATION> "Shortest gap to list, such as 12h, 3d or 2w.")
+                    .value_parser(events::parse_duration)
+                    .default_value("30d")),
+        )
+        .subcommand(
+            ClapCommand::new("when")
+                .about("Print how long until or since each event of FILE matching QUERY, as grep matches, like \"Jena: ended 219 years ago\".")
+                .arg(arg!(<FILE>))
+                .arg(arg!(<QUERY>)),
+        )
+        .subcommand(
+            ClapCommand::new("remind")
+                .about("List the events in all given FILEs that start in the next few days, one per line, for scripts.")
+                .arg(arg!(<FILE> ...))
+                .arg(arg!(--days <DAYS> "How many days ahead to look.")
+                    .value_parser(clap::value_parser!(i64))
+                    .default_value("7"))
+                .arg(arg!(--ics "Write them as an iCalendar file instead, with an alarm for each."))
+                .arg(arg!(--alarm <MINUTES> "How long before each event its alarm goes off, with --ics.")
+                    .value_parser(clap::value_parser!(i64))
+                    .default_value("15")),
+        )
+        .subcommand(
+            ClapCommand::new("validate")
+                .about("Check each given FILE for problems, such as spans that end before they start, and warn of events outside their node's line.")
+                .arg(arg!(<FILE> ...))
+                .arg(arg!(--fix "Repair the problems in place where possible.")),
+        )
+        .subcommand(
+            ClapCommand::new("fmt")
+                .about("Rewrite each FILE in the canonical, indented form, so equal documents are byte for byte equal.")
+                .arg(arg!(<FILE> ...))
+                .arg(arg!(--check "Only report the files not in that form, failing if there are any.")),
+        )
+        .subcommand(
+            ClapCommand::new("doctor")
+                .about("Load FILE leniently, report what had to be repaired, and write a repaired copy.")
+                .arg(arg!(<FILE>))
+                .arg(arg!(-o --output <OUTPUT> "Where to write the repaired copy, instead of FILE.repaired.json.")),
+        )
+        .subcommand(
+            ClapCommand::new("merge")
+                .about("Merge the changes OURS and THEIRS made to BASE event by event, writing the result to OUT. Usable as a git merge driver: saga merge %O %A %B %A")
+                .arg(arg!(<BASE>))
+                .arg(arg!(<OURS>))
+                .arg(arg!(<THEIRS>))
+                .arg(arg!(<OUT>)),
+        )
+        .subcommand(
+            ClapCommand::new("sync")
+                .about("Bring FILE and its copy at the remote URL, on WebDAV or S3-compatible storage, up to date with each other.")
+                .arg(arg!(<FILE>))
+                .arg(arg!(--remote <URL> "Where the remote copy is kept.").required(true))
+                .arg(arg!(--s3 <REGION> "Sign requests for S3-compatible storage, with credentials from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY."))
+                .arg(arg!(--newest "When both copies changed, keep the one written last instead of merging them.")),
+        )
+        .subcommand(
+            ClapCommand::new("snapshot")
+                .about("Keep a compressed copy of FILE's events next to it, to go back to with restore.")
+                .arg(arg!(<FILE>))
+                .arg(arg!(-l --label <LABEL> "Name the snapshot, so it can be restored by name."))
+                .arg(arg!(--list "List the snapshots of FILE instead of taking one.")),
+        )
+        .subcommand(
+            ClapCommand::new("restore")
+                .about("Put back the events of FILE as they were in SNAPSHOT, given by number or label. The current ones are snapshotted first.")
+                .arg(arg!(<FILE>))
+                .arg(arg!(<SNAPSHOT>)),
+        )
+        .subcommand(
+            ClapCommand::new("sed")
+                .about("Replace matches of the regex PATTERN with REPLACEMENT in every name and description of each FILE.")
+                .arg(arg!(<PATTERN>))
+                .arg(arg!(<REPLACEMENT>))
+                .arg(arg!(<FILE> ...))
+                .arg(arg!(--check "Only report the files with matches, failing if there are any.")),
+        )
+        .subcommand(
+            ClapCommand::new("scheme")
+                .about("Manage the color schemes of FILE, which nodes and events can be colored with by name.")
+                .subcommand_required(true)
+                .subcommand(
+                    ClapCommand::new("list")
+                        .about("List each scheme and its colors, marking the active one with '*'.")
+                        .arg(arg!(<FILE>)),
+                )
+                .subcommand(
+                    ClapCommand::new("add")
+                        .about("Add a scheme called NAME, made of each COLOR such as #ff8800.")
+                        .arg(arg!(<FILE>))
+                        .arg(arg!(<NAME>))
+                        .arg(arg!(<COLOR> ...)),
+                )
+                .subcommand(
+                    ClapCommand::new("rename")
+                        .about("Rename the scheme OLD to NEW, along with every color using it.")
+                        .arg(arg!(<FILE>))
+                        .arg(arg!(<OLD>))
+                        .arg(arg!(<NEW>)),
+                )
+                .subcommand(
+                    ClapCommand::new("delete")
+                        .about("Delete the scheme NAME.")
+                        .arg(arg!(<FILE>))
+                        .arg(arg!(<NAME>)),
+                )
+                .subcommand(
+                    ClapCommand::new("use")
+                        .about("Color events without a color of their own from the scheme NAME when rendering, or with the defaults if NAME is left out.")
+                        .arg(arg!(<FILE>))
+                        .arg(arg!([NAME])),
+                ),
+        )
+        .subcommand(
+            ClapCommand::new("encrypt")
+                .about("Encrypt each given FILE in place with a passphrase.")
+                .arg(arg!(<FILE> ...)),
+        )
+        .subcommand(
+            ClapCommand::new("decrypt")
+                .about("Decrypt each given FILE in place. Never commits, even with --commit.")
                 .arg(arg!(<FILE> ...)),
         )
         .subcommand(
@@ -139,17 +778,36 @@ fn build_arg_parser() -> ClapCommand {
         )
 }
 
+fn arg_init(sub_matches: &ArgMatches) -> MainResult {
+    let dir = match sub_matches.get_one::<String>("DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::current_dir().map_err(MainError::FileIO)?,
+    };
+    let mut project = Project::new(&dir);
+    if let Some(locale) = sub_matches.get_one::<String>("locale") {
+        project.locale = Some(locale.to_string());
+    }
+    if let Some(scheme) = sub_matches.get_one::<String>("scheme") {
+        project.scheme = Some(scheme.to_string());
+    }
+    if let Some(output) = sub_matches.get_one::<String>("output") {
+        project.output = Some(PathBuf::from(output));
+    }
+    let path = project.init()?;
+    info!("Started a project, its settings are in {}", path.display());
+    Ok(())
+}
+
 fn arg_new(sub_matches: &ArgMatches) -> MainResult {
     // Extract the raw data.
     let fp: &str = sub_matches.get_one::<String>("FILE")
         .expect("Clap guarantees that this should be here.");
     // Create the new document.
     let saga: SagaDoc = SagaDoc::blank();
-    let contents = saga_serialize(&saga)?;
     // Then write the changes to the disk.
-    write_to_file(fp, &contents)?;
-    println!("Successfully made {}", fp);
-    Ok(())
+    saga.save(fp)?;
+    info!("Successfully made {}", fp);
+    commit(sub_matches, fp, "new document")
 }
 
 fn arg_node(sub_matches: &ArgMatches) -> MainResult {
@@ -159,15 +817,12 @@ fn arg_node(sub_matches: &ArgMatches) -> MainResult {
     let fp: &str = sub_matches.get_one::<String>("FILE")
         .expect("Clap guarantees that this should be here.");
     // Wrangle it into the correct form. 
-    let mut contents = open_file(fp)?;
-    let mut saga: SagaDoc = saga_deserialize(&contents)?;
+    let (mut saga, stamp) = SagaDoc::load_stamped(fp)?;
     // Do our editting.
-    saga.add_node(&query)?;
+    saga.add_node(query).map_err(|e|MainError::from(e).in_file(fp))?;
     // Then write the changes to the disk.
-    contents.clear();
-    contents = saga_serialize(&saga)?;
-    write_to_file(fp, &contents)?;
-    Ok(())
+    saga.save_unchanged(fp, stamp)?;
+    commit(sub_matches, fp, &format!("add node at {}", query))
 }
 
 fn arg_add(sub_matches: &ArgMatches) -> MainResult {
@@ -177,123 +832,988 @@ fn arg_add(sub_matches: &ArgMatches) -> MainResult {
     let fp: &str = sub_matches.get_one::<String>("FILE")
         .expect("Clap guarantees that this should be here.");
     // Wrangle it into the correct form. 
-    let mut contents = open_file(fp)?;
-    let mut saga: SagaDoc = saga_deserialize(&contents)?;
+    let (mut saga, stamp) = SagaDoc::load_stamped(fp)?;
     // Do our editting.
-    saga.add_event(&query)?;
+    let added = match sub_matches.get_one::<String>("json") {
+        Some(source) => {
+            let events = read_events(source)?;
+            let count = events.len();
+            saga.add_events(query, events).map(|_|count)
+        },
+        None => saga.add_event(query).map(|_|1),
+    };
+    let added = added
+        .map_err(|e|MainError::from(e).with_path_hint(saga.get_data(), query).in_file(fp))?;
     // Then write the changes to the disk.
-    contents.clear();
-    contents = saga_serialize(&saga)?;
-    write_to_file(fp, &contents)?;
-    Ok(())
+    saga.save_unchanged(fp, stamp)?;
+    match added {
+        1 => commit(sub_matches, fp, &format!("add event at {}", query)),
+        n => commit(sub_matches, fp, &format!("add {} events at {}", n, query)),
+    }
+}
+
+/// Reads a JSON array of events from the file at `source`, or stdin if it's `-`.
+fn read_events(source: &str) -> Result<Vec<Event>, MainError> {
+    let bytes = match source {
+        "-" => {
+            let mut bytes = Vec::new();
+            std::io::stdin().read_to_end(&mut bytes)
+                .map_err(MainError::FileIO)?;
+            bytes
+        },
+        _ => std::fs::read(source)
+            .map_err(|e|MainError::FileIO(e).in_file(source))?,
+    };
+    let text = std::str::from_utf8(&bytes)
+        .map_err(|e|MainError::NotUtf8(Some(e.valid_up_to())).in_file(source))?;
+    serde_json::from_str(text)
+        .map_err(|e|MainError::BadEvents(e).in_file(source))
+}
+
+fn arg_log(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let text: &str = sub_matches.get_one::<String>("TEXT")
+        .expect("Clap guarantees that this should be here.");
+    if text.trim().is_empty() {
+        return Err(MainError::NothingToLog);
+    }
+    let config = Config::load()?;
+    let (mut saga, stamp) = SagaDoc::load_stamped(fp)?;
+    let now = chrono::Local::now().naive_local();
+    let locale = saga.locale();
+    let path = journal::log(saga.get_data_mut(), config.journal(), text, now, locale);
+    saga.save_unchanged(fp, stamp)?;
+    info!("Logged at {} in {}", path_string(&path), fp);
+    commit(sub_matches, fp, &format!("log at {}", path_string(&path)))
 }
 
 fn arg_edit(sub_matches: &ArgMatches) -> MainResult {
     // Extract the raw data.
     let fp: &str = sub_matches.get_one::<String>("FILE")
         .expect("Clap guarantees that this should be here.");
-    let query: Vec<usize> = sub_matches.get_one::<String>("INT_LIST")
-        .map(|s|saga::parse_to_int_path(s))
-        .expect("Clap guarantees that this should be here.")?;
-    let command: EvalCommand = sub_matches.get_many::<String>("COMMAND")
+    let at: &str = sub_matches.get_one::<String>("INT_LIST")
+        .expect("Clap guarantees that this should be here.");
+    let command: String = sub_matches.get_many::<String>("COMMAND")
         .expect("Clap guarantees that this should be here.")
         .map(|s|s.to_string())
         .collect::<Vec<String>>()
-        .join(" ")
-        .parse::<EvalCommand>()?;
-    // Wrangle it into the correct form. 
-    let mut contents: String = open_file(fp)?;
-    let mut saga: SagaDoc = saga_deserialize(&contents)?;
-    let mut query = saga.get_data_mut().query(&query[..])?;
-    // Commit changes to the document's data node.
-    command.eval_query(&mut query)?;
+        .join(" ");
+    let command = Config::load()?.expand(&command);
+    let check = sub_matches.get_flag("check");
+    let files: Vec<&str> = std::iter::once(fp)
+        .chain(sub_matches.get_many::<String>("also").into_iter().flatten().map(|s|s.as_str()))
+        .collect();
+    // Edit every document before saving any, so that one the edit fails on
+    // leaves them all as they were.
+    let mut edited = vec![];
+    for fp in files {
+        // Wrangle it into the correct form.
+        let (mut saga, stamp) = SagaDoc::load_stamped(fp)?;
+        let mut query: Vec<usize> = saga::parse_to_int_path(at)
+            .map_err(|e|MainError::from(e).with_path_hint(saga.get_data(), at).in_file(fp))?;
+        // Dates in the command are read using the document's calendar, if it has one.
+        let program = Program::parse_with(&command, saga.calendar())
+            .map_err(|e|MainError::CommandParse(command.clone(), e))?;
+        if program.is_help() {
+            println!("{}", edit::help_text());
+            return Ok(());
+        }
+        let before = check.then(||saga.clone());
+        // Commit changes to the document's data node.
+        program.eval_at(saga.get_data_mut(), &mut query)
+            .map_err(|e|MainError::from(e).with_path_hint(saga.get_data(), at).in_file(fp))?;
+        edited.push((fp, before, saga, stamp));
+    }
+    if check {
+        let mut changed = 0;
+        for (fp, before, saga, _) in edited.iter() {
+            if let Some(before) = before {
+                changed += report_check(fp, saga.differs(before)?);
+            }
+        }
+        return checked(changed);
+    }
     // Write back to file.
-    contents = saga_serialize(&saga)?;
-    write_to_file(fp, &contents)?;
+    for (fp, _, saga, stamp) in edited {
+        saga.save_unchanged(fp, stamp)?;
+        commit(sub_matches, fp, &format!("edit {}: {}", at, command))?;
+    }
     Ok(())
 }
 
+fn arg_editor(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let (mut saga, mut stamp) = SagaDoc::load_stamped(fp)?;
+    let config = Config::load()?;
+    editor::run(&mut saga, &config, &mut |doc|{
+        stamp = doc.save_unchanged(fp, stamp)?;
+        info!("Wrote {}", fp);
+        commit(sub_matches, fp, "edit in the editor")
+    })
+}
+
+fn arg_gui(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let (doc, stamp) = SagaDoc::load_stamped(fp)?;
+    let config = Config::load()?;
+    app::run(app::Flags { path: fp.to_string(), doc, stamp, config })
+        .map_err(MainError::Gui)
+}
+
+fn arg_open(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let opener = match sub_matches.get_one::<Opener>("with") {
+        Some(opener) => *opener,
+        None => Config::load()?.open,
+    };
+    let project = Project::discover()?.unwrap_or_default();
+    let options = RenderOptions {
+        locale: project.locale,
+        scheme: project.scheme,
+        ..RenderOptions::default()
+    };
+    // Kept out of the way, since nobody asked for a file.
+    let dir = std::env::temp_dir().join("saga");
+    let load = || -> Result<SagaDoc, MainError> {
+        let mut saga = SagaDoc::load(fp)?;
+        saga.read_images(fp);
+        Ok(saga)
+    };
+    let path = match opener {
+        Opener::Editor => return arg_editor(sub_matches),
+        Opener::Gui => return arg_gui(sub_matches),
+        Opener::Term => {
+            let scene = load()?.scene(&options);
+            return view::page(&Terminal::detect().render(&scene));
+        },
+        Opener::Html => {
+            let saga = load()?;
+            let path = in_dir(&dir, Path::new(fp), "html");
+            save_output(&path, &html::export(&saga, &options, &page_title(&saga, fp, &options)))?;
+            path
+        },
+        Opener::Svg => {
+            let path = in_dir(&dir, Path::new(fp), "svg");
+            save_output(&path, &load()?.draw(&options))?;
+            path
+        },
+    };
+    open_in_viewer(&path)
+}
+
+fn arg_selftest(sub_matches: &ArgMatches) -> MainResult {
+    let number = |id: &str|*sub_matches.get_one::<usize>(id)
+        .expect("Clap provides a default.");
+    let (events, depth) = (number("events"), number("depth"));
+    let seed = *sub_matches.get_one::<u64>("seed")
+        .expect("Clap provides a default.");
+    let started = std::time::Instant::now();
+    let doc = selftest::generate(events, depth, seed);
+    println!("Generated {} events, {} level(s) deep, in {:.1?}", events, doc.get_data().max_depth(), started.elapsed());
+    let checks = selftest::run(&doc);
+    for check in checks.iter() {
+        let result = match check.failure.as_ref() {
+            Some(failure) => format!("FAILED: {}", failure),
+            None => "ok".to_string(),
+        };
+        println!("  {:<14} {:>10.1?}  {}", check.name, check.elapsed, result);
+    }
+    match checks.iter().filter(|check|check.failure.is_some()).count() {
+        0 => Ok(()),
+        n => Err(MainError::SelfTest(n)),
+    }
+}
+
 fn arg_print(sub_matches: &ArgMatches) -> MainResult {
+    let options = PrintOptions {
+        lang: sub_matches.get_one::<String>("lang").map(|s|s.as_str()),
+        verbose: sub_matches.get_flag("verbose"),
+        ..PrintOptions::default()
+    };
+    let by_date = sub_matches.get_flag("chrono");
     // Assume all of the paths are valid files that have been parsed correctly.
     open_saga_docs(sub_matches, "FILE")?.iter().for_each(|(fp, parsed_doc)|{
-        println!("\n{}\n{}", fp, parsed_doc.print(false));
+        let text = match by_date {
+            true => parsed_doc.print_chrono(&options),
+            false => parsed_doc.print(&options),
+        };
+        println!("\n{}\n{}", fp, text);
         // let s = parsed_doc.print(false);
         // println!("{}", s);
     });
     Ok(())
 }
 
+fn arg_view(sub_matches: &ArgMatches) -> MainResult {
+    let fp = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let doc = SagaDoc::load(fp)?;
+    let options = doc.print_options(PrintOptions {
+        lang: sub_matches.get_one::<String>("lang").map(|s|s.as_str()),
+        verbose: true,
+        ..PrintOptions::default()
+    });
+    let paged = !sub_matches.get_flag("no-pager");
+    let color = paged && std::io::stdout().is_terminal();
+    let text = view::view(doc.get_data(), &options, color);
+    match paged {
+        true => view::page(&text),
+        false => view::print(&text),
+    }
+}
+
+fn arg_count(sub_matches: &ArgMatches) -> MainResult {
+    let by: Grouping = *sub_matches.get_one::<Grouping>("by")
+        .expect("Clap provides a default.");
+    for (fp, doc) in open_saga_docs(sub_matches, "FILE")?.iter() {
+        let counts = count::count(doc.get_data(), by);
+        println!("\n{}", fp);
+        for (label, n) in counts.iter() {
+            println!("{:>6} {}", n, label);
+        }
+        println!("{:>6} total", counts.iter().map(|(_, n)|n).sum::<usize>());
+    }
+    Ok(())
+}
+
+fn arg_stats(sub_matches: &ArgMatches) -> MainResult {
+    let gaps: usize = *sub_matches.get_one::<usize>("gaps")
+        .expect("Clap provides a default.");
+    let dates = |range: (i64, i64)|events::Dates::from_stamps(range)
+        .map(|dates|dates.to_string())
+        .unwrap_or_default();
+    for (fp, doc) in open_saga_docs(sub_matches, "FILE")?.iter() {
+        println!("\n{}", fp);
+        let found = match stats::stats(doc.get_data(), gaps) {
+            Some(found) => found,
+            None => {
+                println!("  No events");
+                continue;
+            },
+        };
+        println!("  Range:    {} ({})", dates(found.range), stats::length(found.range.1 - found.range.0));
+        println!("  Covered:  {} ({:.1}%)", stats::length(found.covered), found.coverage() * 100.0);
+        println!("  Average duration by node:");
+        for (label, n, average) in found.per_node.iter() {
+            println!("    {:>20} {} ({} event{})", stats::length(*average), label, n, if *n == 1 { "" } else { "s" });
+        }
+        if !found.gaps.is_empty() {
+            println!("  Longest gaps:");
+        }
+        for gap in found.gaps.iter() {
+            println!("    {:>20} {}", stats::length(gap.1 - gap.0), dates(*gap));
+        }
+    }
+    Ok(())
+}
+
+fn arg_gaps(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let at: &str = sub_matches.get_one::<String>("INT_LIST").map_or("", |at|at.as_str());
+    let min: &chrono::Duration = sub_matches.get_one::<chrono::Duration>("min")
+        .expect("Clap provides a default.");
+    let saga = SagaDoc::load(fp)?;
+    let path = saga::parse_to_int_path(at)
+        .map_err(|e|MainError::from(e).in_file(fp))?;
+    let found = match saga.get_data().query_ref(&path) {
+        Ok(QueryRef::Node(node)) => stats::gaps(node.iter_events(), min.num_seconds()),
+        Ok(QueryRef::Event(event)) => stats::gaps(std::iter::once(event), min.num_seconds()),
+        Err(e) => return Err(MainError::from(e).with_path_hint(saga.get_data(), at).in_file(fp)),
+    };
+    for (start, end) in found.iter() {
+        let dates = events::Dates::from_stamps((*start, *end))
+            .map(|dates|dates.to_string())
+            .unwrap_or_default();
+        println!("{:>20} {}", stats::length(end - start), dates);
+    }
+    println!("{} gap{} of at least {}", found.len(), if found.len() == 1 { "" } else { "s" }, stats::length(min.num_seconds()));
+    Ok(())
+}
+
+fn arg_dupes(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let (mut saga, stamp) = SagaDoc::load_stamped(fp)?;
+    let found = dupes::find(saga.get_data());
+    let index = PathIndex::build(saga.get_data());
+    let describe = |path: &[usize]|match index.get(path) {
+        Some(QueryRef::Event(event)) => format!("{} {} ({})", path_string(path), event.name(), event.dates()),
+        _ => path_string(path),
+    };
+    let merge = sub_matches.get_flag("merge");
+    let mut agreed = vec![];
+    for dupe in found.iter() {
+        let pair = format!("{} and {}, {:.0}% alike", describe(&dupe.first), describe(&dupe.second), dupe.similarity * 100.0);
+        match merge {
+            true => if input::ask_bool(&format!("{}. Merge? [y/N]", pair))? {
+                agreed.push(dupe);
+            },
+            false => println!("{}: {}", fp, pair),
+        }
+    }
+    if found.is_empty() {
+        println!("{}: no likely duplicates", fp);
+    }
+    if agreed.is_empty() {
+        return Ok(());
+    }
+    let merged = dupes::merge(saga.get_data_mut(), &agreed);
+    saga.save_unchanged(fp, stamp)?;
+    info!("Merged {} events in {}", merged, fp);
+    commit(sub_matches, fp, "merge duplicate events")
+}
+
+fn arg_when(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let query: &str = sub_matches.get_one::<String>("QUERY")
+        .expect("Clap guarantees that this should be here.");
+    let saga = SagaDoc::load(fp)?;
+    let now = chrono::Local::now().naive_local();
+    let found: Vec<&Event> = saga.get_data().iter_events()
+        .filter(|event|event.matches(query))
+        .collect();
+    if found.is_empty() {
+        return Err(MainError::NoMatch(query.to_string()).in_file(fp));
+    }
+    for event in found {
+        println!("{}: {}", event.name(), remind::when(event, now));
+    }
+    Ok(())
+}
+
+fn arg_remind(sub_matches: &ArgMatches) -> MainResult {
+    let days: i64 = *sub_matches.get_one::<i64>("days")
+        .expect("Clap provides a default.");
+    let docs = open_saga_docs(sub_matches, "FILE")?;
+    let now = chrono::Local::now().naive_local();
+    let mut reminders: Vec<remind::Reminder> = docs.iter()
+        .flat_map(|(fp, doc)|remind::upcoming(fp, doc.get_data(), now, days))
+        .collect();
+    reminders.sort_by_key(|reminder|*reminder.event.dates().start());
+    match sub_matches.get_flag("ics") {
+        true => {
+            let alarm: i64 = *sub_matches.get_one::<i64>("alarm")
+                .expect("Clap provides a default.");
+            print!("{}", remind::to_ics(&reminders, chrono::Utc::now().naive_utc(), alarm));
+        },
+        false => print!("{}", remind::to_lines(&reminders)),
+    }
+    Ok(())
+}
+
+fn arg_grep(sub_matches: &ArgMatches) -> MainResult {
+    let query: &str = sub_matches.get_one::<String>("QUERY")
+        .expect("Clap guarantees that this should be here.");
+    let status: Option<Status> = sub_matches.get_one::<Status>("status").copied();
+    let context: usize = sub_matches.get_one::<usize>("context").copied().unwrap_or(0);
+    for (fp, doc) in open_saga_docs(sub_matches, "FILE")?.iter() {
+        let options = doc.print_options(PrintOptions::default());
+        let data = doc.get_data();
+        let found = data.find_all(|event|{
+            event.matches(query) && (status.is_none() || event.status() == status)
+        });
+        // Matches are listed below the names of the nodes they are in, with
+        // ':' after their paths and '-' after those of their siblings.
+        let lookup = PathIndex::build(data);
+        for (parent, shown) in data.with_siblings(&found, context) {
+            let ancestors = data.ancestor_names(&parent, None);
+            if !ancestors.is_empty() {
+                println!("{} {}", fp, ancestors.join(" › "));
+            }
+            for (index, matched) in shown {
+                let path = [&parent[..], &[index]].concat();
+                if let Some(QueryRef::Event(event)) = lookup.get(&path) {
+                    let mark = if matched { ':' } else { '-' };
+                    println!("{} {}{} {}", fp, path_string(&path), mark, event.print(0, &options));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 fn arg_catenate(sub_matches: &ArgMatches) -> MainResult {
     // Get the file, parse the, then catenate them down.
-    let saga_docs = open_saga_docs(sub_matches, "FILE")?
-        .into_iter()
-        .map(|(_,doc)|doc)
-        .collect();
+    let mut saga_docs = vec![];
+    for fp in sub_matches.get_many::<String>("FILE")
+        .expect("Clap guarantees that this should be here.") {
+        let manifest = match fp.strip_prefix('@') {
+            Some(manifest) => manifest,
+            None => {
+                saga_docs.push(SagaDoc::load(fp)?);
+                continue;
+            },
+        };
+        let text = std::fs::read_to_string(manifest)
+            .map_err(|e|MainError::FileIO(e).in_file(manifest))?;
+        // Paths in the manifest are relative to it.
+        let dir = Path::new(manifest).parent().unwrap_or(Path::new(""));
+        let inputs = manifest::parse(&text, dir)
+            .map_err(|e|MainError::from(e).in_file(manifest))?;
+        for input in inputs {
+            let mut doc = SagaDoc::load(&input.path.to_string_lossy())?;
+            input.apply(doc.get_data_mut());
+            saga_docs.push(doc);
+        }
+    }
     let doc = SagaDoc::catenate(saga_docs);
-    let contents = saga_serialize(&doc)?;
     let dest: &str = sub_matches.get_one::<String>("DEST")
         .expect("Clap guarantees that this should be here.");
-    write_to_file(dest, &contents)?;
+    doc.save(dest)?;
+    commit(sub_matches, dest, "cat")
+}
+
+/// Writes the document `compare` makes of A and B to DEST, keeping the
+/// settings of A.
+fn arg_compare(sub_matches: &ArgMatches, compare: fn(&SagaDoc, &SagaDoc) -> SagaDoc, name: &str) -> MainResult {
+    let path = |id: &str|sub_matches.get_one::<String>(id)
+        .expect("Clap guarantees that this should be here.");
+    let (a, b, dest) = (path("A"), path("B"), path("DEST"));
+    let doc = compare(&SagaDoc::load(a)?, &SagaDoc::load(b)?);
+    doc.save(dest)?;
+    info!("Wrote {} events to {}", logging::thousands(doc.get_data().iter_events().count()), dest);
+    commit(sub_matches, dest, &format!("{} {} {}", name, a, b))
+}
+
+fn arg_drift(sub_matches: &ArgMatches) -> MainResult {
+    let path = |id: &str|sub_matches.get_one::<String>(id)
+        .expect("Clap guarantees that this should be here.");
+    let (fp, mut planned, mut actual) = (path("PLANNED"), SagaDoc::load(path("PLANNED"))?, SagaDoc::load(path("ACTUAL"))?);
+    planned.read_images(fp);
+    actual.read_images(path("ACTUAL"));
+    let found = drift::drift(planned.get_data(), actual.get_data());
+    for slip in found.slips.iter() {
+        println!("{:>20} {:>20}  {}: {}", drift::signed(slip.start), drift::signed(slip.duration), path_string(&slip.actual), slip.name);
+    }
+    let (late, on_time, early) = found.counts();
+    println!("\n{} matched: {} late, {} on time, {} early", found.slips.len(), late, on_time, early);
+    if let (Some(average), Some(worst)) = (found.average(), found.worst()) {
+        println!("Average slip {}, worst {} ({})", drift::signed(average), drift::signed(worst.start), worst.name);
+    }
+    let name = |doc: &SagaDoc, at: &[usize]|match doc.get_data().query_ref(at) {
+        Ok(QueryRef::Event(event)) => event.name().to_string(),
+        _ => String::new(),
+    };
+    for at in found.missing.iter() {
+        println!("Didn't happen: {}: {}", path_string(at), name(&planned, at));
+    }
+    for at in found.unplanned.iter() {
+        println!("Unplanned: {}: {}", path_string(at), name(&actual, at));
+    }
+    if sub_matches.get_flag("render") {
+        let project = Project::discover()?.unwrap_or_default();
+        let (planned, actual) = found.annotate(&planned, &actual);
+        let options = RenderOptions {
+            locale: project.locale.clone(),
+            annotate: Some(String::new()),
+            ..RenderOptions::default()
+        };
+        let svg = Svg { animate: None }.render(&planned.overlay_scene(&actual, &options));
+        save_output(&project.output_path(Path::new(fp), "drift.svg"), &svg)?;
+    }
     Ok(())
 }
 
 fn arg_render(sub_matches: &ArgMatches) -> MainResult {
-    for (fp,saga) in open_saga_docs(sub_matches, "FILE")?.iter() {
-        let svg = saga.draw();
-        let mut fp_svg = PathBuf::from(fp);
-        fp_svg.set_extension("svg");
-        svg::save(&fp_svg, &svg)
-            .map_err(|e|MainError::FileIO(e))?;
-        println!("Wrote {:?} successfully.", &fp_svg);
+    let project = Project::discover()?.unwrap_or_default();
+    let options = RenderOptions {
+        lang: sub_matches.get_one::<String>("lang").cloned(),
+        locale: sub_matches.get_one::<String>("locale").cloned().or(project.locale.clone()),
+        date_pattern: sub_matches.get_one::<String>("date-format").cloned(),
+        animate: sub_matches.get_one::<f64>("animate").copied(),
+        density: sub_matches.get_one::<usize>("density").copied(),
+        heat: sub_matches.get_one::<usize>("heat").copied(),
+        durations: sub_matches.get_flag("durations"),
+        ruler: sub_matches.get_flag("ruler"),
+        captions: sub_matches.get_flag("captions"),
+        annotate: sub_matches.get_one::<String>("annotate").cloned(),
+        grid: sub_matches.get_one::<Interval>("grid").copied(),
+        outside: sub_matches.get_one::<Outside>("outside").copied().unwrap_or_default(),
+        scheme: project.scheme.clone(),
+    };
+    let term = sub_matches.get_flag("term");
+    let open = sub_matches.get_flag("open");
+    let as_html = sub_matches.get_flag("html");
+    let pages = match (sub_matches.get_one::<usize>("pages"), sub_matches.get_one::<Interval>("per")) {
+        (Some(count), _) => Some(Pages::Count(*count)),
+        (_, Some(interval)) => Some(Pages::Every(*interval)),
+        _ => None,
+    };
+    let output_path = |fp: &str, extension: &str|match sub_matches.get_one::<String>("out-dir") {
+        Some(dir) => in_dir(Path::new(dir), Path::new(fp), extension),
+        None => project.output_path(Path::new(fp), extension),
+    };
+    if sub_matches.contains_id("overlay") {
+        let mut docs = open_saga_docs(sub_matches, "overlay")?;
+        docs.iter_mut().for_each(|(fp, saga)|saga.read_images(fp));
+        let ((fp, first), (_, second)) = (&docs[0], &docs[1]);
+        let scene = first.overlay_scene(second, &options);
+        if term {
+            println!("{}", Terminal::detect().render(&scene));
+            return Ok(());
+        }
+        let svg = Svg { animate: options.animate }.render(&scene);
+        let fp_svg = output_path(fp, "overlay.svg");
+        save_output(&fp_svg, &svg)?;
+        if open {
+            open_in_viewer(&fp_svg)?;
+        }
+        return Ok(());
+    }
+    let mut written = BTreeSet::new();
+    for (fp,saga) in open_saga_docs(sub_matches, "FILE")?.iter_mut() {
+        saga.read_images(fp);
+        if term {
+            println!("{}\n{}", fp, Terminal::detect().render(&saga.scene(&options)));
+            continue;
+        }
+        if as_html {
+            let fp_html = output_path(fp, "html");
+            if !written.insert(fp_html.clone()) {
+                warn!("{}: overwriting {:?}, written from another file of the same name", fp, fp_html);
+            }
+            save_output(&fp_html, &html::export(saga, &options, &page_title(saga, fp, &options)))?;
+            if open {
+                open_in_viewer(&fp_html)?;
+            }
+            continue;
+        }
+        // Pages are numbered, as in file1.2.svg.
+        let drawn: Vec<(String, svg::Document)> = match pages {
+            Some(pages) => {
+                let scenes = saga.pages(&options, pages);
+                if scenes.len() == MAX_PAGES {
+                    warn!("{}: only drawing the first {} pages", fp, MAX_PAGES);
+                }
+                let svg = Svg { animate: options.animate };
+                scenes.iter()
+                    .enumerate()
+                    .map(|(i, scene)|(format!("{}.svg", i + 1), svg.render(scene)))
+                    .collect()
+            },
+            None => vec![("svg".to_string(), saga.draw(&options))],
+        };
+        for (page, (extension, svg)) in drawn.iter().enumerate() {
+            let fp_svg = output_path(fp, extension);
+            if !written.insert(fp_svg.clone()) {
+                warn!("{}: overwriting {:?}, written from another file of the same name", fp, fp_svg);
+            }
+            save_output(&fp_svg, svg)?;
+            // Just the first page, rather than a window for each.
+            if open && page == 0 {
+                open_in_viewer(&fp_svg)?;
+            }
+        }
     }
     Ok(())
 }
 
-/// Util function used by the arg_* class of functions.
-fn open_saga_docs<'a>(sub_matches: &'a ArgMatches, tag: &str) -> Result<Vec<(&'a str, SagaDoc)>, MainError> {
-    // TODO rewrite this such that the Err variant returns the error AND the file path that caused it.
-    Ok(sub_matches.get_many::<String>(tag)
-        .expect("Flying on a prayer.")
-        .map(|fp|(fp, open_file(fp)))
-        .map(|(fp,res)|res.map(|f|(fp,f)))  // Wrap fp inside the Result, so we can call try on it.
-        .try_collect::<Vec<_>>()?
-        .iter() // Re-iterate after collecting.
-        .map(|(fp,file)|(fp, serde_json::from_str::<SagaDoc>(file) ))
-        .map(|(fp,res)|res.map(|r|(fp.as_str(),r)))  // Wrap fp inside the Result, so we can call try on it.
-        .try_collect::<Vec<_>>()
-        .map_err(|e|MainError::NotASagaDoc(e))?)
+/// Title of the HTML page exported from `saga`: its name, or else the name of
+/// the file it came from.
+fn page_title(saga: &SagaDoc, fp: &str, options: &RenderOptions) -> String {
+    match saga.get_data().name_in(options.lang.as_deref()) {
+        Some(name) => name.to_string(),
+        None => Path::new(fp).file_stem().unwrap_or_default().to_string_lossy().to_string(),
+    }
 }
 
-/// Util function used by the arg_* class of functions.
-fn open_file(file_path: &str) -> Result<String, MainError> {
-    use std::fs::File;
-    use std::io::Read;
-    let mut file = File::open(file_path)
-        .map_err(|e|MainError::FileIO(e))?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .map_err(|e|MainError::FileIO(e))?;
-    Ok(contents)
+/// Writes `contents`, an SVG or HTML page, to `fp`, making the directory it
+/// goes in if need be.
+fn save_output(fp: &Path, contents: &impl std::fmt::Display) -> MainResult {
+    let write = || {
+        if let Some(dir) = fp.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(fp, contents.to_string())
+    };
+    write().map_err(|e|MainError::FileIO(e).in_file(&fp.to_string_lossy()))?;
+    info!("Wrote {:?} successfully.", fp);
+    Ok(())
+}
+
+fn arg_layout(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let project = Project::discover()?.unwrap_or_default();
+    let options = RenderOptions {
+        lang: sub_matches.get_one::<String>("lang").cloned(),
+        locale: sub_matches.get_one::<String>("locale").cloned().or(project.locale),
+        date_pattern: sub_matches.get_one::<String>("date-format").cloned(),
+        density: sub_matches.get_one::<usize>("density").copied(),
+        heat: sub_matches.get_one::<usize>("heat").copied(),
+        durations: sub_matches.get_flag("durations"),
+        ruler: sub_matches.get_flag("ruler"),
+        captions: sub_matches.get_flag("captions"),
+        annotate: sub_matches.get_one::<String>("annotate").cloned(),
+        grid: sub_matches.get_one::<Interval>("grid").copied(),
+        outside: sub_matches.get_one::<Outside>("outside").copied().unwrap_or_default(),
+        scheme: project.scheme,
+        ..RenderOptions::default()
+    };
+    let mut saga = SagaDoc::load(fp)?;
+    saga.read_images(fp);
+    let scene = saga.scene(&options);
+    let json = serde_json::to_string_pretty(&scene)
+        .map_err(MainError::SerializeFail)?;
+    match sub_matches.get_one::<String>("output") {
+        Some(output) => {
+            std::fs::write(output, json + "\n")
+                .map_err(|e|MainError::FileIO(e).in_file(output))?;
+            info!("Wrote {} successfully.", output);
+        },
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+/// Opens `path` in the platform's default program for its type, without
+/// waiting for it to be closed.
+fn open_in_viewer(path: &Path) -> MainResult {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        // The empty argument is the window title `start` would otherwise
+        // take the path for.
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(path)
+        .spawn()
+        .map(|_|())
+        .map_err(|e|MainError::Viewer(e).in_file(&path.to_string_lossy()))
 }
 
-fn saga_deserialize(input: &str) -> Result<SagaDoc, MainError> {
-    serde_json::from_str::<SagaDoc>(&input)
-        .map_err(|e|MainError::NotASagaDoc(e))
+fn arg_validate(sub_matches: &ArgMatches) -> MainResult {
+    let fix = sub_matches.get_flag("fix");
+    let mut unfixed = 0;
+    for fp in sub_matches.get_many::<String>("FILE")
+        .expect("Clap guarantees that this should be here.") {
+        let (mut saga, stamp) = SagaDoc::load_stamped(fp)?;
+        let report = validate::validate(saga.get_data_mut(), fix);
+        for issue in report.issues.iter() {
+            println!("{}: {}", fp, issue);
+        }
+        for warning in report.warnings.iter() {
+            warn!("{}: {}", fp, warning);
+        }
+        match (report.is_ok(), report.fixed) {
+            (true, _) => println!("{}: Ok", fp),
+            (false, true) => {
+                saga.save_unchanged(fp, stamp)?;
+                info!("{}: Fixed {} problem(s).", fp, report.issues.len());
+                commit(sub_matches, fp, &format!("validate: fix {} problem(s)", report.issues.len()))?;
+            },
+            (false, false) => unfixed += report.issues.len(),
+        }
+    }
+    match unfixed {
+        0 => Ok(()),
+        n => Err(MainError::Invalid(n)),
+    }
+}
+
+fn arg_fmt(sub_matches: &ArgMatches) -> MainResult {
+    let check = sub_matches.get_flag("check");
+    let mut changed = 0;
+    for fp in sub_matches.get_many::<String>("FILE")
+        .expect("Clap guarantees that this should be here.") {
+        let (saga, stamp) = SagaDoc::load_stamped(fp)?;
+        if check {
+            changed += report_check(fp, !saga.is_formatted(fp)?);
+            continue;
+        }
+        saga.save_pretty(fp, stamp)?;
+        info!("Formatted {}", fp);
+        commit(sub_matches, fp, "fmt")?;
+    }
+    checked(changed)
+}
+
+fn arg_merge(sub_matches: &ArgMatches) -> MainResult {
+    let [base, ours, theirs, out] = ["BASE", "OURS", "THEIRS", "OUT"].map(|tag|{
+        sub_matches.get_one::<String>(tag)
+            .expect("Clap guarantees that this should be here.")
+            .as_str()
+    });
+    let (saga, conflicts) = merge::merge(&SagaDoc::load(base)?, &SagaDoc::load(ours)?, &SagaDoc::load(theirs)?)?;
+    for conflict in conflicts.iter() {
+        println!("{}: {}", out, conflict);
+    }
+    saga.save(out)?;
+    match conflicts.len() {
+        0 => Ok(()),
+        n => Err(MainError::Conflicts(n)),
+    }
+}
+
+fn arg_sync(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let remote = sync::Remote {
+        url: sub_matches.get_one::<String>("remote")
+            .expect("Clap guarantees that this should be here.")
+            .to_string(),
+        s3_region: sub_matches.get_one::<String>("s3").cloned(),
+    };
+    let strategy = match sub_matches.get_flag("newest") {
+        true => sync::Strategy::Newest,
+        false => sync::Strategy::Merge,
+    };
+    match sync::sync(fp, &remote, strategy)? {
+        sync::Outcome::InSync => info!("{}: Already in sync", fp),
+        sync::Outcome::Uploaded => info!("{}: Uploaded to {}", fp, remote.url),
+        sync::Outcome::Downloaded => {
+            info!("{}: Downloaded from {}", fp, remote.url);
+            commit(sub_matches, fp, &format!("sync: download from {}", remote.url))?;
+        },
+        sync::Outcome::Merged(conflicts) => {
+            for conflict in conflicts.iter() {
+                println!("{}: {}", fp, conflict);
+            }
+            info!("{}: Merged with {}", fp, remote.url);
+            commit(sub_matches, fp, &format!("sync: merge with {}", remote.url))?;
+        },
+    }
+    Ok(())
+}
+
+fn arg_snapshot(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    if sub_matches.get_flag("list") {
+        for snapshot in snapshot::list(fp)?.iter() {
+            println!("{}", snapshot.describe());
+        }
+        return Ok(());
+    }
+    let label = sub_matches.get_one::<String>("label").map(|s|s.as_str());
+    let number = snapshot::take(fp, &SagaDoc::load(fp)?, label)?;
+    info!("{}: Took snapshot {}", fp, number);
+    Ok(())
+}
+
+fn arg_restore(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let name: &str = sub_matches.get_one::<String>("SNAPSHOT")
+        .expect("Clap guarantees that this should be here.");
+    let (mut saga, stamp) = SagaDoc::load_stamped(fp)?;
+    let restored = snapshot::find(fp, name)?;
+    let backup = snapshot::take(fp, &saga, Some(&format!("before restoring {}", restored.number)))?;
+    let message = format!("restore snapshot {}", restored.describe());
+    *saga.get_data_mut() = restored.data;
+    saga.save_unchanged(fp, stamp)?;
+    info!("{}: Restored snapshot {}, the previous events are in snapshot {}", fp, restored.number, backup);
+    commit(sub_matches, fp, &message)
+}
+
+fn arg_doctor(sub_matches: &ArgMatches) -> MainResult {
+    let fp: &str = sub_matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let output = match sub_matches.get_one::<String>("output") {
+        Some(output) => output.to_string(),
+        None => format!("{}.repaired.json", fp.strip_suffix(".json").unwrap_or(fp)),
+    };
+    let mut bytes = std::fs::read(fp)
+        .map_err(|e|MainError::FileIO(e).in_file(fp))?;
+    let encrypted = crypt::is_encrypted(&bytes);
+    if encrypted {
+        bytes = crypt::decrypt(&bytes, &crypt::passphrase(false)?)
+            .map_err(|e|MainError::from(e).in_file(fp))?;
+    }
+    let (saga, fixes) = doctor::repair(&String::from_utf8_lossy(&bytes))
+        .map_err(|e|e.in_file(fp))?;
+    if fixes.is_empty() {
+        println!("{}: Ok", fp);
+        return Ok(());
+    }
+    for fix in fixes.iter() {
+        println!("{}: {}", fp, fix);
+    }
+    // The copy of an encrypted document is encrypted too.
+    let mut contents = Vec::new();
+    saga.to_writer(&mut contents)?;
+    if encrypted {
+        contents = crypt::encrypt(&contents, &crypt::passphrase(false)?)?;
+    }
+    std::fs::write(&output, contents)
+        .map_err(|e|MainError::FileIO(e).in_file(&output))?;
+    info!("Wrote {}", output);
+    commit(sub_matches, &output, &format!("doctor: repair {}", fp))
 }
 
-fn saga_serialize(input: &SagaDoc) -> Result<String, MainError> {
-    serde_json::to_string(&input)
-        .map_err(|e|MainError::SerializeFail(e))
+fn arg_scheme(sub_matches: &ArgMatches) -> MainResult {
+    let (action, matches) = sub_matches.subcommand()
+        .expect("Clap guarantees that this should be here.");
+    let fp: &str = matches.get_one::<String>("FILE")
+        .expect("Clap guarantees that this should be here.");
+    let name = |id: &str|matches.get_one::<String>(id)
+        .map(|s|s.as_str())
+        .expect("Clap guarantees that this should be here.");
+    let (mut saga, stamp) = SagaDoc::load_stamped(fp)?;
+    let changed = match action {
+        "list" => {
+            for (name, colors) in saga.color_schemes().iter() {
+                let active = match saga.active_scheme() == Some(name.as_str()) {
+                    true => "*",
+                    false => " ",
+                };
+                let colors = colors.iter().map(|color|color.to_string()).collect::<Vec<String>>();
+                println!("{} {}: {}", active, name, colors.join(" "));
+            }
+            return Ok(());
+        },
+        "add" => {
+            let colors = matches.get_many::<String>("COLOR")
+                .expect("Clap guarantees that this should be here.")
+                .map(|color|color.parse::<Color>())
+                .collect::<Result<Colors, ColorParseError>>()
+                .map_err(MainError::BadColor)?;
+            saga.add_scheme(name("NAME"), colors)
+                .map(|_|format!("add {}", name("NAME")))
+        },
+        "rename" => saga.rename_scheme(name("OLD"), name("NEW"))
+            .map(|_|format!("rename {} to {}", name("OLD"), name("NEW"))),
+        "delete" => saga.delete_scheme(name("NAME"))
+            .map(|uses|{
+                if uses > 0 {
+                    warn!("{}: {} value(s) still use {}, and are drawn as if they had no color", fp, uses, name("NAME"));
+                }
+                format!("delete {}", name("NAME"))
+            }),
+        "use" => {
+            let chosen = matches.get_one::<String>("NAME").map(|s|s.as_str());
+            saga.set_active_scheme(chosen)
+                .map(|_|format!("use {}", chosen.unwrap_or("no scheme")))
+        },
+        _ => unreachable!("Clap should guarantee that this doesn't happen."),
+    };
+    let message = changed.map_err(|e|MainError::from(e).in_file(fp))?;
+    saga.save_unchanged(fp, stamp)?;
+    commit(matches, fp, &format!("scheme: {}", message))
+}
+
+fn arg_sed(sub_matches: &ArgMatches) -> MainResult {
+    let pattern: &str = sub_matches.get_one::<String>("PATTERN")
+        .expect("Clap guarantees that this should be here.");
+    let replacement: &str = sub_matches.get_one::<String>("REPLACEMENT")
+        .expect("Clap guarantees that this should be here.");
+    let message = format!("sed: '{}' → '{}'", pattern, replacement);
+    let pattern = regex::Regex::new(pattern)
+        .map_err(|e|MainError::CommandParse(pattern.to_string(), ParseErrorKind::NotARegex(e).at(0..pattern.len())))?;
+    let command = EvalCommand::Replace(Pattern(pattern), replacement.to_string());
+    let check = sub_matches.get_flag("check");
+    let mut changed = 0;
+    for fp in sub_matches.get_many::<String>("FILE")
+        .expect("Clap guarantees that this should be here.") {
+        let (mut saga, stamp) = SagaDoc::load_stamped(fp)?;
+        let before = check.then(||saga.clone());
+        command.eval_node(saga.get_data_mut())
+            .map_err(|e|MainError::from(e).in_file(fp))?;
+        if let Some(before) = before {
+            changed += report_check(fp, saga.differs(&before)?);
+            continue;
+        }
+        saga.save_unchanged(fp, stamp)?;
+        commit(sub_matches, fp, &message)?;
+    }
+    checked(changed)
+}
+
+fn arg_encrypt(sub_matches: &ArgMatches) -> MainResult {
+    for fp in sub_matches.get_many::<String>("FILE")
+        .expect("Clap guarantees that this should be here.") {
+        if crypt::is_encrypted_file(fp) {
+            warn!("{} is already encrypted, skipping.", fp);
+            continue;
+        }
+        // Make sure we only ever encrypt valid documents.
+        let contents = std::fs::read(fp)
+            .map_err(|e|MainError::FileIO(e).in_file(fp))?;
+        SagaDoc::from_reader(contents.as_slice()).map_err(|e|e.in_file(fp))?;
+        let blob = crypt::encrypt(&contents, &crypt::passphrase(true)?)?;
+        std::fs::write(fp, blob)
+            .map_err(|e|MainError::FileIO(e).in_file(fp))?;
+        info!("Encrypted {}", fp);
+        commit(sub_matches, fp, "encrypt")?;
+    }
+    Ok(())
+}
+
+fn arg_decrypt(sub_matches: &ArgMatches) -> MainResult {
+    for fp in sub_matches.get_many::<String>("FILE")
+        .expect("Clap guarantees that this should be here.") {
+        if !crypt::is_encrypted_file(fp) {
+            warn!("{} isn't encrypted, skipping.", fp);
+            continue;
+        }
+        // Decrypted by hand rather than through SagaDoc::load(), so that the
+        // file keeps its exact contents.
+        let blob = std::fs::read(fp)
+            .map_err(|e|MainError::FileIO(e).in_file(fp))?;
+        let contents = crypt::decrypt(&blob, &crypt::passphrase(false)?)
+            .map_err(|e|MainError::from(e).in_file(fp))?;
+        std::fs::write(fp, contents)
+            .map_err(|e|MainError::FileIO(e).in_file(fp))?;
+        info!("Decrypted {}", fp);
+        // Never committed, as that would keep the plaintext in git's history
+        // for good, whatever is done with the file afterwards.
+        if sub_matches.get_flag("commit") {
+            warn!("Not committing {}, to keep it out of git's history while decrypted.", fp);
+        }
+    }
+    Ok(())
 }
 
-fn write_to_file(dest: &str, contents: &str) -> MainResult {
-    std::fs::write(dest, contents)
-        .map_err(|e|MainError::FileIO(e))
+/// Commits `fp` to git after it was changed, if --commit was given or the
+/// config asks for it.
+fn commit(sub_matches: &ArgMatches, fp: &str, message: &str) -> MainResult {
+    if sub_matches.get_flag("commit") || Config::load()?.auto_commit {
+        git::commit(fp, message).map_err(|e|MainError::from(e).in_file(fp))?;
+    }
+    Ok(())
+}
+
+/// Prints whether `--check` found `fp` would change, counting it if so.
+fn report_check(fp: &str, changed: bool) -> usize {
+    match changed {
+        true => println!("{}: Would change", fp),
+        false => println!("{}: Ok", fp),
+    }
+    changed as usize
+}
+
+/// Fails if `--check` found any files would change.
+fn checked(changed: usize) -> MainResult {
+    match changed {
+        0 => Ok(()),
+        n => Err(MainError::WouldChange(n)),
+    }
+}
+
+/// Util function used by the arg_* class of functions.
+fn open_saga_docs<'a>(sub_matches: &'a ArgMatches, tag: &str) -> Result<Vec<(&'a str, SagaDoc)>, MainError> {
+    sub_matches.get_many::<String>(tag)
+        .expect("Flying on a prayer.")
+        .map(|fp|{
+            Ok((fp.as_str(), SagaDoc::load(fp)?))
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::build_arg_parser;
+    use super::{build_arg_parser, MainError, EXIT_CHECK, EXIT_FAILURE, EXIT_IO, EXIT_NOT_FOUND, EXIT_PARSE};
+    use super::events::{Event, Node};
 
     #[test]
     fn test_arg_parsing() {
@@ -303,18 +1823,131 @@ mod tests {
             vec!["saga", "print", "file1", "file2"],
             vec!["saga", "print", "file1", "file2", "file3"],
             vec!["saga", "cat", "file1", "dest"],
+            vec!["saga", "intersect", "plan", "reality", "dest"],
+            vec!["saga", "subtract", "plan", "reality", "dest"],
             vec!["saga", "cat", "file1", "file2", "dest"],
             vec!["saga", "cat", "file1", "file2", "file3", "dest"],
             vec!["saga", "render", "file1"],
             vec!["saga", "render", "file1", "file2"],
             vec!["saga", "render", "file1", "file2", "file3"],
+            vec!["saga", "render", "file1", "--lang", "fr"],
+            vec!["saga", "render", "file1", "--locale", "en-gb"],
+            vec!["saga", "render", "file1", "--date-format", "Q{q} {y}"],
+            vec!["saga", "layout", "file1", "--date-format", "{b} {y}"],
+            vec!["saga", "render", "file1", "file2", "--term"],
+            vec!["saga", "render", "file1", "--animate", "12.5"],
+            vec!["saga", "render", "--overlay", "planned", "actual"],
+            vec!["saga", "render", "file1", "--density", "24"],
+            vec!["saga", "render", "file1", "--heat", "12"],
+            vec!["saga", "render", "file1", "--durations"],
+            vec!["saga", "render", "file1", "file2", "--open"],
+            vec!["saga", "fmt", "file1", "file2"],
+            vec!["saga", "doctor", "file1"],
+            vec!["saga", "doctor", "file1", "-o", "fixed.json"],
+            vec!["saga", "merge", "base", "ours", "theirs", "out"],
+            vec!["saga", "snapshot", "file1"],
+            vec!["saga", "snapshot", "file1", "--label", "before the move"],
+            vec!["saga", "snapshot", "file1", "--list"],
+            vec!["saga", "restore", "file1", "3"],
+            vec!["saga", "sync", "file1", "--remote", "https://dav.example.com/file1.json"],
+            vec!["saga", "sync", "file1", "--remote", "https://s3.example.com/b/f", "--s3", "eu-west-1", "--newest"],
+            vec!["saga", "edit", "file1", "3:2", "name", "Moved", "house", "--commit"],
+            vec!["saga", "--commit", "sed", "a", "b", "file1"],
+            vec!["saga", "print", "file1", "--lang", "fr"],
+            vec!["saga", "print", "file1", "-v"],
+            vec!["saga", "print", "file1", "file2", "--chrono"],
+            vec!["saga", "count", "file1"],
+            vec!["saga", "stats", "file1", "file2", "--gaps", "5"],
+            vec!["saga", "gaps", "file1"],
+            vec!["saga", "drift", "planned", "actual", "--render"],
+            vec!["saga", "gaps", "file1", "2:1", "--min", "2w"],
+            vec!["saga", "count", "file1", "file2", "--by", "node"],
+            vec!["saga", "remind", "file1"],
+            vec!["saga", "remind", "file1", "file2", "--days", "30", "--ics", "--alarm", "60"],
             vec!["saga", "add", "file1", "path"],
             vec!["saga", "edit", "file1", "1:2:4", "line"],
+            vec!["saga", "edit", "file1", "1:2:4", "name", "Foo;", "+desc", "bar"],
+            vec!["saga", "grep", "war", "file1"],
+            vec!["saga", "grep", "war", "file1", "file2", "--status", "planned"],
+            vec!["saga", "grep", "war", "file1", "-C", "2"],
+            vec!["saga", "editor", "file1"],
+            vec!["saga", "validate", "file1", "file2"],
+            vec!["saga", "sed", "Ulm", "Elchingen", "file1", "file2"],
+            vec!["saga", "validate", "file1", "--fix"],
+            vec!["saga", "encrypt", "file1"],
+            vec!["saga", "layout", "file1", "-o", "file1.layout.json", "--heat", "12"],
+            vec!["saga", "scheme", "add", "file1", "warm", "#f80", "#c00"],
+            vec!["saga", "scheme", "use", "file1"],
+            vec!["saga", "scheme", "rename", "file1", "warm", "autumn"],
+            vec!["saga", "decrypt", "file1", "file2"],
+            vec!["saga", "view", "file1", "--no-pager", "--lang", "fr"],
+            vec!["saga", "selftest", "--events", "50", "--depth", "4"],
+            vec!["saga", "render", "file1", "--grid", "--outside", "badge"],
+            vec!["saga", "-q", "fmt", "file1"],
+            vec!["saga", "init"],
+            vec!["saga", "render", "file1", "--pages", "4"],
+            vec!["saga", "render", "file1", "--ruler", "--term"],
+            vec!["saga", "layout", "file1", "--captions"],
+            vec!["saga", "render", "file1", "--annotate"],
+            vec!["saga", "render", "file1", "--annotate", "treaty", "--captions"],
+            vec!["saga", "render", "file1", "--per", "10y", "--out-dir", "pages"],
+            vec!["saga", "render", "file1", "dir/file1", "--out-dir", "site/timelines"],
+            vec!["saga", "render", "file1", "--html", "--open"],
+            vec!["saga", "open", "file1"],
+            vec!["saga", "gui", "file1"],
+            vec!["saga", "dupes", "file1"],
+            vec!["saga", "log", "file1", "Calibrated the scope"],
+            vec!["saga", "when", "file1", "jena"],
+            vec!["saga", "add", "file1", "2:1", "--json", "-"],
+            vec!["saga", "dupes", "file1", "--merge"],
+            vec!["saga", "open", "file1", "--with", "term"],
+            vec!["saga", "open", "file1", "--with", "gui"],
+            vec!["saga", "init", "wars", "--locale", "fr", "--scheme", "warm", "--output", "out"],
+            vec!["saga", "print", "file1", "-v"],
+            vec!["saga", "edit", "file1", "2", "--also", "file2", "--also", "file3", "name", "Moved"],
+            vec!["saga", "fmt", "file1", "file2", "--check"],
+            vec!["saga", "cat", "@teams.txt", "file1", "dest"],
+            vec!["saga", "sed", "Ulm", "Elchingen", "file1", "--check"],
+            vec!["saga", "edit", "file1", "2", "--check", "name", "Moved"],
         ];
         for sentence in ok_cases.iter() {
             let parse = arg_parser.clone().try_get_matches_from(sentence);
             assert!(parse.is_ok(), "{:?}", sentence);
         }
     }
-}
 
+    #[test]
+    fn test_error_messages() {
+        let root = Node::new(Some("Wars".to_string()), vec![
+            Event::new("Jena", "14/10/1806".parse().unwrap()).into_value(),
+            Node::new(Some("Spain".to_string()), vec![]).into_value(),
+        ]);
+        let fail = |path: &[usize]|MainError::NodeNotFound(root.query_ref(path).err().unwrap());
+        let missing = fail(&[3, 2]).in_file("foo.json");
+        assert_eq!(missing.to_string(), "path 3:2 not found: the root (Wars) holds 2 value(s), not 3 in foo.json");
+        let missing = missing.in_file("bar.json");
+        assert!(missing.to_string().ends_with(" in foo.json"));
+        let hinted = missing.with_path_hint(&root, "3:2");
+        assert!(hinted.to_string().ends_with(" in foo.json"));
+        assert_eq!(hinted.hint(), Some("the nearest path that exists is / Wars, which holds:\n  1 <Event> Jena, [14/10/1806]\n  2 <Node> Spain"));
+        let hinted = fail(&[2, 1]).with_path_hint(&root, "2:x");
+        assert_eq!(hinted.hint(), Some("the nearest path that exists is 2 Spain, which is empty"));
+        assert_eq!(
+            MainError::TooDeep(60, 48).to_string(),
+            "nodes are nested 60 deep, past the limit of 48 (see max_depth in ~/.saga.json)",
+        );
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let root = Node::from_vec(vec![]);
+        let missing = MainError::NodeNotFound(root.query_ref(&[2]).err().unwrap());
+        // Told apart through the file and hint they're wrapped in.
+        assert_eq!(missing.in_file("foo.json").with_path_hint(&root, "2").exit_code(), EXIT_NOT_FOUND);
+        assert_eq!(MainError::FileIO(std::io::ErrorKind::NotFound.into()).exit_code(), EXIT_IO);
+        assert_eq!(MainError::BadPathParse("x".parse::<usize>().unwrap_err()).exit_code(), EXIT_PARSE);
+        assert_eq!(MainError::WouldChange(2).exit_code(), EXIT_CHECK);
+        assert_eq!(MainError::WouldChange(2).to_string(), "2 file(s) would be changed");
+        assert_eq!(MainError::Conflicts(1).exit_code(), EXIT_FAILURE);
+    }
+}
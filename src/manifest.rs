@@ -0,0 +1,114 @@
+use std::path::{Path, PathBuf};
+
+use super::MainError;
+use super::events::Node;
+
+/// One document listed in a manifest, and where to put it.
+#[derive(Debug, PartialEq)]
+pub struct Input {
+    pub path: PathBuf,
+    /// Added to the offset of the document's root node.
+    pub offset: Option<f64>,
+    /// Names the document's root node, in place of its own name.
+    pub label: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ManifestError {
+    /// The line, counting from 1, and the offset that isn't a number.
+    BadOffset(usize, String),
+    /// The line, counting from 1, and the setting that isn't `offset=` or `label=`.
+    UnknownSetting(usize, String),
+}
+
+/// Reads a manifest: one document per line, given by a path relative to
+/// `dir`, optionally followed by `offset=N` and `label=TEXT`, the label
+/// taking the rest of the line. Blank lines and those starting with `#` are
+/// skipped.
+pub fn parse(text: &str, dir: &Path) -> Result<Vec<Input>, ManifestError> {
+    let mut inputs = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (path, mut rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let mut input = Input { path: dir.join(path), offset: None, label: None };
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() { break; }
+            if let Some(label) = rest.strip_prefix("label=") {
+                input.label = Some(label.trim().to_string());
+                break;
+            }
+            let (setting, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let offset = setting.strip_prefix("offset=")
+                .ok_or_else(||ManifestError::UnknownSetting(i + 1, setting.to_string()))?;
+            input.offset = Some(offset.parse()
+                .map_err(|_|ManifestError::BadOffset(i + 1, offset.to_string()))?);
+            rest = after;
+        }
+        inputs.push(input);
+    }
+    Ok(inputs)
+}
+
+impl Input {
+    /// Moves and names `root`, the root node of the document read from self.
+    pub fn apply(&self, root: &mut Node) {
+        if let Some(offset) = self.offset {
+            root.set_offset(&(root.offset() + offset));
+        }
+        if let Some(label) = self.label.as_deref() {
+            root.set_name(Some(label));
+        }
+    }
+}
+
+impl std::fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ManifestError::BadOffset(line, offset) => write!(f, "line {}: offset '{}' isn't a number", line, offset),
+            ManifestError::UnknownSetting(line, setting) => write!(f, "line {}: unknown setting '{}', expected offset=N or label=TEXT", line, setting),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl From<ManifestError> for MainError {
+    fn from(err: ManifestError) -> Self {
+        MainError::Manifest(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{parse, Input, ManifestError};
+    use super::super::events::Node;
+
+    #[test]
+    fn test_manifest() {
+        let text = "\
+            # Teams, top to bottom.\n\
+            france.json\n\
+            \n\
+            allies/britain.json offset=1.5 label=Royal Navy\n\
+            /srv/austria.json label=Austria offset=2\n";
+        let inputs = parse(text, Path::new("wars")).unwrap();
+        assert_eq!(inputs, vec![
+            Input { path: PathBuf::from("wars/france.json"), offset: None, label: None },
+            Input { path: PathBuf::from("wars/allies/britain.json"), offset: Some(1.5), label: Some("Royal Navy".to_string()) },
+            // The label takes the rest of the line.
+            Input { path: PathBuf::from("/srv/austria.json"), offset: None, label: Some("Austria offset=2".to_string()) },
+        ]);
+        assert_eq!(parse("a.json offset=x", Path::new("")), Err(ManifestError::BadOffset(1, "x".to_string())));
+        assert_eq!(parse("\na.json size=2", Path::new("")), Err(ManifestError::UnknownSetting(2, "size=2".to_string())));
+        let mut root = Node::new(Some("Britain".to_string()), vec![]);
+        root.set_offset(&1.0);
+        inputs[1].apply(&mut root);
+        assert_eq!((root.name_in(None), root.offset()), (Some("Royal Navy"), 2.5));
+    }
+}
@@ -0,0 +1,209 @@
+use serde_json::{Map, Value as Json};
+
+use super::MainError;
+use super::saga::SagaDoc;
+use super::validate::path_string;
+
+/// Identifies a child across the three versions of a list: its type, name
+/// and dates, and how many identical ones come before it.
+type Key = (String, String, String, usize);
+
+/// Merges the changes made in `ours` and in `theirs` since `base`, matching
+/// events by name and dates and nodes by name. Where both sides changed the
+/// same thing, `ours` wins; the conflicts are returned along with the result.
+pub fn merge(base: &SagaDoc, ours: &SagaDoc, theirs: &SagaDoc) -> Result<(SagaDoc, Vec<String>), MainError> {
+    let to_json = |doc: &SagaDoc|serde_json::to_value(doc)
+        .map_err(MainError::SerializeFail);
+    let (base, ours, theirs) = (to_json(base)?, to_json(ours)?, to_json(theirs)?);
+    let mut conflicts = vec![];
+    let merged = merge_value(Some(&base), Some(&ours), Some(&theirs), &[], "", &mut conflicts);
+    let doc = serde_json::from_value(merged.unwrap_or(ours))
+        .map_err(MainError::NotASagaDoc)?;
+    Ok((doc, conflicts))
+}
+
+/// Merges a single value, `None` standing for a field left out.
+fn merge_value(
+    base: Option<&Json>,
+    ours: Option<&Json>,
+    theirs: Option<&Json>,
+    at: &[usize],
+    field: &str,
+    conflicts: &mut Vec<String>,
+) -> Option<Json> {
+    if ours == theirs || base == theirs {
+        return ours.cloned();
+    }
+    if base == ours {
+        return theirs.cloned();
+    }
+    match (ours, theirs) {
+        (Some(Json::Object(ours)), Some(Json::Object(theirs))) => {
+            let base = base.and_then(Json::as_object);
+            Some(Json::Object(merge_fields(base, ours, theirs, at, field, conflicts)))
+        },
+        (Some(Json::Array(ours)), Some(Json::Array(theirs))) if field == "children" => {
+            let base = base.and_then(Json::as_array).map(|base|base.as_slice()).unwrap_or(&[]);
+            Some(Json::Array(merge_children(base, ours, theirs, at, conflicts)))
+        },
+        _ => {
+            conflicts.push(format!("{}: '{}' changed on both sides, kept ours", location(at), field));
+            ours.or(theirs).cloned()
+        },
+    }
+}
+
+/// Merges two objects field by field. Fields are named by their dotted path
+/// from the event or node they belong to.
+fn merge_fields(
+    base: Option<&Map<String, Json>>,
+    ours: &Map<String, Json>,
+    theirs: &Map<String, Json>,
+    at: &[usize],
+    field: &str,
+    conflicts: &mut Vec<String>,
+) -> Map<String, Json> {
+    let keys = ours.keys().chain(theirs.keys().filter(|key|!ours.contains_key(*key)));
+    let mut merged = Map::new();
+    for key in keys {
+        let inner = match field.is_empty() {
+            true => key.clone(),
+            false => format!("{}.{}", field, key),
+        };
+        let base = base.and_then(|base|base.get(key));
+        let value = match key.as_str() {
+            "children" => merge_value(base, ours.get(key), theirs.get(key), at, key, conflicts),
+            _ => merge_value(base, ours.get(key), theirs.get(key), at, &inner, conflicts),
+        };
+        if let Some(value) = value {
+            merged.insert(key.clone(), value);
+        }
+    }
+    merged
+}
+
+/// Merges the children of a node. Children keep the order they have in
+/// `ours`, with those only `theirs` added placed after the child preceding
+/// them there.
+fn merge_children(base: &[Json], ours: &[Json], theirs: &[Json], at: &[usize], conflicts: &mut Vec<String>) -> Vec<Json> {
+    let (base, ours, theirs) = (keyed(base), keyed(ours), keyed(theirs));
+    // Settled first, so that conflicts are reported at their final paths.
+    let mut order: Vec<(&Key, Option<&Json>, Option<&Json>)> = vec![];
+    for (key, value) in ours.iter() {
+        let other = find(&theirs, key);
+        // Left alone by us and removed by them.
+        if other.is_none() && find(&base, key) == Some(*value) { continue; }
+        order.push((key, Some(*value), other));
+    }
+    for (i, (key, value)) in theirs.iter().enumerate() {
+        if find(&ours, key).is_some() || find(&base, key) == Some(*value) { continue; }
+        let index = theirs[..i].iter().rev()
+            .find_map(|(before, _)|order.iter().position(|(other, _, _)|*other == before))
+            .map(|j|j + 1)
+            .unwrap_or(0);
+        order.insert(index, (key, None, Some(*value)));
+    }
+    order.into_iter().enumerate().map(|(i, (key, value, other))|{
+        let child = [at, &[i + 1]].concat();
+        let old = find(&base, key);
+        match (value, other) {
+            (Some(value), Some(other)) => merge_value(old, Some(value), Some(other), &child, "", conflicts)
+                .unwrap_or_else(||value.clone()),
+            (value, other) => {
+                if old.is_some() {
+                    let (removed, changed) = match value.is_some() {
+                        true => ("theirs", "ours"),
+                        false => ("ours", "theirs"),
+                    };
+                    conflicts.push(format!(
+                        "{}: removed in {} but changed in {}, kept it",
+                        path_string(&child), removed, changed,
+                    ));
+                }
+                value.or(other).expect("Every child is on at least one side.").clone()
+            },
+        }
+    }).collect()
+}
+
+/// Pairs every child with its `Key`.
+fn keyed(list: &[Json]) -> Vec<(Key, &Json)> {
+    let mut keyed: Vec<(Key, &Json)> = vec![];
+    for value in list.iter() {
+        let field = |name: &str|value.get(name).and_then(Json::as_str).unwrap_or("").to_string();
+        let (kind, name, dates) = (field("type"), field("name"), field("datetime"));
+        let n = keyed.iter()
+            .filter(|((k, m, d, _), _)|(k, m, d) == (&kind, &name, &dates))
+            .count();
+        keyed.push(((kind, name, dates, n), value));
+    }
+    keyed
+}
+
+fn find<'a>(list: &[(Key, &'a Json)], key: &Key) -> Option<&'a Json> {
+    list.iter()
+        .find(|(other, _)|other == key)
+        .map(|(_, value)|*value)
+}
+
+fn location(at: &[usize]) -> String {
+    match at.is_empty() {
+        true => "document".to_string(),
+        false => path_string(at),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge;
+    use super::super::events::{QueryRef, Value};
+    use super::super::saga::SagaDoc;
+
+    fn doc(children: &str) -> SagaDoc {
+        let text = format!(r#"{{"x":800.0,"y":600.0,"padding":0.0,"data":{{"children":[{}]}}}}"#, children);
+        serde_json::from_str(&text).unwrap()
+    }
+
+    fn event(name: &str, dates: &str, extra: &str) -> String {
+        format!(r#"{{"type":"Event","name":"{}","datetime":"{}"{}}}"#, name, dates, extra)
+    }
+
+    #[test]
+    fn test_merge() {
+        let spain = |extra: &str|format!(
+            r#"{{"type":"Node","name":"Spain"{},"children":[{}]}}"#,
+            extra, event("Bailén", "1808", if extra.is_empty() { "" } else { r#","status":"done""# }),
+        );
+        let base = doc(&[event("Ulm", "1805", ""), event("Jena", "1806", ""), spain("")].join(","));
+        let ours = doc(&[
+            event("Ulm", "1805", r#","descriptions":["Mack surrenders."]"#),
+            event("Austerlitz", "1805", ""),
+            event("Jena", "1806", ""),
+            spain(r#","y_scale":2.0"#),
+        ].join(","));
+        let theirs = doc(&[
+            event("Ulm", "1805", r#","descriptions":["Mack capitulates."]"#),
+            event("Wagram", "1809", ""),
+            spain(r#","offset":1.0"#).replace("done", "cancelled"),
+        ].join(","));
+        let (merged, conflicts) = merge(&base, &ours, &theirs).unwrap();
+        let data = merged.get_data();
+        let names: Vec<&str> = data.children().iter()
+            .filter_map(|value|match value {
+                Value::Event(event) => Some(event.name()),
+                Value::Node(_) => None,
+            })
+            .collect();
+        // Jena was removed on their side, the others added on either side.
+        assert_eq!(names, vec!["Ulm", "Wagram", "Austerlitz"]);
+        assert!(matches!(data.query_ref(&[4]), Ok(QueryRef::Node(node)) if node.offset() == 1.0 && node.scale() == 2.0));
+        assert_eq!(conflicts, vec![
+            "1: 'descriptions' changed on both sides, kept ours".to_string(),
+            "4:1: 'status' changed on both sides, kept ours".to_string(),
+        ]);
+        // With nothing changed on their side, the result is just ours.
+        let (same, conflicts) = merge(&base, &ours, &base).unwrap();
+        assert!(conflicts.is_empty());
+        assert_eq!(serde_json::to_string(&same).unwrap(), serde_json::to_string(&ours).unwrap());
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    io::{Error as IoError, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use serde::{Serialize, Deserialize};
+
+use super::MainError;
+use super::locale::Locale;
+
+/// Directory marking the top of a project, made by `saga init`.
+pub const PROJECT_DIR: &str = ".saga";
+
+/// File in `PROJECT_DIR` holding the project's settings.
+pub const PROJECT_CONFIG: &str = "config.json";
+
+/// Directory rendered files go in, relative to the project, unless `init`
+/// is told otherwise.
+pub const DEFAULT_OUTPUT: &str = "renders";
+
+/// Defaults shared by every document in a project, picked up by subcommands
+/// run anywhere inside it, the way git finds its repository.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    /// Locale dates are formatted in, as if given with `--locale`.
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Color scheme to render with, for documents that have one by that name,
+    /// in place of their active scheme.
+    #[serde(default)]
+    pub scheme: Option<String>,
+    /// Directory rendered files are written to, relative to the project,
+    /// rather than next to their document.
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+    /// Directory holding `PROJECT_DIR`.
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum ProjectError {
+    NotAProject(PathBuf, serde_json::Error),
+    UnknownLocale(PathBuf, String),
+    /// `init` found a project there already.
+    Exists(PathBuf),
+    IoError(PathBuf, IoError),
+}
+
+impl Project {
+    /// Settings for a new project in `root`, before `init` writes them.
+    pub fn new(root: &Path) -> Project {
+        Project {
+            locale: Some(Locale::fallback().code.to_string()),
+            scheme: None,
+            output: Some(PathBuf::from(DEFAULT_OUTPUT)),
+            root: root.to_path_buf(),
+        }
+    }
+
+    /// Finds the project the working directory is in, if any.
+    pub fn discover() -> Result<Option<Project>, ProjectError> {
+        match std::env::current_dir() {
+            Ok(dir) => Project::find(&dir),
+            // Without a working directory there's no project to be in.
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Loads the project of the nearest directory, from `start` upwards,
+    /// holding `PROJECT_DIR`.
+    pub fn find(start: &Path) -> Result<Option<Project>, ProjectError> {
+        for dir in start.ancestors() {
+            let path = config_path(dir);
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => return Project::from_json(&contents, dir).map(Some),
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(ProjectError::IoError(path, e)),
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn from_json(contents: &str, root: &Path) -> Result<Project, ProjectError> {
+        let mut project: Project = serde_json::from_str(contents)
+            .map_err(|e|ProjectError::NotAProject(config_path(root), e))?;
+        if let Some(code) = project.locale.as_ref().filter(|code|Locale::find(code).is_none()) {
+            return Err(ProjectError::UnknownLocale(config_path(root), code.to_string()));
+        }
+        project.root = root.to_path_buf();
+        Ok(project)
+    }
+
+    /// Writes the project's settings into a new `PROJECT_DIR`, returning
+    /// the path of its config.
+    pub fn init(&self) -> Result<PathBuf, ProjectError> {
+        let path = config_path(&self.root);
+        if path.exists() {
+            return Err(ProjectError::Exists(path));
+        }
+        let json = serde_json::to_string_pretty(self)
+            .expect("Settings always serialize.");
+        std::fs::create_dir_all(self.root.join(PROJECT_DIR))
+            .and_then(|_|std::fs::write(&path, json + "\n"))
+            .map_err(|e|ProjectError::IoError(path.clone(), e))?;
+        Ok(path)
+    }
+
+    /// Where the file rendered from `document` with `extension` goes: in the
+    /// output directory if there is one, else next to the document.
+    pub fn output_path(&self, document: &Path, extension: &str) -> PathBuf {
+        match &self.output {
+            Some(output) => in_dir(&self.root.join(output), document, extension),
+            None => document.with_extension(extension),
+        }
+    }
+}
+
+/// Path in `dir` of the file with `extension` made from `document`.
+pub fn in_dir(dir: &Path, document: &Path, extension: &str) -> PathBuf {
+    let name = document.file_name().unwrap_or(document.as_os_str());
+    dir.join(name).with_extension(extension)
+}
+
+fn config_path(root: &Path) -> PathBuf {
+    root.join(PROJECT_DIR).join(PROJECT_CONFIG)
+}
+
+impl std::fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProjectError::NotAProject(path, e) => write!(f, "bad project config {}: {}", path.display(), e),
+            ProjectError::UnknownLocale(path, code) => write!(f, "unknown locale '{}' in {}, expected one of {}", code, path.display(), Locale::codes().join(", ")),
+            ProjectError::Exists(path) => write!(f, "there is a project here already, see {}", path.display()),
+            ProjectError::IoError(path, e) => write!(f, "couldn't access {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProjectError::NotAProject(_, e) => Some(e),
+            ProjectError::IoError(_, e) => Some(e),
+            ProjectError::UnknownLocale(..) | ProjectError::Exists(_) => None,
+        }
+    }
+}
+
+impl From<ProjectError> for MainError {
+    fn from(err: ProjectError) -> Self {
+        MainError::Project(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{in_dir, Project, ProjectError, DEFAULT_OUTPUT};
+
+    #[test]
+    fn test_project() {
+        let root = std::env::temp_dir().join(format!("saga-project-{}", std::process::id()));
+        let nested = root.join("wars").join("napoleonic");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(Project::find(&nested).unwrap(), None);
+        Project::new(&root).init().unwrap();
+        assert!(matches!(Project::new(&root).init(), Err(ProjectError::Exists(_))));
+        let project = Project::find(&nested).unwrap().unwrap();
+        assert_eq!(project.locale.as_deref(), Some("en"));
+        assert_eq!(project.output, Some(PathBuf::from(DEFAULT_OUTPUT)));
+        assert_eq!(
+            project.output_path(&nested.join("jena.json"), "svg"),
+            root.join(DEFAULT_OUTPUT).join("jena.svg"),
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+        let bare = Project::from_json("{}", Path::new("/tmp")).unwrap();
+        assert_eq!(bare.output_path(Path::new("a/jena.json"), "svg"), PathBuf::from("a/jena.svg"));
+        assert_eq!(in_dir(Path::new("site"), Path::new("a/jena.json"), "overlay.svg"), PathBuf::from("site/jena.overlay.svg"));
+        assert!(matches!(Project::from_json(r#"{"locale": "xx"}"#, Path::new("/tmp")), Err(ProjectError::UnknownLocale(..))));
+    }
+}
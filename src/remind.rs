@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use chrono::Duration;
+
+use super::events::{elapsed, to_stamp, Dt, Event, Node, Precision};
+use super::validate::path_string;
+
+/// An event coming up soon, and where it was found.
+pub struct Reminder<'a> {
+    pub file: &'a str,
+    pub path: Vec<usize>,
+    pub event: &'a Event,
+}
+
+/// Events in `node`, from the document at `file`, that start within `days`
+/// days after `now`, soonest first.
+pub fn upcoming<'a>(file: &'a str, node: &'a Node, now: Dt, days: i64) -> Vec<Reminder<'a>> {
+    let until = now + Duration::days(days);
+    let mut found: Vec<Reminder> = node.iter_events_with_paths()
+        .filter(|(_, event)|(now..=until).contains(event.dates().start()))
+        .map(|(path, event)|Reminder { file, path, event })
+        .collect();
+    found.sort_by_key(|reminder|*reminder.event.dates().start());
+    found
+}
+
+/// How `event` stands at `now`, like "starts in 12 days", "started 2 days
+/// ago, ends in 5 hours" or "ended 3 years ago". Imprecise dates count as
+/// the whole of their unit, so an event in "1806" ends with the year.
+pub fn when(event: &Event, now: Dt) -> String {
+    let dates = event.dates();
+    let (start, end) = dates.span();
+    let ago = |dt: &Dt|elapsed(dt, &now).unwrap_or_default();
+    let until = |dt: &Dt|elapsed(&now, dt).unwrap_or_default();
+    match end {
+        _ if now < start => format!("starts in {}", until(&start)),
+        _ if dates.is_ongoing() => format!("started {} ago, still ongoing", ago(&start)),
+        Some(end) if now < end => format!("started {} ago, ends in {}", ago(&start), until(&end)),
+        Some(end) => format!("ended {} ago", ago(&end)),
+        None => format!("was {} ago", ago(&start)),
+    }
+}
+
+/// One line per reminder, holding its start and name separated by a tab,
+/// for scripts to hand to something like notify-send.
+pub fn to_lines(reminders: &[Reminder]) -> String {
+    reminders.iter()
+        .map(|reminder|{
+            let dates = reminder.event.dates();
+            let format = match dates.start_precision() {
+                Precision::Minute => "%Y-%m-%d %H:%M",
+                _ => "%Y-%m-%d",
+            };
+            format!("{}\t{}\n", dates.start().format(format), reminder.event.name())
+        })
+        .collect()
+}
+
+/// An iCalendar file holding every reminder, each with an alarm going off
+/// `alarm` minutes before it starts. `stamp` is the current time in UTC.
+/// Times are left floating, as documents don't record a time zone.
+pub fn to_ics(reminders: &[Reminder], stamp: Dt, alarm: i64) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Saga//Reminders//EN".to_string(),
+    ];
+    for reminder in reminders.iter() {
+        let event = reminder.event;
+        let (start, end) = event.dates().span();
+        let time = |dt: &Dt|match event.dates().start_precision() {
+            Precision::Minute => format!(":{}", dt.format("%Y%m%dT%H%M%S")),
+            _ => format!(";VALUE=DATE:{}", dt.format("%Y%m%d")),
+        };
+        let file = Path::new(reminder.file).file_name()
+            .and_then(|name|name.to_str())
+            .unwrap_or(reminder.file);
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}-{}-{}@saga", file, path_string(&reminder.path).replace(':', "."), to_stamp(&start)));
+        lines.push(format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")));
+        lines.push(format!("DTSTART{}", time(&start)));
+        if let Some(end) = end {
+            lines.push(format!("DTEND{}", time(&end)));
+        }
+        lines.push(format!("SUMMARY:{}", escape(event.name())));
+        let descriptions = event.descriptions_in(None);
+        if !descriptions.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&descriptions.join("\n"))));
+        }
+        lines.push("BEGIN:VALARM".to_string());
+        lines.push("ACTION:DISPLAY".to_string());
+        lines.push(format!("DESCRIPTION:{}", escape(event.name())));
+        lines.push(format!("TRIGGER:-PT{}M", alarm));
+        lines.push("END:VALARM".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line|fold(line) + "\r\n").collect()
+}
+
+/// Escapes the characters with a meaning in iCalendar text values.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Splits `line` into lines of at most 75 bytes, as iCalendar requires,
+/// continuing each with a space.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fold, to_ics, to_lines, upcoming, when};
+    use super::super::events::{Dates, Event, Node};
+
+    #[test]
+    fn test_reminders() {
+        let mut dentist = Event::new("Dentist, again", "21/10/2026 09:30".parse().unwrap());
+        dentist.add_description("Bring the forms.");
+        let node = Node::from_vec(vec![
+            Event::new("Holiday", "25/10/2026 - 1/11/2026".parse().unwrap()).into_value(),
+            dentist.into_value(),
+            Event::new("Past", "10/10/2026".parse().unwrap()).into_value(),
+            Event::new("Too far", "1/12/2026".parse().unwrap()).into_value(),
+        ]);
+        let now = *"16/10/2026 12:00".parse::<Dates>().unwrap().start();
+        let reminders = upcoming("plans/life.json", &node, now, 14);
+        assert_eq!(reminders.iter().map(|r|r.path.clone()).collect::<Vec<_>>(), vec![vec![2], vec![1]]);
+        assert_eq!(to_lines(&reminders), "2026-10-21 09:30\tDentist, again\n2026-10-25\tHoliday\n");
+        let ics = to_ics(&reminders, now, 30);
+        for expected in [
+            "UID:life.json-2-",
+            "DTSTART:20261021T093000\r\n",
+            "SUMMARY:Dentist\\, again\r\n",
+            "DESCRIPTION:Bring the forms.\r\n",
+            "TRIGGER:-PT30M\r\n",
+            "DTSTART;VALUE=DATE:20261025\r\nDTEND;VALUE=DATE:20261102\r\n",
+        ] {
+            assert!(ics.contains(expected), "missing: {}", expected);
+        }
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n") && ics.ends_with("END:VCALENDAR\r\n"));
+        let long = "SUMMARY:".to_string() + &"é".repeat(40);
+        assert!(fold(&long).split("\r\n").all(|line|line.len() <= 75));
+        assert_eq!(fold(&long).replace("\r\n ", ""), long);
+    }
+
+    #[test]
+    fn test_when() {
+        let now = *"16/10/2026 12:00".parse::<Dates>().unwrap().start();
+        let when_at = |dates: &str|when(&Event::new("Event", dates.parse().unwrap()), now);
+        assert_eq!(when_at("28/10/2026 12:00"), "starts in 12 days");
+        assert_eq!(when_at("14/10/2026 12:00 - 16/10/2026 17:00"), "started 2 days ago, ends in 5 hours");
+        assert_eq!(when_at("1/1/2026 - now"), "started 9 months 15 days ago, still ongoing");
+        assert_eq!(when_at("1806"), "ended 219 years 9 months ago");
+        assert_eq!(when_at("16/10/2023 11:00"), "was 3 years ago");
+        // Within the day it was given to, so not over yet.
+        assert_eq!(when_at("16/10/2026"), "started 12 hours ago, ends in 12 hours");
+    }
+}
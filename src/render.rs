@@ -0,0 +1,1074 @@
+use svg::{
+    Document, Node as SvgNode,
+    node::element::{path::Data,Animate,Definitions,Line as SvgLine,Path as SvgPath,Pattern,Text as SvgText,Title,SVG}
+};
+
+use std::io::IsTerminal;
+
+use serde::{Serialize, Deserialize};
+
+use super::events::Status;
+
+/// Columns `Terminal` keeps for event titles, right of the bars.
+const TITLE_WIDTH: usize = 30;
+
+/// Block characters of increasing height, for histograms in the terminal.
+const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Fill and outline of events, for each document drawn together.
+const LAYER_COLORS: [(&str, &str); 2] = [("#C3B2A4", "#2e3d50"), ("#A4C3B8", "#502e3d")];
+
+/// One thing to draw, in the document's pixel space.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum Shape {
+    /// A box for spans, or a vertical bar for single points in time.
+    Event {
+        x: f64,
+        end: Option<f64>,
+        y: f64,
+        height: f64,
+        status: Option<Status>,
+        /// Name and dates, shown on hover where the backend can.
+        title: String,
+        /// Which of the documents drawn together the event is from, 0 when
+        /// there is only one. Each gets its own colors.
+        layer: usize,
+        /// Color chosen in the document, like "#ff8800", used instead of
+        /// the layer's.
+        fill: Option<String>,
+    },
+    /// A node's timeline.
+    Line { start: f64, end: f64, y: f64 },
+    /// A tick mark on a timeline.
+    Tick { x: f64, y: f64 },
+    /// A faint line from the top of the document to the bottom.
+    Gridline { x: f64 },
+    /// Flags the event drawn at `x`, on the row starting at `y`, as falling
+    /// outside its node's line.
+    Badge { x: f64, y: f64 },
+    /// Marks the event on the row starting at `y` as carrying on past the
+    /// edge of the page at `x`, onto the next page if `later`, else from
+    /// the one before.
+    Continues { x: f64, y: f64, later: bool },
+    /// Text centered on `x`.
+    Label { x: f64, y: f64, text: String },
+    /// An event's name, `size` high and centered on `y`, placed against `x`
+    /// as `anchor` says.
+    Caption { x: f64, y: f64, size: f64, anchor: Anchor, text: String },
+    /// Joins an event at `x`, `y` to its caption or annotation, placed
+    /// beside it.
+    Leader { x: f64, y: f64, to_x: f64, to_y: f64 },
+    /// A box with its top left corner at `x`, `y` holding `lines` of text
+    /// `size` high, describing an event.
+    Annotation { x: f64, y: f64, width: f64, height: f64, size: f64, lines: Vec<String> },
+    /// A scale bar from `x`, `length` long, showing how far apart the
+    /// stretch of time `text` puts events.
+    Ruler { x: f64, y: f64, length: f64, text: String },
+    /// One bar of a histogram, rising `height` from `y`, standing for
+    /// `count` events.
+    Bar { x: f64, end: f64, y: f64, height: f64, count: usize },
+    /// Part of a row's background, tinted by `heat`, from 0 for none to 1
+    /// for the most.
+    Band { x: f64, end: f64, y: f64, height: f64, heat: f64 },
+    /// An SVG fragment, such as a flag, scaled to fit a square `size` wide
+    /// with its top left corner at `x`, `y`.
+    Image { x: f64, y: f64, size: f64, svg: String },
+}
+
+/// Which part of a text lines up with its position.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+/// Everything `SagaDoc::scene` laid out, independent of how it gets drawn.
+/// Serializes to JSON for drawing it elsewhere, e.g. with `saga layout`.
+#[derive(Debug, Default, Serialize)]
+pub struct Scene {
+    pub width: f64,
+    pub height: f64,
+    pub style: Style,
+    pub shapes: Vec<Shape>,
+}
+
+/// How the boxes of events are drawn, chosen per document.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    /// Width of the outline of events, in pixels.
+    pub stroke_width: f64,
+    /// How far the corners of spans are rounded, in pixels.
+    pub corner_radius: f64,
+    /// Opacity of the inside of events, from 0 for none to 1 for solid.
+    pub opacity: f64,
+    /// How single points in time are drawn.
+    #[serde(skip_serializing_if = "Mark::is_default")]
+    pub points: Mark<PointMarker>,
+    /// How spans of time are drawn.
+    #[serde(skip_serializing_if = "Mark::is_default")]
+    pub spans: Mark<SpanMarker>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style { stroke_width: 2.0, corner_radius: 0.0, opacity: 1.0, points: Mark::default(), spans: Mark::default() }
+    }
+}
+
+impl Style {
+    pub fn is_default(&self) -> bool {
+        *self == Style::default()
+    }
+}
+
+/// How one kind of event, points or spans, is drawn.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mark<M> {
+    pub marker: M,
+    /// Share of its row the event is drawn in, from 0 to 1, centred in it.
+    pub height: f64,
+    /// Where the event's caption goes.
+    pub label: Label,
+}
+
+impl<M: Default> Default for Mark<M> {
+    fn default() -> Self {
+        Mark { marker: M::default(), height: 1.0, label: Label::default() }
+    }
+}
+
+impl<M: Default + PartialEq> Mark<M> {
+    pub fn is_default(&self) -> bool {
+        *self == Mark::default()
+    }
+
+    /// Top and height of the event drawn in the row from `y`, `height` high.
+    pub fn within(&self, y: f64, height: f64) -> (f64, f64) {
+        let drawn = height * self.height.clamp(0.0, 1.0);
+        (y + (height - drawn) / 2.0, drawn)
+    }
+}
+
+/// What single points in time are drawn as.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PointMarker {
+    /// A vertical bar.
+    #[default]
+    Bar,
+    Dot,
+    Diamond,
+}
+
+/// What spans of time are drawn as.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpanMarker {
+    #[default]
+    Box,
+    /// A line through the middle of the row.
+    Bar,
+}
+
+/// Where captions are put, relative to their event.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Label {
+    /// Inside spans wide enough for it, else beside the event.
+    #[default]
+    Auto,
+    Above,
+    Below,
+    /// Beside the event on a leader line, even when it would fit inside.
+    Beside,
+}
+
+/// A backend that turns a `Scene` into some output, e.g. an SVG document.
+pub trait Renderer {
+    type Output;
+
+    fn render(&self, scene: &Scene) -> Self::Output;
+}
+
+/// Draws scenes as SVG documents.
+#[derive(Debug, Default)]
+pub struct Svg {
+    /// Seconds it takes a cursor to sweep across the timeline, revealing
+    /// each event as it passes. The drawing is static when unset.
+    pub animate: Option<f64>,
+}
+
+impl Svg {
+    /// Fades a shape in once the cursor reaches `x`.
+    fn reveal(&self, x: f64, scene: &Scene) -> Option<Animate> {
+        self.animate.map(|seconds|Animate::new()
+            .set("attributeName", "opacity")
+            .set("from", 0)
+            .set("to", 1)
+            .set("begin", format!("{:.2}s", x / scene.width * seconds))
+            .set("dur", "0.3s")
+            .set("fill", "freeze"))
+    }
+
+    /// A vertical line moving from one end of the timeline to the other.
+    fn cursor(seconds: f64, scene: &Scene) -> SvgLine {
+        let slide = |attribute: &str|Animate::new()
+            .set("attributeName", attribute)
+            .set("from", 0)
+            .set("to", scene.width)
+            .set("dur", format!("{}s", seconds))
+            .set("fill", "freeze");
+        SvgLine::new()
+            .set("x1", 0).set("y1", 0)
+            .set("x2", 0).set("y2", scene.height)
+            .set("stroke", "#2e3d50")
+            .set("stroke-width", 1)
+            .add(slide("x1"))
+            .add(slide("x2"))
+    }
+}
+
+impl Renderer for Svg {
+    type Output = Document;
+
+    fn render(&self, scene: &Scene) -> Document {
+        if scene.shapes.is_empty() { return Document::new(); }
+        let mut document = Document::new()
+            .set("viewbox", (0,0,scene.width,scene.height))
+            .set("width",  format!("{}px", scene.width))
+            .set("height", format!("{}px", scene.height))
+            .set("background-color", "#ff3400");
+        document.append(Definitions::new().add(hatch_pattern()));
+        for shape in scene.shapes.iter() {
+            match shape {
+                Shape::Event { x, end, y, height, status, title, layer, fill: chosen } => {
+                    let (fill, stroke) = LAYER_COLORS[layer % LAYER_COLORS.len()];
+                    let (x, y, height) = (*x, *y, *height);
+                    let style = &scene.style;
+                    let middle = y + height / 2.0;
+                    let data = match end {
+                        // If span of time...
+                        Some(end) => match style.spans.marker {
+                            SpanMarker::Box => rounded_box(x, *end, y, height, style.corner_radius),
+                            SpanMarker::Bar => Data::new()
+                                .move_to((x,    middle))
+                                .line_to((*end, middle)),
+                        },
+                        // If single point in time...
+                        None => match style.points.marker {
+                            PointMarker::Bar => Data::new()
+                                .move_to((x, y))
+                                .line_to((x, y + height))
+                                .close(),
+                            PointMarker::Dot => dot(x, middle, height / 2.0),
+                            PointMarker::Diamond => Data::new()
+                                .move_to((x,                y))
+                                .line_to((x + height / 2.0, middle))
+                                .line_to((x,                y + height))
+                                .line_to((x - height / 2.0, middle))
+                                .close(),
+                        },
+                    };
+                    let fill = match status {
+                        Some(Status::Planned) => format!("url(#{})", HATCH_ID),
+                        _ => chosen.as_deref().unwrap_or(fill).to_string(),
+                    };
+                    let mut path = SvgPath::new()
+                        .set("fill", fill)
+                        .set("fill-opacity", style.opacity)
+                        .set("stroke", stroke)
+                        .set("stroke-width", style.stroke_width)
+                        .set("d", data)
+                        .add(Title::new().add(text_node(title)));
+                    if let Some(animate) = self.reveal(x, scene) {
+                        path = path.set("opacity", 0).add(animate);
+                    }
+                    document.append(path);
+                    // Strike through cancelled events, points across their marker.
+                    if let Some(Status::Cancelled) = status {
+                        let (left, right) = match end {
+                            Some(end) => (x, *end),
+                            None => (x - height / 2.0, x + height / 2.0),
+                        };
+                        let strike = Data::new()
+                            .move_to((left,  y))
+                            .line_to((right, y + height))
+                            .move_to((left,  y + height))
+                            .line_to((right, y));
+                        let mut path = SvgPath::new()
+                            .set("stroke", stroke)
+                            .set("stroke-width", style.stroke_width)
+                            .set("d", strike);
+                        if let Some(animate) = self.reveal(x, scene) {
+                            path = path.set("opacity", 0).add(animate);
+                        }
+                        document.append(path);
+                    }
+                },
+                Shape::Line { start, end, y } => {
+                    let data = Data::new()
+                        .move_to((*start, *y))
+                        .line_to((*end,   *y))
+                        .close();
+                    document.append(SvgPath::new()
+                        .set("fill", "#C3B2A4")
+                        .set("stroke", "#000000")
+                        .set("stroke-width",5)
+                        .set("d", data));
+                },
+                Shape::Tick { x, y } => {
+                    document.append(SvgPath::new()
+                        .set("stroke", "#000000")
+                        .set("stroke-width", 2)
+                        .set("d", Data::new().move_to((*x, y - 8.0)).line_to((*x, y + 8.0))));
+                },
+                Shape::Gridline { x } => {
+                    document.append(SvgLine::new()
+                        .set("x1", *x).set("y1", 0)
+                        .set("x2", *x).set("y2", scene.height)
+                        .set("stroke", "#2e3d50")
+                        .set("stroke-opacity", 0.2)
+                        .set("stroke-width", 1));
+                },
+                Shape::Badge { x, y } => {
+                    document.append(SvgText::new()
+                        .set("x", *x)
+                        .set("y", y - 2.0)
+                        .set("text-anchor", "middle")
+                        .set("font-size", 12)
+                        .set("font-weight", "bold")
+                        .set("fill", "#d9482b")
+                        .add(text_node("!"))
+                        .add(Title::new().add(text_node("Outside its node's line"))));
+                },
+                Shape::Ruler { x, y, length, text } => {
+                    let end = x + length;
+                    let data = Data::new()
+                        .move_to((*x, y - 4.0))
+                        .line_to((*x, *y))
+                        .line_to((end, *y))
+                        .line_to((end, y - 4.0));
+                    document.append(SvgPath::new()
+                        .set("fill", "none")
+                        .set("stroke", "#000000")
+                        .set("stroke-width", 1)
+                        .set("d", data));
+                    document.append(SvgText::new()
+                        .set("x", x + length / 2.0)
+                        .set("y", y - 6.0)
+                        .set("text-anchor", "middle")
+                        .set("font-size", 12)
+                        .add(text_node(text)));
+                },
+                Shape::Caption { x, y, size, anchor, text } => {
+                    let anchor = match anchor {
+                        Anchor::Start => "start",
+                        Anchor::Middle => "middle",
+                        Anchor::End => "end",
+                    };
+                    let mut caption = SvgText::new()
+                        .set("x", *x)
+                        .set("y", *y)
+                        .set("text-anchor", anchor)
+                        .set("dominant-baseline", "central")
+                        .set("font-size", format!("{:.1}", size))
+                        .add(text_node(text));
+                    if let Some(animate) = self.reveal(*x, scene) {
+                        caption = caption.set("opacity", 0).add(animate);
+                    }
+                    document.append(caption);
+                },
+                Shape::Annotation { x, y, width, height, size, lines } => {
+                    document.append(SvgPath::new()
+                        .set("fill", "#fffdf5")
+                        .set("stroke", "#555555")
+                        .set("stroke-width", 1)
+                        .set("d", rounded_box(*x, x + width, *y, *height, size / 2.0)));
+                    for (i, line) in lines.iter().enumerate() {
+                        document.append(SvgText::new()
+                            .set("x", x + size / 2.0)
+                            .set("y", y + (i as f64 + 1.0) * LINE_HEIGHT * size)
+                            .set("font-size", format!("{:.1}", size))
+                            .add(text_node(line)));
+                    }
+                },
+                Shape::Leader { x, y, to_x, to_y } => {
+                    document.append(SvgPath::new()
+                        .set("fill", "none")
+                        .set("stroke", "#555555")
+                        .set("stroke-width", 1)
+                        .set("d", Data::new().move_to((*x, *y)).line_to((*to_x, *to_y))));
+                },
+                Shape::Continues { x, y, later } => {
+                    let (anchor, arrow, title) = match later {
+                        true => ("end", "▶", "Continues on the next page"),
+                        false => ("start", "◀", "Continued from the previous page"),
+                    };
+                    document.append(SvgText::new()
+                        .set("x", *x)
+                        .set("y", y - 2.0)
+                        .set("text-anchor", anchor)
+                        .set("font-size", 12)
+                        .set("fill", "#555555")
+                        .add(text_node(arrow))
+                        .add(Title::new().add(text_node(title))));
+                },
+                Shape::Bar { x, end, y, height, count } => {
+                    let data = Data::new()
+                        .move_to((*x,   *y))
+                        .line_to((*end, *y))
+                        .line_to((*end, y - height))
+                        .line_to((*x,   y - height))
+                        .close();
+                    document.append(SvgPath::new()
+                        .set("fill", "#2e3d50")
+                        .set("fill-opacity", 0.4)
+                        .set("d", data)
+                        .add(Title::new().add(text_node(&format!("{} event(s)", count)))));
+                },
+                Shape::Band { x, end, y, height, heat } => {
+                    let data = Data::new()
+                        .move_to((*x,   *y))
+                        .line_to((*end, *y))
+                        .line_to((*end, y + height))
+                        .line_to((*x,   y + height))
+                        .close();
+                    document.append(SvgPath::new()
+                        .set("fill", "#d9482b")
+                        .set("fill-opacity", format!("{:.2}", 0.6 * heat))
+                        .set("d", data));
+                },
+                Shape::Image { x, y, size, svg } => {
+                    // Text nodes aren't escaped, so the fragment goes in as is.
+                    document.append(SVG::new()
+                        .set("x", *x)
+                        .set("y", *y)
+                        .set("width", *size)
+                        .set("height", *size)
+                        .add(svg::node::Text::new(svg.as_str())));
+                },
+                Shape::Label { x, y, text } => {
+                    document.append(SvgText::new()
+                        .set("x", *x)
+                        .set("y", *y)
+                        .set("text-anchor", "middle")
+                        .set("font-size", 12)
+                        .add(text_node(text)));
+                },
+            }
+        }
+        if let Some(seconds) = self.animate {
+            document.append(Svg::cursor(seconds, scene));
+        }
+        document.set("saga_doc", "TODO: Add the deserialized json here.")
+    }
+}
+
+/// Draws scenes with Unicode block characters, one event per row, for
+/// checking a timeline where SVG can't be viewed.
+pub struct Terminal {
+    /// Columns available, titles included.
+    pub width: usize,
+    /// Colors events by status with ANSI escapes.
+    pub color: bool,
+}
+
+impl Terminal {
+    /// Fits the terminal the program runs in, coloring only if stdout is one.
+    pub fn detect() -> Terminal {
+        let width = terminal_size::terminal_size()
+            .map(|(width, _)|width.0 as usize)
+            .unwrap_or(80);
+        Terminal { width, color: std::io::stdout().is_terminal() }
+    }
+
+    fn paint(&self, text: &str, status: &Option<Status>) -> String {
+        match self.color {
+            true => ansi(text, status_code(status)),
+            false => text.to_string(),
+        }
+    }
+}
+
+/// ANSI code of the color events with `status` are drawn in, in terminals.
+pub fn status_code(status: &Option<Status>) -> &'static str {
+    match status {
+        Some(Status::Planned) => "33",
+        Some(Status::InProgress) => "36",
+        Some(Status::Done) => "32",
+        Some(Status::Cancelled) => "2;31",
+        None => "34",
+    }
+}
+
+/// Wraps `text` in the ANSI escapes for `code`, e.g. "32" for green.
+pub fn ansi(text: &str, code: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+impl Renderer for Terminal {
+    type Output = String;
+
+    fn render(&self, scene: &Scene) -> String {
+        let bars = self.width.saturating_sub(TITLE_WIDTH + 1).max(10);
+        let column = |x: f64|{
+            let x = (x / scene.width * (bars - 1) as f64).round();
+            x.clamp(0.0, (bars - 1) as f64) as usize
+        };
+        let mut rows: Vec<String> = vec![];
+        // Histogram bars share one row, scaled to the tallest.
+        let tallest = scene.shapes.iter()
+            .filter_map(|shape|match shape {
+                Shape::Bar { height, .. } => Some(*height),
+                _ => None,
+            })
+            .fold(0.0, f64::max);
+        let mut density: Option<Vec<char>> = None;
+        let mut ruler: Option<String> = None;
+        // The timeline being drawn, and the row of labels under it.
+        let mut axis: Option<(Vec<char>, Vec<char>)> = None;
+        let flush = |axis: &mut Option<(Vec<char>, Vec<char>)>, rows: &mut Vec<String>|{
+            if let Some((line, labels)) = axis.take() {
+                for row in [line, labels] {
+                    rows.push(row.into_iter().collect::<String>().trim_end().to_string());
+                }
+            }
+        };
+        for shape in scene.shapes.iter() {
+            match shape {
+                Shape::Event { x, end, status, title, layer, .. } => {
+                    let start = column(*x);
+                    // Overlaid documents are told apart by shading.
+                    let block = match layer {
+                        0 => "█",
+                        _ => "▒",
+                    };
+                    let bar = match end {
+                        Some(end) => block.repeat(column(*end).max(start) - start + 1),
+                        None => "│".to_string(),
+                    };
+                    let used = start + bar.chars().count();
+                    let title = title.chars().take(TITLE_WIDTH).collect::<String>();
+                    rows.push(format!(
+                        "{}{}{} {}",
+                        " ".repeat(start), self.paint(&bar, status), " ".repeat(bars - used), title,
+                    ));
+                },
+                Shape::Line { start, end, .. } => {
+                    flush(&mut axis, &mut rows);
+                    let mut line = vec![' '; bars];
+                    line[column(*start)..=column(*end)].iter_mut().for_each(|c|*c = '─');
+                    axis = Some((line, vec![' '; bars]));
+                },
+                Shape::Tick { x, .. } => {
+                    if let Some((line, _)) = axis.as_mut() { line[column(*x)] = '┼'; }
+                },
+                Shape::Bar { x, end, height, .. } => {
+                    let row = density.get_or_insert_with(||vec![' '; bars]);
+                    let level = LEVELS[((height / tallest) * (LEVELS.len() - 1) as f64).round() as usize];
+                    let last = column(*end).saturating_sub(1).max(column(*x));
+                    row[column(*x)..=last].iter_mut().for_each(|c|*c = level.max(*c));
+                },
+                // Backgrounds don't fit between the rows of text.
+                Shape::Band { .. } | Shape::Gridline { .. } => {},
+                // Every row ends with its event's title already.
+                Shape::Caption { .. } | Shape::Leader { .. } => {},
+                // Descriptions are left to print and view.
+                Shape::Annotation { .. } => {},
+                // Pictures don't fit in text.
+                Shape::Image { .. } => {},
+                // Follows the event it flags, which ends the last row.
+                Shape::Badge { .. } => {
+                    if let Some(row) = rows.last_mut() { row.push_str(" !"); }
+                },
+                // Drawn below everything else, at the terminal's own scale.
+                Shape::Ruler { x, length, text, .. } => {
+                    let (start, end) = (column(*x), column(x + length));
+                    let bar = format!("├{}┤", "─".repeat(end.saturating_sub(start + 1)));
+                    ruler = Some(format!("{}{} {}", " ".repeat(start), bar, text));
+                },
+                Shape::Continues { later, .. } => {
+                    let arrow = match later {
+                        true => " ▶",
+                        false => " ◀",
+                    };
+                    if let Some(row) = rows.last_mut() { row.push_str(arrow); }
+                },
+                Shape::Label { x, text, .. } => {
+                    if let Some((_, labels)) = axis.as_mut() {
+                        // Centered under the tick where it fits, and dropped if it would overlap another.
+                        let text: Vec<char> = text.chars().collect();
+                        let start = column(*x).saturating_sub(text.len() / 2).min(bars.saturating_sub(text.len()));
+                        let end = (start + text.len()).min(bars);
+                        let free = labels[start.saturating_sub(1)..(end + 1).min(bars)].iter().all(|c|*c == ' ');
+                        if free {
+                            labels[start..end].copy_from_slice(&text[..end - start]);
+                        }
+                    }
+                },
+            }
+        }
+        flush(&mut axis, &mut rows);
+        if let Some(row) = density {
+            rows.push(row.into_iter().collect::<String>().trim_end().to_string());
+        }
+        rows.extend(ruler);
+        rows.join("\n")
+    }
+}
+
+/// Roughly how wide `text` is in a sans-serif font `size` high, from the
+/// typical widths of a few kinds of character.
+pub fn text_width(text: &str, size: f64) -> f64 {
+    text.chars().map(|c|match c {
+        'i' | 'j' | 'l' | 'I' | '.' | ',' | ':' | ';' | '\'' | '!' | '|' => 0.28,
+        ' ' | 'f' | 'r' | 't' | '(' | ')' | '-' => 0.35,
+        'm' | 'w' | 'M' | 'W' | '@' => 0.85,
+        c if c.is_uppercase() => 0.68,
+        // CJK and the other scripts drawn a full em wide.
+        c if c >= '\u{2E80}' => 1.0,
+        _ => 0.55,
+    }).sum::<f64>() * size
+}
+
+/// Distance between the baselines of lines of text, relative to their size.
+pub const LINE_HEIGHT: f64 = 1.3;
+
+/// Splits `text` into lines no wider than `width` in a font `size` high,
+/// breaking between words. Words too wide for a line get one to themselves.
+pub fn wrap(text: &str, width: f64, size: f64) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let longer = match line.is_empty() {
+            true => word.to_string(),
+            false => format!("{} {}", line, word),
+        };
+        if text_width(&longer, size) <= width || line.is_empty() {
+            line = longer;
+        } else {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Outline of a box from `x` to `end`, with its corners rounded by `radius`,
+/// or less where the box is too small for it.
+/// A circle of `radius` around (`x`, `y`), as two half circles.
+fn dot(x: f64, y: f64, radius: f64) -> Data {
+    let arc = |to_x: f64|vec![radius as f32, radius as f32, 0.0, 0.0, 1.0, to_x as f32, y as f32];
+    Data::new()
+        .move_to((x - radius, y))
+        .elliptical_arc_to(arc(x + radius))
+        .elliptical_arc_to(arc(x - radius))
+        .close()
+}
+
+fn rounded_box(x: f64, end: f64, y: f64, height: f64, radius: f64) -> Data {
+    let r = radius.min((end - x) / 2.0).min(height / 2.0);
+    if r <= 0.0 {
+        return Data::new()
+            .move_to((x,   y))
+            .line_to((end, y))
+            .line_to((end, y + height))
+            .line_to((x,   y + height))
+            .close();
+    }
+    // Quarter circles, clockwise, ending at (to_x, to_y).
+    let arc = |to_x: f64, to_y: f64|vec![r as f32, r as f32, 0.0, 0.0, 1.0, to_x as f32, to_y as f32];
+    Data::new()
+        .move_to((x + r, y))
+        .line_to((end - r, y))
+        .elliptical_arc_to(arc(end, y + r))
+        .line_to((end, y + height - r))
+        .elliptical_arc_to(arc(end - r, y + height))
+        .line_to((x + r, y + height))
+        .elliptical_arc_to(arc(x, y + height - r))
+        .line_to((x, y + r))
+        .elliptical_arc_to(arc(x + r, y))
+        .close()
+}
+
+/// Wraps `text` in an svg text node, escaping it since the svg crate doesn't.
+fn text_node(text: &str) -> svg::node::Text {
+    let escaped = text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    svg::node::Text::new(escaped)
+}
+
+/// Id of the `<pattern>` used to fill planned events.
+const HATCH_ID: &str = "saga-hatch";
+
+/// Diagonal hatching, drawn over the usual fill color.
+fn hatch_pattern() -> Pattern {
+    let background = SvgPath::new()
+        .set("fill", "#C3B2A4")
+        .set("d", Data::new()
+            .move_to((0, 0)).line_to((8, 0)).line_to((8, 8)).line_to((0, 8)).close());
+    let stripe = SvgPath::new()
+        .set("stroke", "#2e3d50")
+        .set("stroke-width", 1)
+        .set("d", Data::new().move_to((0, 8)).line_to((8, 0)));
+    Pattern::new()
+        .set("id", HATCH_ID)
+        .set("width", 8)
+        .set("height", 8)
+        .set("patternUnits", "userSpaceOnUse")
+        .add(background)
+        .add(stripe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{text_width, wrap, Anchor, Label, Mark, PointMarker, Renderer, Scene, Shape, SpanMarker, Style, Svg, Terminal};
+    use super::super::axis::Interval;
+    use super::super::events::{Event, Node, Status};
+    use super::super::saga::{Outside, Pages, RenderOptions, SagaDoc};
+
+    #[test]
+    fn test_svg_rendering() {
+        assert!(Svg::default().render(&Scene::default()).to_string().find("<path").is_none());
+        let scene = Scene {
+            width: 100.0,
+            height: 50.0,
+            style: Style::default(),
+            shapes: vec![
+                Shape::Event { x: 10.0, end: Some(20.0), y: 5.0, height: 10.0, status: Some(Status::Planned), title: "R&D".to_string(), layer: 0, fill: None },
+                Shape::Label { x: 10.0, y: 30.0, text: "<1805>".to_string() },
+                Shape::Event { x: 40.0, end: None, y: 5.0, height: 10.0, status: Some(Status::Cancelled), title: "Landing".to_string(), layer: 0, fill: None },
+            ],
+        };
+        let svg = Svg::default().render(&scene).to_string();
+        assert!(svg.contains("fill=\"url(#saga-hatch)\""));
+        // Cancelled points are struck through like spans.
+        assert!(svg.contains("d=\"M35,5 L45,15 M35,15 L45,5\""));
+        assert!(svg.contains("R&amp;D"));
+        assert!(svg.contains("&lt;1805&gt;"));
+        assert!(!svg.contains("<animate"));
+        let animated = Svg { animate: Some(10.0) }.render(&scene).to_string();
+        assert!(animated.contains("opacity=\"0\""));
+        assert!(animated.contains("begin=\"1.00s\""));
+        assert!(animated.contains("attributeName=\"x1\""));
+    }
+
+    #[test]
+    fn test_markers() {
+        let event = |end: Option<f64>|Shape::Event { x: 10.0, end, y: 0.0, height: 10.0, status: None, title: String::new(), layer: 0, fill: None };
+        let mut style = Style::default();
+        style.points.marker = PointMarker::Dot;
+        style.spans.marker = SpanMarker::Bar;
+        let scene = Scene { width: 100.0, height: 50.0, style, shapes: vec![event(None), event(Some(40.0))] };
+        let svg = Svg::default().render(&scene).to_string();
+        assert!(svg.contains("d=\"M5,5 A5,5,0,0,1,15,5 A5,5,0,0,1,5,5 z\""));
+        assert!(svg.contains("d=\"M10,5 L40,5\""));
+        let mut style = Style::default();
+        style.points.marker = PointMarker::Diamond;
+        let scene = Scene { width: 100.0, height: 50.0, style, shapes: vec![event(None)] };
+        assert!(Svg::default().render(&scene).to_string().contains("d=\"M10,0 L15,5 L10,10 L5,5 z\""));
+        assert_eq!(Mark::<PointMarker>::default().within(20.0, 10.0), (20.0, 10.0));
+        let half = Mark { marker: SpanMarker::Box, height: 0.5, label: Label::Auto };
+        assert_eq!(half.within(20.0, 10.0), (22.5, 5.0));
+    }
+
+    #[test]
+    fn test_json_layout() {
+        let scene = Scene {
+            width: 100.0,
+            height: 50.0,
+            style: Style::default(),
+            shapes: vec![
+                Shape::Event { x: 10.0, end: None, y: 5.0, height: 10.0, status: Some(Status::InProgress), title: "Siege".to_string(), layer: 0, fill: Some("#ff8800".to_string()) },
+                Shape::Tick { x: 10.0, y: 30.0 },
+            ],
+        };
+        let json: serde_json::Value = serde_json::to_value(&scene).unwrap();
+        assert_eq!(json["width"], 100.0);
+        assert_eq!(json["shapes"][0], serde_json::json!({
+            "type": "Event", "x": 10.0, "end": null, "y": 5.0, "height": 10.0,
+            "status": "in-progress", "title": "Siege", "layer": 0, "fill": "#ff8800",
+        }));
+        assert_eq!(json["shapes"][1], serde_json::json!({"type": "Tick", "x": 10.0, "y": 30.0}));
+    }
+
+    #[test]
+    fn test_terminal_rendering() {
+        let scene = Scene {
+            width: 100.0,
+            height: 50.0,
+            style: Style::default(),
+            shapes: vec![
+                Shape::Event { x: 0.0, end: Some(40.0), y: 5.0, height: 10.0, status: None, title: "Siege".to_string(), layer: 0, fill: None },
+                Shape::Event { x: 90.0, end: None, y: 5.0, height: 10.0, status: Some(Status::Done), title: "Treaty".to_string(), layer: 0, fill: None },
+                Shape::Line { start: 0.0, end: 90.0, y: 30.0 },
+                Shape::Tick { x: 0.0, y: 30.0 },
+                Shape::Label { x: 0.0, y: 40.0, text: "1805".to_string() },
+                Shape::Tick { x: 90.0, y: 30.0 },
+                Shape::Label { x: 90.0, y: 40.0, text: "1806".to_string() },
+            ],
+        };
+        let plain = Terminal { width: 41, color: false }.render(&scene);
+        assert_eq!(plain.lines().collect::<Vec<&str>>(), vec![
+            "█████      Siege",
+            "        │  Treaty",
+            "┼───────┼",
+            "1805  1806",
+        ]);
+        let colored = Terminal { width: 41, color: true }.render(&scene);
+        assert!(colored.contains("\x1b[32m│\x1b[0m"));
+    }
+
+    #[test]
+    fn test_scene_layout() {
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            Event::new("Austerlitz", "02/12/1805".parse().unwrap()).into_value(),
+            Event::new("Jena", "14/10/1806".parse().unwrap()).into_value(),
+        ]);
+        let scene = doc.scene(&RenderOptions::default());
+        let xs = scene.shapes.iter().filter_map(|shape|match shape {
+            Shape::Event { x, .. } => Some(*x),
+            _ => None,
+        }).collect::<Vec<f64>>();
+        assert_eq!(xs.len(), 2);
+        assert!(xs[0] == 0.0 && xs[0] < xs[1] && xs[1] <= scene.width);
+        assert!(SagaDoc::blank().scene(&RenderOptions::default()).shapes.is_empty());
+        let dense = doc.scene(&RenderOptions { density: Some(4), ..RenderOptions::default() });
+        let bars = dense.shapes.iter().filter_map(|shape|match shape {
+            Shape::Bar { x, count, .. } => Some((*x, *count)),
+            _ => None,
+        }).collect::<Vec<(f64, usize)>>();
+        assert_eq!(bars, vec![(0.0, 1), (dense.width * 0.75, 1)]);
+        let row = Terminal { width: 41, color: false }.render(&dense);
+        assert_eq!(row.lines().last(), Some("██     ██"));
+        let heated = doc.scene(&RenderOptions { heat: Some(2), ..RenderOptions::default() });
+        let bands = heated.shapes.iter().filter_map(|shape|match shape {
+            Shape::Band { x, y, heat, .. } => Some((*x, *y, *heat)),
+            _ => None,
+        }).collect::<Vec<(f64, f64, f64)>>();
+        assert_eq!(bands, vec![(0.0, 0.1 * heated.height, 1.0), (heated.width / 2.0, 0.1 * heated.height, 1.0)]);
+        assert!(matches!(heated.shapes[0], Shape::Band { .. }));
+        let gridded = doc.scene(&RenderOptions { grid: Some(Interval::Years(1)), ..RenderOptions::default() });
+        let lines = gridded.shapes.iter().filter_map(|shape|match shape {
+            Shape::Gridline { x } => Some(*x),
+            _ => None,
+        }).collect::<Vec<f64>>();
+        // Only New Year's Day 1806 falls between the two.
+        assert_eq!(lines.len(), 1);
+        assert!(xs[0] < lines[0] && lines[0] < xs[1]);
+        assert!(matches!(gridded.shapes[0], Shape::Gridline { .. }));
+    }
+
+    #[test]
+    fn test_nested_offsets() {
+        // Each node's offset moves its own events and lines too, measured in
+        // its parent's scaling.
+        let text = r#"{"x":100.0,"y":100.0,"padding":0.0,"spacing":{"top":0.0,"per_depth":0.0,"row":0.125},"data":{"children":[
+            {"type":"Event","name":"a","datetime":"1800"},
+            {"type":"Node","offset":0.25,"y_scale":0.5,"line":2.0,"children":[
+                {"type":"Event","name":"b","datetime":"1850"},
+                {"type":"Node","offset":0.5,"children":[{"type":"Event","name":"c","datetime":"1900"}]}
+            ]}
+        ]}}"#;
+        let doc: SagaDoc = serde_json::from_str(text).unwrap();
+        let scene = doc.scene(&RenderOptions::default());
+        let rows = scene.shapes.iter().filter_map(|shape|match shape {
+            Shape::Event { y, height, .. } => Some((*y, *height)),
+            Shape::Line { y, .. } => Some((*y, 0.0)),
+            _ => None,
+        }).collect::<Vec<(f64, f64)>>();
+        assert_eq!(rows, vec![(0.0, 12.5), (25.0, 12.5), (50.0, 12.5), (25.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_outside_line() {
+        let mut campaign = Node::from_vec(vec![
+            Event::new("Inside", "01/06/1806 00:00".parse().unwrap()).into_value(),
+            Event::new("Across", "01/06/1805 00:00 - 01/06/1806 00:00".parse().unwrap()).into_value(),
+            Event::new("After", "01/01/1808 00:00".parse().unwrap()).into_value(),
+        ]).with_line(None);
+        campaign.set_line_span(Some("01/01/1806 00:00 - 01/01/1807 00:00".parse().unwrap()));
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![campaign.into_value()]);
+        let scene = |outside: Outside|doc.scene(&RenderOptions { outside, ..RenderOptions::default() });
+        let line = |scene: &Scene|scene.shapes.iter().find_map(|shape|match shape {
+            Shape::Line { start, end, .. } => Some((*start, *end)),
+            _ => None,
+        }).unwrap();
+        let events = |scene: &Scene|scene.shapes.iter().filter_map(|shape|match shape {
+            Shape::Event { x, end, .. } => Some((*x, *end)),
+            _ => None,
+        }).collect::<Vec<(f64, Option<f64>)>>();
+        let extended = scene(Outside::Extend);
+        assert_eq!(line(&extended), (0.0, extended.width));
+        assert_eq!(events(&extended).len(), 3);
+        // Cut at the start of the line, with the event after it dropped.
+        let clipped = scene(Outside::Clip);
+        let (start, end) = line(&clipped);
+        assert!(0.0 < start && end < clipped.width);
+        assert_eq!(events(&clipped).iter().map(|(x, _)|*x).collect::<Vec<f64>>()[1], start);
+        assert_eq!(events(&clipped).len(), 2);
+        let badged = scene(Outside::Badge);
+        assert_eq!(line(&badged), (start, end));
+        assert_eq!(badged.shapes.iter().filter(|shape|matches!(shape, Shape::Badge { .. })).count(), 2);
+        assert!(Terminal { width: 60, color: false }.render(&badged).lines().any(|row|row.ends_with(" !")));
+    }
+
+    #[test]
+    fn test_captions() {
+        assert!(text_width("ill", 12.0) < text_width("MWM", 12.0));
+        assert_eq!(text_width("", 12.0), 0.0);
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            Event::new("Napoleonic Wars", "18/05/1803 00:00 - 20/11/1815 00:00".parse().unwrap()).into_value(),
+            Event::new("Ulm", "20/10/1805 00:00".parse().unwrap()).into_value(),
+            Event::new("Austerlitz", "02/12/1805 00:00".parse().unwrap()).into_value(),
+            Event::new("Hundred Days", "20/03/1815 00:00 - 08/07/1815 00:00".parse().unwrap()).into_value(),
+        ]);
+        assert!(!doc.scene(&RenderOptions::default()).shapes.iter().any(|shape|matches!(shape, Shape::Caption { .. })));
+        let scene = doc.scene(&RenderOptions { captions: true, ..RenderOptions::default() });
+        let captions = scene.shapes.iter().filter_map(|shape|match shape {
+            Shape::Caption { y, anchor, text, .. } => Some((text.as_str(), *anchor, *y)),
+            _ => None,
+        }).collect::<Vec<(&str, Anchor, f64)>>();
+        // Only the long war fits in its box, and the last event is too near the edge to have its caption after it.
+        let anchors = captions.iter().map(|(text, anchor, _)|(*text, *anchor)).collect::<Vec<(&str, Anchor)>>();
+        assert_eq!(anchors, vec![
+            ("Napoleonic Wars", Anchor::Middle),
+            ("Ulm", Anchor::Start),
+            ("Austerlitz", Anchor::Start),
+            ("Hundred Days", Anchor::End),
+        ]);
+        assert_eq!(scene.shapes.iter().filter(|shape|matches!(shape, Shape::Leader { .. })).count(), 3);
+        // Austerlitz is close enough after Ulm for its caption to be moved down, clear of Ulm's.
+        assert!(captions[2].2 > captions[1].2);
+        let svg = Svg::default().render(&scene).to_string();
+        assert!(svg.contains("text-anchor=\"end\"") && svg.contains("Hundred Days"));
+    }
+
+    #[test]
+    fn test_annotations() {
+        assert_eq!(wrap("The Grande Armée crosses the Rhine", 110.0, 12.0), vec!["The Grande Armée", "crosses the Rhine"]);
+        assert_eq!(wrap("Unbreakable", 10.0, 12.0), vec!["Unbreakable"]);
+        assert!(wrap("  ", 100.0, 12.0).is_empty());
+        let mut ulm = Event::new("Ulm", "20/10/1805 00:00".parse().unwrap());
+        ulm.add_description("Mack surrenders his army after being encircled.");
+        let mut jena = Event::new("Jena", "14/10/1806 00:00".parse().unwrap());
+        jena.add_description("Prussia is defeated.");
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            ulm.into_value(),
+            jena.into_value(),
+            Event::new("Austerlitz", "02/12/1805 00:00".parse().unwrap()).into_value(),
+        ]);
+        let annotations = |query: &str|doc.scene(&RenderOptions { annotate: Some(query.to_string()), ..RenderOptions::default() })
+            .shapes.into_iter()
+            .filter_map(|shape|match shape {
+                Shape::Annotation { x, y, width, height, lines, .. } => Some(((x, x + width, y, y + height), lines)),
+                _ => None,
+            })
+            .collect::<Vec<((f64, f64, f64, f64), Vec<String>)>>();
+        // Only events with a description get a box.
+        let all = annotations("");
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].1, vec!["Prussia is defeated."]);
+        assert!(all[0].1.len() > 1);
+        let ((l, r, t, b), (l2, r2, t2, b2)) = (all[0].0, all[1].0);
+        assert!(r <= l2 || r2 <= l || b <= t2 || b2 <= t);
+        assert_eq!(annotations("prussia").len(), 1);
+        assert!(annotations("austerlitz").is_empty());
+    }
+
+    #[test]
+    fn test_ruler() {
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            Event::new("Start", "01/01/1800 00:00".parse().unwrap()).into_value(),
+            Event::new("End", "01/01/1900 00:00".parse().unwrap()).into_value(),
+        ]);
+        assert!(!doc.scene(&RenderOptions::default()).shapes.iter().any(|shape|matches!(shape, Shape::Ruler { .. })));
+        let scene = doc.scene(&RenderOptions { ruler: true, ..RenderOptions::default() });
+        let (x, length, text) = scene.shapes.iter().find_map(|shape|match shape {
+            Shape::Ruler { x, length, text, .. } => Some((*x, *length, text.clone())),
+            _ => None,
+        }).unwrap();
+        assert_eq!(text, "20 years");
+        // A fifth of the century, give or take the leap days.
+        assert!((length / scene.width - 0.2).abs() < 0.001);
+        assert!(x > 0.0 && x + length < scene.width);
+        let text = Terminal { width: 60, color: false }.render(&scene);
+        assert!(text.lines().last().unwrap().trim_start().starts_with('├'));
+        assert!(text.ends_with("┤ 20 years"));
+    }
+
+    #[test]
+    fn test_pages() {
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            Event::new("Start", "01/01/1800 00:00".parse().unwrap()).into_value(),
+            Event::new("War", "01/01/1805 00:00 - 01/01/1815 00:00".parse().unwrap()).into_value(),
+            Event::new("End", "01/01/1830 00:00".parse().unwrap()).into_value(),
+        ]);
+        let ranges = doc.page_ranges(Pages::Every(Interval::Years(10)));
+        assert_eq!(ranges.len(), 3);
+        // Every page as long as the others, the last reaching the end.
+        assert!(ranges.iter().all(|(from, to)|to - from == ranges[0].1 - ranges[0].0));
+        assert!(ranges[2].1 >= doc.get_data().range().1);
+        let pages = doc.pages(&RenderOptions::default(), Pages::Every(Interval::Years(10)));
+        let count = |scene: &Scene, matches: &dyn Fn(&Shape) -> bool|scene.shapes.iter().filter(|shape|matches(shape)).count();
+        let events = pages.iter().map(|page|count(page, &|shape|matches!(shape, Shape::Event { .. }))).collect::<Vec<usize>>();
+        assert_eq!(events, vec![2, 1, 1]);
+        assert_eq!(count(&pages[0], &|shape|matches!(shape, Shape::Continues { later: true, .. })), 1);
+        assert_eq!(count(&pages[1], &|shape|matches!(shape, Shape::Continues { later: false, .. })), 1);
+        assert_eq!(count(&pages[2], &|shape|matches!(shape, Shape::Continues { .. })), 0);
+        assert!(pages[1].shapes.iter().all(|shape|match shape {
+            Shape::Event { x, end, .. } => 0.0 <= *x && end.is_none_or(|end|end <= pages[1].width),
+            _ => true,
+        }));
+        // The war ends just before the second of two pages starts, so isn't on it.
+        let halves = doc.pages(&RenderOptions::default(), Pages::Count(2));
+        let events = halves.iter().map(|page|count(page, &|shape|matches!(shape, Shape::Event { .. }))).collect::<Vec<usize>>();
+        assert_eq!(events, vec![2, 1]);
+        // A single page is the same as the whole timeline.
+        assert_eq!(doc.pages(&RenderOptions::default(), Pages::Count(1))[0].shapes, doc.scene(&RenderOptions::default()).shapes);
+    }
+
+    #[test]
+    fn test_overlay_layout() {
+        let doc = |events: &[(&str, &str)]|{
+            let mut doc = SagaDoc::blank();
+            *doc.get_data_mut() = Node::from_vec(events.iter()
+                .map(|(name, dates)|Event::new(name, dates.parse().unwrap()).into_value())
+                .collect());
+            doc
+        };
+        let planned = doc(&[("Campaign", "01/09/1806 00:00 - 01/11/1806 00:00")]);
+        let actual = doc(&[("Campaign", "08/10/1806 00:00 - 14/12/1806 00:00")]);
+        let scene = planned.overlay_scene(&actual, &RenderOptions::default());
+        let events = scene.shapes.iter().filter_map(|shape|match shape {
+            Shape::Event { x, end, y, height, layer, .. } => Some((*x, end.unwrap(), *y, *height, *layer)),
+            _ => None,
+        }).collect::<Vec<(f64, f64, f64, f64, usize)>>();
+        // Both on one axis, running from the earliest start to the latest end.
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].0, events[0].4), (0.0, 0));
+        assert_eq!((events[1].1, events[1].4), (scene.width, 1));
+        assert!(events[0].1 > events[1].0);
+        // Sharing a row, one above the other.
+        assert_eq!(events[0].2 + events[0].3, events[1].2);
+        let svg = Svg::default().render(&scene).to_string();
+        assert!(svg.contains("#C3B2A4") && svg.contains("#A4C3B8"));
+        let labeled = planned.scene(&RenderOptions { durations: true, ..RenderOptions::default() });
+        assert!(matches!(&labeled.shapes[0], Shape::Event { title, .. } if title.ends_with(", 2 months)")));
+    }
+}
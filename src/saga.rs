@@ -1,45 +1,500 @@
 
 use std::{
-    collections::HashMap,
-    io::Error as IoError,
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{Error as IoError, Read, Write},
     num::ParseIntError,
+    path::Path,
+    str::FromStr,
+    sync::OnceLock,
+    time::Instant,
 };
 
 pub type DtParseError = chrono::format::ParseError;
+use log::{debug, warn};
+use rustyline::error::ReadlineError;
 use serde::{Serialize, Deserialize};
-use svg::{
-    Document, Node as SvgNode,
-    node::element::{path::Data,Path as SvgPath}
-};
+use svg::Document;
+use tempfile::NamedTempFile;
 
-use super::events::{Event, Node, PathFail, Query, Value};
+use super::{MainError, MainResult};
+use super::axis::{AxisLabels, Interval};
+use super::input;
+use super::calendar::{Calendar, CalendarError};
+use super::config::DEFAULT_MAX_DEPTH;
+use super::crypt;
+use super::events::{from_stamp, to_stamp, Dt, Event, InvertedDates, Node, PathFail, Position, PrintOptions, Query, QueryRef, Value, Visitor};
+use super::locale::{DateFormat, DatePattern, Locale};
+use super::logging::thousands;
+use super::render::{text_width, wrap, Anchor, Label, Renderer, Scene, Shape, Style, Svg, LINE_HEIGHT};
+use super::validate::path_string;
 
 /// Temp error type.
+#[derive(Debug)]
 pub enum SagaDocError {
     PathParse(ParseIntError),
     PathFind(PathFail),
     AddToEvent,
     DtParse(DtParseError),
+    CalendarParse(CalendarError),
+    InvertedDates(InvertedDates),
+    /// The user abandoned a prompt with Ctrl+C or Ctrl+D.
+    Interrupted,
+    Readline(ReadlineError),
     IoError(IoError),
+    /// Input piped in ran out before a line was given.
+    EndOfInput,
+    /// A line was longer than the most that's read, given in bytes.
+    LineTooLong(usize),
+    /// A line wasn't valid UTF-8, from the byte given if known.
+    NotUtf8(Option<usize>),
 }
 
 pub type Colors = Vec<Color>;
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Color {
     r: u8,
     g: u8,
     b: u8,
 }
 
+/// Either a literal color, or the name of one of the document's `color_schemes`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColorRef {
+    Rgb(Color),
+    Scheme(String),
+}
+
+/// Created when a string isn't a hex color like "#ff8800" or "#f80".
+#[derive(Debug, PartialEq)]
+pub struct ColorParseError(pub String);
+
+/// Created when a color scheme can't be managed by the name given.
+#[derive(Debug, PartialEq)]
+pub enum SchemeError {
+    /// No scheme has this name.
+    NotFound(String),
+    /// A scheme has this name already.
+    Exists(String),
+}
+
+/// What rendering does with events falling outside the span declared for
+/// their node's line, see `Node::line_span_at`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Outside {
+    /// Stretches the line over them.
+    #[default]
+    Extend,
+    /// Cuts them off at the ends of the line, dropping those wholly outside.
+    Clip,
+    /// Draws them where they are, marked with a badge.
+    Badge,
+}
+
+/// Created when a string isn't one of the `Outside` names.
+#[derive(Debug, PartialEq)]
+pub struct OutsideParseError(pub String);
+
+/// How `SagaDoc::pages` splits a timeline too long for one page.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pages {
+    /// Into this many pages.
+    Count(usize),
+    /// Into a page starting at every boundary of the interval.
+    Every(Interval),
+}
+
+/// Most pages `SagaDoc::page_ranges` splits a timeline into.
+pub const MAX_PAGES: usize = 500;
+
+/// Settings chosen per invocation of `SagaDoc::draw`, as opposed to the
+/// ones stored in the document itself.
+#[derive(Debug, Default)]
+pub struct RenderOptions {
+    /// Language used for translated names.
+    pub lang: Option<String>,
+    /// Overrides the document's locale when formatting dates.
+    pub locale: Option<String>,
+    /// Overrides the document's date pattern, see `locale::DatePattern`.
+    pub date_pattern: Option<String>,
+    /// Seconds an animated sweep across the timeline takes, see `Svg::animate`.
+    pub animate: Option<f64>,
+    /// Number of slices the timeline is split into for a histogram of how
+    /// many events fall in each, drawn along the bottom.
+    pub density: Option<usize>,
+    /// Number of slices each row's background is split into, tinted by how
+    /// many of the row's events fall in each.
+    pub heat: Option<usize>,
+    /// Adds how long each span lasts to its label.
+    pub durations: bool,
+    /// Draws a gridline across the whole timeline at every boundary of
+    /// this interval.
+    pub grid: Option<Interval>,
+    /// How events outside their node's declared line are drawn.
+    pub outside: Outside,
+    /// Scheme used in place of the active one, if the document has it.
+    pub scheme: Option<String>,
+    /// Adds a scale bar showing how far apart a round stretch of time puts
+    /// events.
+    pub ruler: bool,
+    /// Writes each event's name on its box, or beside it where it doesn't fit.
+    pub captions: bool,
+    /// Puts the first description of the events matching this, as with
+    /// `Event::matches`, in a box beside them. An empty query matches all.
+    pub annotate: Option<String>,
+}
+
+/// A node listed in the table of contents of a rendered document, placed in
+/// the scene's pixel space.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub depth: usize,
+    /// Where the node's events start and end.
+    pub x: f64,
+    pub end: f64,
+    /// Top of the row of the node's own events.
+    pub y: f64,
+}
+
+/// Collects every event along with where it sits in the tree, for `draw()`.
+#[derive(Default)]
+struct Placed<'a>(Vec<(&'a Event, Position)>);
+
+impl<'a> Visitor<'a> for Placed<'a> {
+    fn event(&mut self, event: &'a Event, at: &Position) {
+        self.0.push((event, at.clone()));
+    }
+}
+
+/// Like `Placed`, but leaving out the events under collapsed nodes, which
+/// are collected instead to be drawn as one band each.
+#[derive(Default)]
+struct Shown<'a> {
+    events: Vec<(&'a Event, Position)>,
+    collapsed: Vec<(&'a Node, Position)>,
+    /// Path of the collapsed node being walked through, if any.
+    hiding: Option<Vec<usize>>,
+}
+
+impl<'a> Visitor<'a> for Shown<'a> {
+    fn enter(&mut self, node: &'a Node, at: &Position) {
+        if self.hiding.is_none() && node.is_collapsed() {
+            self.hiding = Some(at.path.clone());
+            self.collapsed.push((node, at.clone()));
+        }
+    }
+
+    fn leave(&mut self, _node: &'a Node, at: &Position) {
+        if self.hiding.as_ref() == Some(&at.path) {
+            self.hiding = None;
+        }
+    }
+
+    fn event(&mut self, event: &'a Event, at: &Position) {
+        if self.hiding.is_none() {
+            self.events.push((event, at.clone()));
+        }
+    }
+}
+
+/// Where `dt` falls in `range`, from 0 at its start to 1 at its end.
+fn x_of(dt: &Dt, range: &(i64, i64)) -> f64 {
+    fraction(to_stamp(dt), range)
+}
+
+/// Same as `x_of`, for a timestamp.
+fn fraction(stamp: i64, range: &(i64, i64)) -> f64 {
+    (stamp - range.0) as f64 / (range.1 - range.0) as f64
+}
+
+/// Draws a gridline at every boundary of `interval` within `range`, picking
+/// the same spacing as a node's line would across the whole timeline.
+fn lay_out_grid(scene: &mut Scene, range: &(i64, i64), interval: &Interval) {
+    if let (Some(from), Some(to)) = (from_stamp(range.0), from_stamp(range.1)) {
+        for dt in interval.ticks(&from, &to) {
+            scene.shapes.push(Shape::Gridline { x: x_of(&dt, range) * scene.width });
+        }
+    }
+}
+
+/// Places a scale bar in the bottom right corner, as long as the longest
+/// usual stretch of time within a fifth of `range`. Timelines shorter than
+/// any get none.
+fn lay_out_ruler(scene: &mut Scene, range: &(i64, i64)) {
+    let span = range.1 - range.0;
+    let interval = Interval::fitting(span / 5);
+    let length = interval.approx_seconds() as f64 / span as f64 * scene.width;
+    if length > scene.width { return; }
+    let margin = 0.02 * scene.width;
+    scene.shapes.push(Shape::Ruler {
+        x: scene.width - margin - length,
+        y: scene.height - margin,
+        length,
+        text: interval.describe(),
+    });
+}
+
+/// Height of captions, unless the rows are too short for it.
+const CAPTION_SIZE: f64 = 12.0;
+
+/// Length of the leader line joining an event to a caption beside it.
+const LEADER: f64 = 12.0;
+
+/// Left, right, top and bottom of something placed in a scene.
+type Area = (f64, f64, f64, f64);
+
+fn overlap(a: &Area, b: &Area) -> bool {
+    a.0 < b.1 && b.0 < a.1 && a.2 < b.3 && b.2 < a.3
+}
+
+/// Places the captions of events, keeping track of those put beside their
+/// event so that later ones don't cover them.
+#[derive(Default)]
+struct Captions {
+    /// Each caption beside its event.
+    taken: Vec<Area>,
+}
+
+impl Captions {
+    /// Captions the event drawn from `x` to `end` and from `y`, `height`
+    /// high, in a row `row` high. Above or below it if `label` says so.
+    /// Otherwise inside its box if the text fits with room to spare, unless
+    /// `label` keeps it beside, else beside it on a leader line, on the right
+    /// unless that runs off the page, and moved down until clear of the other
+    /// captions there.
+    fn place(&mut self, scene: &mut Scene, (x, end): (f64, Option<f64>), (y, height): (f64, f64), row: f64, label: Label, text: &str) {
+        let size = CAPTION_SIZE.min(0.8 * row);
+        let width = text_width(text, size);
+        let middle = y + height / 2.0;
+        let centre = (x + end.unwrap_or(x)) / 2.0;
+        let at_y = match label {
+            Label::Above => Some(y - size * 0.6),
+            Label::Below => Some(y + height + size * 0.6),
+            Label::Auto => end.filter(|end|width + size <= end - x).map(|_|middle),
+            Label::Beside => None,
+        };
+        if let Some(at_y) = at_y {
+            if label != Label::Auto {
+                self.taken.push((centre - width / 2.0, centre + width / 2.0, at_y - size / 2.0, at_y + size / 2.0));
+            }
+            scene.shapes.push(Shape::Caption { x: centre, y: at_y, size, anchor: Anchor::Middle, text: text.to_string() });
+            return;
+        }
+        let edge = end.unwrap_or(x);
+        let (from, anchor, at_x, left) = match edge + LEADER + width <= scene.width {
+            true => (edge, Anchor::Start, edge + LEADER, edge + LEADER),
+            false => (x, Anchor::End, x - LEADER, x - LEADER - width),
+        };
+        let area = |at_y: f64|(left, left + width, at_y - size / 2.0, at_y + size / 2.0);
+        let mut at_y = middle;
+        while self.taken.iter().any(|taken|overlap(taken, &area(at_y))) {
+            at_y += size;
+        }
+        self.taken.push(area(at_y));
+        scene.shapes.push(Shape::Leader { x: from, y: middle, to_x: at_x, to_y: at_y });
+        scene.shapes.push(Shape::Caption { x: at_x, y: at_y, size, anchor, text: text.to_string() });
+    }
+}
+
+/// Height of the text in annotations.
+const ANNOTATION_SIZE: f64 = 11.0;
+
+/// Widest an annotation's text is wrapped to.
+const ANNOTATION_WIDTH: f64 = 220.0;
+
+/// Most lines of a description shown in an annotation.
+const ANNOTATION_LINES: usize = 6;
+
+/// Places boxes of descriptions next to their events, above their row where
+/// there's room and below otherwise, moved further away until clear of the
+/// ones already placed.
+#[derive(Default)]
+struct Annotations {
+    taken: Vec<Area>,
+}
+
+impl Annotations {
+    /// Annotates the event drawn from `x` to `end` in the row from `y`,
+    /// `height` high, with `text` wrapped in a box.
+    fn place(&mut self, scene: &mut Scene, (x, end): (f64, Option<f64>), (y, height): (f64, f64), text: &str) {
+        let size = ANNOTATION_SIZE;
+        let mut lines = wrap(text, ANNOTATION_WIDTH, size);
+        if lines.len() > ANNOTATION_LINES {
+            lines.truncate(ANNOTATION_LINES);
+            if let Some(last) = lines.last_mut() { last.push('…'); }
+        }
+        let widest = lines.iter().map(|line|text_width(line, size)).fold(0.0, f64::max);
+        let (width, tall) = (widest + size, (lines.len() as f64 + 0.5) * LINE_HEIGHT * size);
+        let middle = (x + end.unwrap_or(x)) / 2.0;
+        let left = (middle - width / 2.0).clamp(0.0, (scene.width - width).max(0.0));
+        let gap = size;
+        let above = y - gap - tall >= 0.0;
+        let mut top = match above {
+            true => y - gap - tall,
+            false => y + height + gap,
+        };
+        let area = |top: f64|(left, left + width, top, top + tall);
+        while self.taken.iter().any(|taken|overlap(taken, &area(top))) {
+            top += match above {
+                true => -size,
+                false => size,
+            };
+        }
+        self.taken.push(area(top));
+        let (from_y, to_y) = match above {
+            true => (y, top + tall),
+            false => (y + height, top),
+        };
+        scene.shapes.push(Shape::Leader { x: middle, y: from_y, to_x: middle.clamp(left, left + width), to_y });
+        scene.shapes.push(Shape::Annotation { x: left, y: top, width, height: tall, size, lines });
+    }
+}
+
+/// How a document's rows are spaced out vertically, each as a fraction of
+/// its height. Node offsets are added on top, scaled the same way.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Spacing {
+    /// Gap above the rows of the root node.
+    pub top: f64,
+    /// Added to the top of a row for every level its node is nested.
+    pub per_depth: f64,
+    /// Height of each row of events.
+    pub row: f64,
+}
+
+impl Default for Spacing {
+    fn default() -> Self {
+        Spacing { top: 0.1, per_depth: 0.0, row: 0.2 }
+    }
+}
+
+impl Spacing {
+    fn is_default(&self) -> bool {
+        *self == Spacing::default()
+    }
+
+    /// Height of a row of events in a scene `height` tall.
+    fn row_height(&self, height: f64) -> f64 {
+        self.row * height
+    }
+
+    /// Top of a row nested `depth` deep and moved down by `offset`, in a
+    /// scene `height` tall.
+    fn top_of(&self, offset: f64, depth: usize, height: f64) -> f64 {
+        (self.top + self.per_depth * depth as f64 + offset) * height
+    }
+
+    /// Top of the row holding events at `at`, in a scene `height` tall.
+    fn row_top(&self, at: &Position, height: f64) -> f64 {
+        self.top_of(at.offset, at.depth, height)
+    }
+}
+
+/// Deepest nesting of nodes documents may have, once read from the config.
+static MAX_DEPTH: OnceLock<usize> = OnceLock::new();
+
+/// Sets the nesting limit documents are held to when read or written, in
+/// place of `DEFAULT_MAX_DEPTH`. Only the first call has any effect.
+pub fn set_max_depth(limit: usize) {
+    let _ = MAX_DEPTH.set(limit);
+}
+
+/// Fingerprint of a file's contents, taken when loading it so that saving
+/// can tell whether another process wrote to it in the meantime.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stamp(u64);
+
+impl Stamp {
+    /// Stamp of a file holding `bytes`.
+    pub fn of(bytes: &[u8]) -> Stamp {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        Stamp(hasher.finish())
+    }
+}
+
+/// Replaces the file at `path` with `bytes` by way of a temporary file next
+/// to it, so that a failed write never leaves half a file behind. Given the
+/// `expected` stamp of the file, it's locked while being checked and
+/// replaced, and left alone if something else wrote to it since.
+pub fn replace_file(path: &str, bytes: &[u8], expected: Option<Stamp>) -> MainResult {
+    // Held until the new contents are in place, so that other saves of the
+    // file wait for this one to finish before checking it themselves.
+    let _lock = match expected {
+        Some(expected) => {
+            let file = File::open(path)
+                .and_then(|file|file.lock().map(|_|file))
+                .map_err(MainError::FileIO)?;
+            // Read by path rather than through the lock, as a save that held
+            // it before us may have replaced the file it belongs to.
+            let current = std::fs::read(path)
+                .map_err(MainError::FileIO)?;
+            if Stamp::of(&current) != expected {
+                return Err(MainError::Changed);
+            }
+            Some(file)
+        },
+        None => None,
+    };
+    let dir = match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut temp = NamedTempFile::new_in(dir)
+        .map_err(MainError::FileIO)?;
+    // The temporary file is only readable by us, unlike the one it replaces.
+    if let Ok(meta) = std::fs::metadata(path) {
+        temp.as_file().set_permissions(meta.permissions())
+            .map_err(MainError::FileIO)?;
+    }
+    temp.write_all(bytes)
+        .and_then(|_|temp.as_file().sync_all())
+        .map_err(MainError::FileIO)?;
+    temp.persist(path)
+        .map(|_|())
+        .map_err(|e|MainError::FileIO(e.error))
+}
+
 /// Root-Level wrapper for Node, that contains drawing/formatting data.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SagaDoc {
     x: f64,
     y: f64,
     padding: f64,
-    color_schemes: HashMap<String, Colors>,
+    /// Ordered by name, so that saving a document always gives the same output.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    color_schemes: BTreeMap<String, Colors>,
+    /// Scheme whose colors are taken in turn by the events that have none
+    /// of their own, one for each value in the root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    active_scheme: Option<String>,
+    /// Code of the `Locale` used to present dates, see `locale::LOCALES`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    locale: Option<String>,
+    /// Layout of the dates shown in renders, like "{b} {y}", in place of
+    /// the locale's, see `locale::DatePattern`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date_pattern: Option<String>,
+    /// How tick marks are labeled.
+    #[serde(default, skip_serializing_if = "AxisLabels::is_calendar")]
+    axis: AxisLabels,
+    /// Invented calendar used instead of the real one for entering and presenting dates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    calendar: Option<Calendar>,
+    #[serde(default, skip_serializing_if = "Spacing::is_default")]
+    spacing: Spacing,
+    #[serde(default, skip_serializing_if = "Style::is_default")]
+    style: Style,
     // Font,
     data: Node,
+    /// SVG fragments the nodes' images name, by the path each gives, read
+    /// by `read_images()` for drawing.
+    #[serde(skip)]
+    images: BTreeMap<String, String>,
 }
 
 impl SagaDoc {
@@ -50,131 +505,654 @@ impl SagaDoc {
             x: 1920.0,
             y: 1080.0,
             padding: 0.0,
-            color_schemes: HashMap::new(),
+            color_schemes: BTreeMap::new(),
+            active_scheme: None,
+            locale: None,
+            date_pattern: None,
+            axis: AxisLabels::Calendar,
+            calendar: None,
+            spacing: Spacing::default(),
+            style: Style::default(),
             data:   Node::from_vec(vec![]),
+            images: BTreeMap::new(),
+        }
+    }
+
+    /// Reads a document, decrypting it first if it was encrypted.
+    pub fn from_reader(mut reader: impl Read) -> Result<SagaDoc, MainError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)
+            .map_err(MainError::FileIO)?;
+        if crypt::is_encrypted(&bytes) {
+            bytes = crypt::decrypt(&bytes, &crypt::passphrase(false)?)?;
         }
+        // Checked first, as serde_json only notices bad bytes inside strings,
+        // and then without saying where.
+        std::str::from_utf8(&bytes)
+            .map_err(|e|MainError::NotUtf8(Some(e.valid_up_to())))?;
+        let doc: SagaDoc = serde_json::from_slice(&bytes)
+            .map_err(MainError::NotASagaDoc)?;
+        doc.check_depth()?;
+        Ok(doc)
+    }
+
+    /// Writes the document out as plain JSON.
+    pub fn to_writer(&self, writer: impl Write) -> MainResult {
+        self.check_depth()?;
+        serde_json::to_writer(writer, self)
+            .map_err(MainError::SerializeFail)
+    }
+
+    /// Writes the document out as indented JSON, the form `saga fmt` leaves
+    /// documents in.
+    pub fn to_writer_pretty(&self, writer: impl Write) -> MainResult {
+        self.check_depth()?;
+        serde_json::to_writer_pretty(writer, self)
+            .map_err(MainError::SerializeFail)
+    }
+
+    /// Whether the file at `path` holds self as `save_pretty()` would write
+    /// it, comparing with its contents decrypted if need be.
+    pub fn is_formatted(&self, path: &str) -> Result<bool, MainError> {
+        let compare = || {
+            let mut current = std::fs::read(path)
+                .map_err(MainError::FileIO)?;
+            if crypt::is_encrypted(&current) {
+                current = crypt::decrypt(&current, &crypt::passphrase(false)?)?;
+            }
+            let mut bytes = Vec::new();
+            self.to_writer_pretty(&mut bytes)?;
+            Ok(bytes == current)
+        };
+        compare().map_err(|e: MainError|e.in_file(path))
+    }
+
+    /// Whether self and `other` would be saved differently.
+    pub fn differs(&self, other: &SagaDoc) -> Result<bool, MainError> {
+        let (mut ours, mut theirs) = (Vec::new(), Vec::new());
+        self.to_writer(&mut ours)?;
+        other.to_writer(&mut theirs)?;
+        Ok(ours != theirs)
+    }
+
+    /// Reads the document stored at `path`.
+    pub fn load(path: &str) -> Result<SagaDoc, MainError> {
+        SagaDoc::load_stamped(path).map(|(doc, _)|doc)
     }
 
+    /// Same as `load()`, along with a stamp of the file to hand to
+    /// `save_unchanged()`.
+    pub fn load_stamped(path: &str) -> Result<(SagaDoc, Stamp), MainError> {
+        let started = Instant::now();
+        let read = || -> Result<_, MainError> {
+            let bytes = std::fs::read(path)
+                .map_err(MainError::FileIO)?;
+            Ok((SagaDoc::from_reader(bytes.as_slice())?, Stamp::of(&bytes)))
+        };
+        let (doc, stamp) = read().map_err(|e|e.in_file(path))?;
+        let events = doc.data.iter_events().count();
+        debug!("Parsed {} ({} events) in {:.1?}", path, thousands(events), started.elapsed());
+        Ok((doc, stamp))
+    }
+
+    /// Writes the document to `path`, encrypting it if the file there already
+    /// is. The new contents go to a temporary file that then replaces `path`,
+    /// so a failed write never leaves half a document behind.
+    pub fn save(&self, path: &str) -> MainResult {
+        self.save_with(path, false, None).map(|_|())
+    }
+
+    /// Same as `save_unchanged()`, but indented like `to_writer_pretty()`.
+    pub fn save_pretty(&self, path: &str, stamp: Stamp) -> Result<Stamp, MainError> {
+        self.save_with(path, true, Some(stamp))
+    }
+
+    /// Same as `save()`, but refuses to overwrite `path` if it no longer
+    /// matches `stamp`, meaning something else wrote to it since it was
+    /// loaded. Returns the stamp of what was written, for the next save.
+    pub fn save_unchanged(&self, path: &str, stamp: Stamp) -> Result<Stamp, MainError> {
+        self.save_with(path, false, Some(stamp))
+    }
+
+    fn save_with(&self, path: &str, pretty: bool, stamp: Option<Stamp>) -> Result<Stamp, MainError> {
+        let write = || -> Result<_, MainError> {
+            let mut bytes = Vec::new();
+            match pretty {
+                true => self.to_writer_pretty(&mut bytes)?,
+                false => self.to_writer(&mut bytes)?,
+            }
+            if crypt::is_encrypted_file(path) {
+                bytes = crypt::encrypt(&bytes, &crypt::passphrase(false)?)?;
+            }
+            replace_file(path, &bytes, stamp)?;
+            Ok(Stamp::of(&bytes))
+        };
+        let started = Instant::now();
+        let stamp = write().map_err(|e|e.in_file(path))?;
+        debug!("Saved {} in {:.1?}", path, started.elapsed());
+        Ok(stamp)
+    }
+
+    /// Refuses documents whose nodes are nested deeper than the limit given
+    /// to `set_max_depth()`.
+    fn check_depth(&self) -> MainResult {
+        let limit = MAX_DEPTH.get().copied().unwrap_or(DEFAULT_MAX_DEPTH);
+        match self.data.max_depth() {
+            depth if depth > limit => Err(MainError::TooDeep(depth, limit)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get_data(&self) -> &Node { &self.data }
+
+    /// Reads the SVG files named by the images of self's nodes, relative to
+    /// `path`, where self was loaded from. Those that can't be read, or
+    /// don't hold an `<svg>` element, are warned about and left out.
+    pub fn read_images(&mut self, path: &str) {
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        let named: BTreeSet<String> = self.data.iter_visits(0.0, 1.0)
+            .filter_map(|visit|visit.node.image().map(|image|image.to_string()))
+            .collect();
+        for image in named {
+            let text = match std::fs::read_to_string(dir.join(&image)) {
+                Ok(text) => text,
+                Err(e) => {
+                    warn!("{}: can't read image {}, {}", path, image, e);
+                    continue;
+                },
+            };
+            // Anything before the element, like an XML declaration, can't
+            // go inside another document.
+            match text.find("<svg") {
+                Some(start) => { self.images.insert(image, text[start..].trim_end().to_string()); },
+                None => warn!("{}: image {} holds no <svg> element", path, image),
+            }
+        }
+    }
+
+    /// Width and height of the scenes self is laid out in.
+    pub fn size(&self) -> (f64, f64) { (self.x, self.y) }
+
     pub fn get_data_mut(&mut self) -> &mut Node { &mut self.data }
 
-    pub fn draw(&self) -> Document {
+    /// Draws the document as SVG, see `scene()` for other backends.
+    pub fn draw(&self, options: &RenderOptions) -> Document {
+        Svg { animate: options.animate }.render(&self.scene(options))
+    }
+
+    /// Lays the document out as shapes, ready for any `Renderer`.
+    pub fn scene(&self, options: &RenderOptions) -> Scene {
         // Bail if we have nothing.
-        if self.data.is_empty() { return Document::new(); }
-        // Compose then zip iterators.
+        if self.data.is_empty() {
+            return Scene { width: self.x, height: self.y, style: self.style.clone(), shapes: vec![] };
+        }
+        self.scene_within(options, &self.data.range())
+    }
+
+    /// Lays the timeline out over several scenes, one per page, as split by
+    /// `pages`. Every page covers as long a stretch of time, so they share
+    /// a scale, and events running over the edge of one are marked there.
+    pub fn pages(&self, options: &RenderOptions, pages: Pages) -> Vec<Scene> {
+        self.page_ranges(pages).iter()
+            .map(|range|self.scene_within(options, range))
+            .collect()
+    }
+
+    /// The stretch of time each page covers when split by `pages`, each as
+    /// long as the longest, at most `MAX_PAGES` of them.
+    pub fn page_ranges(&self, pages: Pages) -> Vec<(i64, i64)> {
+        if self.data.is_empty() { return vec![]; }
         let range = self.data.range();
-        if range.1 - range.0 == 0 { return Document::new(); }
-        let y_slide: f64 = 0.1 * self.y;
-        let events = self.data.iter_events();
-        let depths = self.data.depth();
-        let scales = self.data.transform_iter(0.0, 1f64);
-        // Construct SVG document, we'll be pushing drawing commands into it.
-        let mut document = Document::new()
-            .set("viewbox", (0,0,self.x,self.y))
-            .set("width",  format!("{}px", self.x))
-            .set("height", format!("{}px", self.y))
-            .set("background-color", "#ff3400");
-        for ((event,depth),(offset,scale)) in events.zip(depths).zip(scales) {
-            // let mut svg_node = self.event_to_data(event, depth, offset, scale, y_slide, range);
-            // Transform the data points into screen space coords.
-            let locs = event.location(range);
-            let x_start = locs.0 as f64 * self.x;
-            let x_end = locs.1.map(|some|some as f64 * self.x);
-            let y = offset * scale * self.y * depth as f64;
-            let height = 0.2 * self.y; // TODO: Add height:f64 to Node.
-            // Start making the path.
-            let data = match x_end {
-                Some(some_end) => { // If span of time...
-                    Data::new()
-                        .move_to((x_start,  y + y_slide))
-                        .line_to((some_end, y + y_slide))
-                        .line_to((some_end, y + y_slide + height))
-                        .line_to((x_start,  y + y_slide + height))
-                        .close()
+        let starts: Vec<i64> = match pages {
+            Pages::Count(count) => {
+                let count = count.clamp(1, MAX_PAGES) as i64;
+                // Rounded up, so the last page reaches the end.
+                let length = (range.1 - range.0 + count - 1) / count;
+                (0..count).map(|page|range.0 + page * length).collect()
+            },
+            Pages::Every(interval) => match (from_stamp(range.0), from_stamp(range.1)) {
+                (Some(from), Some(to)) => {
+                    let interval = interval.resolve(&from, &to);
+                    let first = interval.floor(&from).unwrap_or(from);
+                    let mut starts: Vec<i64> = interval.ticks(&first, &to).iter().map(to_stamp).collect();
+                    // Whatever starts exactly at the end is on the page before already.
+                    if starts.len() > 1 && starts.last() == Some(&range.1) {
+                        starts.pop();
+                    }
+                    starts.truncate(MAX_PAGES);
+                    match starts.is_empty() {
+                        true => vec![range.0],
+                        false => starts,
+                    }
                 },
-                None => {   // If single point in time...
-                    Data::new()
-                        .move_to((x_start, y + y_slide))
-                        .line_to((x_start, y + y_slide + height))
-                        .close()
+                _ => vec![range.0],
+            },
+        };
+        let length = starts.iter()
+            .zip(starts.iter().skip(1).chain(std::iter::once(&range.1)))
+            .map(|(start, end)|end - start)
+            .max()
+            .unwrap_or(0);
+        starts.into_iter().map(|start|(start, start + length)).collect()
+    }
+
+    /// Lays out the part of the timeline within `range`.
+    fn scene_within(&self, options: &RenderOptions, range: &(i64, i64)) -> Scene {
+        let started = Instant::now();
+        let range = *range;
+        let mut scene = Scene { width: self.x, height: self.y, style: self.style.clone(), shapes: vec![] };
+        if range.1 - range.0 == 0 { return scene; }
+        // Drawn first, to end up behind the events.
+        if let Some(buckets) = options.heat {
+            self.lay_out_heat(&mut scene, &range, buckets);
+        }
+        if let Some(interval) = options.grid {
+            lay_out_grid(&mut scene, &range, &interval);
+        }
+        self.lay_out_events(&mut scene, &range, options, (0, 1));
+        self.lay_out_lines(&mut scene, &range, options);
+        self.lay_out_images(&mut scene, &range);
+        if let Some(buckets) = options.density {
+            self.lay_out_density(&mut scene, &range, buckets);
+        }
+        if options.ruler {
+            lay_out_ruler(&mut scene, &range);
+        }
+        debug!("Laid out {} shapes in {:.1?}", thousands(scene.shapes.len()), started.elapsed());
+        scene
+    }
+
+    /// Every named node holding events, in document order, placed where
+    /// `scene()` draws them.
+    pub fn contents(&self, options: &RenderOptions) -> Vec<Entry> {
+        if self.data.is_empty() { return vec![]; }
+        let range = self.data.range();
+        let lang = options.lang.as_deref();
+        self.data.iter_visits(0.0, 1.0)
+            .filter_map(|visit|{
+                let name = visit.node.name_in(lang)?;
+                let (x, end) = visit.node.location(range)?;
+                Some(Entry {
+                    name: name.to_string(),
+                    depth: visit.depth,
+                    x: x * self.x,
+                    end: end * self.x,
+                    y: self.spacing.top_of(visit.offset, visit.depth, self.y),
+                })
+            })
+            .collect()
+    }
+
+    /// Lays this document and `other` out on one time axis spanning both,
+    /// e.g. to compare what was planned with what happened. Each row is
+    /// shared, with the events of `other` in its lower half.
+    pub fn overlay_scene(&self, other: &SagaDoc, options: &RenderOptions) -> Scene {
+        let mut scene = Scene { width: self.x.max(other.x), height: self.y.max(other.y), style: self.style.clone(), shapes: vec![] };
+        if self.data.is_empty() && other.data.is_empty() { return scene; }
+        let (ours, theirs) = (self.data.range(), other.data.range());
+        let range = (ours.0.min(theirs.0), ours.1.max(theirs.1));
+        if range.1 - range.0 == 0 { return scene; }
+        if let Some(interval) = options.grid {
+            lay_out_grid(&mut scene, &range, &interval);
+        }
+        self.lay_out_events(&mut scene, &range, options, (0, 2));
+        other.lay_out_events(&mut scene, &range, options, (1, 2));
+        self.lay_out_lines(&mut scene, &range, options);
+        if options.ruler {
+            lay_out_ruler(&mut scene, &range);
+        }
+        scene
+    }
+
+    /// Places every event within `range`, in the part of each row given by
+    /// `layer`: its index, out of how many documents are drawn together.
+    fn lay_out_events(&self, scene: &mut Scene, range: &(i64, i64), options: &RenderOptions, layer: (usize, usize)) {
+        let lang = options.lang.as_deref();
+        let dates = self.date_format(options);
+        let width = scene.width;
+        let share = self.spacing.row_height(scene.height) / layer.1 as f64;
+        let mut shown = Shown::default();
+        let mut captions = Captions::default();
+        let mut annotations = Annotations::default();
+        self.data.walk(&mut shown);
+        // Each collapsed node is one band over all of its events, cut at the
+        // edges of the page, and drawn first to go under the events around it.
+        for (node, at) in shown.collapsed {
+            let (x, end) = match node.location(*range) {
+                Some((x, end)) if end >= 0.0 && x <= 1.0 => (x.max(0.0) * width, end.min(1.0) * width),
+                _ => continue,
+            };
+            let row = self.spacing.row_top(&at, scene.height) + share * layer.0 as f64;
+            let (end, mark, label) = match end > x {
+                true => (Some(end), self.style.spans.within(row, share), self.style.spans.label),
+                false => (None, self.style.points.within(row, share), self.style.points.label),
+            };
+            let name = node.name_in(lang).unwrap_or("(No name)");
+            let count = node.iter_events().count();
+            scene.shapes.push(Shape::Event {
+                x,
+                end,
+                y: mark.0,
+                height: mark.1,
+                status: None,
+                title: format!("{} ({} event{})", name, count, if count == 1 { "" } else { "s" }),
+                layer: layer.0,
+                fill: None,
+            });
+            if options.captions {
+                captions.place(scene, (x, end), mark, share, label, name);
+            }
+        }
+        // Later shapes are drawn over earlier ones, so the highest layers go last.
+        shown.events.sort_by_key(|(event, _)|event.layer());
+        for (event, at) in shown.events {
+            // Transform the data points into screen space coords.
+            let mut locs = event.location(*range);
+            let outside = self.data.line_span_at(&at.path)
+                .filter(|span|event.dates().exceeds(span));
+            if let (Outside::Clip, Some(span)) = (options.outside, outside) {
+                let (from, to) = span.bounds();
+                let (a, b) = (fraction(from, range), fraction(to, range));
+                match locs {
+                    (x, None) if x < a || x > b => continue,
+                    (x, Some(end)) if end < a || x > b => continue,
+                    (x, end) => locs = (x.max(a), end.map(|end|end.min(b))),
+                }
+            }
+            // Only when drawing one page of many: events on other pages are
+            // left to them, and those running over the edges cut there.
+            let (earlier, later) = match locs {
+                (x, None) if !(0.0..=1.0).contains(&x) => continue,
+                (x, Some(end)) if end < 0.0 || x > 1.0 => continue,
+                // Only touching the edge, so drawn on the neighbouring page.
+                (x, Some(end)) if (x < 0.0 && end == 0.0) || (x == 1.0 && end > 1.0) => continue,
+                (x, end) => (x < 0.0, end.is_some_and(|end|end > 1.0)),
+            };
+            locs = (locs.0.max(0.0), locs.1.map(|end|end.min(1.0)));
+            let row = self.spacing.row_top(&at, scene.height) + share * layer.0 as f64;
+            // Points and spans are styled apart, each within its row.
+            let ((y, height), label) = match locs.1 {
+                Some(_) => (self.style.spans.within(row, share), self.style.spans.label),
+                None => (self.style.points.within(row, share), self.style.points.label),
+            };
+            let duration = match event.dates().duration() {
+                Some(duration) if options.durations => format!(", {}", duration),
+                _ => String::new(),
+            };
+            scene.shapes.push(Shape::Event {
+                x: locs.0 * width,
+                end: locs.1.map(|some|some * width),
+                y,
+                height,
+                status: event.status(),
+                title: format!("{} ({}{})", event.name_in(lang), dates.format_dates(event.dates()), duration),
+                layer: layer.0,
+                fill: self.fill(event, &at, options).map(|color|color.to_string()),
+            });
+            if options.outside == Outside::Badge && outside.is_some() {
+                scene.shapes.push(Shape::Badge { x: locs.0 * width, y: row });
+            }
+            let drawn = (locs.0 * width, locs.1.map(|end|end * width));
+            if options.captions {
+                captions.place(scene, drawn, (y, height), share, label, event.name_in(lang));
+            }
+            let described = options.annotate.as_deref()
+                .filter(|query|event.matches(query))
+                .and_then(|_|event.descriptions_in(lang).first());
+            if let Some(description) = described {
+                annotations.place(scene, drawn, (y, height), description);
+            }
+            if earlier {
+                scene.shapes.push(Shape::Continues { x: 0.0, y: row, later: false });
+            }
+            if later {
+                scene.shapes.push(Shape::Continues { x: width, y: row, later: true });
+            }
+        }
+    }
+
+    /// Splits the background of every row holding events into `buckets`
+    /// slices of `range`, tinted by how many of its events fall in each,
+    /// relative to the busiest slice of any row.
+    fn lay_out_heat(&self, scene: &mut Scene, range: &(i64, i64), buckets: usize) {
+        let mut shown = Shown::default();
+        self.data.walk(&mut shown);
+        // Nodes drawn on the same row share a background.
+        let mut rows: Vec<(f64, Vec<usize>)> = vec![];
+        for (event, at) in shown.events {
+            let y = self.spacing.row_top(&at, scene.height);
+            let index = match rows.iter().position(|(top, _)|*top == y) {
+                Some(index) => index,
+                None => {
+                    rows.push((y, vec![0; buckets]));
+                    rows.len() - 1
                 },
             };
-            let path = SvgPath::new()
-                .set("fill", "#C3B2A4")
-                .set("stroke", "#2e3d50")
-                .set("stroke-width", 2)
-                .set("d", data);
-            document.append(path);
-            // document.append(svg_node);
-        }
-        self.paint_lines(&mut document, &range, y_slide);
-        document.set("saga_doc", "TODO: Add the deserialized json here.")
-    }
-
-    fn event_to_data(&self, event: &Event, depth: usize, offset: f64,
-                     scale: f64, y_slide: f64, range: (i64, i64)) -> Box<dyn SvgNode> {
-        use svg::node::element::{Line, Rectangle};
-        let locs = event.location(range);
-        let y = offset * scale * self.y * depth as f64;
-        let height = 0.2 * self.y; // TODO: Add height:f64 to Node.
-        match locs {
-            (start, Some(end)) => {
-                let width = (end - start) as f64 * self.x;
-                let rect = Rectangle::new()
-                    .set("x",      start)
-                    .set("y",      y + y_slide)
-                    .set("width",  width)
-                    .set("height", height);
-                Box::new(rect)
-            },
-            (start, None) => {
-                let line = Line::new()
-                    .set("x1", start).set("y1", y+y_slide)
-                    .set("x2", start).set("y2", height);
-                Box::new(line)
-            },
+            if let Some(slices) = event.dates().buckets(range, buckets) {
+                rows[index].1[slices].iter_mut().for_each(|count|*count += 1);
+            }
+        }
+        let most = rows.iter().flat_map(|(_, counts)|counts.iter()).max().copied().unwrap_or(0);
+        if most == 0 { return; }
+        let width = scene.width / buckets as f64;
+        for (y, counts) in rows.into_iter() {
+            for (i, count) in counts.into_iter().enumerate().filter(|(_, count)|*count > 0) {
+                scene.shapes.push(Shape::Band {
+                    x: i as f64 * width,
+                    end: (i + 1) as f64 * width,
+                    y,
+                    height: self.spacing.row_height(scene.height),
+                    heat: count as f64 / most as f64,
+                });
+            }
+        }
+    }
+
+    /// Draws a bar for each of `buckets` slices of `range`, as tall as how
+    /// many events fall in it, relative to the busiest.
+    fn lay_out_density(&self, scene: &mut Scene, range: &(i64, i64), buckets: usize) {
+        let counts = self.data.density(range, buckets);
+        let most = counts.iter().max().copied().unwrap_or(0);
+        if most == 0 { return; }
+        let band = 0.1 * scene.height;
+        let width = scene.width / buckets as f64;
+        for (i, count) in counts.into_iter().enumerate().filter(|(_, count)|*count > 0) {
+            scene.shapes.push(Shape::Bar {
+                x: i as f64 * width,
+                end: (i + 1) as f64 * width,
+                y: scene.height,
+                height: band * count as f64 / most as f64,
+                count,
+            });
+        }
+    }
+
+    fn lay_out_lines(&self, scene: &mut Scene, range: &(i64, i64), options: &RenderOptions) {
+        let dates = self.date_format(options);
+        let lines = self.data.lines(range, options.outside == Outside::Extend);
+        for line in lines.iter().filter(|line|line.end >= 0.0 && line.start <= 1.0) {
+            let y = self.spacing.top_of(line.y, line.depth, scene.height);
+            // Cut at the edges of the page.
+            let (start, end) = (line.start.max(0.0), line.end.min(1.0));
+            scene.shapes.push(Shape::Line { start: start * scene.width, end: end * scene.width, y });
+            // Labeled tick marks on the interval's boundaries within the line.
+            let stamp = |frac: f64|from_stamp(range.0 + ((range.1 - range.0) as f64 * frac) as i64);
+            if let (Some(interval), Some(from), Some(to)) = (line.interval, stamp(start), stamp(end)) {
+                for dt in interval.ticks(&from, &to) {
+                    let x = x_of(&dt, range) * scene.width;
+                    scene.shapes.push(Shape::Tick { x, y });
+                    scene.shapes.push(Shape::Label { x, y: y + 24.0, text: self.axis.label(&dt, &*dates) });
+                }
+            }
+        }
+    }
+
+    /// Draws the image of each shown node whose SVG was read, a row high,
+    /// just before its events start, or against the left edge if there's
+    /// no room.
+    fn lay_out_images(&self, scene: &mut Scene, range: &(i64, i64)) {
+        let size = self.spacing.row_height(scene.height);
+        for visit in self.data.iter_shown(0.0, 1.0) {
+            let svg = match visit.node.image().and_then(|image|self.images.get(image)) {
+                Some(svg) => svg,
+                None => continue,
+            };
+            let x = match visit.node.location(*range) {
+                Some((x, _)) if (0.0..=1.0).contains(&x) => x * scene.width,
+                _ => continue,
+            };
+            scene.shapes.push(Shape::Image {
+                x: (x - size).max(0.0),
+                y: self.spacing.top_of(visit.offset, visit.depth, scene.height),
+                size,
+                svg: svg.clone(),
+            });
+        }
+    }
+
+    /// Uses the document's calendar if it has one. Otherwise picks the locale
+    /// requested by `options`, then the document's, then the fallback, laid
+    /// out by the date pattern requested by `options` or the document's.
+    fn date_format<'a>(&'a self, options: &'a RenderOptions) -> Box<dyn DateFormat + 'a> {
+        if let Some(calendar) = &self.calendar {
+            return Box::new(calendar);
+        }
+        let locale = options.locale.as_deref()
+            .or(self.locale.as_deref())
+            .and_then(Locale::find)
+            .unwrap_or(Locale::fallback());
+        match options.date_pattern.as_deref().or(self.date_pattern.as_deref()) {
+            Some(pattern) => Box::new(DatePattern { pattern, locale }),
+            None => Box::new(locale),
+        }
+    }
+
+    /// Getter for the calendar.
+    pub fn calendar(&self) -> Option<&Calendar> { self.calendar.as_ref() }
+
+    /// The locale dates are presented in, the fallback if none is set.
+    pub fn locale(&self) -> &'static Locale {
+        self.locale.as_deref().and_then(Locale::find).unwrap_or(Locale::fallback())
+    }
+
+    /// Every color scheme, by name.
+    pub fn color_schemes(&self) -> &BTreeMap<String, Colors> { &self.color_schemes }
+
+    pub fn active_scheme(&self) -> Option<&str> { self.active_scheme.as_deref() }
+
+    /// Adds a scheme called `name`, unless there is one already.
+    pub fn add_scheme(&mut self, name: &str, colors: Colors) -> Result<(), SchemeError> {
+        if self.color_schemes.contains_key(name) {
+            return Err(SchemeError::Exists(name.to_string()));
+        }
+        self.color_schemes.insert(name.to_string(), colors);
+        Ok(())
+    }
+
+    /// Renames a scheme, along with every color, node and the active scheme
+    /// referring to it.
+    pub fn rename_scheme(&mut self, from: &str, to: &str) -> Result<(), SchemeError> {
+        if self.color_schemes.contains_key(to) {
+            return Err(SchemeError::Exists(to.to_string()));
+        }
+        let colors = self.color_schemes.remove(from)
+            .ok_or_else(||SchemeError::NotFound(from.to_string()))?;
+        self.color_schemes.insert(to.to_string(), colors);
+        self.data.visit_scheme_refs_mut(&mut |name|{
+            if *name == from {
+                *name = to.to_string();
+            }
+        });
+        if self.active_scheme.as_deref() == Some(from) {
+            self.active_scheme = Some(to.to_string());
+        }
+        Ok(())
+    }
+
+    /// Deletes a scheme, and stops it being active. Returns how many nodes
+    /// and events still refer to it, which are drawn as if they had no color.
+    pub fn delete_scheme(&mut self, name: &str) -> Result<usize, SchemeError> {
+        self.color_schemes.remove(name)
+            .ok_or_else(||SchemeError::NotFound(name.to_string()))?;
+        if self.active_scheme.as_deref() == Some(name) {
+            self.active_scheme = None;
+        }
+        let mut uses = 0;
+        self.data.visit_scheme_refs_mut(&mut |used|{
+            if *used == name { uses += 1; }
+        });
+        Ok(uses)
+    }
+
+    /// Makes the scheme called `name` the active one, or none when `None`.
+    pub fn set_active_scheme(&mut self, name: Option<&str>) -> Result<(), SchemeError> {
+        if let Some(name) = name {
+            if !self.color_schemes.contains_key(name) {
+                return Err(SchemeError::NotFound(name.to_string()));
+            }
+        }
+        self.active_scheme = name.map(|name|name.to_string());
+        Ok(())
+    }
+
+    /// The color `color` stands for, the first of a scheme's colors.
+    fn resolve<'a>(&'a self, color: &'a ColorRef) -> Option<&'a Color> {
+        match color {
+            ColorRef::Rgb(color) => Some(color),
+            ColorRef::Scheme(name) => self.color_schemes.get(name)?.first(),
+        }
+    }
+
+    /// Color `index` of the scheme called `name`, going back to its first
+    /// color after its last, with indices counting from 1.
+    fn in_turn(&self, name: &str, index: usize) -> Option<&Color> {
+        let colors = self.color_schemes.get(name)?;
+        colors.get((index - 1) % colors.len().max(1))
+    }
+
+    /// Color of `event`, found at `at`: its own, else that of the nearest
+    /// node above it with a color or a scheme, else the active scheme's,
+    /// unless `options` picked another. Schemes give each value directly
+    /// under their node the next color.
+    fn fill<'a>(&'a self, event: &'a Event, at: &Position, options: &RenderOptions) -> Option<&'a Color> {
+        if let Some(color) = event.color().and_then(|color|self.resolve(color)) {
+            return Some(color);
+        }
+        for depth in (0..at.path.len()).rev() {
+            if let Ok(QueryRef::Node(node)) = self.data.query_ref(&at.path[..depth]) {
+                let color = node.color()
+                    .and_then(|color|self.resolve(color))
+                    .or_else(||self.in_turn(node.scheme()?, at.path[depth]));
+                if color.is_some() {
+                    return color;
+                }
+            }
+        }
+        let scheme = options.scheme.as_deref()
+            .filter(|name|self.color_schemes.contains_key(*name))
+            .or(self.active_scheme.as_deref());
+        self.in_turn(scheme?, at.path[0])
+    }
+
+    /// Fills in the document's calendar, unless `options` already picked a date format.
+    pub fn print_options<'a>(&'a self, options: PrintOptions<'a>) -> PrintOptions<'a> {
+        PrintOptions {
+            dates: options.dates.or(self.calendar.as_ref().map(|c|c as &dyn DateFormat)),
+            ..options
         }
-        /*
-        let x_start = locs.0 as f64 * self.x;
-        let x_end = locs.1.map(|some|some as f64 * self.x);
-        let mut data = Data::new();
-        // Draw either a vertical line, or a box.
-        data.append(Command::Move(Absolute, (x_start, y + y_slide).into()));
-        if let Some(some_end) = x_end {
-            data.append(Command::Line(Absolute, (some_end, y + y_slide + height).into()));
-            data.append(Command::Line(Absolute, (some_end, y + y_slide + height).into()));
-        }
-        data.append(Command::Line(Absolute, (x_start, y + y_slide + height).into()));
-        todo!();
-        */
-    }
-
-    fn paint_lines(&self, doc: &mut Document, range: &(i64, i64), slide: f64) {
-        for line in self.data.lines(range).iter() {
-            println!("> Line.y: {}", line.y);
-            let data = Data::new()
-                .move_to((line.start * self.x, line.y * self.y + slide))
-                .line_to((line.end   * self.x, line.y * self.y + slide))
-                .close();
-            let path = SvgPath::new()
-                .set("fill", "#C3B2A4")
-                .set("stroke", "#000000")
-                .set("stroke-width",5)
-                .set("d", data);
-            doc.append(path);
-        }        
     }
 
     /// Interactively build an `Node` and place it at the requested location.
     pub fn add_node(&mut self, query: &str) -> Result<(), SagaDocError> {
         let path = parse_to_int_path(query)?;
-        match self.data.query(&path[..])? {
+        match self.data.query_mut(&path[..])? {
             Query::Node(node) => {
                 let opt_name = input::ask_user("Name? [Y/n]")?;
                 // TODO: Ask for color override (impl parse::<Color>()).
                 // Make events's for as long as the user is willing to make them.
                 let mut children: Vec<Value> = vec![];
                 while let Ok(true) = input::ask_bool("Add Event? [Y/n]") {
-                    let event: Value = input::make_event()?.into_value();
+                    let event: Value = input::make_event(self.calendar.as_ref())?.into_value();
                     children.push(event);
                 }
                 let new_node = Node::new(opt_name, children);
@@ -188,9 +1166,10 @@ impl SagaDoc {
     /// Interactively build an `Event` and place it at the requested location.
     pub fn add_event(&mut self, query: &str) -> Result<(), SagaDocError> {
         let path = parse_to_int_path(query)?;
-        match self.data.query(&path[..])? {
+        let calendar = self.calendar.as_ref();
+        match self.data.query_mut(&path[..])? {
             Query::Node(node) => {
-                let wrapped_event = input::make_event()?.into_value();
+                let wrapped_event = input::make_event(calendar)?.into_value();
                 node.push(wrapped_event);
                 Ok(())
             },
@@ -198,79 +1177,221 @@ impl SagaDoc {
         }
     }
 
+    /// Appends `events`, in order, to the node at the requested location.
+    /// None are added if any of them ends before it starts.
+    pub fn add_events(&mut self, query: &str, events: Vec<Event>) -> Result<(), SagaDocError> {
+        let path = parse_to_int_path(query)?;
+        for event in events.iter() {
+            event.dates().clone().checked()?;
+        }
+        match self.data.query_mut(&path[..])? {
+            Query::Node(node) => {
+                events.into_iter().for_each(|event|node.push(event.into_value()));
+                Ok(())
+            },
+            Query::Event(_) => Err(SagaDocError::AddToEvent),
+        }
+    }
+
     /// Creates a new SagaDoc who's value is a list of the values of each
     /// SagaDoc in the given vector.
     pub fn catenate(list: Vec<SagaDoc>) -> SagaDoc {
         let mut doc = SagaDoc::blank();
         list.into_iter().for_each(|mut item|{
             // Build up new doc data.
+            doc.data.push(std::mem::replace(&mut item.data, Node::from_vec(vec![])).into_value());
             doc.x = doc.x.max(item.x);
             doc.y = doc.y.max(item.y);
             doc.padding = doc.padding.max(item.padding);
-            item.color_schemes
-                .drain()
-                .for_each(|(k,v)|{doc.color_schemes.insert(k,v);});
+            if doc.locale.is_none() { doc.locale = item.locale.take(); }
+            if doc.date_pattern.is_none() { doc.date_pattern = item.date_pattern.take(); }
+            if doc.active_scheme.is_none() { doc.active_scheme = item.active_scheme.take(); }
+            if doc.axis.is_calendar() { doc.axis = std::mem::take(&mut item.axis); }
+            if doc.calendar.is_none() { doc.calendar = item.calendar.take(); }
+            if doc.spacing.is_default() { doc.spacing = item.spacing.clone(); }
+            if doc.style.is_default() { doc.style = item.style.clone(); }
+            doc.color_schemes.append(&mut item.color_schemes);
         });
         doc
     }
 
-    pub fn print(&self, verbose: bool) -> String {
-        self.data.print(0_usize, verbose)
+    /// A copy of self holding only the events also in `other`, matched by
+    /// name and dates, such as the planned events that happened.
+    pub fn intersect(&self, other: &SagaDoc) -> SagaDoc {
+        self.compare(other, true)
+    }
+
+    /// A copy of self without the events also in `other`, matched by name
+    /// and dates, such as the planned events that didn't happen.
+    pub fn subtract(&self, other: &SagaDoc) -> SagaDoc {
+        self.compare(other, false)
+    }
+
+    /// Keeps the events of a copy of self that are in `other` if `shared`,
+    /// and those that aren't otherwise.
+    fn compare(&self, other: &SagaDoc, shared: bool) -> SagaDoc {
+        let key = |event: &Event|(event.name().to_string(), event.dates().to_string());
+        let theirs: BTreeSet<(String, String)> = other.data.iter_events().map(key).collect();
+        let mut doc = self.clone();
+        let dropped = doc.data.retain_events(&mut |event|theirs.contains(&key(event)) == shared);
+        debug!("Dropped {} of {} events", thousands(dropped), thousands(self.data.iter_events().count()));
+        doc
+    }
+
+    pub fn print(&self, options: &PrintOptions) -> String {
+        self.data.print(0_usize, &self.print_options(*options))
+    }
+
+    /// Lists every event by its start date, ignoring the tree apart from
+    /// giving each one's path. Events starting together keep their order.
+    pub fn print_chrono(&self, options: &PrintOptions) -> String {
+        let options = self.print_options(*options);
+        let mut placed = Placed::default();
+        self.data.walk(&mut placed);
+        placed.0.sort_by_key(|(event, _)|*event.dates().start());
+        placed.0.iter()
+            .map(|(event, at)|format!("{}: {}", path_string(&at.path), event.print(0, &options)))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl FromStr for Color {
+    type Err = ColorParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fail = ||ColorParseError(s.to_string());
+        let hex = s.trim().strip_prefix('#').ok_or_else(fail)?;
+        if !hex.chars().all(|c|c.is_ascii_hexdigit()) {
+            return Err(fail());
+        }
+        // Short colors repeat each digit, so "#f80" is "#ff8800".
+        let channel = |i: usize, width: usize|{
+            let digits = &hex[i * width..(i + 1) * width];
+            u8::from_str_radix(&digits.repeat(3 - width), 16)
+        };
+        let width = match hex.len() {
+            3 => 1,
+            6 => 2,
+            _ => return Err(fail()),
+        };
+        Ok(Color {
+            r: channel(0, width).map_err(|_|fail())?,
+            g: channel(1, width).map_err(|_|fail())?,
+            b: channel(2, width).map_err(|_|fail())?,
+        })
+    }
+}
+
+impl FromStr for ColorRef {
+    type Err = ColorParseError;
+    /// Anything starting with '#' is read as a color, anything else names a scheme.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().starts_with('#') {
+            true => s.parse::<Color>().map(ColorRef::Rgb),
+            false => Ok(ColorRef::Scheme(s.trim().to_string())),
+        }
     }
 }
 
-mod input {
-    use std::io::Write;
+impl std::fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "'{}' isn't a hex color, such as #ff8800", self.0)
+    }
+}
+
+impl std::error::Error for ColorParseError {}
+
+impl std::fmt::Display for OutsideParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "'{}' isn't one of: extend, clip, badge", self.0)
+    }
+}
 
-    use super::SagaDocError;
-    use super::super::events::{Dates, Event};
+impl std::error::Error for OutsideParseError {}
 
-    /// Takes user's input after printing a prompt.
-    pub fn get_user(prompt: &str) -> Result<String, SagaDocError> {
-        print!("{} > ", prompt);
-        std::io::stdout().flush()     // Ensure we print before we read stdin.
-            .map_err(|e|SagaDocError::IoError(e))?;
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)
-            .map_err(|e|SagaDocError::IoError(e))?;
-        Ok(input.trim_end().to_string())
+impl FromStr for Outside {
+    type Err = OutsideParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "extend" => Ok(Outside::Extend),
+            "clip"   => Ok(Outside::Clip),
+            "badge"  => Ok(Outside::Badge),
+            other => Err(OutsideParseError(other.to_string())),
+        }
     }
+}
 
-    /// Asks yes/no question to user:
-    ///   - Answers beginning with 'y' or 'Y' allows the user
-    ///     to give a follow up answer, returning Ok(Some(_)).
-    ///   - Answers beginning with anything else returns Ok(None).
-    ///   - Returns Err(_) if an Io error occured.
-    pub fn ask_user(prompt: &str) -> Result<Option<String>, SagaDocError> {
-        match ask_bool(prompt)? {
-            true => get_user("").map(|s|Some(s)),
-            false => Ok(None),
+impl std::fmt::Display for SchemeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SchemeError::NotFound(name) => write!(f, "no color scheme is called '{}'", name),
+            SchemeError::Exists(name) => write!(f, "there is a color scheme called '{}' already", name),
         }
     }
+}
+
+impl std::error::Error for SchemeError {}
 
-    /// Ask the user a for a yes/no, intrepting it as a `bool`.
-    pub fn ask_bool(prompt: &str) -> Result<bool, SagaDocError> {
-        print!("{} > ", prompt);
-        std::io::stdout().flush()     // Ensure we print before we read stdin.
-            .map_err(|e|SagaDocError::IoError(e))?;
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)
-            .map_err(|e|SagaDocError::IoError(e))?;
-        Ok(input.starts_with(&['y', 'Y']))
+impl From<InvertedDates> for SagaDocError {
+    fn from(err: InvertedDates) -> Self {
+        SagaDocError::InvertedDates(err)
     }
+}
 
-    /// Internal function called by `SagaDoc::add_*` to make event instances.
-    pub fn make_event() -> Result<Event, SagaDocError> {
-        let name = get_user("Name")?;
-        let date_input: String = get_user("Date")?;
-        println!("[{}]", date_input);
-        let date = date_input.parse::<Dates>()?;
-        let mut event = Event::new(&name, date);
-        // Get desc's for as long as the user is willing to give them.
-        while let Ok(Some(input)) = ask_user("Description [Y/n]") {
-            event.with_desc(&input);
+impl From<CalendarError> for SagaDocError {
+    fn from(err: CalendarError) -> Self {
+        SagaDocError::CalendarParse(err)
+    }
+}
+
+impl std::fmt::Display for SagaDocError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SagaDocError::PathParse(e) => write!(f, "bad path, expected numbers like 1:2: {}", e),
+            SagaDocError::PathFind(e) => write!(f, "{}", e),
+            SagaDocError::AddToEvent => write!(f, "can't add to an event, only to a node"),
+            SagaDocError::DtParse(e) => write!(f, "bad date: {}", e),
+            SagaDocError::CalendarParse(e) => write!(f, "{}", e),
+            SagaDocError::InvertedDates(e) => write!(f, "{}", e),
+            SagaDocError::Interrupted => write!(f, "interrupted"),
+            SagaDocError::Readline(e) => write!(f, "{}", e),
+            SagaDocError::IoError(e) => write!(f, "{}", e),
+            SagaDocError::EndOfInput => write!(f, "input ended before everything asked for was given"),
+            SagaDocError::LineTooLong(max) => write!(f, "line too long, at most {} bytes are read", thousands(*max)),
+            SagaDocError::NotUtf8(Some(at)) => write!(f, "not valid UTF-8 from byte {} on", at),
+            SagaDocError::NotUtf8(None) => write!(f, "not valid UTF-8"),
         }
-        Ok(event)
+    }
+}
+
+impl std::error::Error for SagaDocError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SagaDocError::PathParse(e) => Some(e),
+            SagaDocError::PathFind(e) => Some(e),
+            SagaDocError::DtParse(e) => Some(e),
+            SagaDocError::CalendarParse(e) => Some(e),
+            SagaDocError::InvertedDates(e) => Some(e),
+            SagaDocError::Readline(e) => Some(e),
+            SagaDocError::IoError(e) => Some(e),
+            SagaDocError::AddToEvent     |
+            SagaDocError::Interrupted    |
+            SagaDocError::EndOfInput     |
+            SagaDocError::LineTooLong(_) |
+            SagaDocError::NotUtf8(_) => None,
+        }
+    }
+}
+
+impl From<SchemeError> for MainError {
+    fn from(err: SchemeError) -> Self {
+        MainError::Scheme(err)
     }
 }
 
@@ -281,8 +1402,15 @@ impl From<SagaDocError> for super::MainError {
             SagaDocError::PathParse(e) => MainError::BadPathParse(e),
             SagaDocError::PathFind(e)  => MainError::NodeNotFound(e),
             SagaDocError::DtParse(e)   => MainError::BadDateTimeParse(e),
+            SagaDocError::CalendarParse(e) => MainError::BadCalendarParse(e),
+            SagaDocError::InvertedDates(e) => MainError::InvertedDates(e),
+            SagaDocError::Interrupted  => MainError::Interrupted,
+            SagaDocError::Readline(e)  => MainError::Readline(e),
             SagaDocError::IoError(e)   => MainError::FileIO(e),
             SagaDocError::AddToEvent   => MainError::AddToEvent,
+            SagaDocError::EndOfInput   => MainError::EndOfInput,
+            SagaDocError::LineTooLong(max) => MainError::LineTooLong(max),
+            SagaDocError::NotUtf8(at)  => MainError::NotUtf8(at),
         }
     }
 }
@@ -293,13 +1421,459 @@ pub fn parse_to_int_path(query: &str) -> Result<Vec<usize>, SagaDocError> {
         .split(":")
         .map(|s|s.trim())
         .map(|s|s.parse::<usize>())
-        .try_collect::<Vec<usize>>()
+        .collect::<Result<Vec<usize>, _>>()
         .map_err(|e|SagaDocError::PathParse(e))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::super::saga::parse_to_int_path;
+    use super::super::saga::{parse_to_int_path, Color, ColorRef, SagaDoc, SagaDocError};
+    use super::super::MainError;
+
+    #[test]
+    fn test_reading_and_writing() {
+        let mut doc = SagaDoc::blank();
+        doc.get_data_mut().set_name(Some("Campaigns"));
+        let mut bytes = Vec::new();
+        doc.to_writer(&mut bytes).unwrap();
+        let read = SagaDoc::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(read.get_data().name_in(None), Some("Campaigns"));
+        assert!(SagaDoc::from_reader("{}".as_bytes()).is_err());
+        let latin1 = b"{\"x\":1.0,\"y\":1.0,\"padding\":0.0,\"data\":{\"children\":[],\"name\":\"Arm\xe9e\"}}";
+        assert!(matches!(SagaDoc::from_reader(&latin1[..]), Err(MainError::NotUtf8(Some(64)))));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("saga-test.json");
+        let path = path.to_str().unwrap();
+        doc.save(path).unwrap();
+        // Nothing is left behind next to the document.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        let loaded = SagaDoc::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.get_data().name_in(None), Some("Campaigns"));
+        let missing = SagaDoc::load(path).err().unwrap().to_string();
+        assert!(missing.ends_with(&format!("in {}", path)));
+    }
+
+    #[test]
+    fn test_concurrent_saves() {
+        let path = std::env::temp_dir().join(format!("saga-race-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        SagaDoc::blank().save(path).unwrap();
+        let (mut first, stamp) = SagaDoc::load_stamped(path).unwrap();
+        let (mut second, other) = SagaDoc::load_stamped(path).unwrap();
+        first.get_data_mut().set_name(Some("First"));
+        let stamp = first.save_unchanged(path, stamp).unwrap();
+        second.get_data_mut().set_name(Some("Second"));
+        let lost = second.save_unchanged(path, other).err().unwrap().to_string();
+        assert!(lost.starts_with("changed by someone else"));
+        // Saving again on top of its own write is fine.
+        let stamp = first.save_unchanged(path, stamp).unwrap();
+        let loaded = SagaDoc::load(path).unwrap();
+        assert_eq!(loaded.get_data().name_in(None), Some("First"));
+        // Of several saves racing from the same stamp, only one gets through.
+        let saved = std::thread::scope(|scope|{
+            let racers: Vec<_> = (0..8).map(|i|scope.spawn(move ||{
+                let mut doc = SagaDoc::load(path).unwrap();
+                doc.get_data_mut().set_name(Some(&format!("Racer {}", i)));
+                doc.save_unchanged(path, stamp).is_ok()
+            })).collect();
+            racers.into_iter().map(|racer|racer.join().unwrap()).filter(|saved|*saved).count()
+        });
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(saved, 1);
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        let verbose = r#"{"x":1920.0,"y":1080.0,"padding":0.0,"color_schemes":{},"data":{"children":[
+            {"type":"Event","name":"Jena","descriptions":[],"datetime":" 14/10/1806 9:05"},
+            {"type":"Node","children":[],"name":null,"style_override":null,"color_override":null,"offset":0.0,"y_scale":1.0,"line":null,"graphs":[]}
+        ],"name":null,"style_override":null,"color_override":null,"offset":0.0,"y_scale":1.0,"line":null,"graphs":[]}}"#;
+        let doc: SagaDoc = serde_json::from_str(verbose).unwrap();
+        let canonical = serde_json::to_string(&doc).unwrap();
+        assert_eq!(canonical, concat!(
+            r#"{"x":1920.0,"y":1080.0,"padding":0.0,"data":{"children":["#,
+            r#"{"type":"Event","name":"Jena","datetime":"14/10/1806 09:05"},{"type":"Node","children":[]}]}}"#,
+        ));
+        let mut pretty = Vec::new();
+        doc.to_writer_pretty(&mut pretty).unwrap();
+        let reread = SagaDoc::from_reader(pretty.as_slice()).unwrap();
+        assert_eq!(serde_json::to_string(&reread).unwrap(), canonical);
+        // Nodes without children aren't taken for empty ones.
+        let legacy = r#"{"x":1920.0,"y":1080.0,"padding":0.0,"data":{"type":"List","next":{"type":"Event"}}}"#;
+        assert!(SagaDoc::from_reader(legacy.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_stable_serialization() {
+        let scheme = |name: &str|format!(r#""{}": [{{"r": 0, "g": 0, "b": 0}}]"#, name);
+        let mut json: serde_json::Value = serde_json::to_value(SagaDoc::blank()).unwrap();
+        json["color_schemes"] = serde_json::from_str(
+            &format!("{{{}}}", ["sea", "autumn", "night", "forest"].map(scheme).join(", "))
+        ).unwrap();
+        let doc: SagaDoc = serde_json::from_value(json).unwrap();
+        let first = serde_json::to_string(&doc).unwrap();
+        let second = serde_json::to_string(&serde_json::from_str::<SagaDoc>(&first).unwrap()).unwrap();
+        assert_eq!(first, second);
+        let order = ["autumn", "forest", "night", "sea"].map(|name|first.find(name).unwrap());
+        assert!(order.windows(2).all(|pair|pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_print_chrono() {
+        use super::super::events::{Event, Node, PrintOptions};
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            Node::from_vec(vec![
+                Event::new("Jena", "14/10/1806".parse().unwrap()).into_value(),
+                Event::new("Ulm", "1805".parse().unwrap()).into_value(),
+            ]).into_value(),
+            Event::new("Austerlitz", "02/12/1805".parse().unwrap()).into_value(),
+        ]);
+        assert_eq!(doc.print_chrono(&PrintOptions::default()), [
+            "1:2: <Event> Ulm, [1805]",
+            "2: <Event> Austerlitz, [02/12/1805]",
+            "1:1: <Event> Jena, [14/10/1806]",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_intersect_and_subtract() {
+        use super::super::events::{Event, Node, Value};
+        let doc = |values: Vec<Value>|{
+            let mut doc = SagaDoc::blank();
+            *doc.get_data_mut() = Node::from_vec(values);
+            doc
+        };
+        let plan = doc(vec![
+            Event::new("Ulm", "20/10/1805".parse().unwrap()).into_value(),
+            Node::new(Some("Prussia".to_string()), vec![
+                Event::new("Jena", "14/10/1806".parse().unwrap()).into_value(),
+                Event::new("Berlin", "27/10/1806".parse().unwrap()).into_value(),
+            ]).into_value(),
+        ]);
+        let reality = doc(vec![
+            Event::new("Ulm", "20/10/1805".parse().unwrap()).into_value(),
+            // Late, so not the one planned.
+            Event::new("Jena", "15/10/1806".parse().unwrap()).into_value(),
+            Event::new("Berlin", "27/10/1806".parse().unwrap()).into_value(),
+        ]);
+        let names = |doc: &SagaDoc|doc.get_data().iter_events().map(|event|event.name().to_string()).collect::<Vec<String>>();
+        let shared = plan.intersect(&reality);
+        assert_eq!(names(&shared), vec!["Ulm", "Berlin"]);
+        assert_eq!(shared.get_data().children().len(), 2);
+        let missed = plan.subtract(&reality);
+        assert_eq!(names(&missed), vec!["Jena"]);
+        // Nothing is left in the root but the node holding it.
+        assert_eq!(missed.get_data().children().len(), 1);
+        assert!(plan.subtract(&plan).get_data().children().is_empty());
+        assert_eq!(names(&plan), vec!["Ulm", "Jena", "Berlin"]);
+    }
+
+    #[test]
+    fn test_spacing() {
+        use super::super::render::Shape;
+        use super::RenderOptions;
+        let text = r#"{"x":80.0,"y":80.0,"padding":0.0,"spacing":{"top":0.125,"per_depth":0.25,"row":0.5},"data":{"children":[
+            {"type":"Event","name":"Root","datetime":"1805"},
+            {"type":"Node","children":[{"type":"Event","name":"Nested","datetime":"1806"}]}
+        ]}}"#;
+        let doc: SagaDoc = serde_json::from_str(text).unwrap();
+        let rows = doc.scene(&RenderOptions::default()).shapes.iter()
+            .filter_map(|shape|match shape {
+                Shape::Event { y, height, .. } => Some((*y, *height)),
+                _ => None,
+            })
+            .collect::<Vec<(f64, f64)>>();
+        assert_eq!(rows, vec![(10.0, 40.0), (30.0, 40.0)]);
+        assert!(serde_json::to_string(&doc).unwrap().contains(r#""spacing":{"top":0.125,"per_depth":0.25,"row":0.5}"#));
+    }
+
+    #[test]
+    fn test_style() {
+        use super::RenderOptions;
+        let text = r#"{"x":80.0,"y":80.0,"padding":0.0,"style":{"stroke_width":0.5,"corner_radius":4.0},"data":{"children":[
+            {"type":"Event","name":"Siege","datetime":"1805 - 1807"}
+        ]}}"#;
+        let doc: SagaDoc = serde_json::from_str(text).unwrap();
+        let svg = doc.draw(&RenderOptions::default()).to_string();
+        assert!(svg.contains(r#"stroke-width="0.5""#));
+        assert!(svg.contains(r#"fill-opacity="1""#));
+        assert!(svg.contains("A4,4,0,0,1,"));
+        assert!(serde_json::to_string(&doc).unwrap().contains(r#""style":{"stroke_width":0.5,"corner_radius":4.0,"opacity":1.0}"#));
+        let plain = SagaDoc::blank();
+        assert!(!serde_json::to_string(&plain).unwrap().contains("style"));
+    }
+
+    #[test]
+    fn test_catenate() {
+        use super::super::events::{Event, Node};
+        let doc = |name: &str, event: &str|{
+            let mut doc = SagaDoc::blank();
+            *doc.get_data_mut() = Node::new(Some(name.to_string()), vec![
+                Event::new(event, "1805".parse().unwrap()).into_value(),
+            ]);
+            doc
+        };
+        let both = SagaDoc::catenate(vec![doc("France", "Ulm"), doc("Britain", "Trafalgar")]);
+        let names = both.get_data().iter_nodes().map(|node|node.name_in(None)).collect::<Vec<Option<&str>>>();
+        assert_eq!(names, vec![None, Some("France"), Some("Britain")]);
+        assert_eq!(both.get_data().iter_events().count(), 2);
+    }
+
+    #[test]
+    fn test_collapsed() {
+        use super::super::events::{Event, Node};
+        use super::super::render::Shape;
+        use super::RenderOptions;
+        let mut campaign = Node::new(Some("Campaign".to_string()), vec![
+            Event::new("Ulm", "20/10/1805".parse().unwrap()).into_value(),
+            Node::from_vec(vec![Event::new("Austerlitz", "02/12/1805".parse().unwrap()).into_value()]).into_value(),
+        ]);
+        campaign.set_collapsed(true);
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            Event::new("Trafalgar", "21/10/1805".parse().unwrap()).into_value(),
+            campaign.into_value(),
+        ]);
+        let scene = doc.scene(&RenderOptions::default());
+        let events = scene.shapes.iter().filter_map(|shape|match shape {
+            Shape::Event { x, end, title, .. } => Some((*x, *end, title.as_str())),
+            _ => None,
+        }).collect::<Vec<(f64, Option<f64>, &str)>>();
+        assert_eq!(events.len(), 2);
+        // One band from the first event hidden to the end of the last, under the rest.
+        assert_eq!((events[0].0, events[0].1, events[0].2), (0.0, Some(scene.width), "Campaign (2 events)"));
+        assert!(events[1].2.starts_with("Trafalgar"));
+    }
+
+    #[test]
+    fn test_layers() {
+        use super::super::events::{Event, Node};
+        use super::super::render::Shape;
+        use super::RenderOptions;
+        let event = |name: &str, layer: i32|{
+            let mut event = Event::new(name, "1805".parse().unwrap());
+            event.set_layer(layer);
+            event.into_value()
+        };
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            event("Ulm", 2),
+            event("War of the Third Coalition", 0),
+            event("Austerlitz", 2),
+            event("Background", -1),
+        ]);
+        let titles = doc.scene(&RenderOptions::default()).shapes.into_iter()
+            .filter_map(|shape|match shape {
+                Shape::Event { title, .. } => title.split(" (").next().map(|name|name.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<String>>();
+        // Drawn bottom up, keeping document order within a layer.
+        assert_eq!(titles, vec!["Background", "War of the Third Coalition", "Ulm", "Austerlitz"]);
+        let json = serde_json::to_string(doc.get_data()).unwrap();
+        assert_eq!(json.matches(r#""layer":2"#).count(), 2);
+        assert!(!json.contains(r#""layer":0"#));
+    }
+
+    #[test]
+    fn test_date_pattern() {
+        use super::super::render::Shape;
+        use super::RenderOptions;
+        let mut doc: SagaDoc = serde_json::from_str(r#"{
+            "x": 1000.0, "y": 500.0, "padding": 0.0,
+            "date_pattern": "{b} {y}",
+            "data": { "line": "1y", "children": [
+                { "type": "Event", "name": "Ulm", "datetime": "20/10/1805" },
+                { "type": "Event", "name": "Wagram", "datetime": "06/07/1809" }
+            ] }
+        }"#).unwrap();
+        let texts = |doc: &SagaDoc, options: &RenderOptions|doc.scene(options).shapes.into_iter()
+            .filter_map(|shape|match shape {
+                Shape::Event { title, .. } => Some(title),
+                Shape::Label { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect::<Vec<String>>();
+        let drawn = texts(&doc, &RenderOptions::default());
+        assert_eq!(drawn[..3], ["Ulm (Oct 1805)", "Wagram (Jul 1809)", "Jan 1806"]);
+        let options = RenderOptions { date_pattern: Some("Q{q} {y}".to_string()), ..RenderOptions::default() };
+        assert_eq!(texts(&doc, &options)[..2], ["Ulm (Q4 1805)", "Wagram (Q3 1809)"]);
+        // Stored as always.
+        let json = serde_json::to_string(&doc).unwrap();
+        assert!(json.contains(r#""datetime":"20/10/1805""#) && json.contains(r#""date_pattern":"{b} {y}""#));
+        doc.date_pattern = None;
+        assert_eq!(texts(&doc, &RenderOptions::default())[0], "Ulm (October 20, 1805)");
+    }
+
+    #[test]
+    fn test_images() {
+        use super::super::events::{Event, Node};
+        use super::super::render::{Renderer, Shape, Svg};
+        use super::RenderOptions;
+        let dir = std::env::temp_dir().join(format!("saga-images-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("flags")).unwrap();
+        std::fs::write(dir.join("flags/prussia.svg"), "<?xml version=\"1.0\"?>\n<svg viewBox=\"0 0 3 2\"><rect width=\"3\" height=\"2\"/></svg>\n").unwrap();
+        let mut prussia = Node::new(Some("Prussia".to_string()), vec![
+            Event::new("Jena", "14/10/1806".parse().unwrap()).into_value(),
+        ]);
+        prussia.set_image(Some("flags/prussia.svg"));
+        let mut missing = Node::new(Some("Austria".to_string()), vec![
+            Event::new("Wagram", "06/07/1809".parse().unwrap()).into_value(),
+        ]);
+        missing.set_image(Some("flags/austria.svg"));
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            Event::new("Ulm", "20/10/1805".parse().unwrap()).into_value(),
+            prussia.into_value(),
+            missing.into_value(),
+        ]);
+        doc.read_images(dir.join("wars.json").to_str().unwrap());
+        let scene = doc.scene(&RenderOptions::default());
+        let images: Vec<&Shape> = scene.shapes.iter().filter(|shape|matches!(shape, Shape::Image { .. })).collect();
+        // Only the image that could be read, without its XML declaration.
+        match images.as_slice() {
+            [Shape::Image { x, size, svg, .. }] => {
+                assert!(*x > 0.0 && *size > 0.0);
+                assert!(svg.starts_with("<svg viewBox"));
+            },
+            _ => panic!("Draws one image, not {:?}", images),
+        }
+        let drawn = Svg::default().render(&scene).to_string();
+        assert!(drawn.contains("<rect width=\"3\" height=\"2\"/></svg>"));
+        // Left out when saving.
+        assert!(!serde_json::to_string(&doc).unwrap().contains("<svg"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_point_and_span_styles() {
+        use super::super::render::{Anchor, Shape};
+        use super::RenderOptions;
+        let text = |style: &str|format!(r#"{{"x":800.0,"y":80.0,"padding":0.0,"style":{},"data":{{"children":[
+            {{"type":"Event","name":"Siege","datetime":"1805 - 1807"}},
+            {{"type":"Event","name":"Jena","datetime":"14/10/1806"}}
+        ]}}}}"#, style);
+        let styled: SagaDoc = serde_json::from_str(&text(r#"{"points":{"marker":"diamond","height":0.5,"label":"above"},"spans":{"label":"beside"}}"#)).unwrap();
+        let plain: SagaDoc = serde_json::from_str(&text("{}")).unwrap();
+        let options = RenderOptions { captions: true, ..RenderOptions::default() };
+        let shapes = |doc: &SagaDoc|doc.scene(&options).shapes;
+        let events = |shapes: &[Shape]|shapes.iter().filter_map(|shape|match shape {
+            Shape::Event { y, height, .. } => Some((*y, *height)),
+            _ => None,
+        }).collect::<Vec<(f64, f64)>>();
+        let (before, after) = (events(&shapes(&plain)), events(&shapes(&styled)));
+        // Spans are as they were, points half as high, in the middle of their row.
+        assert_eq!(before[0], after[0]);
+        assert_eq!(after[1], (before[1].0 + before[1].1 / 4.0, before[1].1 / 2.0));
+        let captions = shapes(&styled).into_iter().filter_map(|shape|match shape {
+            Shape::Caption { y, anchor, .. } => Some((anchor, y)),
+            _ => None,
+        }).collect::<Vec<(Anchor, f64)>>();
+        // The span would fit its caption, but is told to keep it beside, and
+        // runs to the edge of the page so before it.
+        assert_eq!(captions[0].0, Anchor::End);
+        assert_eq!(captions[1].0, Anchor::Middle);
+        assert!(captions[1].1 < after[1].0);
+        let json = serde_json::to_string(&styled).unwrap();
+        assert!(json.contains(r#""points":{"marker":"diamond","height":0.5,"label":"above"},"spans":{"marker":"box","height":1.0,"label":"beside"}"#));
+        assert!(!serde_json::to_string(&plain).unwrap().contains("points"));
+    }
+
+    #[test]
+    fn test_color_parsing() {
+        let ok_cases = [
+            ("#ff8800", "#ff8800"),
+            ("#F80", "#ff8800"),
+            (" #000000 ", "#000000"),
+        ];
+        for (left, right) in ok_cases.iter() {
+            assert_eq!(left.parse::<Color>().unwrap().to_string(), *right);
+        }
+        for bad in ["ff8800", "#ff88", "#gg8800", "#ff880"].iter() {
+            assert!(bad.parse::<Color>().is_err());
+        }
+        assert_eq!("warm".parse::<ColorRef>().unwrap(), ColorRef::Scheme("warm".to_string()));
+        assert_eq!("#f80".parse::<ColorRef>().unwrap(), ColorRef::Rgb("#ff8800".parse().unwrap()));
+        let old: ColorRef = serde_json::from_str(r#"{"r": 255, "g": 136, "b": 0}"#).unwrap();
+        assert_eq!(old, ColorRef::Rgb("#ff8800".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_schemes() {
+        use super::super::edit::Program;
+        use super::super::render::Shape;
+        use super::{RenderOptions, SchemeError};
+        let mut doc = SagaDoc::builder()
+            .node(|n|n.event("Ulm", "1805").event("Austerlitz", "1805"))
+            .event("Jena", "1806")
+            .build()
+            .unwrap();
+        let colors = |hex: &[&str]|hex.iter().map(|c|c.parse::<Color>().unwrap()).collect::<Vec<Color>>();
+        doc.add_scheme("warm", colors(&["#f80", "#c00"])).unwrap();
+        assert_eq!(doc.add_scheme("warm", vec![]), Err(SchemeError::Exists("warm".to_string())));
+        doc.get_data_mut().set_color(Some(ColorRef::Scheme("warm".to_string())));
+        doc.set_active_scheme(Some("warm")).unwrap();
+        assert!(doc.set_active_scheme(Some("cold")).is_err());
+        doc.rename_scheme("warm", "autumn").unwrap();
+        assert_eq!(doc.active_scheme(), Some("autumn"));
+        assert_eq!(doc.get_data().color(), Some(&ColorRef::Scheme("autumn".to_string())));
+        assert_eq!(doc.rename_scheme("warm", "cold"), Err(SchemeError::NotFound("warm".to_string())));
+        // The root's color wins over the active scheme.
+        let fills = |doc: &SagaDoc|doc.scene(&RenderOptions::default()).shapes.iter()
+            .filter_map(|shape|match shape {
+                Shape::Event { fill, .. } => Some(fill.clone().unwrap_or_default()),
+                _ => None,
+            })
+            .collect::<Vec<String>>();
+        assert_eq!(fills(&doc), vec!["#ff8800", "#ff8800", "#ff8800"]);
+        doc.get_data_mut().set_color(None);
+        assert_eq!(fills(&doc), vec!["#ff8800", "#ff8800", "#cc0000"]);
+        // Nodes with a scheme of their own use it for the values in them.
+        doc.add_scheme("cold", colors(&["#00c", "#0cc"])).unwrap();
+        "scheme cold".parse::<Program>().unwrap().eval_at(doc.get_data_mut(), &mut vec![1]).unwrap();
+        assert_eq!(fills(&doc), vec!["#0000cc", "#00cccc", "#cc0000"]);
+        // A scheme picked when rendering stands in for the active one, if the document has it.
+        let jena = |scheme: &str|doc.scene(&RenderOptions { scheme: Some(scheme.to_string()), ..RenderOptions::default() })
+            .shapes.iter()
+            .filter_map(|shape|match shape {
+                Shape::Event { fill, .. } => fill.clone(),
+                _ => None,
+            })
+            .next_back();
+        assert_eq!(jena("cold"), Some("#00cccc".to_string()));
+        assert_eq!(jena("missing"), Some("#cc0000".to_string()));
+        doc.rename_scheme("cold", "winter").unwrap();
+        assert_eq!(doc.get_data().nearest(&[1]).1.scheme(), Some("winter"));
+        assert_eq!(doc.delete_scheme("winter"), Ok(1));
+        assert_eq!(doc.delete_scheme("autumn"), Ok(0));
+        assert_eq!((doc.active_scheme(), fills(&doc)), (None, vec![String::new(); 3]));
+    }
+
+    #[test]
+    fn test_add_events() {
+        use super::super::events::{Event, Node};
+        let mut doc = SagaDoc::blank();
+        *doc.get_data_mut() = Node::from_vec(vec![
+            Event::new("Ulm", "20/10/1805".parse().unwrap()).into_value(),
+            Node::new(Some("Prussia".to_string()), vec![]).into_value(),
+        ]);
+        let events: Vec<Event> = serde_json::from_str(r#"[
+            {"name": "Jena", "datetime": "14/10/1806", "status": "done"},
+            {"name": "Berlin", "datetime": "27/10/1806", "descriptions": ["Entered."]}
+        ]"#).unwrap();
+        doc.add_events("2", events.clone()).unwrap();
+        let names: Vec<&str> = doc.get_data().iter_events().map(|event|event.name()).collect();
+        assert_eq!(names, vec!["Ulm", "Jena", "Berlin"]);
+        assert!(matches!(doc.add_events("1", events), Err(SagaDocError::AddToEvent)));
+        let inverted = vec![
+            Event::new("Eylau", "08/02/1807".parse().unwrap()),
+            Event::new("Backwards", "1807 - 1806".parse().unwrap()),
+        ];
+        assert!(doc.add_events("2", inverted).is_err());
+        assert_eq!(doc.get_data().iter_events().count(), 3);
+    }
 
     #[test]
     fn test_node_querying() {
@@ -0,0 +1,278 @@
+use std::{
+    io::{Read, Write},
+    time::{Duration, Instant},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde_json::Value as Json;
+
+use super::MainError;
+use super::axis::Interval;
+use super::builder::NodeBuilder;
+use super::crypt;
+use super::events::{from_stamp, Dates, Event, Status};
+use super::render::{Renderer, Shape, Terminal};
+use super::saga::{RenderOptions, SagaDoc};
+
+/// Passphrase the encrypted round trip uses, never one the user typed.
+const PASSPHRASE: &str = "saga selftest";
+
+/// Most nodes generated directly under one node.
+const BRANCHES: usize = 3;
+
+/// Color scheme some of the generated nodes take their colors from.
+const SCHEME_NAME: &str = "selftest";
+const SCHEME: [&str; 3] = ["#c0392b", "#2980b9", "#27ae60"];
+
+/// What one check of `run()` found.
+pub struct Check {
+    pub name: &'static str,
+    pub elapsed: Duration,
+    /// Why the check failed, if it did.
+    pub failure: Option<String>,
+}
+
+/// Linear congruential generator, so documents are the same for a seed on
+/// every platform without another dependency.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) % n.max(1)
+    }
+}
+
+/// Makes a document of `events` events spread between 1700 and 2100, in
+/// nodes nested up to `depth` deep, the same for every `seed`. Some nodes
+/// are given a line, an offset and scale, or a color scheme, so that the
+/// checks cover those too.
+pub fn generate(events: usize, depth: usize, seed: u64) -> SagaDoc {
+    let mut rng = Rng(seed);
+    let mut count = 0;
+    let depth = depth.max(1);
+    let (direct, kids) = split(events, depth);
+    let mut root = SagaDoc::builder().title("Selftest");
+    for _ in 0..direct {
+        count += 1;
+        root = match event(&mut rng, count) {
+            (name, dates, None) => root.event(&name, &dates),
+            (name, dates, Some(edit)) => root.event_with(&name, &dates, edit),
+        };
+    }
+    for (kid, share) in kids.into_iter().enumerate() {
+        let name = format!("Selftest.{}", kid + 1);
+        root = root.node(|node|grow(&mut rng, &mut count, share, depth - 1, name, node));
+    }
+    let mut doc = root.build().expect("Generated dates are valid.");
+    let colors = SCHEME.iter().map(|color|color.parse().expect("Valid colors.")).collect();
+    doc.add_scheme(SCHEME_NAME, colors).expect("A blank document has no schemes.");
+    doc
+}
+
+/// How many of `events` go directly in a node allowed `depth` levels, and
+/// how many in each node under it. Half go in the nodes under it while
+/// `depth` allows, the first taking what doesn't divide evenly.
+fn split(events: usize, depth: usize) -> (usize, Vec<usize>) {
+    let kids = match depth > 1 {
+        true => BRANCHES.min(events / 2),
+        false => 0,
+    };
+    let nested = match kids {
+        0 => 0,
+        _ => events / 2,
+    };
+    let shares = (0..kids)
+        .map(|kid|nested / kids + match kid {
+            0 => nested % kids,
+            _ => 0,
+        })
+        .collect();
+    (events - nested, shares)
+}
+
+/// Fills `node`, called `name`, with `events` events, split as `split()`
+/// says. `count` numbers the events.
+fn grow(rng: &mut Rng, count: &mut usize, events: usize, depth: usize, name: String, mut node: NodeBuilder) -> NodeBuilder {
+    node = node.name(&name);
+    match rng.below(6) {
+        0 => node = node.line(Some(Interval::Auto)),
+        1 => node = node.offset(rng.below(3) as f64).scale(1.0 + rng.below(2) as f64 / 2.0),
+        2 => node = node.scheme(SCHEME_NAME),
+        _ => {},
+    }
+    let (direct, kids) = split(events, depth);
+    for _ in 0..direct {
+        *count += 1;
+        node = match event(rng, *count) {
+            (name, dates, None) => node.event(&name, &dates),
+            (name, dates, Some(edit)) => node.event_with(&name, &dates, edit),
+        };
+    }
+    for (kid, share) in kids.into_iter().enumerate() {
+        let name = format!("{}.{}", name, kid + 1);
+        node = node.node(|kid|grow(rng, count, share, depth - 1, name, kid));
+    }
+    node
+}
+
+/// The name and dates of event `n`, and what else to fill in, if anything.
+fn event(rng: &mut Rng, n: usize) -> (String, String, Option<impl FnOnce(&mut Event)>) {
+    const YEAR: u64 = 365 * 24 * 60;
+    // Whole minutes, which is all that dates keep.
+    let minutes = -(270 * YEAR as i64) + rng.below(400 * YEAR) as i64;
+    let start = from_stamp(minutes * 60).expect("Within the range of dates.");
+    let end = match rng.below(3) {
+        0 => Some(start + chrono::Duration::minutes(rng.below(2 * YEAR) as i64)),
+        _ => None,
+    };
+    let dates = Dates::from_dts(start, end).to_string();
+    let statuses = [None, Some(Status::Planned), Some(Status::InProgress), Some(Status::Done), Some(Status::Cancelled)];
+    let status = statuses[rng.below(statuses.len() as u64) as usize];
+    let description = (rng.below(4) == 0)
+        .then(||format!("Description of event {}, with \"quotes\" & <brackets>.", n));
+    let edit = (status.is_some() || description.is_some()).then_some(move |event: &mut Event|{
+        event.set_status(status);
+        if let Some(description) = description {
+            event.add_description(&description);
+        }
+    });
+    (format!("Event {}", n), dates, edit)
+}
+
+/// One of the checks made by `run()`, failing with what went wrong.
+type Checker<'a> = &'a dyn Fn() -> Result<(), String>;
+
+/// Round trips `doc` through every form documents are stored in, and lays
+/// it out and draws it with every renderer, timing each.
+pub fn run(doc: &SagaDoc) -> Vec<Check> {
+    let events = doc.get_data().iter_events().count();
+    let checks: [(&'static str, Checker); 7] = [
+        ("json", &||round_trip(doc, |bytes|Ok(bytes.to_vec()), |bytes|Ok(bytes.to_vec()), false)),
+        ("json, pretty", &||round_trip(doc, |bytes|Ok(bytes.to_vec()), |bytes|Ok(bytes.to_vec()), true)),
+        ("encrypted", &||round_trip(
+            doc,
+            |bytes|crypt::encrypt(bytes, PASSPHRASE).map_err(MainError::Crypt),
+            |bytes|crypt::decrypt(bytes, PASSPHRASE).map_err(MainError::Crypt),
+            false,
+        )),
+        ("compressed", &||round_trip(doc, compress, decompress, false)),
+        ("layout", &||{
+            let scene = doc.scene(&RenderOptions::default());
+            let drawn = scene.shapes.iter().filter(|shape|matches!(shape, Shape::Event { .. })).count();
+            serde_json::to_string(&scene).map_err(|e|e.to_string())?;
+            expect_count("events laid out", drawn, events)
+        }),
+        ("svg", &||{
+            let svg = doc.draw(&RenderOptions::default()).to_string();
+            expect_count("events drawn", svg.matches("<title>").count(), events)
+        }),
+        ("terminal", &||{
+            let scene = doc.scene(&RenderOptions::default());
+            let text = Terminal { width: 120, color: false }.render(&scene);
+            let rows = text.lines().count();
+            match rows >= events {
+                true => Ok(()),
+                false => Err(format!("{} rows drawn for {} events", rows, events)),
+            }
+        }),
+    ];
+    checks.iter()
+        .map(|&(name, check)|{
+            let started = Instant::now();
+            let failure = check().err();
+            Check { name, elapsed: started.elapsed(), failure }
+        })
+        .collect()
+}
+
+/// Writes `doc` out, passes the bytes through `encode` and `decode`, reads
+/// it back and compares the two.
+fn round_trip(
+    doc: &SagaDoc,
+    encode: impl Fn(&[u8]) -> Result<Vec<u8>, MainError>,
+    decode: impl Fn(&[u8]) -> Result<Vec<u8>, MainError>,
+    pretty: bool,
+) -> Result<(), String> {
+    let trip = || {
+        let mut bytes = Vec::new();
+        match pretty {
+            true => doc.to_writer_pretty(&mut bytes)?,
+            false => doc.to_writer(&mut bytes)?,
+        }
+        let bytes = decode(&encode(&bytes)?)?;
+        SagaDoc::from_reader(bytes.as_slice())
+    };
+    let back = trip().map_err(|e|e.to_string())?;
+    let json = |doc: &SagaDoc|serde_json::to_value(doc).map_err(|e|e.to_string());
+    match difference(&json(doc)?, &json(&back)?, "") {
+        Some(at) => Err(format!("the document changed at {}", at)),
+        None => Ok(()),
+    }
+}
+
+fn compress(bytes: &[u8]) -> Result<Vec<u8>, MainError> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(bytes)
+        .and_then(|_|encoder.finish())
+        .map_err(MainError::FileIO)
+}
+
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>, MainError> {
+    let mut plain = Vec::new();
+    GzDecoder::new(bytes).read_to_end(&mut plain)
+        .map(|_|plain)
+        .map_err(MainError::FileIO)
+}
+
+fn expect_count(what: &str, found: usize, expected: usize) -> Result<(), String> {
+    match found == expected {
+        true => Ok(()),
+        false => Err(format!("{} {}, expected {}", found, what, expected)),
+    }
+}
+
+/// Where `a` and `b` first differ, as a JSON pointer like "/data/children/3".
+fn difference(a: &Json, b: &Json, at: &str) -> Option<String> {
+    match (a, b) {
+        (Json::Object(a), Json::Object(b)) => {
+            a.keys().chain(b.keys())
+                .find_map(|key|match (a.get(key), b.get(key)) {
+                    (Some(a), Some(b)) => difference(a, b, &format!("{}/{}", at, key)),
+                    _ => Some(format!("{}/{}", at, key)),
+                })
+        },
+        (Json::Array(a), Json::Array(b)) if a.len() == b.len() => {
+            a.iter().zip(b.iter()).enumerate()
+                .find_map(|(i, (a, b))|difference(a, b, &format!("{}/{}", at, i)))
+        },
+        _ if a == b => None,
+        _ => Some(match at.is_empty() {
+            true => "/".to_string(),
+            false => at.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{difference, generate, round_trip};
+
+    #[test]
+    fn test_selftest() {
+        let doc = generate(50, 3, 7);
+        let data = doc.get_data();
+        assert_eq!(data.iter_events().count(), 50);
+        assert_eq!(data.max_depth(), 3);
+        // Seeded, so the same every time.
+        let json = |seed|serde_json::to_string(&generate(50, 3, seed)).unwrap();
+        assert_eq!(json(7), json(7));
+        assert_ne!(json(7), json(8));
+        assert_eq!(round_trip(&doc, |bytes|Ok(bytes.to_vec()), |bytes|Ok(bytes.to_vec()), true), Ok(()));
+        let a = serde_json::json!({"data": {"children": [1, {"name": "a"}]}});
+        let b = serde_json::json!({"data": {"children": [1, {"name": "b"}]}});
+        assert_eq!(difference(&a, &b, ""), Some("/data/children/1/name".to_string()));
+        assert_eq!(difference(&a, &a, ""), None);
+        assert_eq!(difference(&a, &serde_json::json!(1), ""), Some("/".to_string()));
+    }
+}
@@ -0,0 +1,146 @@
+use std::{io::Read, path::PathBuf};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Serialize, Deserialize};
+
+use super::MainError;
+use super::crypt;
+use super::events::{Dt, Node, FORMAT};
+use super::saga::SagaDoc;
+
+/// Copy of a document's data, kept compressed in a directory next to it.
+/// Snapshots of an encrypted document are encrypted too.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Counts up from 1, in the order the snapshots were taken.
+    #[serde(skip)]
+    pub number: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub taken: Dt,
+    pub data: Node,
+}
+
+impl Snapshot {
+    /// Names the snapshot the way `find()` accepts it.
+    pub fn describe(&self) -> String {
+        let taken = self.taken.format(FORMAT);
+        match self.label.as_ref() {
+            Some(label) => format!("{} [{}] {}", self.number, taken, label),
+            None => format!("{} [{}]", self.number, taken),
+        }
+    }
+}
+
+/// Where the snapshots of `fp` are kept.
+fn directory(fp: &str) -> PathBuf {
+    PathBuf::from(format!("{}.snapshots", fp))
+}
+
+fn file(fp: &str, number: usize) -> PathBuf {
+    directory(fp).join(format!("{}.json.gz", number))
+}
+
+/// Stores the data of `doc`, the document at `fp`, as a new snapshot and
+/// returns its number.
+pub fn take(fp: &str, doc: &SagaDoc, label: Option<&str>) -> Result<usize, MainError> {
+    let number = numbers(fp)?.last().map(|n|n + 1).unwrap_or(1);
+    let snapshot = Snapshot {
+        number,
+        label: label.map(|label|label.to_string()),
+        taken: chrono::Local::now().naive_local(),
+        data: doc.get_data().clone(),
+    };
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    serde_json::to_writer(&mut encoder, &snapshot)
+        .map_err(MainError::SerializeFail)?;
+    let mut bytes = encoder.finish()
+        .map_err(MainError::FileIO)?;
+    if crypt::is_encrypted_file(fp) {
+        bytes = crypt::encrypt(&bytes, &crypt::passphrase(false)?)?;
+    }
+    let path = file(fp, number);
+    std::fs::create_dir_all(directory(fp))
+        .and_then(|_|std::fs::write(&path, bytes))
+        .map_err(|e|MainError::FileIO(e).in_file(&path.to_string_lossy()))?;
+    Ok(number)
+}
+
+/// Every snapshot of `fp`, oldest first.
+pub fn list(fp: &str) -> Result<Vec<Snapshot>, MainError> {
+    numbers(fp)?.into_iter().map(|number|read(fp, number)).collect()
+}
+
+/// Finds a snapshot of `fp` by its number, or failing that by its label.
+/// The latest one wins if several share a label.
+pub fn find(fp: &str, name: &str) -> Result<Snapshot, MainError> {
+    if let Ok(number) = name.trim().parse::<usize>() {
+        if file(fp, number).exists() {
+            return read(fp, number);
+        }
+    }
+    list(fp)?.into_iter().rev()
+        .find(|snapshot|snapshot.label.as_deref() == Some(name))
+        .ok_or_else(||MainError::NoSnapshot(name.to_string()).in_file(fp))
+}
+
+fn read(fp: &str, number: usize) -> Result<Snapshot, MainError> {
+    let path = file(fp, number);
+    let read = || -> Result<_, MainError> {
+        let mut bytes = std::fs::read(&path)
+            .map_err(MainError::FileIO)?;
+        if crypt::is_encrypted(&bytes) {
+            bytes = crypt::decrypt(&bytes, &crypt::passphrase(false)?)?;
+        }
+        let mut json = Vec::new();
+        GzDecoder::new(bytes.as_slice()).read_to_end(&mut json)
+            .map_err(MainError::FileIO)?;
+        let snapshot: Snapshot = serde_json::from_slice(&json)
+            .map_err(MainError::NotASagaDoc)?;
+        Ok(Snapshot { number, ..snapshot })
+    };
+    read().map_err(|e|e.in_file(&path.to_string_lossy()))
+}
+
+/// Numbers of the snapshots taken of `fp`, in order.
+fn numbers(fp: &str) -> Result<Vec<usize>, MainError> {
+    let entries = match std::fs::read_dir(directory(fp)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(MainError::FileIO(e).in_file(fp)),
+    };
+    let mut numbers: Vec<usize> = entries
+        .filter_map(|entry|entry.ok())
+        .filter_map(|entry|{
+            entry.file_name().to_str()?
+                .strip_suffix(".json.gz")?
+                .parse().ok()
+        })
+        .collect();
+    numbers.sort();
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{directory, find, list, take};
+    use super::super::saga::SagaDoc;
+
+    #[test]
+    fn test_snapshots() {
+        let path = std::env::temp_dir().join(format!("saga-snapshots-{}.json", std::process::id()));
+        let fp = path.to_str().unwrap();
+        let mut doc = SagaDoc::blank();
+        doc.get_data_mut().set_name(Some("First"));
+        assert_eq!(take(fp, &doc, None).unwrap(), 1);
+        doc.get_data_mut().set_name(Some("Second"));
+        assert_eq!(take(fp, &doc, Some("before the move")).unwrap(), 2);
+        let snapshots = list(fp).unwrap();
+        assert_eq!(snapshots.iter().map(|s|s.number).collect::<Vec<usize>>(), vec![1, 2]);
+        assert!(snapshots[1].describe().ends_with("] before the move"));
+        assert_eq!(find(fp, "1").unwrap().data.name_in(None), Some("First"));
+        assert_eq!(find(fp, "before the move").unwrap().data.name_in(None), Some("Second"));
+        assert!(find(fp, "3").is_err());
+        std::fs::remove_dir_all(directory(fp)).unwrap();
+    }
+}
@@ -0,0 +1,153 @@
+use super::count::node_label;
+use super::events::{elapsed, from_stamp, Event, Node};
+
+/// How much of the time under a node its events cover, in seconds.
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    /// From the start of the first event to the end of the last.
+    pub range: (i64, i64),
+    /// Time covered by at least one event, counting overlaps once.
+    pub covered: i64,
+    /// Label of each node holding events directly, how many it holds, and
+    /// their average duration, in the order the nodes appear.
+    pub per_node: Vec<(String, usize, i64)>,
+    /// The longest stretches of the range no event covers, longest first.
+    pub gaps: Vec<(i64, i64)>,
+}
+
+/// Works out the `Stats` of the events under `node`, keeping the `gaps`
+/// longest gaps. Nothing if there are no events.
+pub fn stats(node: &Node, gaps: usize) -> Option<Stats> {
+    let mut spans = vec![];
+    let mut per_node: Vec<(String, usize, i64)> = vec![];
+    for (path, event) in node.iter_events_with_paths() {
+        let (start, end) = span(event);
+        spans.push((start, end));
+        let label = node_label(node, &path[..path.len() - 1]);
+        match per_node.iter_mut().find(|(other, _, _)|*other == label) {
+            Some(group) => {
+                group.1 += 1;
+                group.2 += end - start;
+            },
+            None => per_node.push((label, 1, end - start)),
+        }
+    }
+    let (range, covered, mut found) = sweep(spans)?;
+    // Stable, so gaps as long as each other stay in order.
+    found.sort_by_key(|(start, end)|start - end);
+    found.truncate(gaps);
+    per_node.iter_mut().for_each(|group|group.2 /= group.1 as i64);
+    Some(Stats { range, covered, per_node, gaps: found })
+}
+
+/// The stretches between `events` lasting at least `min` seconds that none
+/// of them cover, in order.
+pub fn gaps<'a>(events: impl Iterator<Item = &'a Event>, min: i64) -> Vec<(i64, i64)> {
+    sweep(events.map(span).collect())
+        .map(|(_, _, gaps)|gaps)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(start, end)|end - start >= min)
+        .collect()
+}
+
+/// Start and end of `event`, the start again for a moment.
+pub fn span(event: &Event) -> (i64, i64) {
+    let (start, end) = event.dates().stamps();
+    (start, end.unwrap_or(start).max(start))
+}
+
+/// The range of some spans, the time they cover, and the gaps between them.
+type Sweep = ((i64, i64), i64, Vec<(i64, i64)>);
+
+/// Sweeps `spans` in order of their starts, merging those that overlap, to
+/// find their range, the time they cover, and the gaps between them in
+/// order. Nothing if there are no spans.
+fn sweep(mut spans: Vec<(i64, i64)>) -> Option<Sweep> {
+    spans.sort();
+    let range = (spans.first()?.0, spans.iter().map(|(_, end)|*end).max()?);
+    let (mut covered, mut gaps) = (0, vec![]);
+    let mut current = spans[0];
+    for &(start, end) in spans[1..].iter() {
+        match start <= current.1 {
+            true => current.1 = current.1.max(end),
+            false => {
+                covered += current.1 - current.0;
+                gaps.push((current.1, start));
+                current = (start, end);
+            },
+        }
+    }
+    covered += current.1 - current.0;
+    Some((range, covered, gaps))
+}
+
+impl Stats {
+    /// Share of the range covered by events, from 0 to 1. Ranges of a
+    /// single moment are covered entirely.
+    pub fn coverage(&self) -> f64 {
+        match self.range.1 - self.range.0 {
+            0 => 1.0,
+            length => self.covered as f64 / length as f64,
+        }
+    }
+}
+
+/// `seconds` in human terms, like "3 years 2 months", as `elapsed()` puts
+/// it. Months are counted from the start of 1970, so are only roughly right
+/// for lengths of time not starting then.
+pub fn length(seconds: i64) -> String {
+    from_stamp(0).zip(from_stamp(seconds))
+        .and_then(|(start, end)|elapsed(&start, &end))
+        .unwrap_or_else(||format!("{} seconds", seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gaps, length, stats};
+    use super::super::events::{Event, Node};
+
+    #[test]
+    fn test_stats() {
+        let event = |dates: &str|Event::new("", dates.parse().unwrap()).into_value();
+        let node = Node::from_vec(vec![
+            event("01/01/2000 00:00 - 01/01/2000 10:00"),
+            // Overlapping the first, so only adding two hours.
+            event("01/01/2000 08:00 - 01/01/2000 12:00"),
+            Node::new(Some("Later".to_string()), vec![
+                event("01/01/2000 20:00 - 01/01/2000 21:00"),
+                event("01/01/2000 22:00"),
+                event("02/01/2000 00:00 - 02/01/2000 04:00"),
+            ]).into_value(),
+        ]);
+        let found = stats(&node, 2).unwrap();
+        let hour = 3600;
+        assert_eq!(found.range.1 - found.range.0, 28 * hour);
+        assert_eq!(found.covered, 17 * hour);
+        assert!((found.coverage() - 17.0 / 28.0).abs() < 1e-9);
+        assert_eq!(found.per_node, vec![("/".to_string(), 2, 7 * hour), ("3 Later".to_string(), 3, 5 * hour / 3)]);
+        let gaps: Vec<i64> = found.gaps.iter().map(|(start, end)|(end - start) / hour).collect();
+        assert_eq!(gaps, vec![8, 2]);
+        assert!(stats(&Node::from_vec(vec![]), 3).is_none());
+        assert_eq!(length(28 * hour), "1 day 4 hours");
+    }
+
+    #[test]
+    fn test_gaps() {
+        let events = [
+            Event::new("", "1800 - 1805".parse().unwrap()),
+            Event::new("", "1803".parse().unwrap()),
+            Event::new("", "1807".parse().unwrap()),
+            Event::new("", "01/06/1808".parse().unwrap()),
+        ];
+        let years = |(start, end): &(i64, i64)|(end - start) as f64 / (365.25 * 86400.0);
+        // From the end of 1805 to 1807, and from the end of 1807 to June 1808.
+        let found = gaps(events.iter(), 0);
+        assert_eq!(found.len(), 2);
+        assert!((years(&found[0]) - 1.0).abs() < 0.01);
+        let found = gaps(events.iter(), 200 * 86400);
+        assert_eq!(found.len(), 1);
+        assert!(gaps(events.iter(), 400 * 86400).is_empty());
+        assert!(gaps(std::iter::empty(), 0).is_empty());
+    }
+}
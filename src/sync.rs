@@ -0,0 +1,323 @@
+use std::{
+    io::{Error as IoError, Write},
+    process::{Command, Stdio},
+    time::SystemTime,
+};
+
+use chrono::DateTime;
+
+use super::{MainError, MainResult};
+use super::merge;
+use super::saga::{self, SagaDoc, Stamp};
+
+/// Environment variables holding the credentials for S3-compatible storage.
+pub const ACCESS_KEY_VAR: &str = "AWS_ACCESS_KEY_ID";
+pub const SECRET_KEY_VAR: &str = "AWS_SECRET_ACCESS_KEY";
+
+/// What to do when both the local and the remote copy changed since the
+/// last sync.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Strategy {
+    /// Merge the two, keeping the local side of any conflict.
+    Merge,
+    /// Keep whichever copy was written last.
+    Newest,
+}
+
+/// Where a document is kept remotely. Transfers are done by curl, which
+/// reads any WebDAV login from ~/.netrc.
+pub struct Remote {
+    pub url: String,
+    /// Region to sign requests for, when the remote is S3-compatible storage.
+    pub s3_region: Option<String>,
+}
+
+/// The remote copy's contents, and when it was last written if the server
+/// said.
+type Fetched = (Vec<u8>, Option<SystemTime>);
+
+/// What a sync has to do, judging by which copies changed since the last one.
+#[derive(Debug, PartialEq)]
+pub enum Plan {
+    InSync,
+    Upload,
+    Download,
+    /// Both copies changed.
+    Reconcile,
+}
+
+/// What a sync did, for reporting back to the user.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    InSync,
+    Uploaded,
+    Downloaded,
+    /// Merged, listing the conflicts settled in favour of the local copy.
+    Merged(Vec<String>),
+}
+
+#[derive(Debug)]
+pub enum SyncError {
+    /// curl couldn't be run at all, most likely because it isn't installed.
+    Spawn(IoError),
+    /// A temporary file for curl to read or write couldn't be made.
+    Temp(IoError),
+    /// curl failed before getting a response, with what it wrote to stderr.
+    Failed(String),
+    /// The server answered with an unexpected HTTP status.
+    Status(u16),
+    /// S3 storage was asked for, but a credential isn't set.
+    MissingCredential(&'static str),
+}
+
+/// Compares both copies with the one from the last sync, if there was one.
+pub fn plan(base: Option<&[u8]>, local: &[u8], remote: Option<&[u8]>) -> Plan {
+    match (base, remote) {
+        (_, Some(remote)) if remote == local => Plan::InSync,
+        (_, None) => Plan::Upload,
+        (Some(base), Some(_)) if base == local => Plan::Download,
+        (Some(base), Some(remote)) if base == remote => Plan::Upload,
+        _ => Plan::Reconcile,
+    }
+}
+
+/// Brings the document at `fp` and its remote copy up to date with each
+/// other. The copy from the last sync is kept next to the document, so that
+/// later syncs can tell which side changed.
+pub fn sync(fp: &str, remote: &Remote, strategy: Strategy) -> Result<Outcome, MainError> {
+    let base_fp = base_path(fp);
+    let local = std::fs::read(fp)
+        .map_err(|e|MainError::FileIO(e).in_file(fp))?;
+    let base = std::fs::read(&base_fp).ok();
+    let fetched = remote.get()?;
+    let outcome = match plan(base.as_deref(), &local, fetched.as_ref().map(|(bytes, _)|bytes.as_slice())) {
+        Plan::InSync => Outcome::InSync,
+        Plan::Upload => {
+            remote.put(&local)?;
+            Outcome::Uploaded
+        },
+        Plan::Download => {
+            let (bytes, _) = fetched.expect("Only planned with a remote copy.");
+            replace(fp, &local, &bytes)?;
+            Outcome::Downloaded
+        },
+        Plan::Reconcile => {
+            let (bytes, modified) = fetched.expect("Only planned with a remote copy.");
+            reconcile(fp, remote, strategy, base.as_deref(), &local, &bytes, modified)?
+        },
+    };
+    let synced = std::fs::read(fp)
+        .map_err(|e|MainError::FileIO(e).in_file(fp))?;
+    std::fs::write(&base_fp, synced)
+        .map_err(|e|MainError::FileIO(e).in_file(&base_fp))?;
+    Ok(outcome)
+}
+
+/// Settles a sync where both copies changed.
+fn reconcile(
+    fp: &str,
+    remote: &Remote,
+    strategy: Strategy,
+    base: Option<&[u8]>,
+    local: &[u8],
+    fetched: &[u8],
+    modified: Option<SystemTime>,
+) -> Result<Outcome, MainError> {
+    if strategy == Strategy::Newest {
+        let written = std::fs::metadata(fp)
+            .and_then(|meta|meta.modified())
+            .map_err(|e|MainError::FileIO(e).in_file(fp))?;
+        return match modified {
+            Some(modified) if modified > written => {
+                replace(fp, local, fetched)?;
+                Ok(Outcome::Downloaded)
+            },
+            _ => {
+                remote.put(local)?;
+                Ok(Outcome::Uploaded)
+            },
+        };
+    }
+    let read = |bytes: &[u8]|SagaDoc::from_reader(bytes);
+    let ours = read(local).map_err(|e|e.in_file(fp))?;
+    let theirs = read(fetched).map_err(|e|e.in_file(&remote.url))?;
+    // Without a previous sync, everything counts as added on both sides.
+    let base = match base {
+        Some(base) => read(base).map_err(|e|e.in_file(&base_path(fp)))?,
+        None => SagaDoc::blank(),
+    };
+    let (merged, conflicts) = merge::merge(&base, &ours, &theirs)?;
+    let current = std::fs::read(fp)
+        .map_err(|e|MainError::FileIO(e).in_file(fp))?;
+    if current != local {
+        return Err(MainError::Changed.in_file(fp));
+    }
+    merged.save(fp)?;
+    let bytes = std::fs::read(fp)
+        .map_err(|e|MainError::FileIO(e).in_file(fp))?;
+    remote.put(&bytes)?;
+    Ok(Outcome::Merged(conflicts))
+}
+
+/// Overwrites the document at `fp` with `bytes`, once they are known to be a
+/// document, unless it no longer holds `expected`.
+fn replace(fp: &str, expected: &[u8], bytes: &[u8]) -> MainResult {
+    let write = || {
+        SagaDoc::from_reader(bytes)?;
+        saga::replace_file(fp, bytes, Some(Stamp::of(expected)))
+    };
+    write().map_err(|e|e.in_file(fp))
+}
+
+/// Where the copy from the last sync of `fp` is kept.
+fn base_path(fp: &str) -> String {
+    format!("{}.synced", fp)
+}
+
+impl Remote {
+    /// Downloads the remote copy along with when it was last written, or
+    /// nothing if there isn't one yet.
+    fn get(&self) -> Result<Option<Fetched>, SyncError> {
+        // Removed along with what curl wrote into it when dropped.
+        let temp = tempfile::Builder::new().prefix("saga-sync-").tempdir()
+            .map_err(SyncError::Temp)?;
+        let (body, headers) = (temp.path().join("body"), temp.path().join("headers"));
+        let status = self.curl(&[
+            "--output", &*body.to_string_lossy(),
+            "--dump-header", &*headers.to_string_lossy(),
+        ]);
+        let read = |path: &std::path::Path|std::fs::read(path).unwrap_or_default();
+        let (bytes, headers) = (read(&body), read(&headers));
+        match status? {
+            404 => Ok(None),
+            200..=299 => Ok(Some((bytes, last_modified(&String::from_utf8_lossy(&headers))))),
+            status => Err(SyncError::Status(status)),
+        }
+    }
+
+    /// Uploads `bytes` as the new remote copy.
+    fn put(&self, bytes: &[u8]) -> Result<(), SyncError> {
+        let mut temp = tempfile::Builder::new().prefix("saga-sync-").tempfile()
+            .map_err(SyncError::Temp)?;
+        temp.write_all(bytes).and_then(|_|temp.flush()).map_err(SyncError::Temp)?;
+        let status = self.curl(&["--upload-file", &*temp.path().to_string_lossy(), "--output", "/dev/null"]);
+        match status? {
+            200..=299 => Ok(()),
+            status => Err(SyncError::Status(status)),
+        }
+    }
+
+    /// Runs curl against the remote, returning the HTTP status. Credentials
+    /// are passed through curl's config on stdin, to keep them off the
+    /// command line.
+    fn curl(&self, args: &[&str]) -> Result<u16, SyncError> {
+        let mut config = String::new();
+        if let Some(region) = self.s3_region.as_ref() {
+            let var = |name: &'static str|std::env::var(name)
+                .map_err(|_|SyncError::MissingCredential(name));
+            config += &format!("aws-sigv4 = \"aws:amz:{}:s3\"\n", region);
+            config += &format!("user = \"{}:{}\"\n", quoted(&var(ACCESS_KEY_VAR)?), quoted(&var(SECRET_KEY_VAR)?));
+        }
+        let mut child = Command::new("curl")
+            .args(["--silent", "--show-error", "--location", "--netrc-optional", "--config", "-"])
+            .args(["--write-out", "%{http_code}"])
+            .args(args)
+            .arg(&self.url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(SyncError::Spawn)?;
+        child.stdin.take()
+            .expect("Piped above.")
+            .write_all(config.as_bytes())
+            .map_err(SyncError::Spawn)?;
+        let output = child.wait_with_output().map_err(SyncError::Spawn)?;
+        if !output.status.success() {
+            return Err(SyncError::Failed(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+        String::from_utf8_lossy(&output.stdout).trim().parse()
+            .map_err(|_|SyncError::Failed("no HTTP status in curl's output".to_string()))
+    }
+}
+
+/// Escapes `text` to go between double quotes in a curl config, so that a
+/// secret can't end the string early and pass curl options of its own.
+fn quoted(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Reads the Last-Modified header of the final response, after redirects.
+fn last_modified(headers: &str) -> Option<SystemTime> {
+    headers.lines()
+        .filter_map(|line|line.split_once(':'))
+        .rfind(|(name, _)|name.trim().eq_ignore_ascii_case("last-modified"))
+        .and_then(|(_, date)|DateTime::parse_from_rfc2822(date.trim()).ok())
+        .map(SystemTime::from)
+}
+
+impl std::fmt::Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SyncError::Spawn(e) => write!(f, "couldn't run curl: {}", e),
+            SyncError::Temp(e) => write!(f, "couldn't make a temporary file for curl: {}", e),
+            SyncError::Failed(stderr) => write!(f, "curl failed: {}", stderr),
+            SyncError::Status(status) => write!(f, "the remote answered with HTTP status {}", status),
+            SyncError::MissingCredential(var) => write!(f, "S3 storage needs {} to be set", var),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SyncError::Spawn(e) => Some(e),
+            SyncError::Temp(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SyncError> for MainError {
+    fn from(err: SyncError) -> Self {
+        MainError::Sync(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{last_modified, plan, quoted, Plan};
+
+    #[test]
+    fn test_plan() {
+        let (old, new, other) = (&b"old"[..], &b"new"[..], &b"other"[..]);
+        let cases = [
+            (Some(old), new, Some(new), Plan::InSync),
+            (None, new, None, Plan::Upload),
+            (Some(old), new, Some(old), Plan::Upload),
+            (Some(old), old, Some(new), Plan::Download),
+            (Some(old), new, Some(other), Plan::Reconcile),
+            (None, new, Some(other), Plan::Reconcile),
+        ];
+        for (base, local, remote, expected) in cases.into_iter() {
+            assert_eq!(plan(base, local, remote), expected);
+        }
+    }
+
+    #[test]
+    fn test_last_modified() {
+        let headers = "HTTP/1.1 301 Moved\r\nLocation: /b\r\n\r\nHTTP/1.1 200 OK\r\nLast-Modified: Wed, 21 Oct 2015 07:28:00 GMT\r\n";
+        assert!(last_modified(headers).is_some());
+        assert!(last_modified("HTTP/1.1 200 OK\r\n").is_none());
+    }
+
+    #[test]
+    fn test_quoted() {
+        assert_eq!(quoted("plain"), "plain");
+        assert_eq!(quoted(r#"a"b\c"#), r#"a\"b\\c"#);
+        assert_eq!(quoted("x\"\nurl = \"evil"), r#"x\"\nurl = \"evil"#);
+    }
+}
@@ -0,0 +1,120 @@
+use super::events::{Dates, Node};
+
+/// A problem found in a document by `validate()`.
+#[derive(Debug, PartialEq)]
+pub enum Issue {
+    /// The event at `path` ends before it starts.
+    InvertedDates { path: Vec<usize>, name: String, dates: Dates },
+    /// The event at `path` falls outside `span`, declared for the line of
+    /// the node holding it. Only a warning, as rendering copes with it.
+    OutsideLine { path: Vec<usize>, name: String, dates: Dates, span: Dates },
+}
+
+/// Result of checking a document, listing everything that was wrong with it.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+    /// Things that look wrong without being broken, left as they are.
+    pub warnings: Vec<Issue>,
+    /// True if the issues were repaired in place.
+    pub fixed: bool,
+}
+
+/// Checks every event under `node`, repairing what it can if `fix` is set.
+pub fn validate(node: &mut Node, fix: bool) -> Report {
+    let warnings = node.iter_events_with_paths()
+        .filter_map(|(path, event)|{
+            let span = node.line_span_at(&path)?;
+            event.dates().exceeds(span).then(||Issue::OutsideLine {
+                name: event.name().to_string(),
+                dates: event.dates().clone(),
+                span: span.clone(),
+                path,
+            })
+        })
+        .collect();
+    let mut issues = vec![];
+    node.visit_events_mut(&mut |path, event|{
+        if event.dates().is_inverted() {
+            issues.push(Issue::InvertedDates {
+                path: path.to_vec(),
+                name: event.name().to_string(),
+                dates: event.dates().clone(),
+            });
+            if fix { event.dates_mut().fix_inverted(); }
+        }
+    });
+    Report { issues, warnings, fixed: fix }
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Issue::InvertedDates { path, name, dates } => {
+                write!(f, "{} '{}' ends before it starts: [{}]", path_string(path), name, dates)
+            },
+            Issue::OutsideLine { path, name, dates, span } => {
+                write!(f, "{} '{}' [{}] falls outside its node's line [{}]", path_string(path), name, dates, span)
+            },
+        }
+    }
+}
+
+/// Formats a path the same way users type it.
+pub fn path_string(path: &[usize]) -> String {
+    path.iter()
+        .map(|i|i.to_string())
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, Issue};
+    use super::super::events::{Event, Node, Query};
+
+    #[test]
+    fn test_validate_inverted() {
+        let mut node = Node::from_vec(vec![
+            Event::new("Fine", "1/1/1990 0:0 - 1/1/1991 0:0".parse().unwrap()).into_value(),
+            Node::from_vec(vec![
+                Event::new("Backwards", "1/1/1991 0:0 - 1/1/1990 0:0".parse().unwrap()).into_value(),
+            ]).into_value(),
+        ]);
+        let report = validate(&mut node, false);
+        assert_eq!(report.issues, vec![Issue::InvertedDates {
+            path: vec![2, 1],
+            name: "Backwards".to_string(),
+            dates: "1/1/1991 0:0 - 1/1/1990 0:0".parse().unwrap(),
+        }]);
+        assert_eq!(report.issues[0].to_string(), "2:1 'Backwards' ends before it starts: [01/01/1991 00:00 - 01/01/1990 00:00]");
+        assert!(!validate(&mut node, true).is_ok());
+        assert!(validate(&mut node, false).is_ok());
+    }
+
+    #[test]
+    fn test_validate_outside_line() {
+        let mut spain = Node::from_vec(vec![
+            Event::new("Bailén", "19/07/1808 0:0".parse().unwrap()).into_value(),
+            Event::new("Toulouse", "10/04/1814 0:0".parse().unwrap()).into_value(),
+        ]).with_line(None);
+        spain.set_line_span(Some("1/1/1808 0:0 - 1/1/1813 0:0".parse().unwrap()));
+        let mut node = Node::from_vec(vec![spain.into_value()]);
+        let report = validate(&mut node, false);
+        assert!(report.is_ok());
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(
+            report.warnings[0].to_string(),
+            "1:2 'Toulouse' [10/04/1814 00:00] falls outside its node's line [01/01/1808 00:00 - 01/01/1813 00:00]",
+        );
+        // Without a line, the span isn't drawn and so isn't checked.
+        if let Ok(Query::Node(spain)) = node.query_mut(&[1]) { spain.set_line(None); }
+        assert!(validate(&mut node, false).warnings.is_empty());
+    }
+}
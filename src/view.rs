@@ -0,0 +1,136 @@
+use std::{
+    io::{ErrorKind, IsTerminal, Write},
+    process::{Command, Stdio},
+};
+
+use super::MainError;
+use super::events::{Event, Node, Position, PrintOptions, Visitor};
+use super::render::{ansi, status_code};
+
+/// Pager used when `$PAGER` isn't set.
+const PAGER: &str = "less -R";
+
+/// Collects the lines of `view()` while walking the tree.
+struct Annotated<'o> {
+    options: &'o PrintOptions<'o>,
+    /// Place in time order of each event, in the order the tree holds them.
+    ranks: Vec<usize>,
+    /// Events passed so far.
+    seen: usize,
+    /// Columns taken by the widest number.
+    width: usize,
+    color: bool,
+    lines: Vec<String>,
+}
+
+impl Annotated<'_> {
+    fn push(&mut self, number: &str, line: String) {
+        self.lines.push(format!("{:>w$} │ {}", number, line, w = self.width));
+    }
+}
+
+impl<'a> Visitor<'a> for Annotated<'_> {
+    fn enter(&mut self, node: &'a Node, at: &Position) {
+        for line in node.print_header(at.depth, self.options) {
+            let line = match self.color {
+                true => ansi(&line, "1"),
+                false => line,
+            };
+            self.push("", line);
+        }
+    }
+
+    fn event(&mut self, event: &'a Event, at: &Position) {
+        let number = format!("#{}", self.ranks[self.seen]);
+        self.seen += 1;
+        let text = event.print(at.depth + 1, self.options);
+        for (i, line) in text.lines().enumerate() {
+            match i {
+                0 if self.color => self.push(&number, ansi(line, status_code(&event.status()))),
+                0 => self.push(&number, line.to_string()),
+                _ => self.push("", line.to_string()),
+            }
+        }
+    }
+}
+
+/// The tree under `root` as `print` shows it, with every event numbered by
+/// when it starts among all of them, so the order in time reads alongside
+/// the order in the tree. Events starting together keep their order.
+/// Nodes are bold and events colored by status if `color` is set.
+pub fn view(root: &Node, options: &PrintOptions, color: bool) -> String {
+    let mut order: Vec<(usize, &Event)> = root.iter_events().enumerate().collect();
+    order.sort_by_key(|(_, event)|*event.dates().start());
+    let mut ranks = vec![0; order.len()];
+    for (rank, (index, _)) in order.iter().enumerate() {
+        ranks[*index] = rank + 1;
+    }
+    let width = order.len().to_string().len() + 1;
+    let mut annotated = Annotated { options, ranks, seen: 0, width, color, lines: vec![] };
+    root.walk(&mut annotated);
+    annotated.lines.join("\n")
+}
+
+/// Shows `text` through `$PAGER`, or `less -R`, if stdout is a terminal, and
+/// prints it otherwise.
+pub fn page(text: &str) -> Result<(), MainError> {
+    if !std::io::stdout().is_terminal() {
+        return print(text);
+    }
+    let pager = std::env::var("PAGER").ok()
+        .filter(|pager|!pager.trim().is_empty())
+        .unwrap_or_else(||PAGER.to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next().unwrap_or(PAGER);
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e|MainError::Pager(program.to_string(), e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Quitting the pager early closes the pipe, which is fine.
+        let _ = stdin.write_all(text.as_bytes());
+        let _ = stdin.write_all(b"\n");
+    }
+    child.wait()
+        .map(|_|())
+        .map_err(|e|MainError::Pager(program.to_string(), e))
+}
+
+/// Prints `text` to stdout. Whatever reads it stopping early, like `head`,
+/// isn't an error, unlike with `println!()`, which panics.
+pub fn print(text: &str) -> Result<(), MainError> {
+    match writeln!(std::io::stdout().lock(), "{}", text) {
+        Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(MainError::FileIO(e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::view;
+    use super::super::events::{Event, Node, PrintOptions, Status};
+
+    #[test]
+    fn test_view() {
+        let mut jena = Event::new("Jena", "14/10/1806".parse().unwrap());
+        jena.set_status(Some(Status::Done));
+        let root = Node::from_vec(vec![
+            jena.into_value(),
+            Node::new(Some("Third Coalition".to_string()), vec![
+                Event::new("Ulm", "20/10/1805".parse().unwrap()).into_value(),
+                Event::new("Austerlitz", "02/12/1805".parse().unwrap()).into_value(),
+            ]).into_value(),
+        ]);
+        let options = PrintOptions::default();
+        let text = view(&root, &options, false);
+        let numbers = text.lines()
+            .map(|line|line.split('│').next().unwrap().trim())
+            .collect::<Vec<&str>>();
+        assert_eq!(numbers, vec!["", "#3", "", "#1", "#2"]);
+        assert!(text.lines().nth(3).unwrap().ends_with(&Event::new("Ulm", "20/10/1805".parse().unwrap()).print(2, &options)));
+        let colored = view(&root, &options, true);
+        assert!(colored.contains("\x1b[1m"));
+        assert!(colored.contains("\x1b[32m"));
+    }
+}